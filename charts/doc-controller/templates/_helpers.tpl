@@ -17,6 +17,10 @@ app.kubernetes.io/version: {{ .Values.image.tag | default .Chart.AppVersion | qu
 app: {{ include "controller.name" . }}
 {{- end }}
 
+{{- define "controller.webhookSecret" -}}
+{{- .Values.webhook.certSecret | default (printf "%s-webhook-tls" (include "controller.fullname" .)) }}
+{{- end }}
+
 {{- define "controller.tag" -}}
 {{- if .Values.image.tag }}
 {{- .Values.image.tag }}
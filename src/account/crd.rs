@@ -3,22 +3,59 @@ use kube::CustomResource;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::cloudflare::CloudflareResource;
+use crate::{
+    cf_client,
+    cloudflare::CloudflareResource,
+    conditions::{Condition, Conditions},
+    reconcile_policy::ReconcilePolicy,
+};
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(test, derive(Default))]
 #[kube(kind = "Account", group = "cloudflare.com", version = "v1alpha1", namespaced)]
-#[kube(status = "AccountStatus", shortname = "acc")]
+#[kube(
+    status = "AccountStatus",
+    shortname = "acc",
+    shortname = "cfacc",
+    category = "cloudflare"
+)]
+#[kube(printcolumn = r#"{"name":"Ready", "type":"boolean", "jsonPath":".status.ready"}"#)]
+#[kube(printcolumn = r#"{"name":"Name", "type":"string", "jsonPath":".status.name"}"#)]
+#[kube(printcolumn = r#"{"name":"Summary", "type":"string", "jsonPath":".status.summary"}"#)]
+#[kube(printcolumn = r#"{"name":"Type", "type":"string", "jsonPath":".status.account_type", "priority":1}"#)]
 #[serde(rename_all = "camelCase")]
 pub struct AccountSpec {
     pub id: String,
     pub secret_ref: Option<SecretKeySelector>,
+    /// How many zones the account may hold, Zones are not created past it
+    pub zone_quota: Option<u32>,
+    /// Account settings to enforce, settings left out are not touched
+    pub settings: Option<AccountSettings>,
+    /// `DriftReportOnly` to only compare the settings with Cloudflare, `Enforce` by default
+    pub reconcile_policy: Option<ReconcilePolicy>,
+    /// Cloudflare API to manage the account through instead of the operator's, objects referencing
+    /// the account follow it; without a `secretRef` only `CLOUDFLARE_ALLOWED_API_URLS`
+    pub api_url: Option<String>,
 }
 
 impl CloudflareResource for Account {
+    const PERMISSIONS: &'static [&'static str] = &["Account Settings:Read", "Billing:Read"];
+
     fn secret_ref(&self) -> Option<&SecretKeySelector> {
         self.spec.secret_ref.as_ref()
     }
+
+    fn account_id(&self) -> Option<&str> {
+        Some(&self.spec.id)
+    }
+
+    fn api_url(&self) -> Option<&str> {
+        self.spec.api_url.as_deref()
+    }
+
+    fn reconcile_policy(&self) -> ReconcilePolicy {
+        self.spec.reconcile_policy.unwrap_or_default()
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
@@ -26,4 +63,123 @@ pub struct AccountStatus {
     pub ready: bool,
     pub token_id: Option<String>,
     pub error: Option<String>,
+    pub name: Option<String>,
+    pub account_type: Option<String>,
+    pub created_on: Option<String>,
+    pub settings: Option<AccountSettings>,
+    pub zone_count: Option<u32>,
+    pub zone_quota: Option<u32>,
+    pub member_count: Option<u32>,
+    /// Rate plans the account is subscribed to
+    #[serde(default)]
+    pub plans: Vec<String>,
+    /// Type and usage in one line, for `kubectl get`
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Generation of the spec the status was written for
+    #[serde(rename = "observedGeneration")]
+    pub observed_generation: Option<i64>,
+    /// Reconciles failed in a row, gone once one succeeds
+    #[serde(rename = "consecutiveFailures")]
+    pub consecutive_failures: Option<u32>,
+}
+
+impl Conditions for AccountStatus {
+    fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition> {
+        &mut self.conditions
+    }
+
+    fn set_failed(&mut self, message: &str) {
+        self.ready = false;
+        self.error = Some(message.into());
+    }
+}
+
+impl AccountStatus {
+    /// Describe the account, like `standard: 12/50 zones, 4 members`
+    pub fn summarize(&self) -> String {
+        let mut parts = vec![];
+        match (self.zone_count, self.zone_quota) {
+            (Some(count), Some(quota)) => parts.push(format!("{count}/{quota} zones")),
+            (Some(count), None) => parts.push(format!("{count} zones")),
+            _ => {}
+        }
+        if let Some(members) = self.member_count {
+            parts.push(format!("{members} members"));
+        }
+        match &self.account_type {
+            Some(account_type) => format!("{account_type}: {}", parts.join(", ")),
+            None => parts.join(", "),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountSettings {
+    pub enforce_two_factor: Option<bool>,
+    pub abuse_contact_email: Option<String>,
+    pub use_account_custom_ns_by_default: Option<bool>,
+}
+
+impl AccountSettings {
+    /// The settings that differ from `current`, or `None` when everything set here matches
+    pub fn diff(&self, current: &AccountSettings) -> Option<Vec<&'static str>> {
+        let mut differs = vec![];
+        if self.enforce_two_factor.is_some() && self.enforce_two_factor != current.enforce_two_factor {
+            differs.push("enforceTwoFactor");
+        }
+        if self.abuse_contact_email.is_some() && self.abuse_contact_email != current.abuse_contact_email {
+            differs.push("abuseContactEmail");
+        }
+        if self.use_account_custom_ns_by_default.is_some()
+            && self.use_account_custom_ns_by_default != current.use_account_custom_ns_by_default
+        {
+            differs.push("useAccountCustomNsByDefault");
+        }
+        (!differs.is_empty()).then_some(differs)
+    }
+
+    /// Apply the settings set here on top of `current`
+    pub fn merged(&self, current: AccountSettings) -> AccountSettings {
+        AccountSettings {
+            enforce_two_factor: self.enforce_two_factor.or(current.enforce_two_factor),
+            abuse_contact_email: self.abuse_contact_email.clone().or(current.abuse_contact_email),
+            use_account_custom_ns_by_default: self
+                .use_account_custom_ns_by_default
+                .or(current.use_account_custom_ns_by_default),
+        }
+    }
+}
+
+impl From<AccountSettings> for cf_client::AccountSettings {
+    fn from(settings: AccountSettings) -> Self {
+        Self {
+            enforce_twofactor: settings.enforce_two_factor,
+            abuse_contact_email: settings.abuse_contact_email,
+            use_account_custom_ns_by_default: settings.use_account_custom_ns_by_default,
+        }
+    }
+}
+
+impl From<cf_client::AccountSettings> for AccountSettings {
+    fn from(settings: cf_client::AccountSettings) -> Self {
+        Self {
+            enforce_two_factor: settings.enforce_twofactor,
+            abuse_contact_email: settings.abuse_contact_email,
+            use_account_custom_ns_by_default: settings.use_account_custom_ns_by_default,
+        }
+    }
+}
+
+impl AccountStatus {
+    /// Whether the account can't take another zone
+    pub fn at_zone_quota(&self) -> bool {
+        matches!((self.zone_count, self.zone_quota), (Some(count), Some(quota)) if count >= quota)
+    }
 }
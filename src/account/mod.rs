@@ -1,5 +1,5 @@
 mod crd;
 mod reconcile;
 
-pub use crd::{Account, AccountSpec, AccountStatus};
+pub use crd::{Account, AccountSettings, AccountSpec, AccountStatus};
 pub use reconcile::{DOCUMENT_FINALIZER, run};
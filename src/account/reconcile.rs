@@ -1,22 +1,30 @@
 use crate::{
     Context, Error, Result, State,
-    account::{Account, AccountStatus},
-    telemetry,
+    account::{Account, AccountSettings, AccountStatus},
+    cf_client::{AccountDetails, CloudflareClient},
+    cloudflare::{self, CloudflareResource},
+    conditions::{Condition, Conditions},
+    deletion_policy::DeletionPolicy,
+    failures,
+    metrics::AccountLabels,
+    namespaces::scoped,
+    pause,
+    reconcile_policy::Drift,
+    settings::ControllerSettings,
+    status, telemetry,
 };
 use chrono::Utc;
 use futures::StreamExt;
 use kube::{
     Resource,
-    api::{Api, ListParams, Patch, PatchParams, ResourceExt},
+    api::{Api, ListParams, ResourceExt},
     client::Client,
     runtime::{
-        controller::{Action, Controller},
+        controller::Action,
         events::{Event, EventType},
         finalizer::{Event as Finalizer, finalizer},
-        watcher::Config,
     },
 };
-use serde_json::json;
 use std::sync::Arc;
 use tokio::time::Duration;
 use tracing::*;
@@ -28,79 +36,214 @@ async fn reconcile(doc: Arc<Account>, ctx: Arc<Context>) -> Result<Action> {
     if trace_id != opentelemetry::trace::TraceId::INVALID {
         Span::current().record("trace_id", field::display(&trace_id));
     }
-    let _timer = ctx.metrics.reconcile.count_and_measure(&trace_id);
+    let _timer = ctx.metrics.reconcile.count_and_measure(doc.as_ref(), &trace_id);
     ctx.diagnostics.write().await.last_event = Utc::now();
+    ctx.metrics.resources.track(doc.as_ref());
     let ns = doc.namespace().unwrap(); // doc is namespace scoped
     let docs: Api<Account> = Api::namespaced(ctx.client.clone(), &ns);
 
     info!("Reconciling Account \"{}\" in {}", doc.name_any(), ns);
-    finalizer(&docs, DOCUMENT_FINALIZER, doc, |event| async {
+    if pause::is_paused(doc.as_ref()) {
+        return pause::hold(doc.as_ref(), ctx.client.clone()).await;
+    }
+    let finalize = finalizer(&docs, DOCUMENT_FINALIZER, doc.clone(), |event| async {
         match event {
             Finalizer::Apply(doc) => doc.reconcile(ctx.clone()).await,
             Finalizer::Cleanup(doc) => doc.cleanup(ctx.clone()).await,
         }
-    })
-    .await
-    .map_err(|e| Error::FinalizerError(Box::new(e)))
+    });
+    let result = ctx
+        .audit
+        .record(doc.as_ref(), &ctx.recorder, finalize)
+        .await
+        .map_err(|e| Error::FinalizerError(Box::new(e)));
+    cloudflare::preflight::forbidden(doc.as_ref(), &ctx, result).await
 }
 
 fn error_policy(doc: Arc<Account>, error: &Error, ctx: Arc<Context>) -> Action {
     warn!("reconcile failed: {:?}", error);
     ctx.metrics.reconcile.set_failure(doc.as_ref(), error);
-    Action::requeue(Duration::from_secs(5 * 60))
+    failures::requeue(doc, error, &ctx)
 }
 
 impl Account {
     // Reconcile (for non-finalizer related changes)
     async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action> {
-        let client = ctx.client.clone();
         let ns = self.namespace().unwrap(); // we unwrap this, because it's probably impossible to
         // have no ns on the namespaced object
         let name = self.name_any();
-        let docs: Api<Account> = Api::namespaced(client, &ns);
 
         if name == "illegal" {
             return Err(Error::IllegalDocument); // error names show up in metrics
         }
 
-        let cf_client = ctx.provider.get_client(self, &ns).await.unwrap();
-        match cf_client.token_verify().await {
-            Ok(token_id) => {
-                docs.patch_status(
-                    &name,
-                    &PatchParams::apply("cntrlr").force(),
-                    &Patch::Apply(json!({
-                        "apiVersion": "cloudflare.com/v1alpha1",
-                        "kind": "Account",
-                        "status": AccountStatus {
-                            ready: true,
-                            token_id: Some(token_id),
-                            error: None,
-                        }
-                    })),
-                )
+        let (cf_client, source) = match ctx.provider.get_client_with_source(self, &ns).await {
+            Ok(resolved) => resolved,
+            Err(e) => return cloudflare::credentials_unavailable(self, &ctx, e).await,
+        };
+        // ready means the token works and can see the account
+        let lookup = match cf_client.token_verify(Some(&self.spec.id)).await {
+            Ok(token_id) => cf_client
+                .get_account(&self.spec.id)
+                .await
+                .map(|account| (token_id, account)),
+            Err(e) => Err(e),
+        };
+        let mut drift = Drift::default();
+        let lookup = match lookup {
+            Ok((token_id, account)) => self
+                .enforce_settings(&ctx, &cf_client, account, &mut drift)
                 .await
-                .map_err(Error::KubeError)?;
+                .map(|account| (token_id, account)),
+            Err(e) => Err(e.into()),
+        };
+        match lookup {
+            Ok((token_id, account)) => {
+                let mut status = AccountStatus {
+                    ready: drift.is_empty(),
+                    token_id: Some(token_id),
+                    error: None,
+                    name: Some(account.name),
+                    account_type: account.account_type,
+                    created_on: account.created_on.map(|created| created.to_rfc3339()),
+                    settings: account.settings.map(Into::into),
+                    conditions: self.conditions().to_vec(),
+                    ..Default::default()
+                };
+                status.set_ready("Verified", self.meta().generation);
+                drift.apply(&mut status, self.meta().generation);
+                status.set_condition(source.condition(self.conditions(), self.meta().generation));
+                status.set_condition(source.validity(self.conditions(), self.meta().generation));
+                self.collect_usage(&ctx, &cf_client, &mut status).await;
+                status.summary = Some(status.summarize());
+                status::patch(self, ctx.client.clone(), &status).await?;
 
-                return Ok(Action::requeue(Duration::from_secs(5 * 60)));
+                Ok(Action::requeue(ctx.settings.requeue))
             }
             Err(e) => {
-                docs.patch_status(
-                    &name,
-                    &PatchParams::apply("cntrlr").force(),
-                    &Patch::Apply(json!({
-                        "apiVersion": "cloudflare.com/v1alpha1",
-                        "kind": "Account",
-                        "status": AccountStatus {
-                            ready: false,
-                            token_id: None,
-                            error: Some(e.to_string()),
-                        }
-                    })),
-                )
-                .await
-                .map_err(Error::KubeError)?;
-                return Ok(Action::requeue(Duration::from_secs(60)));
+                let mut status = AccountStatus {
+                    ready: false,
+                    token_id: None,
+                    error: Some(e.to_string()),
+                    conditions: self.conditions().to_vec(),
+                    ..Default::default()
+                };
+                // a token that can't see the account is retried, it may have been granted access since
+                status.set_reconciling("VerificationFailed", e.to_string(), self.meta().generation);
+                status.set_condition(source.condition(self.conditions(), self.meta().generation));
+                status.set_condition(source.validity(self.conditions(), self.meta().generation));
+                status::patch(self, ctx.client.clone(), &status).await?;
+                Ok(Action::requeue(Duration::from_secs(60)))
+            }
+        }
+    }
+
+    /// Correct settings that differ from `spec.settings`, returns the account as it is afterwards
+    ///
+    /// Under `DriftReportOnly` the differences go to `drift` and the account is left as it is.
+    async fn enforce_settings(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        account: AccountDetails,
+        drift: &mut Drift,
+    ) -> anyhow::Result<AccountDetails> {
+        let Some(desired) = &self.spec.settings else {
+            return Ok(account);
+        };
+        let current: AccountSettings = account.settings.clone().unwrap_or_default().into();
+        let Some(drifted) = desired.diff(&current) else {
+            return Ok(account);
+        };
+        if self.report_only() {
+            let note = format!(
+                "Settings {} of account `{}` differ from the spec",
+                drifted.join(", "),
+                self.spec.id
+            );
+            drift.report(self, ctx, note).await;
+            return Ok(account);
+        }
+
+        let note = format!(
+            "Updated settings {} of account `{}` to match the spec",
+            drifted.join(", "),
+            self.spec.id
+        );
+        let merged = desired.merged(current);
+        let updated = cf_client
+            .update_account(&self.spec.id, account.name, merged.into())
+            .await?;
+        let reason = if status::spec_changed(self) {
+            "Updated"
+        } else {
+            warn!("Account \"{}\" drifted: {}", self.name_any(), note);
+            ctx.metrics.reconcile.set_drift(self);
+            "DriftCorrected"
+        };
+        ctx.recorder
+            .publish(
+                &Event {
+                    type_: EventType::Normal,
+                    reason: reason.into(),
+                    note: Some(note),
+                    action: "Reconciling".into(),
+                    secondary: None,
+                },
+                &self.object_ref(&()),
+            )
+            .await?;
+        Ok(updated)
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|s| s.conditions.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Fill in zone, member and plan usage, anything that can't be read is left out
+    async fn collect_usage(&self, ctx: &Context, cf_client: &CloudflareClient, status: &mut AccountStatus) {
+        let id = &self.spec.id;
+        let labels = AccountLabels {
+            namespace: self.namespace().unwrap_or_default(),
+            account: self.name_any(),
+        };
+
+        status.zone_quota = self.spec.zone_quota;
+        match cf_client.count_zones(id).await {
+            Ok(count) => status.zone_count = Some(count),
+            Err(e) => warn!("Failed to count zones of account {}: {}", id, e),
+        }
+        match cf_client.count_account_members(id).await {
+            Ok(count) => status.member_count = Some(count),
+            Err(e) => warn!("Failed to count members of account {}: {}", id, e),
+        }
+        match cf_client.list_account_subscriptions(id).await {
+            Ok(subscriptions) => {
+                status.plans = subscriptions
+                    .into_iter()
+                    .filter_map(|s| s.rate_plan)
+                    .map(|plan| plan.public_name.unwrap_or(plan.id))
+                    .collect();
+            }
+            Err(e) => warn!("Failed to list subscriptions of account {}: {}", id, e),
+        }
+
+        let metrics = &ctx.metrics.account;
+        for (family, value) in [
+            (&metrics.zones, status.zone_count),
+            (&metrics.zone_quota, status.zone_quota),
+            (&metrics.members, status.member_count),
+        ] {
+            match value {
+                Some(value) => {
+                    family.get_or_create(&labels).set(value.into());
+                }
+                None => {
+                    family.remove(&labels);
+                }
             }
         }
     }
@@ -108,13 +251,17 @@ impl Account {
     // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
     async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
         let oref = self.object_ref(&());
-        // Document doesn't have any real cleanup, so we just publish an event
+        // accounts are only ever adopted, API tokens can't delete them
+        let mut note = format!("Left account `{}` in Cloudflare", self.spec.id);
+        if DeletionPolicy::of(self, DeletionPolicy::Abandon) == DeletionPolicy::Delete {
+            note.push_str(", accounts can't be deleted by the operator");
+        }
         ctx.recorder
             .publish(
                 &Event {
                     type_: EventType::Normal,
-                    reason: "DeleteRequested".into(),
-                    note: Some(format!("Delete `{}`", self.name_any())),
+                    reason: "Abandoned".into(),
+                    note: Some(note),
                     action: "Deleting".into(),
                     secondary: None,
                 },
@@ -129,19 +276,29 @@ impl Account {
 /// Initialize the controller and shared state (given the crd is installed)
 pub async fn run(state: State) {
     let client = Client::try_default().await.expect("failed to create kube Client");
-    let docs = Api::<Account>::all(client.clone());
+    let scopes = state.namespaces().scopes();
+    let docs: Api<Account> = scoped(client.clone(), scopes[0].as_deref());
     if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
         error!("CRD is not queryable; {e:?}. Is the CRD installed?");
         info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
         std::process::exit(1);
     }
 
-    let api_key =
-        std::env::var("CLOUDFLARE_API_TOKEN").expect("CLOUDFLARE_API_TOKEN environment variable must be set");
-    Controller::new(docs, Config::default().any_semantic())
-        .shutdown_on_signal()
-        .run(reconcile, error_policy, state.to_context(client, api_key).await)
-        .filter_map(|x| async move { std::result::Result::ok(x) })
-        .for_each(|_| futures::future::ready(()))
+    let settings = ControllerSettings::from_env("Account");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
         .await;
+    // one controller per watched namespace, they share the context and with it the Cloudflare clients
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        let controller = state
+            .controller("Account", scoped::<Account>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config());
+        state.health().watch("Account", controller.store());
+        controller
+            .reconcile_on(state.triggers().account.stream())
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("Account", result))
+    }))
+    .await;
 }
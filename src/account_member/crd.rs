@@ -0,0 +1,85 @@
+use k8s_openapi::api::core::v1::LocalObjectReference;
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cloudflare::CloudflareResource,
+    conditions::{Condition, Conditions},
+    reconcile_policy::ReconcilePolicy,
+};
+
+/// Membership of a person in a Cloudflare account
+///
+/// The person is invited when the object is created and removed from the account when it's deleted,
+/// unless the `cloudflare.com/deletion-policy` annotation is `abandon`.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(Default))]
+#[kube(
+    kind = "AccountMember",
+    group = "cloudflare.com",
+    version = "v1alpha1",
+    namespaced
+)]
+#[kube(
+    status = "AccountMemberStatus",
+    shortname = "member",
+    shortname = "cfmember",
+    category = "cloudflare"
+)]
+#[kube(printcolumn = r#"{"name":"Email", "type":"string", "jsonPath":".spec.email"}"#)]
+#[kube(printcolumn = r#"{"name":"Ready", "type":"boolean", "jsonPath":".status.ready"}"#)]
+#[kube(printcolumn = r#"{"name":"Membership", "type":"string", "jsonPath":".status.membershipStatus"}"#)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountMemberSpec {
+    pub account_ref: LocalObjectReference,
+    pub email: String,
+    /// Ids of the account roles granted to the member
+    pub roles: Vec<String>,
+    /// `DriftReportOnly` to only compare the membership with Cloudflare, `Enforce` by default
+    pub reconcile_policy: Option<ReconcilePolicy>,
+}
+
+impl CloudflareResource for AccountMember {
+    const PERMISSIONS: &'static [&'static str] = &["Memberships:Edit"];
+
+    fn account_ref(&self) -> Option<&LocalObjectReference> {
+        Some(&self.spec.account_ref)
+    }
+
+    fn reconcile_policy(&self) -> ReconcilePolicy {
+        self.spec.reconcile_policy.unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountMemberStatus {
+    /// Set once the invitation is accepted
+    pub ready: bool,
+    pub member_id: Option<String>,
+    /// `pending` while the invitation is open, `accepted` once it's taken
+    pub membership_status: Option<String>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Generation of the spec the status was written for
+    pub observed_generation: Option<i64>,
+    /// Reconciles failed in a row, gone once one succeeds
+    pub consecutive_failures: Option<u32>,
+}
+
+impl Conditions for AccountMemberStatus {
+    fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition> {
+        &mut self.conditions
+    }
+
+    fn set_failed(&mut self, message: &str) {
+        self.ready = false;
+        self.error = Some(message.into());
+    }
+}
@@ -0,0 +1,5 @@
+mod crd;
+mod reconcile;
+
+pub use crd::{AccountMember, AccountMemberSpec, AccountMemberStatus};
+pub use reconcile::{DOCUMENT_FINALIZER, run};
@@ -0,0 +1,338 @@
+use crate::{
+    Context, Error, Result, State,
+    account::Account,
+    account_member::{AccountMember, AccountMemberStatus},
+    cf_client::{AccountMember as CfMember, CloudflareClient},
+    cloudflare::{self, CloudflareResource},
+    conditions::{Condition, Conditions},
+    deletion_policy::DeletionPolicy,
+    dependency::{wait_for_dependency, wake_dependents},
+    failures,
+    namespaces::scoped,
+    pause,
+    reconcile_policy::Drift,
+    settings::ControllerSettings,
+    status, telemetry,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use kube::{
+    Resource,
+    api::{Api, ListParams, ResourceExt},
+    client::Client,
+    runtime::{
+        controller::Action,
+        events::{Event, EventType},
+        finalizer::{Event as Finalizer, finalizer},
+        watcher::Config,
+    },
+};
+use std::{collections::BTreeSet, sync::Arc};
+use tracing::*;
+pub static DOCUMENT_FINALIZER: &str = "accountmember.cloudflare.com";
+
+#[instrument(skip(ctx, doc), fields(trace_id))]
+async fn reconcile(doc: Arc<AccountMember>, ctx: Arc<Context>) -> Result<Action> {
+    let trace_id = telemetry::get_trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        Span::current().record("trace_id", field::display(&trace_id));
+    }
+    let _timer = ctx.metrics.reconcile.count_and_measure(doc.as_ref(), &trace_id);
+    ctx.diagnostics.write().await.last_event = Utc::now();
+    ctx.metrics.resources.track(doc.as_ref());
+    let ns = doc.namespace().unwrap(); // doc is namespace scoped
+    let docs: Api<AccountMember> = Api::namespaced(ctx.client.clone(), &ns);
+
+    info!("Reconciling AccountMember \"{}\" in {}", doc.name_any(), ns);
+    if pause::is_paused(doc.as_ref()) {
+        return pause::hold(doc.as_ref(), ctx.client.clone()).await;
+    }
+    let finalize = finalizer(&docs, DOCUMENT_FINALIZER, doc.clone(), |event| async {
+        match event {
+            Finalizer::Apply(doc) => doc.reconcile(ctx.clone()).await,
+            Finalizer::Cleanup(doc) => doc.cleanup(ctx.clone()).await,
+        }
+    });
+    let result = ctx
+        .audit
+        .record(doc.as_ref(), &ctx.recorder, finalize)
+        .await
+        .map_err(|e| Error::FinalizerError(Box::new(e)));
+    cloudflare::preflight::forbidden(doc.as_ref(), &ctx, result).await
+}
+
+fn error_policy(doc: Arc<AccountMember>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}", error);
+    ctx.metrics.reconcile.set_failure(doc.as_ref(), error);
+    failures::requeue(doc, error, &ctx)
+}
+
+impl AccountMember {
+    // Reconcile (for non-finalizer related changes)
+    async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action> {
+        let client = ctx.client.clone();
+        let ns = self.namespace().unwrap(); // doc is namespace scoped
+        let name = self.name_any();
+
+        let account = match wait_for_dependency::<Account>(client, &ns, &self.spec.account_ref.name).await? {
+            Ok(account) => account,
+            Err(blocked) => {
+                warn!("AccountMember \"{}\": {}", name, blocked.message());
+                self.publish(&ctx, "DependencyNotReady", blocked.message()).await;
+                let mut status = AccountMemberStatus {
+                    ready: false,
+                    error: Some(blocked.message()),
+                    ..self.status.clone().unwrap_or_default()
+                };
+                status.set_reconciling("DependencyNotReady", blocked.message(), self.meta().generation);
+                status::patch(self, ctx.client.clone(), &status).await?;
+                // the Account watch wakes us up once it's ready, this is just a fallback
+                return Ok(Action::requeue(ctx.settings.requeue));
+            }
+        };
+
+        // the token of the account, members are managed on its behalf
+        let (cf_client, source) = match ctx.provider.get_client_with_source(&account, &ns).await {
+            Ok(resolved) => resolved,
+            Err(e) => return cloudflare::credentials_unavailable(self, &ctx, e).await,
+        };
+        let mut drift = Drift::default();
+        let mut status = match self
+            .converge(&ctx, &cf_client, &account.spec.id, &mut drift)
+            .await
+        {
+            Ok(None) => AccountMemberStatus {
+                ready: false,
+                member_id: None,
+                membership_status: None,
+                error: None,
+                conditions: self.conditions().to_vec(),
+                ..Default::default()
+            },
+            Ok(Some(member)) => {
+                let accepted = member.status.as_deref() == Some("accepted");
+                let mut status = AccountMemberStatus {
+                    observed_generation: None, // set by status::patch
+                    consecutive_failures: None,
+                    ready: accepted,
+                    member_id: Some(member.id),
+                    membership_status: member.status,
+                    error: None,
+                    conditions: self.conditions().to_vec(),
+                };
+                if accepted {
+                    status.set_ready("Accepted", self.meta().generation);
+                } else {
+                    let message = format!("Waiting for {} to accept the invitation", self.spec.email);
+                    status.set_reconciling("InvitationPending", message, self.meta().generation);
+                }
+                status
+            }
+            Err(e) => {
+                warn!("AccountMember \"{}\": {:?}", name, e);
+                let mut status = AccountMemberStatus {
+                    ready: false,
+                    error: Some(e.to_string()),
+                    ..self.status.clone().unwrap_or_default()
+                };
+                status.set_reconciling("SyncFailed", e.to_string(), self.meta().generation);
+                status
+            }
+        };
+        status.set_condition(source.condition(self.conditions(), self.meta().generation));
+        status.set_condition(source.validity(self.conditions(), self.meta().generation));
+        drift.apply(&mut status, self.meta().generation);
+        status::patch(self, ctx.client.clone(), &status).await?;
+
+        Ok(Action::requeue(ctx.settings.requeue))
+    }
+
+    /// Find the membership, inviting the person when there is none, and bring its roles in line
+    ///
+    /// Under `DriftReportOnly` nobody is invited and roles are left alone, the differences go to `drift`.
+    async fn converge(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        account_id: &str,
+        drift: &mut Drift,
+    ) -> anyhow::Result<Option<CfMember>> {
+        let known = match self.status.as_ref().and_then(|s| s.member_id.as_deref()) {
+            Some(member_id) => cf_client.get_account_member(account_id, member_id).await?,
+            None => None,
+        };
+        let existing = match known {
+            Some(member) => Some(member),
+            // someone may have invited them by hand already
+            None => cf_client
+                .list_account_members(account_id)
+                .await?
+                .into_iter()
+                .find(|member| member.user.email.eq_ignore_ascii_case(&self.spec.email)),
+        };
+
+        let Some(member) = existing else {
+            if self.report_only() {
+                let note = format!("`{}` is not a member of the account", self.spec.email);
+                drift.report(self, ctx, note).await;
+                return Ok(None);
+            }
+            let member = cf_client
+                .create_account_member(account_id, self.spec.email.clone(), self.spec.roles.clone())
+                .await?;
+            self.publish(
+                ctx,
+                "Created",
+                format!(
+                    "Invited `{}` to the account as member `{}`",
+                    self.spec.email, member.id
+                ),
+            )
+            .await;
+            return Ok(Some(member));
+        };
+
+        let current: BTreeSet<_> = member.roles.iter().map(|role| role.id.as_str()).collect();
+        let desired: BTreeSet<_> = self.spec.roles.iter().map(String::as_str).collect();
+        if current == desired {
+            return Ok(Some(member));
+        }
+        if self.report_only() {
+            let note = format!(
+                "The roles of `{}` (member `{}`) differ from the spec",
+                self.spec.email, member.id
+            );
+            drift.report(self, ctx, note).await;
+            return Ok(Some(member));
+        }
+        info!(
+            "Updating roles of {} ({}) in account {}",
+            self.spec.email, member.id, account_id
+        );
+        let member = cf_client
+            .update_account_member(account_id, &member.id, self.spec.roles.clone())
+            .await?;
+        let note = format!(
+            "Updated the roles of `{}` (member `{}`)",
+            self.spec.email, member.id
+        );
+        if status::spec_changed(self) {
+            self.publish(ctx, "Updated", note).await;
+        } else {
+            ctx.metrics.reconcile.set_drift(self);
+            self.publish(ctx, "DriftCorrected", note).await;
+        }
+        Ok(Some(member))
+    }
+
+    async fn publish(&self, ctx: &Context, reason: &str, note: String) {
+        let event = Event {
+            type_: EventType::Normal,
+            reason: reason.into(),
+            note: Some(note),
+            action: "Reconciling".into(),
+            secondary: None,
+        };
+        if let Err(e) = ctx.recorder.publish(&event, &self.object_ref(&())).await {
+            warn!("failed to publish {} event: {}", reason, e);
+        }
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|s| s.conditions.as_slice())
+            .unwrap_or_default()
+    }
+
+    // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
+    async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
+        let abandon = DeletionPolicy::abandons(self) || self.report_only();
+        let member_id = self.status.as_ref().and_then(|s| s.member_id.clone());
+
+        match member_id {
+            Some(_) if abandon => {
+                self.publish(
+                    &ctx,
+                    "Abandoned",
+                    format!(
+                        "Left `{}` in the account as requested by the deletion or reconcile policy",
+                        self.spec.email
+                    ),
+                )
+                .await
+            }
+            Some(member_id) => {
+                let ns = self.namespace().unwrap(); // doc is namespace scoped
+                let accounts: Api<Account> = Api::namespaced(ctx.client.clone(), &ns);
+                // without the Account there's no telling which account to remove them from
+                let Some(account) = accounts
+                    .get_opt(&self.spec.account_ref.name)
+                    .await
+                    .map_err(Error::KubeError)?
+                else {
+                    warn!(
+                        "Account {} is gone, leaving member {} in place",
+                        self.spec.account_ref.name, member_id
+                    );
+                    return Ok(Action::await_change());
+                };
+                let cf_client = ctx.provider.get_client(&account, &ns).await?;
+                // an error keeps the finalizer in place, so the object stays around until the removal goes through
+                cf_client
+                    .delete_account_member(&account.spec.id, &member_id)
+                    .await?;
+                self.publish(
+                    &ctx,
+                    "Removed",
+                    format!("Removed `{}` from the account", self.spec.email),
+                )
+                .await
+            }
+            // the invitation never went out
+            None => {}
+        }
+        Ok(Action::await_change())
+    }
+}
+
+/// Initialize the controller and shared state (given the crd is installed)
+pub async fn run(state: State) {
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let scopes = state.namespaces().scopes();
+    let docs: Api<AccountMember> = scoped(client.clone(), scopes[0].as_deref());
+    if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
+        error!("CRD is not queryable; {e:?}. Is the CRD installed?");
+        info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
+        std::process::exit(1);
+    }
+
+    let settings = ControllerSettings::from_env("AccountMember");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
+        .await;
+    // one controller per watched namespace, they share the context and with it the Cloudflare clients
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        let controller = state
+            .controller(
+                "AccountMember",
+                scoped::<AccountMember>(client.clone(), ns.as_deref()),
+            )
+            .with_config(settings.controller_config());
+        let members = controller.store();
+        state.health().watch("AccountMember", members.clone());
+        controller
+            .watches(
+                scoped::<Account>(client.clone(), ns.as_deref()),
+                Config::default(),
+                wake_dependents(members, |member: &AccountMember| {
+                    Some(member.spec.account_ref.name.as_str())
+                }),
+            )
+            .reconcile_on(state.triggers().account_member.stream())
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("AccountMember", result))
+    }))
+    .await;
+}
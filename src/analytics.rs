@@ -0,0 +1,172 @@
+use crate::{Context, State, metrics::ZoneLabels, zone::Zone};
+use chrono::{DateTime, SecondsFormat, Utc};
+use kube::{Client, ResourceExt};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::*;
+
+/// Traffic of a zone over one hour, totals only
+const ZONE_QUERY: &str = r#"
+query ZoneAnalytics($zoneTag: string, $since: Time, $until: Time) {
+  viewer {
+    zones(filter: { zoneTag: $zoneTag }) {
+      httpRequests1hGroups(limit: 1, filter: { datetime_geq: $since, datetime_lt: $until }) {
+        sum {
+          requests
+          cachedRequests
+          threats
+          bytes
+          responseStatusMap {
+            edgeResponseStatus
+            requests
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+#[derive(Deserialize)]
+struct AnalyticsData {
+    viewer: Viewer,
+}
+
+#[derive(Deserialize)]
+struct Viewer {
+    zones: Vec<ZoneAnalytics>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ZoneAnalytics {
+    http_requests1h_groups: Vec<RequestGroup>,
+}
+
+#[derive(Deserialize)]
+struct RequestGroup {
+    sum: RequestSum,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RequestSum {
+    requests: i64,
+    cached_requests: i64,
+    threats: i64,
+    bytes: i64,
+    #[serde(default)]
+    response_status_map: Vec<StatusCount>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusCount {
+    edge_response_status: u16,
+    requests: i64,
+}
+
+/// Poll the analytics API for every ready Zone and expose the numbers as metrics
+///
+/// Only runs when `CLOUDFLARE_ANALYTICS_INTERVAL` (in seconds) is set.
+pub async fn run(state: State) {
+    let Some(interval) = std::env::var("CLOUDFLARE_ANALYTICS_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+    else {
+        // disabled, but we are raced against the controllers so never finish
+        return futures::future::pending().await;
+    };
+
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let ctx = state.to_context(client).await;
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    loop {
+        ticker.tick().await;
+        if let Err(e) = poll(ctx.clone()).await {
+            warn!("analytics poll failed: {e:?}");
+        }
+    }
+}
+
+async fn poll(ctx: Arc<Context>) -> anyhow::Result<()> {
+    // the last complete hour, the current one is still being aggregated
+    let now = Utc::now().timestamp();
+    let until = DateTime::from_timestamp(now - now % 3600, 0).unwrap_or_default();
+    let since = until - chrono::Duration::hours(1);
+
+    for zone in ctx.namespaces.list::<Zone>(&ctx.client).await? {
+        let Some(zone_id) = zone
+            .status
+            .as_ref()
+            .filter(|s| s.ready)
+            .and_then(|s| s.id.clone())
+        else {
+            continue;
+        };
+        let ns = zone.namespace().unwrap(); // zone is namespace scoped
+        let labels = ZoneLabels {
+            namespace: ns.clone(),
+            zone: zone.name_any(),
+        };
+
+        let cf_client = match ctx.provider.get_client(&zone, &ns).await {
+            Ok(cf_client) => cf_client,
+            Err(e) => {
+                warn!("no client for zone {}/{}: {e}", ns, zone.name_any());
+                continue;
+            }
+        };
+        let variables = json!({
+            "zoneTag": zone_id,
+            "since": since.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "until": until.to_rfc3339_opts(SecondsFormat::Secs, true),
+        });
+        let data: AnalyticsData = match cf_client.graphql(ZONE_QUERY, variables).await {
+            Ok(data) => data,
+            Err(e) => {
+                warn!(
+                    "analytics query for zone {}/{} failed: {e:?}",
+                    ns,
+                    zone.name_any()
+                );
+                continue;
+            }
+        };
+
+        let sum = data
+            .viewer
+            .zones
+            .into_iter()
+            .flat_map(|z| z.http_requests1h_groups)
+            .next()
+            .map(|g| g.sum)
+            .unwrap_or_default();
+        let origin_errors: i64 = sum
+            .response_status_map
+            .iter()
+            .filter(|s| s.edge_response_status >= 500)
+            .map(|s| s.requests)
+            .sum();
+
+        let metrics = &ctx.metrics.analytics;
+        metrics.requests.get_or_create(&labels).set(sum.requests);
+        metrics
+            .cached_requests
+            .get_or_create(&labels)
+            .set(sum.cached_requests);
+        metrics.threats.get_or_create(&labels).set(sum.threats);
+        metrics.origin_errors.get_or_create(&labels).set(origin_errors);
+        metrics.bytes.get_or_create(&labels).set(sum.bytes);
+        let ratio = if sum.requests > 0 {
+            sum.cached_requests as f64 / sum.requests as f64
+        } else {
+            0.0
+        };
+        metrics.cache_ratio.get_or_create(&labels).set(ratio);
+    }
+    Ok(())
+}
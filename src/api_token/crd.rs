@@ -0,0 +1,139 @@
+use k8s_openapi::api::core::v1::{LocalObjectReference, SecretKeySelector};
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    cf_client,
+    cloudflare::CloudflareResource,
+    conditions::{Condition, Conditions},
+};
+
+/// A scoped Cloudflare API token, kept in a Secret next to this object
+///
+/// The token is created with the credentials of the account (or `secretRef`), which need the
+/// `API Tokens:Edit` permission. Expiring tokens are replaced by a fresh one before they run out, the
+/// replaced one is deleted after `rotationGraceSeconds`.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(Default))]
+#[kube(kind = "APIToken", group = "cloudflare.com", version = "v1alpha1", namespaced)]
+#[kube(status = "APITokenStatus", shortname = "cftoken", category = "cloudflare")]
+#[kube(printcolumn = r#"{"name":"Ready", "type":"boolean", "jsonPath":".status.ready"}"#)]
+#[kube(printcolumn = r#"{"name":"Secret", "type":"string", "jsonPath":".spec.secretName"}"#)]
+#[kube(printcolumn = r#"{"name":"Expires", "type":"date", "jsonPath":".status.expiresOn"}"#)]
+#[serde(rename_all = "camelCase")]
+pub struct APITokenSpec {
+    pub account_ref: Option<LocalObjectReference>,
+    pub secret_ref: Option<SecretKeySelector>,
+    pub policies: Vec<TokenPolicy>,
+    /// Lifetime of the token, it doesn't expire when unset
+    pub expiration_seconds: Option<u64>,
+    /// How long before expiry the token is replaced, a fifth of its lifetime by default
+    pub rotate_before_seconds: Option<u64>,
+    /// How long a replaced token keeps working, so consumers can pick up its successor, ten minutes by
+    /// default
+    pub rotation_grace_seconds: Option<u64>,
+    /// Secret the token value is written to, under the `token` key, with its id under `token-id`
+    pub secret_name: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenPolicy {
+    #[serde(default)]
+    pub effect: PolicyEffect,
+    /// Permission group ids, as listed by `GET /user/tokens/permission_groups`
+    pub permission_groups: Vec<String>,
+    /// Resources the permissions apply to, like `com.cloudflare.api.account.zone.<zone id>: "*"`
+    pub resources: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEffect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+impl From<&TokenPolicy> for cf_client::TokenPolicy {
+    fn from(policy: &TokenPolicy) -> Self {
+        cf_client::TokenPolicy {
+            effect: serde_json::to_value(policy.effect)
+                .ok()
+                .and_then(|effect| effect.as_str().map(String::from))
+                .unwrap_or_default(),
+            permission_groups: policy
+                .permission_groups
+                .iter()
+                .map(|id| cf_client::PermissionGroupId { id: id.clone() })
+                .collect(),
+            resources: policy
+                .resources
+                .iter()
+                .map(|(resource, scope)| (resource.clone(), scope.clone().into()))
+                .collect(),
+        }
+    }
+}
+
+impl APIToken {
+    /// When to replace a token that expires after `lifetime` seconds
+    pub fn rotate_before(&self, lifetime: u64) -> u64 {
+        self.spec.rotate_before_seconds.unwrap_or(lifetime / 5)
+    }
+
+    /// How long a replaced token is kept before it's deleted
+    pub fn rotation_grace(&self) -> u64 {
+        self.spec.rotation_grace_seconds.unwrap_or(600)
+    }
+}
+
+impl CloudflareResource for APIToken {
+    const PERMISSIONS: &'static [&'static str] = &["API Tokens:Edit"];
+
+    fn secret_ref(&self) -> Option<&SecretKeySelector> {
+        self.spec.secret_ref.as_ref()
+    }
+
+    fn account_ref(&self) -> Option<&LocalObjectReference> {
+        self.spec.account_ref.as_ref()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct APITokenStatus {
+    pub ready: bool,
+    pub token_id: Option<String>,
+    pub expires_on: Option<String>,
+    /// When the current token was created
+    pub issued_on: Option<String>,
+    /// The token replaced last, deleted once the grace period is over
+    pub retired_token_id: Option<String>,
+    /// When `retiredTokenId` was replaced
+    pub retired_on: Option<String>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Generation of the spec the status was written for
+    pub observed_generation: Option<i64>,
+    /// Reconciles failed in a row, gone once one succeeds
+    pub consecutive_failures: Option<u32>,
+}
+
+impl Conditions for APITokenStatus {
+    fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition> {
+        &mut self.conditions
+    }
+
+    fn set_failed(&mut self, message: &str) {
+        self.ready = false;
+        self.error = Some(message.into());
+    }
+}
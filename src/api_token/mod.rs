@@ -0,0 +1,5 @@
+mod crd;
+mod reconcile;
+
+pub use crd::{APIToken, APITokenSpec, APITokenStatus, PolicyEffect, TokenPolicy};
+pub use reconcile::{DOCUMENT_FINALIZER, SECRET_KEY, run};
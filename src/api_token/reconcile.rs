@@ -0,0 +1,422 @@
+use crate::{
+    Context, Error, Result, State,
+    account::Account,
+    api_token::{APIToken, APITokenStatus},
+    cf_client::{ApiToken, ApiTokenParams, CloudflareClient, TokenPolicy},
+    cloudflare,
+    conditions::Conditions,
+    deletion_policy::DeletionPolicy,
+    dependency::{Blocked, wait_for_dependency, wake_dependents},
+    failures,
+    namespaces::scoped,
+    pause,
+    settings::ControllerSettings,
+    status, telemetry,
+};
+use anyhow::anyhow;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::Secret;
+use kube::{
+    Resource,
+    api::{Api, ListParams, Patch, PatchParams, ResourceExt},
+    client::Client,
+    runtime::{
+        controller::Action,
+        events::{Event, EventType},
+        finalizer::{Event as Finalizer, finalizer},
+        watcher::Config,
+    },
+};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::*;
+pub static DOCUMENT_FINALIZER: &str = "apitoken.cloudflare.com";
+/// Key of the target Secret holding the token value
+pub static SECRET_KEY: &str = "token";
+/// Key of the target Secret holding the id of the token the value belongs to
+pub static TOKEN_ID_KEY: &str = "token-id";
+
+
+#[instrument(skip(ctx, doc), fields(trace_id))]
+async fn reconcile(doc: Arc<APIToken>, ctx: Arc<Context>) -> Result<Action> {
+    let trace_id = telemetry::get_trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        Span::current().record("trace_id", field::display(&trace_id));
+    }
+    let _timer = ctx.metrics.reconcile.count_and_measure(doc.as_ref(), &trace_id);
+    ctx.diagnostics.write().await.last_event = Utc::now();
+    ctx.metrics.resources.track(doc.as_ref());
+    let ns = doc.namespace().unwrap(); // doc is namespace scoped
+    let docs: Api<APIToken> = Api::namespaced(ctx.client.clone(), &ns);
+
+    info!("Reconciling APIToken \"{}\" in {}", doc.name_any(), ns);
+    if pause::is_paused(doc.as_ref()) {
+        return pause::hold(doc.as_ref(), ctx.client.clone()).await;
+    }
+    let finalize = finalizer(&docs, DOCUMENT_FINALIZER, doc.clone(), |event| async {
+        match event {
+            Finalizer::Apply(doc) => doc.reconcile(ctx.clone()).await,
+            Finalizer::Cleanup(doc) => doc.cleanup(ctx.clone()).await,
+        }
+    });
+    ctx.audit
+        .record(doc.as_ref(), &ctx.recorder, finalize)
+        .await
+        .map_err(|e| Error::FinalizerError(Box::new(e)))
+}
+
+fn error_policy(doc: Arc<APIToken>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}", error);
+    ctx.metrics.reconcile.set_failure(doc.as_ref(), error);
+    failures::requeue(doc, error, &ctx)
+}
+
+impl APIToken {
+    // Reconcile (for non-finalizer related changes)
+    async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action> {
+        let client = ctx.client.clone();
+        let ns = self.namespace().unwrap(); // doc is namespace scoped
+        let name = self.name_any();
+
+        let cf_client = match self.issuer(&ctx, &ns).await {
+            Ok(Ok(cf_client)) => cf_client,
+            Err(Error::CredentialsError(e)) => {
+                return cloudflare::credentials_unavailable(self, &ctx, e).await;
+            }
+            Err(e) => return Err(e),
+            Ok(Err(blocked)) => {
+                warn!("APIToken \"{}\": {}", name, blocked.message());
+                self.publish(&ctx, "DependencyNotReady", blocked.message()).await;
+                let mut status = APITokenStatus {
+                    ready: false,
+                    error: Some(blocked.message()),
+                    ..self.status.clone().unwrap_or_default()
+                };
+                status.set_reconciling("DependencyNotReady", blocked.message(), self.meta().generation);
+                status::patch(self, ctx.client.clone(), &status).await?;
+                // the Account watch wakes us up once it's ready, this is just a fallback
+                return Ok(Action::requeue(ctx.settings.requeue));
+            }
+        };
+
+        let secrets: Api<Secret> = Api::namespaced(client, &ns);
+        // tokens are recorded on it as soon as they exist, a failure further on must not lose them
+        let mut status = self.status.clone().unwrap_or_default();
+        let requeue = match self.converge(&ctx, &cf_client, &secrets, &mut status).await {
+            Ok(token) => {
+                let requeue = self.next_check(&token, &status, ctx.settings.requeue);
+                status.ready = true;
+                status.expires_on = token.expires_on.map(|at| at.to_rfc3339());
+                status.token_id = Some(token.id);
+                status.error = None;
+                status.set_ready("Issued", self.meta().generation);
+                requeue
+            }
+            Err(e) => {
+                warn!("APIToken \"{}\": {:?}", name, e);
+                status.ready = false;
+                status.error = Some(e.to_string());
+                status.set_reconciling("IssueFailed", e.to_string(), self.meta().generation);
+                ctx.settings.requeue
+            }
+        };
+        status::patch(self, ctx.client.clone(), &status).await?;
+
+        Ok(Action::requeue(requeue))
+    }
+
+    /// Client with the credentials the token is issued with
+    async fn issuer(&self, ctx: &Context, ns: &str) -> Result<Result<Arc<CloudflareClient>, Blocked>> {
+        let Some(a_ref) = &self.spec.account_ref else {
+            return Ok(Ok(ctx.provider.get_client(self, ns).await?));
+        };
+        Ok(
+            match wait_for_dependency::<Account>(ctx.client.clone(), ns, &a_ref.name).await? {
+                Ok(account) => Ok(ctx.provider.get_client(&account, ns).await?),
+                Err(blocked) => Err(blocked),
+            },
+        )
+    }
+
+    /// Make sure a live token with the right policies sits in the Secret, returns it
+    ///
+    /// `status` keeps track of the current and the retired token as they change.
+    async fn converge(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        secrets: &Api<Secret>,
+        status: &mut APITokenStatus,
+    ) -> anyhow::Result<ApiToken> {
+        if self.grace_over(status) {
+            self.delete_retired(ctx, cf_client, status).await?;
+        }
+        let current = match status.token_id.as_deref() {
+            Some(token_id) => cf_client.get_api_token(token_id).await?,
+            None => None,
+        };
+        // Secrets written before the id was kept along are taken to hold the current token
+        let in_secret = secrets
+            .get_opt(&self.spec.secret_name)
+            .await?
+            .and_then(|secret| secret.data)
+            .is_some_and(|data| {
+                data.contains_key(SECRET_KEY)
+                    && data
+                        .get(TOKEN_ID_KEY)
+                        .is_none_or(|id| current.as_ref().is_some_and(|token| id.0 == token.id.as_bytes()))
+            });
+
+        let replace = match &current {
+            None => Some("Created"),
+            // the value is only handed out once, so a lost Secret means a new token
+            Some(_) if !in_secret => Some("Reissued"),
+            Some(token) if token.status.as_deref().is_some_and(|s| s != "active") => Some("Reissued"),
+            Some(token) if self.rotation_due(token) => Some("Rotated"),
+            Some(_) => None,
+        };
+        let Some(reason) = replace else {
+            let token = current.expect("a token is only kept when there is one");
+            return self.sync_policies(ctx, cf_client, token).await;
+        };
+
+        // make room before anything is created, so a failure leaves nothing untracked behind
+        match &current {
+            // consumers may hold its value, it's retired in place of the one retired before
+            Some(_) if in_secret => self.delete_retired(ctx, cf_client, status).await?,
+            // nobody got its value
+            Some(old) => {
+                cf_client.delete_api_token(&old.id).await?;
+                status.token_id = None;
+            }
+            None => {}
+        }
+        let expires_on = self
+            .spec
+            .expiration_seconds
+            .map(|secs| Utc::now() + TimeDelta::seconds(secs as i64));
+        let token = cf_client.create_api_token(self.params(expires_on)).await?;
+        if let Some(old) = current.filter(|_| in_secret) {
+            status.retired_token_id = Some(old.id);
+            status.retired_on = Some(Utc::now().to_rfc3339());
+        }
+        status.token_id = Some(token.id.clone());
+        status.issued_on = Some(Utc::now().to_rfc3339());
+        status::patch(self, ctx.client.clone(), &*status).await?;
+
+        let value = token
+            .value
+            .clone()
+            .ok_or_else(|| anyhow!("Cloudflare returned no value for token {}", token.id))?;
+        self.write_secret(secrets, &token.id, value).await?;
+        info!("{} token {} for {}", reason, token.id, self.name_any());
+        self.publish(
+            ctx,
+            reason,
+            format!("Wrote token `{}` to Secret `{}`", token.id, self.spec.secret_name),
+        )
+        .await;
+        Ok(token)
+    }
+
+    /// Whether the retired token has been kept long enough
+    fn grace_over(&self, status: &APITokenStatus) -> bool {
+        self.retire_at(status).is_some_and(|at| at <= Utc::now())
+    }
+
+    /// When the retired token is deleted, `None` when there is none
+    fn retire_at(&self, status: &APITokenStatus) -> Option<DateTime<Utc>> {
+        status.retired_token_id.as_ref()?;
+        let retired_on = status
+            .retired_on
+            .as_deref()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            .map_or_else(Utc::now, |at| at.with_timezone(&Utc));
+        Some(retired_on + TimeDelta::seconds(self.rotation_grace() as i64))
+    }
+
+    async fn delete_retired(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        status: &mut APITokenStatus,
+    ) -> anyhow::Result<()> {
+        let Some(token_id) = status.retired_token_id.take() else {
+            return Ok(());
+        };
+        if let Err(e) = cf_client.delete_api_token(&token_id).await {
+            status.retired_token_id = Some(token_id);
+            return Err(e.into());
+        }
+        status.retired_on = None;
+        self.publish(ctx, "Retired", format!("Deleted replaced token `{token_id}`"))
+            .await;
+        Ok(())
+    }
+
+    /// Bring the policies of the token in line with the spec, the value doesn't change
+    async fn sync_policies(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        token: ApiToken,
+    ) -> anyhow::Result<ApiToken> {
+        let desired: Vec<TokenPolicy> = self.spec.policies.iter().map(TokenPolicy::from).collect();
+        if token.policies == desired {
+            return Ok(token);
+        }
+        let token = cf_client
+            .update_api_token(&token.id, self.params(token.expires_on))
+            .await?;
+        let note = format!("Updated the policies of token `{}`", token.id);
+        if status::spec_changed(self) {
+            self.publish(ctx, "Updated", note).await;
+        } else {
+            ctx.metrics.reconcile.set_drift(self);
+            self.publish(ctx, "DriftCorrected", note).await;
+        }
+        Ok(token)
+    }
+
+    fn params(&self, expires_on: Option<DateTime<Utc>>) -> ApiTokenParams {
+        ApiTokenParams {
+            name: format!("{}/{}", self.namespace().unwrap_or_default(), self.name_any()),
+            policies: self.spec.policies.iter().map(TokenPolicy::from).collect(),
+            expires_on,
+        }
+    }
+
+    /// When the token has to be replaced, `None` for tokens that don't expire
+    fn rotate_at(&self, token: &ApiToken) -> Option<DateTime<Utc>> {
+        let lifetime = self.spec.expiration_seconds.unwrap_or_default();
+        let lead = TimeDelta::seconds(self.rotate_before(lifetime) as i64);
+        token.expires_on.map(|expires_on| expires_on - lead)
+    }
+
+    fn rotation_due(&self, token: &ApiToken) -> bool {
+        self.rotate_at(token).is_some_and(|at| at <= Utc::now())
+    }
+
+    /// Come back in time for the rotation and the end of the grace period, or at the usual `interval`
+    fn next_check(&self, token: &ApiToken, status: &APITokenStatus, interval: Duration) -> Duration {
+        [self.rotate_at(token), self.retire_at(status)]
+            .into_iter()
+            .flatten()
+            .min()
+            .and_then(|at| (at - Utc::now()).to_std().ok())
+            .map_or(interval, |until| until.clamp(Duration::from_secs(1), interval))
+    }
+
+    async fn write_secret(&self, secrets: &Api<Secret>, token_id: &str, value: String) -> anyhow::Result<()> {
+        // owned by the APIToken, so the Secret goes away with it
+        let secret = json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": {
+                "name": self.spec.secret_name,
+                "ownerReferences": [self.controller_owner_ref(&()).unwrap()],
+            },
+            "type": "Opaque",
+            "stringData": { SECRET_KEY: value, TOKEN_ID_KEY: token_id },
+        });
+        secrets
+            .patch(
+                &self.spec.secret_name,
+                &PatchParams::apply("cntrlr").force(),
+                &Patch::Apply(secret),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn publish(&self, ctx: &Context, reason: &str, note: String) {
+        let event = Event {
+            type_: EventType::Normal,
+            reason: reason.into(),
+            note: Some(note),
+            action: "Reconciling".into(),
+            secondary: None,
+        };
+        if let Err(e) = ctx.recorder.publish(&event, &self.object_ref(&())).await {
+            warn!("failed to publish {} event: {}", reason, e);
+        }
+    }
+
+    // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
+    async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
+        let abandon = DeletionPolicy::abandons(self);
+        let status = self.status.clone().unwrap_or_default();
+        let Some(token_id) = status.token_id.clone() else {
+            return Ok(Action::await_change());
+        };
+        if abandon {
+            self.publish(
+                &ctx,
+                "Abandoned",
+                format!("Left token `{token_id}` in Cloudflare as requested by the deletion policy"),
+            )
+            .await;
+            return Ok(Action::await_change());
+        }
+
+        let ns = self.namespace().unwrap(); // doc is namespace scoped
+        let cf_client = match self.issuer(&ctx, &ns).await? {
+            Ok(cf_client) => cf_client,
+            Err(blocked) => return Err(Error::CloudflareError(anyhow!(blocked.message()))),
+        };
+        // an error keeps the finalizer in place, so the object stays around until the delete goes through
+        if let Some(retired) = &status.retired_token_id {
+            cf_client.delete_api_token(retired).await?;
+        }
+        cf_client.delete_api_token(&token_id).await?;
+        self.publish(
+            &ctx,
+            "Deleted",
+            format!("Deleted token `{token_id}` from Cloudflare"),
+        )
+        .await;
+        Ok(Action::await_change())
+    }
+}
+
+/// Initialize the controller and shared state (given the crd is installed)
+pub async fn run(state: State) {
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let scopes = state.namespaces().scopes();
+    let docs: Api<APIToken> = scoped(client.clone(), scopes[0].as_deref());
+    if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
+        error!("CRD is not queryable; {e:?}. Is the CRD installed?");
+        info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
+        std::process::exit(1);
+    }
+
+    let settings = ControllerSettings::from_env("APIToken");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
+        .await;
+    // one controller per watched namespace, they share the context and with it the Cloudflare clients
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        let controller = state
+            .controller("APIToken", scoped::<APIToken>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config());
+        let tokens = controller.store();
+        state.health().watch("APIToken", tokens.clone());
+        controller
+            .watches(
+                scoped::<Account>(client.clone(), ns.as_deref()),
+                Config::default(),
+                wake_dependents(tokens, |token: &APIToken| {
+                    token.spec.account_ref.as_ref().map(|a_ref| a_ref.name.as_str())
+                }),
+            )
+            // a deleted or emptied Secret gets a new token right away
+            .owns(scoped::<Secret>(client.clone(), ns.as_deref()), Config::default())
+            .reconcile_on(state.triggers().api_token.stream())
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("APIToken", result))
+    }))
+    .await;
+}
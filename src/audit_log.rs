@@ -0,0 +1,118 @@
+use crate::{Context, State, account::Account, dns_record::DNSRecord, zone::Zone};
+use chrono::{DateTime, Utc};
+use kube::{Client, ResourceExt};
+use std::{collections::HashMap, sync::Arc};
+use tokio::time::Duration;
+use tracing::*;
+
+/// The object that owns a Cloudflare id
+enum Managed {
+    DnsRecord { name: String, namespace: String },
+    Zone { name: String, namespace: String },
+}
+
+/// Poll the audit log of every ready Account and reconcile objects touched outside of the operator right away
+///
+/// Only runs when `CLOUDFLARE_AUDIT_LOG_INTERVAL` (in seconds) is set.
+pub async fn run(state: State) {
+    let Some(interval) = std::env::var("CLOUDFLARE_AUDIT_LOG_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+    else {
+        // disabled, but we are raced against the controllers so never finish
+        return futures::future::pending().await;
+    };
+
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let ctx = state.to_context(client).await;
+
+    // anything older than the operator start is covered by the initial reconciles
+    let mut cursors: HashMap<String, DateTime<Utc>> = HashMap::new();
+    let started = Utc::now();
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    loop {
+        ticker.tick().await;
+        if let Err(e) = poll(ctx.clone(), &mut cursors, started).await {
+            warn!("audit log poll failed: {e:?}");
+        }
+    }
+}
+
+async fn poll(
+    ctx: Arc<Context>,
+    cursors: &mut HashMap<String, DateTime<Utc>>,
+    started: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let managed = managed_ids(&ctx).await?;
+
+    for account in ctx.namespaces.list::<Account>(&ctx.client).await? {
+        if !account.status.as_ref().is_some_and(|s| s.ready) {
+            continue;
+        }
+        let ns = account.namespace().unwrap(); // account is namespace scoped
+        let cf_client = match ctx.provider.get_client(&account, &ns).await {
+            Ok(cf_client) => cf_client,
+            Err(e) => {
+                warn!("no client for account {}/{}: {e}", ns, account.name_any());
+                continue;
+            }
+        };
+
+        let since = *cursors.get(&account.spec.id).unwrap_or(&started);
+        let entries = match cf_client.list_audit_logs(&account.spec.id, since).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("audit log of account {}/{} failed: {e:?}", ns, account.name_any());
+                continue;
+            }
+        };
+
+        let mut cursor = since;
+        for entry in entries {
+            if let Some(when) = entry.when {
+                // `since` is inclusive, step past the newest entry we have seen
+                cursor = cursor.max(when + chrono::Duration::seconds(1));
+            }
+            let Some(target) = entry.resource.id.as_deref().and_then(|id| managed.get(id)) else {
+                continue;
+            };
+            let action = entry.action.action_type.as_deref().unwrap_or("change");
+            match target {
+                Managed::DnsRecord { name, namespace } => {
+                    info!("audit log: {action} on DNSRecord {namespace}/{name}, reconciling");
+                    ctx.triggers.dns_record.reconcile(name, namespace);
+                }
+                Managed::Zone { name, namespace } => {
+                    info!("audit log: {action} on Zone {namespace}/{name}, reconciling");
+                    ctx.triggers.zone.reconcile(name, namespace);
+                }
+            }
+        }
+        cursors.insert(account.spec.id.clone(), cursor);
+    }
+    Ok(())
+}
+
+/// Index all Cloudflare ids recorded in statuses by the object that manages them
+async fn managed_ids(ctx: &Context) -> anyhow::Result<HashMap<String, Managed>> {
+    let mut managed = HashMap::new();
+
+    for record in ctx.namespaces.list::<DNSRecord>(&ctx.client).await? {
+        if let Some(id) = record.status.as_ref().and_then(|s| s.record_id.clone()) {
+            managed.insert(id, Managed::DnsRecord {
+                name: record.name_any(),
+                namespace: record.namespace().unwrap_or_default(),
+            });
+        }
+    }
+
+    for zone in ctx.namespaces.list::<Zone>(&ctx.client).await? {
+        if let Some(id) = zone.status.as_ref().and_then(|s| s.id.clone()) {
+            managed.insert(id, Managed::Zone {
+                name: zone.name_any(),
+                namespace: zone.namespace().unwrap_or_default(),
+            });
+        }
+    }
+    Ok(managed)
+}
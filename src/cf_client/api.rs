@@ -0,0 +1,153 @@
+//! The Cloudflare calls reconcilers make, behind a trait so they can run against `MockCloudflareApi`
+use super::{
+    BatchDnsRecordsParams, BatchDnsRecordsResult, CloudflareClient, CreateDnsRecordParams, CreateZoneParams,
+    CustomHostname, DnsRecord, PatchDnsRecordParams, Result, UpdateDnsRecordParams, Zone,
+};
+use async_trait::async_trait;
+
+/// DNS record, custom hostname and zone calls, with the semantics of `CloudflareClient`
+///
+/// Lookups answer `None` for a missing object and deletes of a missing object succeed, the same as
+/// the client does.
+#[async_trait]
+pub trait CloudflareApi: Send + Sync {
+    async fn get_dns_record(&self, zone_id: &str, record_id: &str) -> Result<Option<DnsRecord>>;
+
+    async fn list_dns_records(
+        &self,
+        zone_id: &str,
+        name: Option<&str>,
+        record_type: Option<&str>,
+    ) -> Result<Vec<DnsRecord>>;
+
+    /// The record of `record_type` named `name`, the first one when there are several
+    async fn find_dns_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        record_type: &str,
+    ) -> Result<Option<DnsRecord>> {
+        Ok(self
+            .list_dns_records(zone_id, Some(name), Some(record_type))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Id of the created record
+    async fn create_dns_record(&self, zone_id: &str, params: CreateDnsRecordParams<'_>) -> Result<String>;
+
+    async fn update_dns_record(
+        &self,
+        zone_id: &str,
+        record_id: &str,
+        params: UpdateDnsRecordParams<'_>,
+    ) -> Result<DnsRecord>;
+
+    async fn patch_dns_record(
+        &self,
+        zone_id: &str,
+        record_id: &str,
+        params: PatchDnsRecordParams,
+    ) -> Result<DnsRecord>;
+
+    async fn batch_dns_records(
+        &self,
+        zone_id: &str,
+        params: BatchDnsRecordsParams,
+    ) -> Result<BatchDnsRecordsResult>;
+
+    async fn delete_dns_record(&self, zone_id: &str, record_id: &str) -> Result<()>;
+
+    async fn find_custom_hostname(&self, zone_id: &str, hostname: &str) -> Result<Option<CustomHostname>>;
+
+    async fn create_custom_hostname(&self, zone_id: &str, hostname: &str) -> Result<CustomHostname>;
+
+    async fn delete_custom_hostname(&self, zone_id: &str, identifier: &str) -> Result<()>;
+
+    async fn get_zone(&self, identifier: &str) -> Result<Zone>;
+
+    async fn find_zone(&self, name: &str, account_id: &str) -> Result<Option<Zone>>;
+
+    async fn create_zone(&self, params: CreateZoneParams<'_>) -> Result<Zone>;
+
+    async fn delete_zone(&self, identifier: &str) -> Result<()>;
+}
+
+#[async_trait]
+impl CloudflareApi for CloudflareClient {
+    async fn get_dns_record(&self, zone_id: &str, record_id: &str) -> Result<Option<DnsRecord>> {
+        CloudflareClient::get_dns_record(self, zone_id, record_id).await
+    }
+
+    async fn list_dns_records(
+        &self,
+        zone_id: &str,
+        name: Option<&str>,
+        record_type: Option<&str>,
+    ) -> Result<Vec<DnsRecord>> {
+        CloudflareClient::list_dns_records(self, zone_id, name, record_type).await
+    }
+
+    async fn create_dns_record(&self, zone_id: &str, params: CreateDnsRecordParams<'_>) -> Result<String> {
+        CloudflareClient::create_dns_record(self, zone_id, params).await
+    }
+
+    async fn update_dns_record(
+        &self,
+        zone_id: &str,
+        record_id: &str,
+        params: UpdateDnsRecordParams<'_>,
+    ) -> Result<DnsRecord> {
+        CloudflareClient::update_dns_record(self, zone_id, record_id, params).await
+    }
+
+    async fn patch_dns_record(
+        &self,
+        zone_id: &str,
+        record_id: &str,
+        params: PatchDnsRecordParams,
+    ) -> Result<DnsRecord> {
+        CloudflareClient::patch_dns_record(self, zone_id, record_id, params).await
+    }
+
+    async fn batch_dns_records(
+        &self,
+        zone_id: &str,
+        params: BatchDnsRecordsParams,
+    ) -> Result<BatchDnsRecordsResult> {
+        CloudflareClient::batch_dns_records(self, zone_id, params).await
+    }
+
+    async fn delete_dns_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        CloudflareClient::delete_dns_record(self, zone_id, record_id).await
+    }
+
+    async fn find_custom_hostname(&self, zone_id: &str, hostname: &str) -> Result<Option<CustomHostname>> {
+        CloudflareClient::find_custom_hostname(self, zone_id, hostname).await
+    }
+
+    async fn create_custom_hostname(&self, zone_id: &str, hostname: &str) -> Result<CustomHostname> {
+        CloudflareClient::create_custom_hostname(self, zone_id, hostname).await
+    }
+
+    async fn delete_custom_hostname(&self, zone_id: &str, identifier: &str) -> Result<()> {
+        CloudflareClient::delete_custom_hostname(self, zone_id, identifier).await
+    }
+
+    async fn get_zone(&self, identifier: &str) -> Result<Zone> {
+        CloudflareClient::get_zone(self, identifier).await
+    }
+
+    async fn find_zone(&self, name: &str, account_id: &str) -> Result<Option<Zone>> {
+        CloudflareClient::find_zone(self, name, account_id).await
+    }
+
+    async fn create_zone(&self, params: CreateZoneParams<'_>) -> Result<Zone> {
+        CloudflareClient::create_zone(self, params).await
+    }
+
+    async fn delete_zone(&self, identifier: &str) -> Result<()> {
+        CloudflareClient::delete_zone(self, identifier).await
+    }
+}
@@ -0,0 +1,139 @@
+//! Record of every change made on the Cloudflare side, to answer who changed what and with which token
+//!
+//! The client knows the call and the token, the reconciler knows the object. Changes made inside
+//! `collect` are handed back to its caller to log along with the object, anything else is logged here
+//! as it happens.
+use super::debug;
+use cloudflare::framework::endpoint::RequestBody;
+use reqwest::Method;
+use std::{cell::RefCell, future::Future};
+use tracing::{info, warn};
+
+/// Longest change summary logged, the rest of the body is cut off
+const MAX_SUMMARY: usize = 512;
+
+tokio::task_local! {
+    static CHANGES: RefCell<Vec<Change>>;
+}
+
+/// A create, update or delete sent to Cloudflare
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub method: Method,
+    pub path: String,
+    /// Id of the Cloudflare object, `None` for a create that failed
+    pub object_id: Option<String>,
+    /// The redacted request body, what the call asked to change
+    pub summary: String,
+    /// Start of the token's SHA-256, never the token itself
+    pub token: String,
+    /// What Cloudflare answered when it refused the change
+    pub error: Option<String>,
+}
+
+impl Change {
+    pub(super) fn new(method: &Method, path: &str, body: Option<RequestBody>, token: &str) -> Self {
+        let mut summary = match body {
+            Some(RequestBody::Json(body)) => debug::redact(&body),
+            Some(_) => "<not JSON>".into(),
+            None => String::new(),
+        };
+        if summary.len() > MAX_SUMMARY {
+            let mut end = MAX_SUMMARY;
+            while !summary.is_char_boundary(end) {
+                end -= 1;
+            }
+            summary.truncate(end);
+            summary.push('…');
+        }
+        // creates post to the collection, everything else names the object last
+        let object_id = (method != Method::POST)
+            .then(|| path.rsplit('/').next().map(str::to_string))
+            .flatten();
+        Self {
+            method: method.clone(),
+            path: path.to_string(),
+            object_id,
+            summary,
+            token: token.to_string(),
+            error: None,
+        }
+    }
+
+    /// `Create`, `Update` or `Delete`
+    pub fn action(&self) -> &'static str {
+        match self.method {
+            Method::POST => "Create",
+            Method::DELETE => "Delete",
+            _ => "Update",
+        }
+    }
+
+    /// Log the change, `kind` and `object` (`namespace/name`) of the object it was made for
+    pub fn log(&self, kind: Option<&str>, object: Option<&str>) {
+        let outcome = if self.error.is_none() {
+            "succeeded"
+        } else {
+            "failed"
+        };
+        let message = format!("Cloudflare {} {}", self.action().to_lowercase(), outcome);
+        match &self.error {
+            None => info!(
+                target: "cloudflare::audit",
+                kind,
+                object,
+                method = %self.method,
+                path = self.path.as_str(),
+                cloudflare_id = self.object_id.as_deref(),
+                token = self.token.as_str(),
+                change = self.summary.as_str(),
+                outcome,
+                "{message}"
+            ),
+            Some(error) => warn!(
+                target: "cloudflare::audit",
+                kind,
+                object,
+                method = %self.method,
+                path = self.path.as_str(),
+                cloudflare_id = self.object_id.as_deref(),
+                token = self.token.as_str(),
+                change = self.summary.as_str(),
+                outcome,
+                error = error.as_str(),
+                "{message}"
+            ),
+        }
+    }
+}
+
+/// Run `fut`, returning the changes it made along with its output instead of logging them
+pub async fn collect<F: Future>(fut: F) -> (F::Output, Vec<Change>) {
+    CHANGES
+        .scope(RefCell::default(), async {
+            let output = fut.await;
+            (output, CHANGES.with(|changes| changes.take()))
+        })
+        .await
+}
+
+/// Hand the change to the enclosing `collect`, or log it when there is none
+pub(super) fn record(change: Change) {
+    let mut change = Some(change);
+    let _ = CHANGES.try_with(|changes| changes.borrow_mut().extend(change.take()));
+    if let Some(change) = change {
+        change.log(None, None);
+    }
+}
+
+/// Fill in the id of the object the last recorded create made
+pub(super) fn created(id: &str) {
+    let _ = CHANGES.try_with(|changes| {
+        if let Some(change) = changes.borrow_mut().last_mut()
+            && change.method == Method::POST
+            && change.object_id.is_none()
+        {
+            change.object_id = Some(id.to_string());
+        }
+    });
+}
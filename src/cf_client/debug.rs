@@ -0,0 +1,73 @@
+//! Logging of every Cloudflare call for diagnosing refused payloads, with the secrets taken out
+//!
+//! Headers are never logged, so `Authorization` and the Origin CA key stay out of it; bodies go
+//! through `redact` first.
+use super::Result;
+use cloudflare::framework::endpoint::RequestBody;
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use tracing::info;
+
+/// Replaces the values of redacted fields
+const REDACTED: &str = "[redacted]";
+
+/// Fields whose values are credentials, matched on the lowercased field name
+const SECRET_FIELDS: &[&str] = &["token", "secret", "password", "authorization", "key"];
+
+/// The JSON `body` with the values of token-like fields replaced, anything else only by its size
+pub fn redact(body: &str) -> String {
+    match serde_json::from_str::<Value>(body) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        }
+        Err(_) => format!("<{} bytes, not JSON>", body.len()),
+    }
+}
+
+fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                let name = name.to_lowercase();
+                if SECRET_FIELDS.iter().any(|secret| name.contains(secret)) && !field.is_null() {
+                    *field = Value::String(REDACTED.into());
+                } else {
+                    redact_value(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+/// Log a request about to be sent
+pub fn request(method: &Method, path: &str, query: Option<String>, body: Option<RequestBody>) {
+    let body = match body {
+        Some(RequestBody::Json(body)) => redact(&body),
+        Some(_) => "<not JSON>".into(),
+        None => String::new(),
+    };
+    info!(
+        %method,
+        path,
+        query = query.unwrap_or_default(),
+        body,
+        "Cloudflare request"
+    );
+}
+
+/// Log how a request went, with the status it was answered with
+pub fn response<T>(method: &Method, path: &str, status: StatusCode, result: &Result<T>) {
+    match result {
+        Ok(_) => info!(%method, path, %status, "Cloudflare accepted the request"),
+        Err(e) => info!(
+            %method,
+            path,
+            status = %e.status().unwrap_or(status),
+            codes = ?e.codes(),
+            "Cloudflare refused the request: {e}"
+        ),
+    }
+}
@@ -0,0 +1,1687 @@
+//! Endpoints that cloudflare-rs doesn't ship, defined the same way the crate defines its own
+use chrono::{DateTime, Utc};
+use cloudflare::{
+    endpoints::{
+        dns::dns::{DnsContent, DnsRecord},
+        users::UserTokenStatus,
+        zones::zone::Zone,
+    },
+    framework::{
+        endpoint::{EndpointSpec, Method, RequestBody, serialize_query},
+        response::{ApiResult, ApiSuccess},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Get a single DNS record
+///
+/// <https://developers.cloudflare.com/api/resources/dns/subresources/records/methods/get/>
+pub struct DnsRecordDetails<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for DnsRecordDetails<'_> {
+    type JsonResponse = DnsRecord;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/dns_records/{}", self.zone_identifier, self.identifier)
+    }
+}
+
+/// List the DNS records of a zone, filtered by name and type
+///
+/// Defined here because the cloudflare-rs one wants a whole record content to filter by type.
+/// <https://developers.cloudflare.com/api/resources/dns/subresources/records/methods/list/>
+pub struct ListDnsRecords<'a> {
+    pub zone_identifier: &'a str,
+    pub name: Option<&'a str>,
+    pub record_type: Option<&'a str>,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+#[derive(Serialize)]
+struct ListDnsRecordsParams<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    record_type: Option<&'a str>,
+    page: u32,
+    per_page: u32,
+}
+
+impl EndpointSpec for ListDnsRecords<'_> {
+    type JsonResponse = Vec<DnsRecord>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/dns_records", self.zone_identifier)
+    }
+
+    fn query(&self) -> Option<String> {
+        serialize_query(&ListDnsRecordsParams {
+            name: self.name,
+            record_type: self.record_type,
+            page: self.page,
+            per_page: self.per_page,
+        })
+    }
+}
+
+/// Change some fields of a DNS record, fields left at `None` are not touched
+///
+/// <https://developers.cloudflare.com/api/resources/dns/subresources/records/methods/edit/>
+pub struct PatchDnsRecord<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+    pub params: PatchDnsRecordParams,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct PatchDnsRecordParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Type and content go together, Cloudflare wants the type along with a new content
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub content: Option<DnsContent>,
+}
+
+impl EndpointSpec for PatchDnsRecord<'_> {
+    type JsonResponse = DnsRecord;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PATCH
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/dns_records/{}", self.zone_identifier, self.identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// List the audit log of an account
+///
+/// <https://developers.cloudflare.com/api/resources/audit_logs/methods/list/>
+pub struct ListAuditLogs<'a> {
+    pub account_identifier: &'a str,
+    pub params: ListAuditLogsParams,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ListAuditLogsParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_page: Option<u32>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AuditLog {
+    pub id: String,
+    #[serde(default)]
+    pub action: AuditLogAction,
+    #[serde(default)]
+    pub resource: AuditLogResource,
+    pub when: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct AuditLogAction {
+    #[serde(rename = "type")]
+    pub action_type: Option<String>,
+    pub result: Option<bool>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct AuditLogResource {
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub resource_type: Option<String>,
+}
+
+impl ApiResult for AuditLog {}
+
+impl EndpointSpec for ListAuditLogs<'_> {
+    type JsonResponse = Vec<AuditLog>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/audit_logs", self.account_identifier)
+    }
+
+    fn query(&self) -> Option<String> {
+        serialize_query(&self.params)
+    }
+}
+
+/// Change the rate plan of a zone
+///
+/// <https://developers.cloudflare.com/api/resources/zones/subresources/subscriptions/methods/update/>
+pub struct UpdateZoneSubscription<'a> {
+    pub zone_identifier: &'a str,
+    pub params: ZoneSubscriptionParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ZoneSubscriptionParams<'a> {
+    pub rate_plan: RatePlan<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RatePlan<'a> {
+    pub id: &'a str,
+}
+
+impl EndpointSpec for UpdateZoneSubscription<'_> {
+    type JsonResponse = serde_json::Value;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/subscription", self.zone_identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// A single zone setting, the value is a string for most settings and an object for a few
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ZoneSetting {
+    pub id: String,
+    pub value: serde_json::Value,
+    pub editable: Option<bool>,
+}
+
+impl ApiResult for ZoneSetting {}
+
+impl ZoneSetting {
+    /// The setting as one of the typed ones, `None` for settings without a variant
+    pub fn typed(&self) -> Option<ZoneSettingValue> {
+        serde_json::from_value(serde_json::json!({ "id": self.id, "value": self.value })).ok()
+    }
+}
+
+/// `on` or `off`, what most zone settings take
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Toggle {
+    On,
+    Off,
+}
+
+impl From<bool> for Toggle {
+    fn from(on: bool) -> Self {
+        if on { Toggle::On } else { Toggle::Off }
+    }
+}
+
+/// A zone setting the operator manages, with the value it takes
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "id", content = "value", rename_all = "snake_case")]
+pub enum ZoneSettingValue {
+    /// `off`, `flexible`, `full` or `strict`
+    Ssl(String),
+    /// `1.0` to `1.3`
+    MinTlsVersion(String),
+    AlwaysUseHttps(Toggle),
+    #[serde(rename = "tls_1_3")]
+    Tls13(Toggle),
+    AutomaticHttpsRewrites(Toggle),
+    OpportunisticEncryption(Toggle),
+    Http3(Toggle),
+    Brotli(Toggle),
+    Ipv6(Toggle),
+    Websockets(Toggle),
+    AlwaysOnline(Toggle),
+    DevelopmentMode(Toggle),
+}
+
+impl ZoneSettingValue {
+    /// Cloudflare's id of the setting
+    pub fn id(&self) -> &'static str {
+        match self {
+            ZoneSettingValue::Ssl(_) => "ssl",
+            ZoneSettingValue::MinTlsVersion(_) => "min_tls_version",
+            ZoneSettingValue::AlwaysUseHttps(_) => "always_use_https",
+            ZoneSettingValue::Tls13(_) => "tls_1_3",
+            ZoneSettingValue::AutomaticHttpsRewrites(_) => "automatic_https_rewrites",
+            ZoneSettingValue::OpportunisticEncryption(_) => "opportunistic_encryption",
+            ZoneSettingValue::Http3(_) => "http3",
+            ZoneSettingValue::Brotli(_) => "brotli",
+            ZoneSettingValue::Ipv6(_) => "ipv6",
+            ZoneSettingValue::Websockets(_) => "websockets",
+            ZoneSettingValue::AlwaysOnline(_) => "always_online",
+            ZoneSettingValue::DevelopmentMode(_) => "development_mode",
+        }
+    }
+
+    /// The value as Cloudflare takes it
+    pub fn value(&self) -> serde_json::Value {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|mut setting| setting.get_mut("value").map(serde_json::Value::take))
+            .unwrap_or_default()
+    }
+}
+
+/// Get all settings of a zone
+///
+/// <https://developers.cloudflare.com/api/resources/zones/subresources/settings/>
+pub struct ZoneSettings<'a> {
+    pub zone_identifier: &'a str,
+}
+
+impl EndpointSpec for ZoneSettings<'_> {
+    type JsonResponse = Vec<ZoneSetting>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/settings", self.zone_identifier)
+    }
+}
+
+/// Change a single zone setting
+///
+/// <https://developers.cloudflare.com/api/resources/zones/subresources/settings/methods/edit/>
+pub struct EditZoneSetting<'a> {
+    pub zone_identifier: &'a str,
+    pub setting_id: &'a str,
+    pub params: EditZoneSettingParams,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EditZoneSettingParams {
+    pub value: serde_json::Value,
+}
+
+impl EndpointSpec for EditZoneSetting<'_> {
+    type JsonResponse = ZoneSetting;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PATCH
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/settings/{}", self.zone_identifier, self.setting_id)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Change several zone settings in one request
+///
+/// <https://developers.cloudflare.com/api/resources/zones/subresources/settings/methods/bulk_edit/>
+pub struct EditZoneSettings<'a> {
+    pub zone_identifier: &'a str,
+    pub params: EditZoneSettingsParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EditZoneSettingsParams<'a> {
+    pub items: &'a [ZoneSettingValue],
+}
+
+impl EndpointSpec for EditZoneSettings<'_> {
+    type JsonResponse = Vec<ZoneSetting>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PATCH
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/settings", self.zone_identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// What to drop from the cache of a zone
+///
+/// <https://developers.cloudflare.com/api/resources/cache/methods/purge/>
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PurgeRequest {
+    #[serde(serialize_with = "purge_everything")]
+    Everything,
+    /// Full URLs, scheme included
+    Files(Vec<String>),
+    /// URLs without the scheme, everything below them is purged
+    Prefixes(Vec<String>),
+    Hosts(Vec<String>),
+    /// `Cache-Tag` header values
+    Tags(Vec<String>),
+}
+
+fn purge_everything<S: serde::Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bool(true)
+}
+
+/// Most items Cloudflare takes in one purge request
+pub const MAX_PURGE_ITEMS: usize = 30;
+
+/// Longest cache tag Cloudflare accepts
+const MAX_TAG_LENGTH: usize = 1024;
+
+impl PurgeRequest {
+    /// Check the request against the rules of its mode before it goes out
+    pub fn validate(&self) -> Result<(), String> {
+        fn url(item: &str) -> bool {
+            item.starts_with("http://") || item.starts_with("https://")
+        }
+        fn prefix(item: &str) -> bool {
+            !item.contains("://") && item.contains('/') && !item.contains(['?', '#'])
+        }
+        fn host(item: &str) -> bool {
+            !item.is_empty() && !item.contains("://") && !item.contains(['/', '?', '#'])
+        }
+        fn tag(item: &str) -> bool {
+            !item.is_empty() && item.len() <= MAX_TAG_LENGTH && !item.contains(',')
+        }
+        let (mode, items, valid, rule): (_, _, fn(&str) -> bool, _) = match self {
+            PurgeRequest::Everything => return Ok(()),
+            PurgeRequest::Files(items) => ("files", items, url, "a full http(s) URL"),
+            PurgeRequest::Prefixes(items) => (
+                "prefixes",
+                items,
+                prefix,
+                "a host and path without scheme, query or fragment",
+            ),
+            PurgeRequest::Hosts(items) => ("hosts", items, host, "a host name without scheme or path"),
+            PurgeRequest::Tags(items) => (
+                "tags",
+                items,
+                tag,
+                "a tag of at most 1024 characters without commas",
+            ),
+        };
+        if items.is_empty() {
+            return Err(format!("no {mode} to purge"));
+        }
+        if items.len() > MAX_PURGE_ITEMS {
+            return Err(format!(
+                "{} {mode} to purge, at most {MAX_PURGE_ITEMS} go in one request",
+                items.len()
+            ));
+        }
+        match items.iter().find(|item| !valid(item)) {
+            Some(item) => Err(format!("{item:?} is not {rule}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Drop cached content of a zone
+pub struct PurgeCache<'a> {
+    pub zone_identifier: &'a str,
+    pub params: &'a PurgeRequest,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PurgeCacheResult {
+    pub id: String,
+}
+
+impl ApiResult for PurgeCacheResult {}
+
+impl EndpointSpec for PurgeCache<'_> {
+    type JsonResponse = PurgeCacheResult;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/purge_cache", self.zone_identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Apply several DNS record changes to a zone in one go, either all of them go through or none
+///
+/// Cloudflare runs deletes first, then patches, puts and posts.
+/// <https://developers.cloudflare.com/api/resources/dns/subresources/records/methods/batch/>
+pub struct BatchDnsRecords<'a> {
+    pub zone_identifier: &'a str,
+    pub params: BatchDnsRecordsParams,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct BatchDnsRecordsParams {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deletes: Vec<BatchDelete>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<BatchPatch>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub posts: Vec<BatchRecord>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub puts: Vec<BatchRecord>,
+}
+
+impl BatchDnsRecordsParams {
+    /// Number of changes in the batch
+    pub fn len(&self) -> usize {
+        self.deletes.len() + self.patches.len() + self.posts.len() + self.puts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A record to delete in a batch
+#[derive(Serialize, Clone, Debug)]
+pub struct BatchDelete {
+    pub id: String,
+}
+
+/// Fields to change on an existing record in a batch
+#[derive(Serialize, Clone, Debug)]
+pub struct BatchPatch {
+    pub id: String,
+    #[serde(flatten)]
+    pub params: PatchDnsRecordParams,
+}
+
+/// A record in a batch, `id` is only set when overwriting an existing record
+#[derive(Serialize, Clone, Debug)]
+pub struct BatchRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+    #[serde(flatten)]
+    pub content: DnsContent,
+}
+
+/// The records as they are after the batch, in the order they were sent
+#[derive(Deserialize, Clone, Debug)]
+pub struct BatchDnsRecordsResult {
+    #[serde(default)]
+    pub deletes: Vec<DnsRecord>,
+    #[serde(default)]
+    pub patches: Vec<DnsRecord>,
+    #[serde(default)]
+    pub posts: Vec<DnsRecord>,
+    #[serde(default)]
+    pub puts: Vec<DnsRecord>,
+}
+
+impl ApiResult for BatchDnsRecordsResult {}
+
+impl EndpointSpec for BatchDnsRecords<'_> {
+    type JsonResponse = BatchDnsRecordsResult;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/dns_records/batch", self.zone_identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Change properties of a zone, fields left at `None` are not touched
+///
+/// Defined here instead of using the cloudflare-rs one so that only the fields we set are sent.
+/// <https://developers.cloudflare.com/api/resources/zones/methods/edit/>
+pub struct EditZone<'a> {
+    pub identifier: &'a str,
+    pub params: EditZoneParams,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct EditZoneParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vanity_name_servers: Option<Vec<String>>,
+}
+
+impl EndpointSpec for EditZone<'_> {
+    type JsonResponse = Zone;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PATCH
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}", self.identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Ask Cloudflare to check the nameserver delegation of a pending zone right away
+///
+/// <https://developers.cloudflare.com/api/resources/zones/subresources/activation_check/methods/trigger/>
+pub struct ZoneActivationCheck<'a> {
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for ZoneActivationCheck<'_> {
+    type JsonResponse = serde_json::Value;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/activation_check", self.identifier)
+    }
+}
+
+/// List zones, of one account or by name; also counts them through `result_info`
+///
+/// <https://developers.cloudflare.com/api/resources/zones/methods/list/>
+pub struct ListAccountZones<'a> {
+    /// Every zone the token can see when `None`
+    pub account_identifier: Option<&'a str>,
+    pub name: Option<&'a str>,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+#[derive(Serialize)]
+struct ListAccountZonesParams<'a> {
+    #[serde(rename = "account.id", skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    page: u32,
+    per_page: u32,
+}
+
+impl EndpointSpec for ListAccountZones<'_> {
+    type JsonResponse = Vec<Zone>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        "zones".to_string()
+    }
+
+    fn query(&self) -> Option<String> {
+        serialize_query(&ListAccountZonesParams {
+            account_id: self.account_identifier,
+            name: self.name,
+            page: self.page,
+            per_page: self.per_page,
+        })
+    }
+}
+
+/// List the members of an account
+///
+/// <https://developers.cloudflare.com/api/resources/accounts/subresources/members/methods/list/>
+pub struct ListAccountMembers<'a> {
+    pub account_identifier: &'a str,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+#[derive(Serialize)]
+struct PageParams {
+    page: u32,
+    per_page: u32,
+}
+
+impl EndpointSpec for ListAccountMembers<'_> {
+    type JsonResponse = Vec<AccountMember>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/members", self.account_identifier)
+    }
+
+    fn query(&self) -> Option<String> {
+        serialize_query(&PageParams {
+            page: self.page,
+            per_page: self.per_page,
+        })
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AccountMember {
+    pub id: String,
+    /// `pending` until the invitation is accepted, `accepted` afterwards
+    pub status: Option<String>,
+    pub user: AccountMemberUser,
+    #[serde(default)]
+    pub roles: Vec<AccountRole>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AccountMemberUser {
+    pub email: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AccountRole {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+impl ApiResult for AccountMember {}
+
+/// Invite someone to an account
+///
+/// <https://developers.cloudflare.com/api/resources/accounts/subresources/members/methods/create/>
+pub struct CreateAccountMember<'a> {
+    pub account_identifier: &'a str,
+    pub params: CreateAccountMemberParams,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CreateAccountMemberParams {
+    pub email: String,
+    /// Role ids
+    pub roles: Vec<String>,
+}
+
+impl EndpointSpec for CreateAccountMember<'_> {
+    type JsonResponse = AccountMember;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/members", self.account_identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Get a member of an account
+///
+/// <https://developers.cloudflare.com/api/resources/accounts/subresources/members/methods/get/>
+pub struct GetAccountMember<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for GetAccountMember<'_> {
+    type JsonResponse = AccountMember;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/members/{}", self.account_identifier, self.identifier)
+    }
+}
+
+/// Replace the roles of an account member
+///
+/// <https://developers.cloudflare.com/api/resources/accounts/subresources/members/methods/update/>
+pub struct UpdateAccountMember<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+    pub params: UpdateAccountMemberParams,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UpdateAccountMemberParams {
+    pub roles: Vec<RoleId>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RoleId {
+    pub id: String,
+}
+
+impl EndpointSpec for UpdateAccountMember<'_> {
+    type JsonResponse = AccountMember;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/members/{}", self.account_identifier, self.identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Remove a member from an account
+///
+/// <https://developers.cloudflare.com/api/resources/accounts/subresources/members/methods/delete/>
+pub struct DeleteAccountMember<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for DeleteAccountMember<'_> {
+    type JsonResponse = serde_json::Value;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/members/{}", self.account_identifier, self.identifier)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Subscription {
+    pub id: String,
+    pub rate_plan: Option<SubscriptionRatePlan>,
+    pub state: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SubscriptionRatePlan {
+    pub id: String,
+    pub public_name: Option<String>,
+}
+
+impl ApiResult for Subscription {}
+
+/// List the subscriptions (plans and add-ons) of an account
+///
+/// <https://developers.cloudflare.com/api/resources/accounts/subresources/subscriptions/methods/get/>
+pub struct ListAccountSubscriptions<'a> {
+    pub account_identifier: &'a str,
+}
+
+impl EndpointSpec for ListAccountSubscriptions<'_> {
+    type JsonResponse = Vec<Subscription>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/subscriptions", self.account_identifier)
+    }
+}
+
+/// Account as returned by the accounts API, with the fields cloudflare-rs leaves out
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AccountDetails {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub account_type: Option<String>,
+    pub created_on: Option<DateTime<Utc>>,
+    pub settings: Option<AccountSettings>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct AccountSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enforce_twofactor: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abuse_contact_email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_account_custom_ns_by_default: Option<bool>,
+}
+
+impl ApiResult for AccountDetails {}
+
+/// Get an account
+///
+/// <https://developers.cloudflare.com/api/resources/accounts/methods/get/>
+pub struct GetAccountDetails<'a> {
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for GetAccountDetails<'_> {
+    type JsonResponse = AccountDetails;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}", self.identifier)
+    }
+}
+
+/// Update the name and settings of an account
+///
+/// <https://developers.cloudflare.com/api/resources/accounts/methods/update/>
+pub struct UpdateAccount<'a> {
+    pub identifier: &'a str,
+    pub params: UpdateAccountParams,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UpdateAccountParams {
+    pub name: String,
+    pub settings: AccountSettings,
+}
+
+impl EndpointSpec for UpdateAccount<'_> {
+    type JsonResponse = AccountDetails;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}", self.identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    /// `active`, `disabled` or `expired`
+    pub status: Option<String>,
+    pub expires_on: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub policies: Vec<TokenPolicy>,
+    /// Only returned when the token is created
+    pub value: Option<String>,
+}
+
+impl ApiResult for ApiToken {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TokenPolicy {
+    /// `allow` or `deny`
+    pub effect: String,
+    pub permission_groups: Vec<PermissionGroupId>,
+    /// Resource identifiers like `com.cloudflare.api.account.zone.<zone id>`, mapped to `*`
+    pub resources: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PermissionGroupId {
+    pub id: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ApiTokenParams {
+    pub name: String,
+    pub policies: Vec<TokenPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_on: Option<DateTime<Utc>>,
+}
+
+/// Create an API token for the user owning the calling token
+///
+/// <https://developers.cloudflare.com/api/resources/user/subresources/tokens/methods/create/>
+pub struct CreateApiToken {
+    pub params: ApiTokenParams,
+}
+
+impl EndpointSpec for CreateApiToken {
+    type JsonResponse = ApiToken;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        "user/tokens".to_string()
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Get an API token, without its value
+///
+/// <https://developers.cloudflare.com/api/resources/user/subresources/tokens/methods/get/>
+pub struct GetApiToken<'a> {
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for GetApiToken<'_> {
+    type JsonResponse = ApiToken;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("user/tokens/{}", self.identifier)
+    }
+}
+
+/// Replace the name, policies and expiry of an API token, the value stays the same
+///
+/// <https://developers.cloudflare.com/api/resources/user/subresources/tokens/methods/update/>
+pub struct UpdateApiToken<'a> {
+    pub identifier: &'a str,
+    pub params: ApiTokenParams,
+}
+
+impl EndpointSpec for UpdateApiToken<'_> {
+    type JsonResponse = ApiToken;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!("user/tokens/{}", self.identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Delete an API token, it stops working right away
+///
+/// <https://developers.cloudflare.com/api/resources/user/subresources/tokens/methods/delete/>
+pub struct DeleteApiToken<'a> {
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for DeleteApiToken<'_> {
+    type JsonResponse = serde_json::Value;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!("user/tokens/{}", self.identifier)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PageRule {
+    pub id: String,
+    pub targets: Vec<PageRuleTarget>,
+    pub actions: Vec<PageRuleAction>,
+    pub priority: i32,
+    /// `active` or `disabled`
+    pub status: String,
+    pub created_on: Option<DateTime<Utc>>,
+    pub modified_on: Option<DateTime<Utc>>,
+}
+
+impl ApiResult for PageRule {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PageRuleTarget {
+    /// Always `url`
+    pub target: String,
+    pub constraint: PageRuleConstraint,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PageRuleConstraint {
+    /// Always `matches`
+    pub operator: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PageRuleAction {
+    pub id: String,
+    /// Shape depends on the action, actions like `always_use_https` take none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PageRuleParams {
+    pub targets: Vec<PageRuleTarget>,
+    pub actions: Vec<PageRuleAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    pub status: String,
+}
+
+/// List the page rules of a zone, they aren't paginated
+///
+/// <https://developers.cloudflare.com/api/resources/page_rules/methods/list/>
+pub struct ListPageRules<'a> {
+    pub zone_identifier: &'a str,
+}
+
+impl EndpointSpec for ListPageRules<'_> {
+    type JsonResponse = Vec<PageRule>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/pagerules", self.zone_identifier)
+    }
+}
+
+/// Get a page rule
+///
+/// <https://developers.cloudflare.com/api/resources/page_rules/methods/get/>
+pub struct GetPageRule<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for GetPageRule<'_> {
+    type JsonResponse = PageRule;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/pagerules/{}", self.zone_identifier, self.identifier)
+    }
+}
+
+/// Create a page rule
+///
+/// <https://developers.cloudflare.com/api/resources/page_rules/methods/create/>
+pub struct CreatePageRule<'a> {
+    pub zone_identifier: &'a str,
+    pub params: PageRuleParams,
+}
+
+impl EndpointSpec for CreatePageRule<'_> {
+    type JsonResponse = PageRule;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/pagerules", self.zone_identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Replace a page rule, fields left out are reset to their defaults
+///
+/// <https://developers.cloudflare.com/api/resources/page_rules/methods/update/>
+pub struct UpdatePageRule<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+    pub params: PageRuleParams,
+}
+
+impl EndpointSpec for UpdatePageRule<'_> {
+    type JsonResponse = PageRule;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/pagerules/{}", self.zone_identifier, self.identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Move a page rule, the other rules of the zone shift to make room
+///
+/// <https://developers.cloudflare.com/api/resources/page_rules/methods/edit/>
+pub struct EditPageRulePriority<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+    pub params: PageRulePriorityParams,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PageRulePriorityParams {
+    pub priority: i32,
+}
+
+impl EndpointSpec for EditPageRulePriority<'_> {
+    type JsonResponse = PageRule;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PATCH
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/pagerules/{}", self.zone_identifier, self.identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Delete a page rule
+///
+/// <https://developers.cloudflare.com/api/resources/page_rules/methods/delete/>
+pub struct DeletePageRule<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for DeletePageRule<'_> {
+    type JsonResponse = serde_json::Value;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/pagerules/{}", self.zone_identifier, self.identifier)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CustomHostname {
+    pub id: String,
+    pub hostname: String,
+    /// `pending`, `active`, `moved`, `deleted` and so on
+    pub status: Option<String>,
+    pub ssl: Option<CustomHostnameSsl>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl ApiResult for CustomHostname {}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CustomHostnameSsl {
+    /// Domain control validation, `http` or `txt`
+    pub method: String,
+    /// Always `dv`
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// Where the certificate is at, `active` once it is served, absent in requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CustomHostnameParams {
+    pub hostname: String,
+    pub ssl: CustomHostnameSsl,
+}
+
+/// List the custom hostnames of a zone, filtered by hostname
+///
+/// <https://developers.cloudflare.com/api/resources/custom_hostnames/methods/list/>
+pub struct ListCustomHostnames<'a> {
+    pub zone_identifier: &'a str,
+    pub hostname: &'a str,
+}
+
+#[derive(Serialize)]
+struct ListCustomHostnamesParams<'a> {
+    hostname: &'a str,
+}
+
+impl EndpointSpec for ListCustomHostnames<'_> {
+    type JsonResponse = Vec<CustomHostname>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/custom_hostnames", self.zone_identifier)
+    }
+
+    fn query(&self) -> Option<String> {
+        serialize_query(&ListCustomHostnamesParams {
+            hostname: self.hostname,
+        })
+    }
+}
+
+/// Create a custom hostname, Cloudflare issues its certificate once the validation passes
+///
+/// <https://developers.cloudflare.com/api/resources/custom_hostnames/methods/create/>
+pub struct CreateCustomHostname<'a> {
+    pub zone_identifier: &'a str,
+    pub params: CustomHostnameParams,
+}
+
+impl EndpointSpec for CreateCustomHostname<'_> {
+    type JsonResponse = CustomHostname;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/custom_hostnames", self.zone_identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Delete a custom hostname along with its certificate
+///
+/// <https://developers.cloudflare.com/api/resources/custom_hostnames/methods/delete/>
+pub struct DeleteCustomHostname<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for DeleteCustomHostname<'_> {
+    type JsonResponse = serde_json::Value;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "zones/{}/custom_hostnames/{}",
+            self.zone_identifier, self.identifier
+        )
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Tunnel {
+    pub id: String,
+    pub name: String,
+    /// `inactive` until a connector ran, then `healthy`, `degraded` or `down`
+    pub status: Option<String>,
+    /// Connections of the connectors running the tunnel
+    #[serde(default)]
+    pub connections: Vec<TunnelConnection>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl ApiResult for Tunnel {}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TunnelConnection {
+    pub id: String,
+    /// Data center the connection goes to
+    pub colo_name: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CreateTunnelParams {
+    pub name: String,
+    /// `cloudflare` for a tunnel configured through the API, run with just its token
+    pub config_src: String,
+}
+
+/// List the tunnels of an account by name, deleted ones left out
+///
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/cloudflared/methods/list/>
+pub struct ListTunnels<'a> {
+    pub account_identifier: &'a str,
+    pub name: &'a str,
+}
+
+#[derive(Serialize)]
+struct ListTunnelsParams<'a> {
+    name: &'a str,
+    is_deleted: bool,
+}
+
+impl EndpointSpec for ListTunnels<'_> {
+    type JsonResponse = Vec<Tunnel>;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/cfd_tunnel", self.account_identifier)
+    }
+
+    fn query(&self) -> Option<String> {
+        serialize_query(&ListTunnelsParams {
+            name: self.name,
+            is_deleted: false,
+        })
+    }
+}
+
+/// Get a tunnel along with its connections
+///
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/cloudflared/methods/get/>
+pub struct GetTunnel<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for GetTunnel<'_> {
+    type JsonResponse = Tunnel;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/cfd_tunnel/{}",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+/// Create a tunnel
+///
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/cloudflared/methods/create/>
+pub struct CreateTunnel<'a> {
+    pub account_identifier: &'a str,
+    pub params: CreateTunnelParams,
+}
+
+impl EndpointSpec for CreateTunnel<'_> {
+    type JsonResponse = Tunnel;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/cfd_tunnel", self.account_identifier)
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Get the token a connector runs the tunnel with, the result is the bare token
+///
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/cloudflared/subresources/token/methods/get/>
+pub struct GetTunnelToken<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for GetTunnelToken<'_> {
+    type JsonResponse = serde_json::Value;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/cfd_tunnel/{}/token",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+/// Drop the connections of a tunnel, connectors still running reconnect
+///
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/cloudflared/subresources/connections/methods/delete/>
+pub struct DeleteTunnelConnections<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for DeleteTunnelConnections<'_> {
+    type JsonResponse = serde_json::Value;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/cfd_tunnel/{}/connections",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+/// Delete a tunnel, refused while it has connections
+///
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/cloudflared/methods/delete/>
+pub struct DeleteTunnel<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for DeleteTunnel<'_> {
+    type JsonResponse = serde_json::Value;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/cfd_tunnel/{}",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TunnelConfiguration {
+    /// `ingress` rules and `originRequest` defaults, in the shape of the cloudflared config file
+    pub config: Option<serde_json::Value>,
+    /// Bumped on every change
+    pub version: Option<i64>,
+}
+
+impl ApiResult for TunnelConfiguration {}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TunnelConfigurationParams {
+    pub config: serde_json::Value,
+}
+
+/// Get the configuration of a tunnel configured through the API
+///
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/cloudflared/subresources/configurations/methods/get/>
+pub struct GetTunnelConfiguration<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl EndpointSpec for GetTunnelConfiguration<'_> {
+    type JsonResponse = TunnelConfiguration;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/cfd_tunnel/{}/configurations",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+/// Replace the configuration of a tunnel, connectors pick it up on their own
+///
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/cloudflared/subresources/configurations/methods/update/>
+pub struct UpdateTunnelConfiguration<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+    pub params: TunnelConfigurationParams,
+}
+
+impl EndpointSpec for UpdateTunnelConfiguration<'_> {
+    type JsonResponse = TunnelConfiguration;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/cfd_tunnel/{}/configurations",
+            self.account_identifier, self.identifier
+        )
+    }
+
+    #[inline]
+    fn body(&self) -> Option<RequestBody> {
+        let body = serde_json::to_string(&self.params).unwrap();
+        Some(RequestBody::Json(body))
+    }
+}
+
+/// Verify a token owned by an account, `/user/tokens/verify` only knows the tokens of users
+///
+/// <https://developers.cloudflare.com/api/resources/accounts/subresources/tokens/methods/verify/>
+pub struct AccountTokenVerification<'a> {
+    pub account_identifier: &'a str,
+}
+
+impl EndpointSpec for AccountTokenVerification<'_> {
+    type JsonResponse = UserTokenStatus;
+    type ResponseType = ApiSuccess<Self::JsonResponse>;
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/tokens/verify", self.account_identifier)
+    }
+}
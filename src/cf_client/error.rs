@@ -0,0 +1,108 @@
+//! What can go wrong talking to Cloudflare, with the error codes kept so callers can branch on them
+use cloudflare::framework::response::ApiError;
+use reqwest::StatusCode;
+use thiserror::Error;
+
+/// Cloudflare already has a DNS record with the same name, type and content
+pub const RECORD_EXISTS: u32 = 81057;
+
+/// The zone already exists, in this account or another one
+pub const ZONE_EXISTS: u32 = 1061;
+
+/// Error code Cloudflare rate limits with, sometimes next to a status other than 429
+pub const RATE_LIMITED: u32 = 971;
+
+#[derive(Error, Debug)]
+pub enum CfApiError {
+    /// Cloudflare answered, and refused the request
+    #[error("Cloudflare returned {status}: {}", messages(errors))]
+    Api {
+        status: StatusCode,
+        errors: Vec<ApiError>,
+    },
+
+    /// The request got no usable answer: connection, timeout or a body that couldn't be read
+    #[error("request to Cloudflare failed: {0}")]
+    Http(#[source] reqwest::Error),
+
+    /// The GraphQL API answered with errors instead of data
+    #[error("GraphQL query failed: {0}")]
+    Graphql(String),
+
+    /// The request was refused before it was sent
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+}
+
+fn messages(errors: &[ApiError]) -> String {
+    if errors.is_empty() {
+        return "no error details".into();
+    }
+    let messages: Vec<_> = errors
+        .iter()
+        .map(|e| format!("{} ({})", e.message, e.code))
+        .collect();
+    messages.join("; ")
+}
+
+impl From<reqwest::Error> for CfApiError {
+    fn from(e: reqwest::Error) -> Self {
+        CfApiError::Http(e)
+    }
+}
+
+impl CfApiError {
+    /// HTTP status of the answer, `None` when there was none
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            CfApiError::Api { status, .. } => Some(*status),
+            CfApiError::Http(e) => e.status(),
+            CfApiError::Graphql(_) | CfApiError::InvalidRequest(_) => None,
+        }
+    }
+
+    /// Cloudflare error codes of the answer
+    pub fn codes(&self) -> Vec<u32> {
+        match self {
+            CfApiError::Api { errors, .. } => errors.iter().map(|e| u32::from(e.code)).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether Cloudflare answered with `code`, such as `RECORD_EXISTS`
+    pub fn has_code(&self, code: u32) -> bool {
+        self.codes().contains(&code)
+    }
+
+    /// The object doesn't exist
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+
+    /// The token isn't allowed the operation
+    pub fn is_forbidden(&self) -> bool {
+        self.status() == Some(StatusCode::FORBIDDEN)
+    }
+
+    /// Cloudflare rate limited the token
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(StatusCode::TOO_MANY_REQUESTS) || self.has_code(RATE_LIMITED)
+    }
+
+    /// Whether sending the same request again may work: rate limits, server and connection errors
+    pub fn is_retryable(&self) -> bool {
+        if self.is_rate_limited() {
+            return true;
+        }
+        match self {
+            CfApiError::Api { status, .. } => status.is_server_error(),
+            CfApiError::Http(e) => e.status().is_none_or(|status| status.is_server_error()),
+            CfApiError::Graphql(_) | CfApiError::InvalidRequest(_) => false,
+        }
+    }
+
+    /// Whether the request fails the same way until the object or the token changes
+    pub fn is_terminal(&self) -> bool {
+        !self.is_retryable()
+    }
+}
@@ -0,0 +1,87 @@
+//! Timeouts, connection pooling and proxying of the HTTP clients talking to Cloudflare
+use reqwest::{NoProxy, Proxy, Url};
+use std::time::Duration;
+
+/// HTTP settings shared by every Cloudflare client of the operator
+///
+/// Without a `proxy`, requests go through `HTTPS_PROXY` and skip `NO_PROXY`, reqwest reads them from
+/// the environment.
+#[derive(Clone, Debug)]
+pub struct HttpSettings {
+    /// Time to establish a connection
+    pub connect_timeout: Duration,
+    /// Time for a whole request, a hung request fails after it instead of stalling the reconcile
+    pub request_timeout: Duration,
+    /// Idle connections kept per host
+    pub pool_max_idle: usize,
+    /// How long an idle connection is kept
+    pub pool_idle_timeout: Duration,
+    /// TCP keep-alive interval of open connections, off for `None`
+    pub tcp_keepalive: Option<Duration>,
+    /// Proxy replacing `HTTPS_PROXY`, hosts in `NO_PROXY` are still reached directly
+    pub proxy: Option<Url>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(30),
+            pool_max_idle: 8,
+            pool_idle_timeout: Duration::from_secs(90),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            proxy: None,
+        }
+    }
+}
+
+impl HttpSettings {
+    /// Defaults overridden by `CLOUDFLARE_CONNECT_TIMEOUT_SECONDS`, `CLOUDFLARE_REQUEST_TIMEOUT_SECONDS`,
+    /// `CLOUDFLARE_POOL_MAX_IDLE`, `CLOUDFLARE_POOL_IDLE_TIMEOUT_SECONDS` and
+    /// `CLOUDFLARE_TCP_KEEPALIVE_SECONDS` (0 turns keep-alive off); `CLOUDFLARE_PROXY_URL` replaces
+    /// `HTTPS_PROXY`, a value that isn't a URL is ignored
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let seconds = |name: &str| var(name).filter(|&s| s > 0).map(Duration::from_secs);
+        let defaults = Self::default();
+        Self {
+            connect_timeout: seconds("CLOUDFLARE_CONNECT_TIMEOUT_SECONDS")
+                .unwrap_or(defaults.connect_timeout),
+            request_timeout: seconds("CLOUDFLARE_REQUEST_TIMEOUT_SECONDS")
+                .unwrap_or(defaults.request_timeout),
+            pool_max_idle: var("CLOUDFLARE_POOL_MAX_IDLE").map_or(defaults.pool_max_idle, |n| n as usize),
+            pool_idle_timeout: seconds("CLOUDFLARE_POOL_IDLE_TIMEOUT_SECONDS")
+                .unwrap_or(defaults.pool_idle_timeout),
+            tcp_keepalive: match var("CLOUDFLARE_TCP_KEEPALIVE_SECONDS") {
+                Some(0) => None,
+                Some(s) => Some(Duration::from_secs(s)),
+                None => defaults.tcp_keepalive,
+            },
+            proxy: std::env::var("CLOUDFLARE_PROXY_URL")
+                .ok()
+                .and_then(|url| Url::parse(&url).ok()),
+        }
+    }
+
+    /// Send everything through `proxy` instead of what `CLOUDFLARE_PROXY_URL` or `HTTPS_PROXY` say
+    pub fn with_proxy(self, proxy: Url) -> Self {
+        Self {
+            proxy: Some(proxy),
+            ..self
+        }
+    }
+
+    /// A reqwest client builder with these settings applied
+    pub fn builder(&self) -> reqwest::Result<reqwest::ClientBuilder> {
+        let builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.request_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .tcp_keepalive(self.tcp_keepalive);
+        Ok(match &self.proxy {
+            Some(url) => builder.proxy(Proxy::all(url.clone())?.no_proxy(NoProxy::from_env())),
+            None => builder,
+        })
+    }
+}
@@ -0,0 +1,68 @@
+//! How long Cloudflare takes to answer, with the trace of the request as exemplar
+//!
+//! Grafana links a slow bucket to the trace behind it, which shows the reconcile the request was for.
+use crate::{metrics::TraceLabel, telemetry};
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{exemplar::HistogramWithExemplars, family::Family},
+};
+use reqwest::Method;
+use std::time::{Duration, SystemTime};
+
+type Histogram = HistogramWithExemplars<TraceLabel>;
+
+/// Segments at least this long made of hex digits are ids, Cloudflare's are 32
+const MIN_ID_LEN: usize = 16;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct LatencyLabels {
+    pub method: String,
+    /// Path of the request with the ids replaced by `:id`
+    pub route: String,
+}
+
+/// Duration of every attempt at a Cloudflare request, by method and route
+#[derive(Clone)]
+pub struct Latency {
+    pub durations: Family<LatencyLabels, Histogram, fn() -> Histogram>,
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Self {
+            durations: Family::new_with_constructor(|| {
+                HistogramWithExemplars::new([0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10., 30.].into_iter())
+            }),
+        }
+    }
+}
+
+impl Latency {
+    /// Record an attempt that took `elapsed`, with the trace of the current span as exemplar
+    pub fn observe(&self, method: &Method, path: &str, elapsed: Duration) {
+        let labels = LatencyLabels {
+            method: method.to_string(),
+            route: route(path),
+        };
+        let exemplar = (&telemetry::get_trace_id()).try_into().ok();
+        self.durations.get_or_create(&labels).observe(
+            elapsed.as_secs_f64(),
+            exemplar,
+            Some(SystemTime::now()),
+        );
+    }
+}
+
+/// `path` with the ids taken out, so that each endpoint is a single series
+pub fn route(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.len() >= MIN_ID_LEN && segment.chars().all(|c| c.is_ascii_hexdigit()) {
+                ":id"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
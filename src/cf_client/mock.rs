@@ -0,0 +1,370 @@
+//! In-memory Cloudflare for reconcile tests, no credentials or network needed
+//!
+//! Objects are kept in the shape the API returns them in and go through the same deserialization
+//! as real responses, so a mocked record looks like one Cloudflare handed out.
+use super::{
+    BatchDnsRecordsParams, BatchDnsRecordsResult, CfApiError, CloudflareApi, CreateDnsRecordParams,
+    CreateZoneParams, CustomHostname, DnsRecord, PatchDnsRecordParams, Result, UpdateDnsRecordParams, Zone,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::StatusCode;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value, json};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Zones and DNS records held in memory, every call is recorded for assertions
+#[derive(Default)]
+pub struct MockCloudflareApi {
+    zones: Mutex<BTreeMap<String, Value>>,
+    /// Records by id, with the id of their zone
+    records: Mutex<BTreeMap<String, (String, Value)>>,
+    /// Custom hostnames by id, with the id of their zone
+    custom_hostnames: Mutex<BTreeMap<String, (String, Value)>>,
+    calls: Mutex<Vec<String>>,
+    next_id: AtomicU64,
+}
+
+impl MockCloudflareApi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start with an active zone `name` in `account_id`
+    pub fn with_zone(self, zone_id: &str, name: &str, account_id: &str) -> Self {
+        let zone = zone_json(zone_id, name, account_id, "active");
+        self.zones.lock().unwrap().insert(zone_id.to_string(), zone);
+        self
+    }
+
+    /// Names of the calls made so far, in order
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Every record of `zone_id`
+    pub fn records(&self, zone_id: &str) -> Vec<DnsRecord> {
+        self.records
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(zone, _)| zone == zone_id)
+            .filter_map(|(_, record)| serde_json::from_value(record.clone()).ok())
+            .collect()
+    }
+
+    fn call(&self, name: &str) {
+        self.calls.lock().unwrap().push(name.to_string());
+    }
+
+    fn id(&self, kind: &str) -> String {
+        format!("mock-{kind}-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn insert_record(&self, zone_id: &str, id: Option<String>, params: impl Serialize) -> Result<DnsRecord> {
+        let zone = self.zone(zone_id)?;
+        let id = id.unwrap_or_else(|| self.id("record"));
+        let now = Utc::now();
+        let mut record = json!({
+            "id": id,
+            "zone_id": zone_id,
+            "ttl": 1,
+            "proxied": false,
+            "proxiable": true,
+            "locked": false,
+            "meta": { "auto_added": false },
+            "created_on": now,
+            "modified_on": now,
+        });
+        merge(&mut record, to_json(params)?);
+        qualify(&mut record, &zone);
+        let parsed = from_json(record.clone())?;
+        self.records
+            .lock()
+            .unwrap()
+            .insert(id, (zone_id.to_string(), record));
+        Ok(parsed)
+    }
+
+    fn patch_record(&self, zone_id: &str, record_id: &str, params: impl Serialize) -> Result<DnsRecord> {
+        let zone = self.zone(zone_id)?;
+        let mut records = self.records.lock().unwrap();
+        let Some((_, record)) = records.get_mut(record_id).filter(|(zone, _)| zone == zone_id) else {
+            return Err(not_found());
+        };
+        merge(record, to_json(params)?);
+        qualify(record, &zone);
+        record["modified_on"] = json!(Utc::now());
+        from_json(record.clone())
+    }
+
+    fn zone(&self, zone_id: &str) -> Result<Value> {
+        self.zones
+            .lock()
+            .unwrap()
+            .get(zone_id)
+            .cloned()
+            .ok_or_else(not_found)
+    }
+}
+
+#[async_trait]
+impl CloudflareApi for MockCloudflareApi {
+    async fn get_dns_record(&self, zone_id: &str, record_id: &str) -> Result<Option<DnsRecord>> {
+        self.call("get_dns_record");
+        match self.records.lock().unwrap().get(record_id) {
+            Some((zone, record)) if zone == zone_id => from_json(record.clone()).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    async fn list_dns_records(
+        &self,
+        zone_id: &str,
+        name: Option<&str>,
+        record_type: Option<&str>,
+    ) -> Result<Vec<DnsRecord>> {
+        self.call("list_dns_records");
+        self.zone(zone_id)?;
+        let records = self.records.lock().unwrap();
+        records
+            .values()
+            .filter(|(zone, _)| zone == zone_id)
+            .map(|(_, record)| record)
+            .filter(|record| name.is_none_or(|name| record["name"] == name))
+            .filter(|record| record_type.is_none_or(|t| record["type"] == t))
+            .map(|record| from_json(record.clone()))
+            .collect()
+    }
+
+    async fn create_dns_record(&self, zone_id: &str, params: CreateDnsRecordParams<'_>) -> Result<String> {
+        self.call("create_dns_record");
+        Ok(self.insert_record(zone_id, None, params)?.id)
+    }
+
+    async fn update_dns_record(
+        &self,
+        zone_id: &str,
+        record_id: &str,
+        params: UpdateDnsRecordParams<'_>,
+    ) -> Result<DnsRecord> {
+        self.call("update_dns_record");
+        self.patch_record(zone_id, record_id, params)
+    }
+
+    async fn patch_dns_record(
+        &self,
+        zone_id: &str,
+        record_id: &str,
+        params: PatchDnsRecordParams,
+    ) -> Result<DnsRecord> {
+        self.call("patch_dns_record");
+        self.patch_record(zone_id, record_id, params)
+    }
+
+    async fn batch_dns_records(
+        &self,
+        zone_id: &str,
+        params: BatchDnsRecordsParams,
+    ) -> Result<BatchDnsRecordsResult> {
+        self.call("batch_dns_records");
+        // Cloudflare applies deletes, patches, puts and posts in that order
+        let mut result = BatchDnsRecordsResult {
+            deletes: vec![],
+            patches: vec![],
+            posts: vec![],
+            puts: vec![],
+        };
+        for delete in params.deletes {
+            let removed = self.records.lock().unwrap().remove(&delete.id);
+            match removed {
+                Some((_, record)) => result.deletes.push(from_json(record)?),
+                None => return Err(not_found()),
+            }
+        }
+        for patch in params.patches {
+            result
+                .patches
+                .push(self.patch_record(zone_id, &patch.id, patch.params)?);
+        }
+        for put in params.puts {
+            let id = put
+                .id
+                .clone()
+                .ok_or_else(|| invalid("a put needs the id of the record"))?;
+            if !self.records.lock().unwrap().contains_key(&id) {
+                return Err(not_found());
+            }
+            result.puts.push(self.insert_record(zone_id, Some(id), put)?);
+        }
+        for post in params.posts {
+            result.posts.push(self.insert_record(zone_id, None, post)?);
+        }
+        Ok(result)
+    }
+
+    async fn delete_dns_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        self.call("delete_dns_record");
+        let mut records = self.records.lock().unwrap();
+        if records.get(record_id).is_some_and(|(zone, _)| zone == zone_id) {
+            records.remove(record_id);
+        }
+        Ok(())
+    }
+
+    async fn find_custom_hostname(&self, zone_id: &str, hostname: &str) -> Result<Option<CustomHostname>> {
+        self.call("find_custom_hostname");
+        let custom_hostnames = self.custom_hostnames.lock().unwrap();
+        custom_hostnames
+            .values()
+            .find(|(zone, custom)| zone == zone_id && custom["hostname"] == hostname)
+            .map(|(_, custom)| from_json(custom.clone()))
+            .transpose()
+    }
+
+    async fn create_custom_hostname(&self, zone_id: &str, hostname: &str) -> Result<CustomHostname> {
+        self.call("create_custom_hostname");
+        self.zone(zone_id)?;
+        let id = self.id("custom-hostname");
+        let custom = json!({
+            "id": id,
+            "hostname": hostname,
+            "status": "pending",
+            "ssl": { "method": "http", "type": "dv", "status": "pending_validation" },
+            "created_at": Utc::now(),
+        });
+        let parsed = from_json(custom.clone())?;
+        self.custom_hostnames
+            .lock()
+            .unwrap()
+            .insert(id, (zone_id.to_string(), custom));
+        Ok(parsed)
+    }
+
+    async fn delete_custom_hostname(&self, zone_id: &str, identifier: &str) -> Result<()> {
+        self.call("delete_custom_hostname");
+        let mut custom_hostnames = self.custom_hostnames.lock().unwrap();
+        if custom_hostnames
+            .get(identifier)
+            .is_some_and(|(zone, _)| zone == zone_id)
+        {
+            custom_hostnames.remove(identifier);
+        }
+        Ok(())
+    }
+
+    async fn get_zone(&self, identifier: &str) -> Result<Zone> {
+        self.call("get_zone");
+        from_json(self.zone(identifier)?)
+    }
+
+    async fn find_zone(&self, name: &str, account_id: &str) -> Result<Option<Zone>> {
+        self.call("find_zone");
+        let zones = self.zones.lock().unwrap();
+        zones
+            .values()
+            .find(|zone| zone["name"] == name && zone["account"]["id"] == account_id)
+            .map(|zone| from_json(zone.clone()))
+            .transpose()
+    }
+
+    async fn create_zone(&self, params: CreateZoneParams<'_>) -> Result<Zone> {
+        self.call("create_zone");
+        let id = self.id("zone");
+        let zone = zone_json(&id, params.name, params.account, "pending");
+        let parsed = from_json(zone.clone())?;
+        self.zones.lock().unwrap().insert(id, zone);
+        Ok(parsed)
+    }
+
+    async fn delete_zone(&self, identifier: &str) -> Result<()> {
+        self.call("delete_zone");
+        self.zones.lock().unwrap().remove(identifier);
+        self.records
+            .lock()
+            .unwrap()
+            .retain(|_, (zone, _)| zone != identifier);
+        Ok(())
+    }
+}
+
+/// A zone as the API returns it
+fn zone_json(id: &str, name: &str, account_id: &str, status: &str) -> Value {
+    let now = Utc::now();
+    json!({
+        "id": id,
+        "name": name,
+        "account": { "id": account_id, "name": account_id },
+        "activated_on": (status == "active").then_some(now),
+        "created_on": now,
+        "modified_on": now,
+        "development_mode": 0,
+        "meta": {
+            "custom_certificate_quota": 0,
+            "page_rule_quota": 3,
+            "wildcard_proxiable": false,
+            "phishing_detected": false,
+        },
+        "name_servers": ["ada.ns.cloudflare.com", "bob.ns.cloudflare.com"],
+        "original_dnshost": null,
+        "original_name_servers": null,
+        "original_registrar": null,
+        "owner": { "type": "user", "id": account_id, "email": "mock@example.com" },
+        "paused": false,
+        "permissions": [],
+        "plan": null,
+        "plan_pending": null,
+        "status": status,
+        "vanity_name_servers": null,
+        "type": "full",
+    })
+}
+
+/// Set the fields of `patch` on `target`, both objects
+/// Fields of `patch` over `target`, unset ones (`null`) keep the value Cloudflare has
+fn merge(target: &mut Value, patch: Value) {
+    if let (Some(target), Value::Object(patch)) = (target.as_object_mut(), patch) {
+        target.extend(patch.into_iter().filter(|(_, value)| !value.is_null()));
+    }
+}
+
+/// Names relative to the zone made full hostnames, as Cloudflare hands them out
+fn qualify(record: &mut Value, zone: &Value) {
+    let (Some(name), Some(zone)) = (record["name"].as_str(), zone["name"].as_str()) else {
+        return;
+    };
+    let name = match name.trim_end_matches('.') {
+        "@" => zone.to_string(),
+        name if name == zone || name.ends_with(&format!(".{zone}")) => name.to_string(),
+        name => format!("{name}.{zone}"),
+    };
+    record["name"] = json!(name);
+}
+
+fn to_json(value: impl Serialize) -> Result<Value> {
+    match serde_json::to_value(value).map_err(|e| invalid(&e.to_string()))? {
+        Value::Null => Ok(Value::Object(Map::new())),
+        value => Ok(value),
+    }
+}
+
+fn from_json<T: DeserializeOwned>(value: Value) -> Result<T> {
+    serde_json::from_value(value).map_err(|e| invalid(&e.to_string()))
+}
+
+fn not_found() -> CfApiError {
+    CfApiError::Api {
+        status: StatusCode::NOT_FOUND,
+        errors: vec![],
+    }
+}
+
+fn invalid(message: &str) -> CfApiError {
+    CfApiError::InvalidRequest(message.to_string())
+}
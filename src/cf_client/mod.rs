@@ -1,36 +1,678 @@
-use std::sync::Arc;
+mod api;
+pub mod audit;
+mod debug;
+mod endpoints;
+mod error;
+mod http;
+mod latency;
+#[cfg(test)] mod mock;
+mod quota;
+mod read_cache;
+mod retry;
+
+use std::{sync::Arc, time::Duration};
 // re-export the types, I feel like it's fine
 pub use cloudflare::endpoints::{
     account::{Account, GetAccount},
-    dns::dns::{CreateDnsRecordParams, DnsContent},
-    users::TokenVerification,
-    zones::zone::{CreateZone, CreateZoneParams, Zone, ZoneDetails},
+    dns::dns::{CreateDnsRecordParams, DnsContent, DnsRecord, UpdateDnsRecordParams},
+    users::{TokenVerification, UserTokenStatus},
+    zones::zone::{
+        CreateZone, CreateZoneParams, DeleteZone, ListZones, ListZonesParams, Plan, Type as ZoneType, Zone,
+        ZoneDetails,
+    },
 };
 
+use crate::telemetry;
+pub use api::CloudflareApi;
+use chrono::{DateTime, Utc};
 use cloudflare::{
-    endpoints::{account::ListAccounts, dns::dns},
+    endpoints::{
+        account::{ListAccounts, ListAccountsParams},
+        dns::dns,
+    },
     framework::{
-        Environment, auth,
-        client::{ClientConfig, async_api},
+        endpoint::{EndpointSpec, RequestBody},
+        response::{ApiError, ApiErrors, ApiSuccess},
     },
 };
+pub use endpoints::{
+    AccountDetails, AccountMember, AccountMemberUser, AccountRole, AccountSettings, ApiToken, ApiTokenParams,
+    AuditLog, BatchDelete, BatchDnsRecordsParams, BatchDnsRecordsResult, BatchPatch, BatchRecord,
+    CreatePageRule, CustomHostname, CustomHostnameParams, CustomHostnameSsl, DeletePageRule,
+    EditPageRulePriority, EditZoneParams, GetPageRule, ListPageRules, MAX_PURGE_ITEMS, PageRule,
+    PageRuleAction, PageRuleConstraint, PageRuleParams, PageRulePriorityParams, PageRuleTarget,
+    PatchDnsRecordParams, PermissionGroupId, PurgeRequest, Subscription, Toggle, TokenPolicy, Tunnel,
+    TunnelConfiguration, TunnelConnection, UpdateAccount, UpdateAccountParams, UpdatePageRule, ZoneSetting,
+    ZoneSettingValue,
+};
+use endpoints::{
+    AccountTokenVerification, BatchDnsRecords, CreateAccountMember, CreateAccountMemberParams,
+    CreateApiToken, CreateCustomHostname, CreateTunnel, CreateTunnelParams, DeleteAccountMember,
+    DeleteApiToken, DeleteCustomHostname, DeleteTunnel, DeleteTunnelConnections, DnsRecordDetails, EditZone,
+    EditZoneSetting, EditZoneSettingParams, EditZoneSettings, EditZoneSettingsParams, GetAccountDetails,
+    GetAccountMember, GetApiToken, GetTunnel, GetTunnelConfiguration, GetTunnelToken, ListAccountMembers,
+    ListAccountSubscriptions, ListAccountZones, ListAuditLogs, ListAuditLogsParams, ListCustomHostnames,
+    ListDnsRecords, ListTunnels, PatchDnsRecord, PurgeCache, RatePlan, RoleId, TunnelConfigurationParams,
+    UpdateAccountMember, UpdateAccountMemberParams, UpdateApiToken, UpdateTunnelConfiguration,
+    UpdateZoneSubscription, ZoneActivationCheck, ZoneSettings, ZoneSubscriptionParams,
+};
+pub use error::{CfApiError, RECORD_EXISTS, ZONE_EXISTS};
+pub use http::HttpSettings;
+pub use latency::{Latency, LatencyLabels};
+#[cfg(test)] pub use mock::MockCloudflareApi;
+pub use quota::{Quota, QuotaLabels};
+use read_cache::{ReadCache, zone_key};
+use reqwest::{
+    Method, StatusCode, Url,
+    header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue},
+};
+pub use retry::{Retries, RetryLabels};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::{
+    sync::{Mutex, Semaphore},
+    time::Instant,
+};
+use tracing::{Instrument, info, info_span, warn};
+use zeroize::Zeroizing;
+
+/// Cloudflare API clients talk to unless the operator or the object names another one
+pub const DEFAULT_API_URL: &str = "https://api.cloudflare.com/client/v4";
+
+/// Header an Origin CA key goes in, in place of `Authorization`
+const ORIGIN_CA_KEY_HEADER: &str = "x-auth-user-service-key";
+
+/// W3C trace context of the request
+const TRACEPARENT: &str = "traceparent";
+
+/// Ray ID Cloudflare tags every response with, what its support asks for
+const CF_RAY: &str = "cf-ray";
+
+/// How many items we ask for per page when walking paginated list endpoints
+const PAGE_SIZE: u32 = 50;
+
+/// How many requests a single token may have in flight at once, unless configured otherwise
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+
+/// Requests a token may send per `RATE_WINDOW` unless configured otherwise, below Cloudflare's 1200
+pub const DEFAULT_RATE_LIMIT: u32 = 1000;
+
+/// Window Cloudflare counts requests in
+const RATE_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// How long a token is held back after Cloudflare rate limited it
+const THROTTLE_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long the outcome of a token verification is trusted, a 401 or 403 ends it early
+const VERIFICATION_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Pagination details Cloudflare returns in `result_info` for list endpoints
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct ResultInfo {
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+    pub count: Option<u32>,
+    pub total_count: Option<u32>,
+    pub total_pages: Option<u32>,
+}
+
+impl ResultInfo {
+    /// Whether there are pages left after the one this info was returned with
+    pub fn has_next_page(&self) -> bool {
+        match (self.page, self.total_pages) {
+            (Some(page), Some(total)) => page < total,
+            // some endpoints only return counts, so fall back to those
+            _ => match (self.count, self.per_page) {
+                (Some(count), Some(per_page)) => count >= per_page && count > 0,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// A message Cloudflare attached to an otherwise successful response (deprecation notices and such)
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ApiMessage {
+    pub code: Option<u32>,
+    pub message: String,
+}
+
+/// Successful Cloudflare response with the envelope fields kept next to the result
+#[derive(Clone, Debug)]
+pub struct CfResponse<T> {
+    pub result: T,
+    pub result_info: Option<ResultInfo>,
+    pub messages: Vec<ApiMessage>,
+    pub errors: Vec<ApiError>,
+}
+
+impl<T> CfResponse<T> {
+    fn from_success(success: ApiSuccess<T>) -> Self {
+        Self {
+            result_info: success
+                .result_info
+                .and_then(|info| serde_json::from_value(info).ok()),
+            messages: parse_messages(&success.messages),
+            errors: success.errors,
+            result: success.result,
+        }
+    }
+}
+
+/// Whether the error is Cloudflare telling us the object doesn't exist
+pub fn is_not_found(error: &CfApiError) -> bool {
+    error.is_not_found()
+}
+
+/// Whether the error is Cloudflare refusing the token the operation
+pub fn is_forbidden(error: &CfApiError) -> bool {
+    error.is_forbidden()
+}
+
+/// Total number of items behind a list call, falling back to what the first page returned
+fn total_count(info: Option<ResultInfo>, fetched: usize) -> u32 {
+    info.and_then(|info| info.total_count).unwrap_or(fetched as u32)
+}
+
+
+/// Cloudflare is not consistent about the shape of `messages`, it's either a list of strings
+/// or a list of `{code, message}` objects, so accept both
+fn parse_messages(messages: &serde_json::Value) -> Vec<ApiMessage> {
+    let Some(items) = messages.as_array() else {
+        return vec![];
+    };
+    items
+        .iter()
+        .filter_map(|item| match item {
+            serde_json::Value::String(message) => Some(ApiMessage {
+                code: None,
+                message: message.clone(),
+            }),
+            other => serde_json::from_value(other.clone()).ok(),
+        })
+        .collect()
+}
+
+/// The envelope of a 2xx answer, the errors Cloudflare gave otherwise
+async fn api_success<T: DeserializeOwned>(response: reqwest::Response) -> Result<ApiSuccess<T>> {
+    let status = response.status();
+    if status.is_success() {
+        return response.json().await.map_err(Into::into);
+    }
+    let errors: ApiErrors = response.json().await.unwrap_or_default();
+    Err(CfApiError::Api {
+        status,
+        errors: errors.errors,
+    })
+}
+
+#[derive(Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+/// Limits of a single token, shared by all clones of its client
+///
+/// Every token gets its own, so a token that is throttled or slow only holds up the reconciles using it.
+struct TokenLimits {
+    in_flight: Semaphore,
+    throttled_until: Mutex<Option<Instant>>,
+    /// Spreads the requests of the token over the rate window, `None` when unlimited
+    bucket: Option<TokenBucket>,
+}
+
+impl TokenLimits {
+    /// Limits allowing `rate_limit` requests per `RATE_WINDOW`, any number for `None`, and
+    /// `max_in_flight` at once
+    fn new(rate_limit: Option<u32>, max_in_flight: usize) -> Self {
+        Self {
+            in_flight: Semaphore::new(max_in_flight.max(1)),
+            throttled_until: Mutex::new(None),
+            bucket: rate_limit.map(TokenBucket::new),
+        }
+    }
+
+    /// Wait out a throttle window and the rate limit, then take an in-flight slot
+    async fn acquire(&self) -> tokio::sync::SemaphorePermit<'_> {
+        let until = *self.throttled_until.lock().await;
+        if let Some(until) = until {
+            tokio::time::sleep_until(until).await;
+        }
+        if let Some(bucket) = &self.bucket {
+            bucket.take().await;
+        }
+        self.in_flight.acquire().await.expect("semaphore is never closed")
+    }
+
+    /// Hold requests back for `wait`, or `THROTTLE_BACKOFF` when Cloudflare didn't say how long
+    async fn throttle(&self, wait: Option<Duration>) {
+        let wait = wait.unwrap_or(THROTTLE_BACKOFF);
+        let until = Instant::now() + wait;
+        let mut throttled_until = self.throttled_until.lock().await;
+        if throttled_until.is_none_or(|current| current < until) {
+            warn!("Cloudflare rate limited the token, holding its requests back for {wait:?}");
+            *throttled_until = Some(until);
+        }
+    }
+}
+
+/// Token bucket holding a window's worth of requests, refilled evenly over the window
+///
+/// A burst (all controllers resyncing after a restart) gets the full bucket at once and then
+/// continues at the refill rate, instead of running into Cloudflare's limit and being locked out.
+struct TokenBucket {
+    capacity: f64,
+    /// Requests regained per second
+    refill: f64,
+    /// Requests left and when that was worked out
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(requests_per_window: u32) -> Self {
+        let capacity = f64::from(requests_per_window.max(1));
+        Self {
+            capacity,
+            refill: capacity / RATE_WINDOW.as_secs_f64(),
+            state: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    /// Wait until a request is left in the bucket and take it
+    async fn take(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                let (left, since) = &mut *state;
+                let now = Instant::now();
+                *left = (*left + (now - *since).as_secs_f64() * self.refill).min(self.capacity);
+                *since = now;
+                if *left >= 1. {
+                    *left -= 1.;
+                    return;
+                }
+                Duration::from_secs_f64((1. - *left) / self.refill)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
 
 pub struct CloudflareClient {
-    client: Arc<async_api::Client>,
+    /// Sends every request, with the credentials as a default header
+    ///
+    /// The endpoints are cloudflare-rs ones, but sending them here rather than through its client
+    /// keeps the response headers: the rate limit quota among them.
+    http: reqwest::Client,
+    /// Base the endpoint paths are resolved against, ending in a slash
+    api_url: Url,
+    limits: Arc<TokenLimits>,
+    /// GraphQL analytics endpoint of the API the client talks to
+    graphql_url: String,
+    /// Outcome of the last token verification and when it was asked for
+    verification: Arc<std::sync::Mutex<Option<(UserTokenStatus, Instant)>>>,
+    /// Requests sent again after a transient failure
+    retries: Retries,
+    /// Log every request and its outcome, bodies redacted
+    debug_http: bool,
+    /// Rate limit headroom, exported as metrics
+    quota: Quota,
+    /// Series of the token in `quota`
+    quota_labels: QuotaLabels,
+    /// Duration of every attempt, exported as a metric
+    latency: Latency,
+    /// Recent reads, shared by all clones
+    reads: Arc<ReadCache>,
 }
 
-use anyhow::Result;
+/// Result of a Cloudflare call
+pub type Result<T, E = CfApiError> = std::result::Result<T, E>;
+
 impl CloudflareClient {
-    pub fn new(token: String) -> Result<Self> {
-        let credentials = auth::Credentials::UserAuthToken { token };
-        let api_client =
-            async_api::Client::new(credentials, ClientConfig::default(), Environment::Production)?;
+    /// Client authenticating with `token`, the caller may wipe its copy afterwards
+    ///
+    /// `api_url` replaces `DEFAULT_API_URL`, for the China network or a mock server.
+    pub fn new(token: &str, api_url: Option<&Url>, http: &HttpSettings) -> anyhow::Result<Self> {
+        let bearer = Zeroizing::new(format!("Bearer {token}"));
+        Self::build(AUTHORIZATION, &bearer, api_url, http)
+    }
+
+    /// Client authenticating with an Origin CA key, only the Origin CA certificate endpoints take it
+    pub fn with_origin_ca_key(key: &str, api_url: Option<&Url>, http: &HttpSettings) -> anyhow::Result<Self> {
+        Self::build(HeaderName::from_static(ORIGIN_CA_KEY_HEADER), key, api_url, http)
+    }
+
+    fn build(
+        header: HeaderName,
+        value: &str,
+        api_url: Option<&Url>,
+        settings: &HttpSettings,
+    ) -> anyhow::Result<Self> {
+        let mut auth_header = HeaderValue::from_str(value)?;
+        auth_header.set_sensitive(true);
+        let http = settings
+            .builder()?
+            .default_headers(HeaderMap::from_iter([(header, auth_header)]))
+            .build()?;
+
+        let base = api_url.map_or(DEFAULT_API_URL, Url::as_str).trim_end_matches('/');
+        let graphql_url = format!("{base}/graphql");
+        // without the slash, joining a path would replace the last segment (`v4`)
+        let api_url = Url::parse(&format!("{base}/"))?;
 
         Ok(Self {
-            client: Arc::new(api_client),
+            http,
+            api_url,
+            limits: Arc::new(TokenLimits::new(Some(DEFAULT_RATE_LIMIT), DEFAULT_MAX_IN_FLIGHT)),
+            graphql_url,
+            verification: Arc::default(),
+            retries: Retries::default(),
+            debug_http: false,
+            quota: Quota::default(),
+            quota_labels: QuotaLabels { token: String::new() },
+            latency: Latency::default(),
+            reads: Arc::default(),
         })
     }
 
+    /// Allow `rate_limit` requests per five minutes instead of `DEFAULT_RATE_LIMIT`, any number for `None`,
+    /// and `max_in_flight` at once instead of `DEFAULT_MAX_IN_FLIGHT`
+    ///
+    /// Only takes effect on a fresh client, clones share the limits of the client they come from.
+    pub fn with_limits(self, rate_limit: Option<u32>, max_in_flight: usize) -> Self {
+        Self {
+            limits: Arc::new(TokenLimits::new(rate_limit, max_in_flight)),
+            ..self
+        }
+    }
+
+    /// Count retries in `retries` instead of a counter of the client's own
+    pub fn with_retries(self, retries: Retries) -> Self {
+        Self { retries, ..self }
+    }
+
+    /// Log method, path, redacted body and outcome of every request
+    pub fn with_debug_http(self, debug_http: bool) -> Self {
+        Self { debug_http, ..self }
+    }
+
+    /// Report the rate limit headroom in `quota`, under `token`: a hash of the token, not the token
+    pub fn with_quota(self, quota: Quota, token: String) -> Self {
+        Self {
+            quota,
+            quota_labels: QuotaLabels { token },
+            ..self
+        }
+    }
+
+    /// Record how long every attempt takes in `latency`
+    pub fn with_latency(self, latency: Latency) -> Self {
+        Self { latency, ..self }
+    }
+
+    /// Reuse zone details, settings and DNS records read less than `ttl` ago, nothing for `None`
+    pub fn with_read_cache(self, ttl: Option<Duration>) -> Self {
+        Self {
+            reads: Arc::new(ReadCache::new(ttl)),
+            ..self
+        }
+    }
+
+    /// What was read under `key` within the cache TTL, otherwise what `read` reads
+    async fn cached<T, F, Fut>(&self, key: String, read: F) -> Result<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if let Some(value) = self.reads.get(&key) {
+            return Ok(value);
+        }
+        let value = read().await?;
+        self.reads.put(key, &value);
+        Ok(value)
+    }
+
+    /// Send what `send` builds until it succeeds, fails for good or runs out of retries
+    ///
+    /// Rate limited requests wait for the throttle window of the token, other transient failures
+    /// back off exponentially. The retries are logged on the current span and counted, every attempt
+    /// is timed.
+    async fn send_with_retries<T, F, Fut>(
+        &self,
+        method: &Method,
+        path: &str,
+        idempotent: bool,
+        send: F,
+    ) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = (Result<T>, Option<Duration>)>,
+    {
+        let mut attempt = 0;
+        loop {
+            let (result, retry_after) = {
+                let _permit = self.limits.acquire().await;
+                let started = Instant::now();
+                let sent = send().await;
+                self.latency.observe(method, path, started.elapsed());
+                sent
+            };
+            let error = match result {
+                Ok(value) => {
+                    if attempt > 0 {
+                        info!(
+                            path,
+                            retries = attempt,
+                            "Cloudflare request went through after retrying"
+                        );
+                    }
+                    return Ok(value);
+                }
+                Err(error) => error,
+            };
+            let rate_limited = error.is_rate_limited();
+            if rate_limited {
+                self.quota.rate_limited.get_or_create(&self.quota_labels).inc();
+                self.limits.throttle(retry_after).await;
+            }
+            let reason = match retry::reason(&error, idempotent) {
+                Some(reason) if attempt < retry::MAX_RETRIES => reason,
+                _ => return Err(error),
+            };
+            attempt += 1;
+            self.retries.get_or_create(&RetryLabels { reason }).inc();
+            warn!(
+                path,
+                attempt, reason, "Cloudflare request failed, retrying: {error}"
+            );
+            // the throttle window is waited out when the next attempt takes its permit
+            if !rate_limited {
+                tokio::time::sleep(retry::backoff(attempt)).await;
+            }
+        }
+    }
+
+    /// Run a query against the GraphQL analytics API
+    ///
+    /// The request carries the `traceparent` of its span, and the span the Ray ID Cloudflare answers with.
+    pub async fn graphql<T: DeserializeOwned>(&self, query: &str, variables: serde_json::Value) -> Result<T> {
+        let body = serde_json::json!({ "query": query, "variables": variables });
+        let span = info_span!(
+            "cloudflare",
+            method = "POST",
+            path = "graphql",
+            cf_ray = tracing::field::Empty
+        );
+        // queries only read, so they are retried like a GET
+        let response: GraphqlResponse<T> = self
+            .send_with_retries(&Method::POST, "graphql", true, || async {
+                if self.debug_http {
+                    let body = Some(RequestBody::Json(body.to_string()));
+                    debug::request(&Method::POST, "graphql", None, body);
+                }
+                let mut request = self.http.post(&self.graphql_url).json(&body);
+                if let Some(traceparent) = telemetry::traceparent() {
+                    request = request.header(TRACEPARENT, traceparent);
+                }
+                let response = match request.send().await {
+                    Ok(response) => response,
+                    Err(e) => return (Err(e.into()), None),
+                };
+                if let Some(ray) = response.headers().get(CF_RAY).and_then(|v| v.to_str().ok()) {
+                    span.record("cf_ray", ray);
+                }
+                let retry_after = retry::retry_after(&response);
+                if let Some((remaining, reset)) = quota::from_headers(response.headers()) {
+                    self.quota.record(&self.quota_labels, remaining, reset);
+                }
+                let status = response.status();
+                let result = match response.error_for_status() {
+                    Ok(response) => response.json().await.map_err(Into::into),
+                    Err(e) => Err(e.into()),
+                };
+                if self.debug_http {
+                    debug::response(&Method::POST, "graphql", status, &result);
+                }
+                (result, retry_after)
+            })
+            .instrument(span.clone())
+            .await?;
+        if !response.errors.is_empty() {
+            let messages: Vec<_> = response.errors.into_iter().map(|e| e.message).collect();
+            return Err(CfApiError::Graphql(messages.join("; ")));
+        }
+        response
+            .data
+            .ok_or_else(|| CfApiError::Graphql("the response has no data".into()))
+    }
+
+    /// Send a request and keep the whole response envelope
+    ///
+    /// Messages and non-fatal errors that Cloudflare returns next to the result are logged here,
+    /// so that every call surfaces them without the callers having to care. Like GraphQL queries,
+    /// the request carries the `traceparent` of its span, and the span the Ray ID of the answer.
+    pub async fn request<E>(&self, endpoint: &E) -> Result<CfResponse<E::JsonResponse>>
+    where
+        E: EndpointSpec<ResponseType = ApiSuccess<<E as EndpointSpec>::JsonResponse>> + Send + Sync,
+    {
+        let path = endpoint.path();
+        let method = endpoint.method();
+        let idempotent = retry::is_idempotent(&method);
+        let mut url = match self.api_url.join(&path) {
+            Ok(url) => url,
+            Err(e) => return Err(CfApiError::InvalidRequest(format!("{path}: {e}"))),
+        };
+        url.set_query(endpoint.query().as_deref());
+        let span = info_span!(
+            "cloudflare",
+            %method,
+            path = %path,
+            cf_ray = tracing::field::Empty
+        );
+        let success = self
+            .send_with_retries(&method, &path, idempotent, || async {
+                if self.debug_http {
+                    debug::request(&method, &path, endpoint.query(), endpoint.body());
+                }
+                let mut request = self.http.request(method.clone(), url.clone());
+                request = match endpoint.body() {
+                    Some(RequestBody::Json(body)) => {
+                        request.header(CONTENT_TYPE, "application/json").body(body)
+                    }
+                    Some(RequestBody::Raw(bytes)) => request
+                        .header(CONTENT_TYPE, "application/octet-stream")
+                        .body(bytes),
+                    Some(RequestBody::MultiPart(_)) => {
+                        let error = CfApiError::InvalidRequest("multipart bodies aren't supported".into());
+                        return (Err(error), None);
+                    }
+                    None => request,
+                };
+                if let Some(traceparent) = telemetry::traceparent() {
+                    request = request.header(TRACEPARENT, traceparent);
+                }
+                let response = match request.send().await {
+                    Ok(response) => response,
+                    Err(e) => return (Err(e.into()), None),
+                };
+                if let Some(ray) = response.headers().get(CF_RAY).and_then(|v| v.to_str().ok()) {
+                    span.record("cf_ray", ray);
+                }
+                let retry_after = retry::retry_after(&response);
+                if let Some((remaining, reset)) = quota::from_headers(response.headers()) {
+                    self.quota.record(&self.quota_labels, remaining, reset);
+                }
+                let status = response.status();
+                let result = api_success(response).await;
+                if self.debug_http {
+                    debug::response(&method, &path, status, &result);
+                }
+                (result, retry_after)
+            })
+            .instrument(span.clone())
+            .await;
+        if let Err(e) = &success
+            && matches!(e.status(), Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN))
+        {
+            // the token may have been revoked or narrowed since it was verified
+            *self.verification.lock().unwrap() = None;
+        }
+        if method != Method::GET {
+            let mut change = audit::Change::new(&method, &path, endpoint.body(), &self.quota_labels.token);
+            change.error = success.as_ref().err().map(ToString::to_string);
+            audit::record(change);
+        }
+        let response = CfResponse::from_success(success?);
+        for message in &response.messages {
+            warn!(
+                path = %endpoint.path(),
+                code = ?message.code,
+                "Cloudflare returned a message: {}",
+                message.message
+            );
+        }
+        for error in &response.errors {
+            warn!(
+                path = %endpoint.path(),
+                code = error.code,
+                "Cloudflare returned a non-fatal error: {}",
+                error.message
+            );
+        }
+        Ok(response)
+    }
+
+    /// Walk all pages of a list endpoint, `make_endpoint` builds the request for a given page
+    pub async fn paginate<E, T, F>(&self, make_endpoint: F) -> Result<Vec<T>>
+    where
+        E: EndpointSpec<JsonResponse = Vec<T>, ResponseType = ApiSuccess<Vec<T>>> + Send + Sync,
+        F: Fn(u32, u32) -> E,
+    {
+        let mut items = vec![];
+        let mut page = 1;
+        loop {
+            let response = self.request(&make_endpoint(page, PAGE_SIZE)).await?;
+            let fetched = response.result.len();
+            items.extend(response.result);
+            let has_next = match response.result_info {
+                Some(info) => info.has_next_page(),
+                None => false,
+            };
+            if !has_next || fetched == 0 {
+                break;
+            }
+            page += 1;
+        }
+        Ok(items)
+    }
+
     pub async fn create_dns_record(
         &self,
         zone_id: &str,
@@ -42,35 +684,788 @@ impl CloudflareClient {
             zone_identifier: zone_id,
             params: dns_params,
         };
-        let response = self.client.request(&endpoint).await?;
+        self.reads.invalidate(zone_id);
+        let response = self.request(&endpoint).await?;
+        audit::created(&response.result.id);
         Ok(response.result.id)
     }
 
-    pub async fn create_zone(&self, params: CreateZoneParams<'_>) -> Result<String> {
-        Ok(self.client.request(&CreateZone { params }).await?.result.id)
+    /// Fetch a DNS record, `None` when it no longer exists on the Cloudflare side
+    pub async fn get_dns_record(&self, zone_id: &str, record_id: &str) -> Result<Option<DnsRecord>> {
+        let endpoint = DnsRecordDetails {
+            zone_identifier: zone_id,
+            identifier: record_id,
+        };
+        let key = format!("{}/dns_records/{record_id}", zone_key(zone_id));
+        self.cached(key, || async {
+            match self.request(&endpoint).await {
+                Ok(response) => Ok(Some(response.result)),
+                Err(e) if is_not_found(&e) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+        .await
+    }
+
+    /// Every record of the zone, only those named `name` and of `record_type` when given
+    pub async fn list_dns_records(
+        &self,
+        zone_id: &str,
+        name: Option<&str>,
+        record_type: Option<&str>,
+    ) -> Result<Vec<DnsRecord>> {
+        let key = format!(
+            "{}/dns_records?name={}&type={}",
+            zone_key(zone_id),
+            name.unwrap_or_default(),
+            record_type.unwrap_or_default()
+        );
+        self.cached(key, || {
+            self.paginate(|page, per_page| ListDnsRecords {
+                zone_identifier: zone_id,
+                name,
+                record_type,
+                page,
+                per_page,
+            })
+        })
+        .await
+    }
+
+    /// Look up the record of `record_type` named `name`, the first one when there are several
+    pub async fn find_dns_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        record_type: &str,
+    ) -> Result<Option<DnsRecord>> {
+        Ok(self
+            .list_dns_records(zone_id, Some(name), Some(record_type))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    pub async fn update_dns_record(
+        &self,
+        zone_id: &str,
+        record_id: &str,
+        params: UpdateDnsRecordParams<'_>,
+    ) -> Result<DnsRecord> {
+        let endpoint = dns::UpdateDnsRecord {
+            zone_identifier: zone_id,
+            identifier: record_id,
+            params,
+        };
+        self.reads.invalidate(zone_id);
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Change only the fields set in `params`, the rest of the record stays as it is
+    pub async fn patch_dns_record(
+        &self,
+        zone_id: &str,
+        record_id: &str,
+        params: PatchDnsRecordParams,
+    ) -> Result<DnsRecord> {
+        let endpoint = PatchDnsRecord {
+            zone_identifier: zone_id,
+            identifier: record_id,
+            params,
+        };
+        self.reads.invalidate(zone_id);
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Apply a batch of record changes to a zone in a single request
+    pub async fn batch_dns_records(
+        &self,
+        zone_id: &str,
+        params: BatchDnsRecordsParams,
+    ) -> Result<BatchDnsRecordsResult> {
+        let endpoint = BatchDnsRecords {
+            zone_identifier: zone_id,
+            params,
+        };
+        self.reads.invalidate(zone_id);
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Delete a DNS record, a record that is already gone counts as deleted
+    pub async fn delete_dns_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        let endpoint = dns::DeleteDnsRecord {
+            zone_identifier: zone_id,
+            identifier: record_id,
+        };
+        self.reads.invalidate(zone_id);
+        match self.request(&endpoint).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn create_zone(&self, params: CreateZoneParams<'_>) -> Result<Zone> {
+        let created = self.request(&CreateZone { params }).await?.result;
+        audit::created(&created.id);
+        Ok(created)
     }
 
     pub async fn get_zone(&self, identifier: &str) -> Result<Zone> {
-        Ok(self.client.request(&ZoneDetails { identifier }).await?.result)
+        self.cached(zone_key(identifier), || async {
+            Ok(self.request(&ZoneDetails { identifier }).await?.result)
+        })
+        .await
+    }
+
+    /// Every zone the token can see, only those named `name` or in `account_id` when given
+    pub async fn list_zones(&self, name: Option<&str>, account_id: Option<&str>) -> Result<Vec<Zone>> {
+        self.paginate(|page, per_page| ListAccountZones {
+            account_identifier: account_id,
+            name,
+            page,
+            per_page,
+        })
+        .await
+    }
+
+    /// Look up a zone by its domain name within an account
+    pub async fn find_zone(&self, name: &str, account_id: &str) -> Result<Option<Zone>> {
+        Ok(self
+            .list_zones(Some(name), Some(account_id))
+            .await?
+            .into_iter()
+            .find(|zone| zone.account.id == account_id))
+    }
+
+    pub async fn edit_zone(&self, identifier: &str, params: EditZoneParams) -> Result<Zone> {
+        self.reads.invalidate(identifier);
+        Ok(self.request(&EditZone { identifier, params }).await?.result)
     }
 
-    pub async fn get_account(&self, identifier: &str) -> Result<Account> {
-        Ok(self.client.request(&GetAccount { identifier }).await?.result)
+    /// Trigger a nameserver check for a pending zone
+    pub async fn check_zone_activation(&self, identifier: &str) -> Result<()> {
+        self.reads.invalidate(identifier);
+        self.request(&ZoneActivationCheck { identifier }).await?;
+        Ok(())
+    }
+
+    /// Move a zone to another rate plan
+    pub async fn update_zone_plan(&self, zone_id: &str, plan: &str) -> Result<()> {
+        let endpoint = UpdateZoneSubscription {
+            zone_identifier: zone_id,
+            params: ZoneSubscriptionParams {
+                rate_plan: RatePlan { id: plan },
+            },
+        };
+        self.reads.invalidate(zone_id);
+        self.request(&endpoint).await?;
+        Ok(())
+    }
+
+    /// Every setting of the zone, `ZoneSetting::typed` turns the managed ones into their values
+    pub async fn get_all_zone_settings(&self, zone_id: &str) -> Result<Vec<ZoneSetting>> {
+        self.cached(format!("{}/settings", zone_key(zone_id)), || async {
+            Ok(self
+                .request(&ZoneSettings {
+                    zone_identifier: zone_id,
+                })
+                .await?
+                .result)
+        })
+        .await
+    }
+
+    pub async fn patch_zone_setting(&self, zone_id: &str, setting: &ZoneSettingValue) -> Result<ZoneSetting> {
+        let endpoint = EditZoneSetting {
+            zone_identifier: zone_id,
+            setting_id: setting.id(),
+            params: EditZoneSettingParams {
+                value: setting.value(),
+            },
+        };
+        self.reads.invalidate(zone_id);
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Change several settings at once, a rejected value fails the whole request
+    pub async fn patch_zone_settings(
+        &self,
+        zone_id: &str,
+        settings: &[ZoneSettingValue],
+    ) -> Result<Vec<ZoneSetting>> {
+        let endpoint = EditZoneSettings {
+            zone_identifier: zone_id,
+            params: EditZoneSettingsParams { items: settings },
+        };
+        self.reads.invalidate(zone_id);
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Purge cached content of the zone, the request is checked before it's sent
+    ///
+    /// Returns the id Cloudflare gives the purge.
+    pub async fn purge_cache(&self, zone_id: &str, request: &PurgeRequest) -> Result<String> {
+        request
+            .validate()
+            .map_err(|e| CfApiError::InvalidRequest(format!("purge: {e}")))?;
+        let endpoint = PurgeCache {
+            zone_identifier: zone_id,
+            params: request,
+        };
+        Ok(self.request(&endpoint).await?.result.id)
+    }
+
+    pub async fn list_page_rules(&self, zone_id: &str) -> Result<Vec<PageRule>> {
+        let endpoint = ListPageRules {
+            zone_identifier: zone_id,
+        };
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Returns `None` once the rule is gone
+    pub async fn get_page_rule(&self, zone_id: &str, rule_id: &str) -> Result<Option<PageRule>> {
+        let endpoint = GetPageRule {
+            zone_identifier: zone_id,
+            identifier: rule_id,
+        };
+        match self.request(&endpoint).await {
+            Ok(response) => Ok(Some(response.result)),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn create_page_rule(&self, zone_id: &str, params: PageRuleParams) -> Result<PageRule> {
+        let endpoint = CreatePageRule {
+            zone_identifier: zone_id,
+            params,
+        };
+        let created = self.request(&endpoint).await?.result;
+        audit::created(&created.id);
+        Ok(created)
+    }
+
+    pub async fn update_page_rule(
+        &self,
+        zone_id: &str,
+        rule_id: &str,
+        params: PageRuleParams,
+    ) -> Result<PageRule> {
+        let endpoint = UpdatePageRule {
+            zone_identifier: zone_id,
+            identifier: rule_id,
+            params,
+        };
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Priorities run from 1 to the number of rules in the zone, the highest wins
+    pub async fn set_page_rule_priority(
+        &self,
+        zone_id: &str,
+        rule_id: &str,
+        priority: i32,
+    ) -> Result<PageRule> {
+        let endpoint = EditPageRulePriority {
+            zone_identifier: zone_id,
+            identifier: rule_id,
+            params: PageRulePriorityParams { priority },
+        };
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// A rule that is already gone counts as deleted
+    pub async fn delete_page_rule(&self, zone_id: &str, rule_id: &str) -> Result<()> {
+        let endpoint = DeletePageRule {
+            zone_identifier: zone_id,
+            identifier: rule_id,
+        };
+        match self.request(&endpoint).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The custom hostname `hostname` of the zone, `None` when there is none
+    pub async fn find_custom_hostname(
+        &self,
+        zone_id: &str,
+        hostname: &str,
+    ) -> Result<Option<CustomHostname>> {
+        let endpoint = ListCustomHostnames {
+            zone_identifier: zone_id,
+            hostname,
+        };
+        Ok(self.request(&endpoint).await?.result.into_iter().next())
+    }
+
+    /// Add `hostname` to the zone with a certificate validated over HTTP
+    pub async fn create_custom_hostname(&self, zone_id: &str, hostname: &str) -> Result<CustomHostname> {
+        let endpoint = CreateCustomHostname {
+            zone_identifier: zone_id,
+            params: CustomHostnameParams {
+                hostname: hostname.to_string(),
+                ssl: CustomHostnameSsl {
+                    method: "http".into(),
+                    type_: "dv".into(),
+                    status: None,
+                },
+            },
+        };
+        let created = self.request(&endpoint).await?.result;
+        audit::created(&created.id);
+        Ok(created)
+    }
+
+    /// A custom hostname that is already gone counts as deleted
+    pub async fn delete_custom_hostname(&self, zone_id: &str, identifier: &str) -> Result<()> {
+        let endpoint = DeleteCustomHostname {
+            zone_identifier: zone_id,
+            identifier,
+        };
+        match self.request(&endpoint).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Delete a zone, a zone that is already gone counts as deleted
+    pub async fn delete_zone(&self, identifier: &str) -> Result<()> {
+        self.reads.invalidate(identifier);
+        match self.request(&DeleteZone { identifier }).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn get_account(&self, identifier: &str) -> Result<AccountDetails> {
+        Ok(self.request(&GetAccountDetails { identifier }).await?.result)
+    }
+
+    /// Replace the settings of an account, the name has to be sent along
+    pub async fn update_account(
+        &self,
+        identifier: &str,
+        name: String,
+        settings: AccountSettings,
+    ) -> Result<AccountDetails> {
+        let endpoint = UpdateAccount {
+            identifier,
+            params: UpdateAccountParams { name, settings },
+        };
+        Ok(self.request(&endpoint).await?.result)
     }
 
     pub async fn list_account(&self) -> Result<Vec<Account>> {
-        Ok(self.client.request(&ListAccounts { params: None }).await?.result)
+        self.paginate(|page, per_page| ListAccounts {
+            params: Some(ListAccountsParams {
+                page: Some(page),
+                per_page: Some(per_page),
+                direction: None,
+            }),
+        })
+        .await
+    }
+
+    /// Number of zones in an account
+    pub async fn count_zones(&self, account_id: &str) -> Result<u32> {
+        let endpoint = ListAccountZones {
+            account_identifier: Some(account_id),
+            name: None,
+            page: 1,
+            per_page: 5,
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(total_count(response.result_info, response.result.len()))
     }
 
-    pub async fn token_verify(&self) -> Result<String> {
-        Ok(self.client.request(&TokenVerification {}).await?.result.id)
+    /// Number of members of an account
+    pub async fn count_account_members(&self, account_id: &str) -> Result<u32> {
+        let endpoint = ListAccountMembers {
+            account_identifier: account_id,
+            page: 1,
+            per_page: 5,
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(total_count(response.result_info, response.result.len()))
+    }
+
+    pub async fn list_account_members(&self, account_id: &str) -> Result<Vec<AccountMember>> {
+        self.paginate(|page, per_page| ListAccountMembers {
+            account_identifier: account_id,
+            page,
+            per_page,
+        })
+        .await
+    }
+
+    /// Returns `None` once the member is gone, also when the invitation was declined
+    pub async fn get_account_member(
+        &self,
+        account_id: &str,
+        member_id: &str,
+    ) -> Result<Option<AccountMember>> {
+        let endpoint = GetAccountMember {
+            account_identifier: account_id,
+            identifier: member_id,
+        };
+        match self.request(&endpoint).await {
+            Ok(response) => Ok(Some(response.result)),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Invite `email` to the account, they become a member once they accept
+    pub async fn create_account_member(
+        &self,
+        account_id: &str,
+        email: String,
+        roles: Vec<String>,
+    ) -> Result<AccountMember> {
+        let endpoint = CreateAccountMember {
+            account_identifier: account_id,
+            params: CreateAccountMemberParams { email, roles },
+        };
+        let created = self.request(&endpoint).await?.result;
+        audit::created(&created.id);
+        Ok(created)
+    }
+
+    pub async fn update_account_member(
+        &self,
+        account_id: &str,
+        member_id: &str,
+        roles: Vec<String>,
+    ) -> Result<AccountMember> {
+        let endpoint = UpdateAccountMember {
+            account_identifier: account_id,
+            identifier: member_id,
+            params: UpdateAccountMemberParams {
+                roles: roles.into_iter().map(|id| RoleId { id }).collect(),
+            },
+        };
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// A member that is already gone counts as removed
+    pub async fn delete_account_member(&self, account_id: &str, member_id: &str) -> Result<()> {
+        let endpoint = DeleteAccountMember {
+            account_identifier: account_id,
+            identifier: member_id,
+        };
+        match self.request(&endpoint).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn list_account_subscriptions(&self, account_id: &str) -> Result<Vec<Subscription>> {
+        let endpoint = ListAccountSubscriptions {
+            account_identifier: account_id,
+        };
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Audit log entries of an account newer than `since`, oldest first
+    pub async fn list_audit_logs(&self, account_id: &str, since: DateTime<Utc>) -> Result<Vec<AuditLog>> {
+        self.paginate(|page, per_page| ListAuditLogs {
+            account_identifier: account_id,
+            params: ListAuditLogsParams {
+                since: Some(since),
+                direction: Some("asc".into()),
+                page: Some(page),
+                per_page: Some(per_page),
+                ..Default::default()
+            },
+        })
+        .await
+    }
+
+    pub async fn create_api_token(&self, params: ApiTokenParams) -> Result<ApiToken> {
+        let created = self.request(&CreateApiToken { params }).await?.result;
+        audit::created(&created.id);
+        Ok(created)
+    }
+
+    /// Returns `None` once the token is gone
+    pub async fn get_api_token(&self, identifier: &str) -> Result<Option<ApiToken>> {
+        match self.request(&GetApiToken { identifier }).await {
+            Ok(response) => Ok(Some(response.result)),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn update_api_token(&self, identifier: &str, params: ApiTokenParams) -> Result<ApiToken> {
+        Ok(self.request(&UpdateApiToken { identifier, params }).await?.result)
+    }
+
+    /// A token that is already gone counts as deleted
+    pub async fn delete_api_token(&self, identifier: &str) -> Result<()> {
+        match self.request(&DeleteApiToken { identifier }).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The live tunnel of the account named `name`
+    pub async fn find_tunnel(&self, account_id: &str, name: &str) -> Result<Option<Tunnel>> {
+        let endpoint = ListTunnels {
+            account_identifier: account_id,
+            name,
+        };
+        Ok(self.request(&endpoint).await?.result.into_iter().next())
+    }
+
+    /// Returns `None` once the tunnel is gone, deleted tunnels are still listed for a while
+    pub async fn get_tunnel(&self, account_id: &str, tunnel_id: &str) -> Result<Option<Tunnel>> {
+        let endpoint = GetTunnel {
+            account_identifier: account_id,
+            identifier: tunnel_id,
+        };
+        match self.request(&endpoint).await {
+            Ok(response) if response.result.deleted_at.is_none() => Ok(Some(response.result)),
+            Ok(_) => Ok(None),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Create a tunnel configured through the API
+    pub async fn create_tunnel(&self, account_id: &str, name: String) -> Result<Tunnel> {
+        let endpoint = CreateTunnel {
+            account_identifier: account_id,
+            params: CreateTunnelParams {
+                name,
+                config_src: "cloudflare".into(),
+            },
+        };
+        let created = self.request(&endpoint).await?.result;
+        audit::created(&created.id);
+        Ok(created)
+    }
+
+    /// The token `cloudflared tunnel run --token` takes
+    pub async fn tunnel_token(&self, account_id: &str, tunnel_id: &str) -> Result<Zeroizing<String>> {
+        let endpoint = GetTunnelToken {
+            account_identifier: account_id,
+            identifier: tunnel_id,
+        };
+        let token = self.request(&endpoint).await?.result;
+        Ok(Zeroizing::new(token.as_str().unwrap_or_default().to_string()))
+    }
+
+    pub async fn get_tunnel_configuration(
+        &self,
+        account_id: &str,
+        tunnel_id: &str,
+    ) -> Result<TunnelConfiguration> {
+        let endpoint = GetTunnelConfiguration {
+            account_identifier: account_id,
+            identifier: tunnel_id,
+        };
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Replace the configuration of the tunnel with `config`
+    pub async fn update_tunnel_configuration(
+        &self,
+        account_id: &str,
+        tunnel_id: &str,
+        config: serde_json::Value,
+    ) -> Result<TunnelConfiguration> {
+        let endpoint = UpdateTunnelConfiguration {
+            account_identifier: account_id,
+            identifier: tunnel_id,
+            params: TunnelConfigurationParams { config },
+        };
+        Ok(self.request(&endpoint).await?.result)
+    }
+
+    /// Drop the connections of the tunnel and delete it, a tunnel that is already gone counts as deleted
+    pub async fn delete_tunnel(&self, account_id: &str, tunnel_id: &str) -> Result<()> {
+        let connections = DeleteTunnelConnections {
+            account_identifier: account_id,
+            identifier: tunnel_id,
+        };
+        match self.request(&connections).await {
+            Ok(_) => {}
+            Err(e) if is_not_found(&e) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        let endpoint = DeleteTunnel {
+            account_identifier: account_id,
+            identifier: tunnel_id,
+        };
+        match self.request(&endpoint).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn token_verify(&self, account: Option<&str>) -> Result<String> {
+        Ok(self.verify(account).await?.id)
+    }
+
+    /// Id and status of the token, remembered by the client for `VERIFICATION_TTL`
+    ///
+    /// A token owned by an account only verifies against that account, so with an `account` that's
+    /// asked first; a user's token is refused there and verified as one.
+    pub async fn verify(&self, account: Option<&str>) -> Result<UserTokenStatus> {
+        if let Some((status, verified)) = &*self.verification.lock().unwrap()
+            && verified.elapsed() < VERIFICATION_TTL
+        {
+            return Ok(status.clone());
+        }
+        let status = match account {
+            Some(account) => {
+                let endpoint = AccountTokenVerification {
+                    account_identifier: account,
+                };
+                match self.request(&endpoint).await {
+                    Ok(response) => response.result,
+                    Err(e) if e.status().is_some_and(|s| s.is_client_error()) && !e.is_rate_limited() => {
+                        self.request(&TokenVerification {}).await?.result
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            None => self.request(&TokenVerification {}).await?.result,
+        };
+        *self.verification.lock().unwrap() = Some((status.clone(), Instant::now()));
+        Ok(status)
     }
 }
 
 impl Clone for CloudflareClient {
     fn clone(&self) -> Self {
         Self {
-            client: Arc::clone(&self.client),
+            http: self.http.clone(),
+            api_url: self.api_url.clone(),
+            limits: Arc::clone(&self.limits),
+            graphql_url: self.graphql_url.clone(),
+            verification: Arc::clone(&self.verification),
+            retries: self.retries.clone(),
+            debug_http: self.debug_http,
+            quota: self.quota.clone(),
+            quota_labels: self.quota_labels.clone(),
+            latency: self.latency.clone(),
+            reads: Arc::clone(&self.reads),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AccountTokenVerification, CloudflareClient, HttpSettings, THROTTLE_BACKOFF, TokenVerification,
+    };
+    use reqwest::Url;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        time::Instant,
+    };
+
+    /// An HTTP/1.1 response with a JSON body, closing the connection
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\n{headers}content-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    /// A server answering one request per connection with `responses`, in order, and the request lines
+    /// it got
+    async fn serve(responses: Vec<String>) -> (Url, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/client/v4", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let log = requests.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buf).await.unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..read]);
+                }
+                let request = String::from_utf8_lossy(&request);
+                log.lock()
+                    .unwrap()
+                    .push(request.lines().next().unwrap_or_default().to_string());
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url.parse().unwrap(), requests)
+    }
+
+    const VERIFIED: &str = r#"{"success":true,"errors":[],"messages":[],"result_info":null,"result":{"id":"token","status":"active"}}"#;
+
+    #[tokio::test]
+    async fn rate_limited_requests_wait_for_retry_after() {
+        let limited = r#"{"success":false,"errors":[{"code":971,"message":"Please wait and consider throttling your request speed"}],"messages":[],"result":null}"#;
+        let (url, _) = serve(vec![
+            response("429 Too Many Requests", "retry-after: 1\r\n", limited),
+            response("200 OK", "", VERIFIED),
+        ])
+        .await;
+        let client = CloudflareClient::new("token", Some(&url), &HttpSettings::default()).unwrap();
+
+        let started = Instant::now();
+        let verification = client.request(&TokenVerification {}).await.unwrap();
+        let waited = started.elapsed();
+
+        assert_eq!(verification.result.id, "token");
+        assert!(waited >= Duration::from_secs(1), "retried after {waited:?}");
+        assert!(
+            waited < THROTTLE_BACKOFF,
+            "Retry-After was ignored, waited {waited:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn verification_is_asked_again_after_a_refusal() {
+        let refused = r#"{"success":false,"errors":[{"code":9109,"message":"Unauthorized to access requested resource"}],"messages":[],"result":null}"#;
+        let (url, requests) = serve(vec![
+            response("200 OK", "", VERIFIED),
+            response("403 Forbidden", "", refused),
+            response("200 OK", "", VERIFIED),
+        ])
+        .await;
+        let client = CloudflareClient::new("token", Some(&url), &HttpSettings::default()).unwrap();
+        let account = Some("023e105f4ecef8ad9ca31a8372d0c353");
+
+        assert_eq!(client.verify(account).await.unwrap().id, "token");
+        // remembered
+        assert_eq!(client.verify(account).await.unwrap().id, "token");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert!(
+            requests.lock().unwrap()[0]
+                .contains("/client/v4/accounts/023e105f4ecef8ad9ca31a8372d0c353/tokens/verify")
+        );
+
+        let endpoint = AccountTokenVerification {
+            account_identifier: "023e105f4ecef8ad9ca31a8372d0c353",
+        };
+        assert!(client.request(&endpoint).await.unwrap_err().is_forbidden());
+        assert_eq!(client.verify(account).await.unwrap().id, "token");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+}
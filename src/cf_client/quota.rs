@@ -0,0 +1,68 @@
+//! How much of its rate limit each token has left, exported so throttling can be alerted on early
+//!
+//! Cloudflare reports the quota in the headers of every response, the gauges show the last one seen
+//! for the token. The operator's own token bucket doesn't stand in for it, it only knows about the
+//! requests of this process.
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+};
+use reqwest::header::HeaderMap;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct QuotaLabels {
+    /// Start of the token's SHA-256, never the token itself
+    pub token: String,
+}
+
+/// Rate limit headroom per token
+#[derive(Clone, Default)]
+pub struct Quota {
+    /// Requests left in the current window
+    pub remaining: Family<QuotaLabels, Gauge>,
+    /// Seconds until the full quota is back
+    pub reset_seconds: Family<QuotaLabels, Gauge>,
+    /// Requests Cloudflare answered with 429
+    pub rate_limited: Family<QuotaLabels, Counter>,
+}
+
+impl Quota {
+    pub fn record(&self, labels: &QuotaLabels, remaining: u64, reset: Duration) {
+        self.remaining
+            .get_or_create(labels)
+            .set(remaining.try_into().unwrap_or(i64::MAX));
+        self.reset_seconds
+            .get_or_create(labels)
+            .set(reset.as_secs().try_into().unwrap_or(i64::MAX));
+    }
+
+    /// Drop the gauges of a token that's gone, its 429s stay counted
+    pub fn forget(&self, labels: &QuotaLabels) {
+        self.remaining.remove(labels);
+        self.reset_seconds.remove(labels);
+    }
+}
+
+/// Remaining requests and time to reset from the response headers
+///
+/// Cloudflare sends `Ratelimit: "default";r=1199;t=300`, older responses `X-RateLimit-Remaining` and
+/// `X-RateLimit-Reset`.
+pub fn from_headers(headers: &HeaderMap) -> Option<(u64, Duration)> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    if let Some(value) = header("ratelimit") {
+        let param = |key: &str| {
+            value
+                .split(';')
+                .filter_map(|part| part.trim().split_once('='))
+                .find(|(name, _)| *name == key)
+                .and_then(|(_, v)| v.trim().parse::<u64>().ok())
+        };
+        if let (Some(remaining), Some(reset)) = (param("r"), param("t")) {
+            return Some((remaining, Duration::from_secs(reset)));
+        }
+    }
+    let remaining = header("x-ratelimit-remaining")?.trim().parse().ok()?;
+    let reset = header("x-ratelimit-reset")?.trim().parse().ok()?;
+    Some((remaining, Duration::from_secs(reset)))
+}
@@ -0,0 +1,64 @@
+//! Short-lived cache of reads, so several reconciles reading the same zone in a row cost one request
+//!
+//! Entries aren't revalidated, they live for a fixed time. Any write to a zone through the client drops
+//! what was read of it.
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+type Entry = (Instant, Arc<dyn Any + Send + Sync>);
+
+/// Reads by resource path, `zone/<id>/...` for everything belonging to a zone
+#[derive(Default)]
+pub struct ReadCache {
+    /// How long a read is reused, nothing is cached for `None`
+    ttl: Option<Duration>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ReadCache {
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// The value read under `key`, unless it's older than the TTL
+    pub fn get<T: Clone + 'static>(&self, key: &str) -> Option<T> {
+        let ttl = self.ttl?;
+        let entries = self.entries.lock().unwrap();
+        let (read, value) = entries.get(key)?;
+        if read.elapsed() >= ttl {
+            return None;
+        }
+        value.downcast_ref::<T>().cloned()
+    }
+
+    pub fn put<T: Clone + Send + Sync + 'static>(&self, key: String, value: &T) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (read, _)| read.elapsed() < ttl);
+        entries.insert(key, (Instant::now(), Arc::new(value.clone())));
+    }
+
+    /// Drop everything read of the zone, after a change to it
+    pub fn invalidate(&self, zone_id: &str) {
+        let prefix = zone_key(zone_id);
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|key, _| key != &prefix && !key.starts_with(&format!("{prefix}/")));
+    }
+}
+
+/// Key of the zone itself, the prefix of everything in it
+pub fn zone_key(zone_id: &str) -> String {
+    format!("zone/{zone_id}")
+}
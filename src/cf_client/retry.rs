@@ -0,0 +1,75 @@
+//! When and how long to wait before a failed Cloudflare request is sent again
+use super::CfApiError;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family},
+};
+use reqwest::{Method, header::RETRY_AFTER};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Attempts after the first one before the error is handed to the caller
+pub const MAX_RETRIES: u32 = 3;
+
+/// Wait before the first retry, doubled for every further one
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest wait between two attempts
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RetryLabels {
+    /// `rate_limited`, `server_error` or `connection`
+    pub reason: &'static str,
+}
+
+/// Retries by reason, exported as a metric
+pub type Retries = Family<RetryLabels, Counter>;
+
+/// Whether sending the request twice does no more than sending it once
+pub fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+    )
+}
+
+/// Why the request is worth another attempt, `None` when it isn't
+///
+/// A rate limited request never reached the API, so it's retried whatever its method. Server and
+/// connection errors may come after the change went through, only idempotent requests are retried.
+pub fn reason(error: &CfApiError, idempotent: bool) -> Option<&'static str> {
+    if error.is_rate_limited() {
+        return Some("rate_limited");
+    }
+    if !idempotent || !error.is_retryable() {
+        return None;
+    }
+    match error.status() {
+        Some(_) => Some("server_error"),
+        None => Some("connection"),
+    }
+}
+
+/// Exponential backoff with up to a quarter of jitter, so clients failing together don't retry together
+pub fn backoff(attempt: u32) -> Duration {
+    let delay = BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_DELAY);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.subsec_nanos());
+    delay + delay.mul_f64(f64::from(nanos % 1000) / 4000.)
+}
+
+/// The `Retry-After` of a response, in seconds; HTTP dates aren't worth parsing for Cloudflare
+pub fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
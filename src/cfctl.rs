@@ -0,0 +1,86 @@
+use controller::{
+    cloudflare::{AllowedApiUrls, CloudflareClientProvider, OperatorToken},
+    namespaces::WatchNamespaces,
+    snapshot, token_scope, zonefile,
+};
+use k8s_openapi::api::core::v1::Secret;
+use kube::{Api, Client};
+
+const USAGE: &str = "usage:
+  cfctl zonefile-export <namespace> <zone>
+  cfctl zonefile-import <file> <namespace> <zone>
+  cfctl token-scope [<namespace>/<secret>/<key>]
+  cfctl snapshot";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["zonefile-export", namespace, zone] => {
+            let client = Client::try_default().await?;
+            print!("{}", zonefile::export(client, namespace, zone).await?);
+        }
+        ["zonefile-import", file, namespace, zone] => {
+            let text = std::fs::read_to_string(file)?;
+            let import = zonefile::parse(zone, &text);
+            print!("{}", import.to_manifests(namespace)?);
+            for skipped in &import.skipped {
+                eprintln!(
+                    "{file}:{}: skipped {} record: {}",
+                    skipped.line, skipped.record_type, skipped.reason
+                );
+            }
+            eprintln!(
+                "imported {} records, skipped {}",
+                import.records.len(),
+                import.skipped.len()
+            );
+        }
+        ["token-scope", rest @ ..] if rest.len() <= 1 => {
+            let client = Client::try_default().await?;
+            // objects without credentials of their own fall back to the operator token
+            let default_token = std::env::var("CLOUDFLARE_API_TOKEN").unwrap_or_default();
+            let token = match rest.first() {
+                Some(secret) => read_token(client.clone(), secret).await?,
+                None if !default_token.is_empty() => default_token.clone(),
+                None => anyhow::bail!("pass a secret or set CLOUDFLARE_API_TOKEN to the operator token"),
+            };
+            let provider =
+                CloudflareClientProvider::new(client.clone(), OperatorToken::fixed(Some(default_token)))
+                    .with_allowed_api_urls(AllowedApiUrls::from_env());
+            let report = token_scope::report(client, &provider, &token).await?;
+            print!("{report}");
+            for unresolved in &report.unresolved {
+                eprintln!("skipped {unresolved}");
+            }
+        }
+        ["snapshot"] => {
+            let client = Client::try_default().await?;
+            // one stream, ready for `kubectl apply -f -` on the new cluster; WATCH_NAMESPACES limits it
+            let manifests: Vec<_> = snapshot::take(client, &WatchNamespaces::from_env())
+                .await?
+                .into_values()
+                .filter(|docs| !docs.is_empty())
+                .collect();
+            print!("{}", manifests.join("---\n"));
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
+
+/// Read a token from a `<namespace>/<secret>/<key>` reference
+async fn read_token(client: Client, reference: &str) -> anyhow::Result<String> {
+    let [namespace, name, key] = reference.split('/').collect::<Vec<_>>()[..] else {
+        anyhow::bail!("expected <namespace>/<secret>/<key>, got {reference}");
+    };
+    let secret = Api::<Secret>::namespaced(client, namespace).get(name).await?;
+    let value = secret
+        .data
+        .and_then(|mut data| data.remove(key))
+        .ok_or_else(|| anyhow::anyhow!("secret {namespace}/{name} has no key {key}"))?;
+    Ok(String::from_utf8(value.0)?)
+}
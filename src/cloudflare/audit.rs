@@ -0,0 +1,78 @@
+//! Audit trail of the changes a reconcile makes on the Cloudflare side
+//!
+//! Every create, update and delete is logged under the `cloudflare::audit` target with the object it
+//! was made for, and with `CLOUDFLARE_AUDIT_EVENTS=true` also published as an Event on the object.
+use crate::{
+    cf_client::audit::{self, Change},
+    events::Events,
+};
+use kube::{
+    Resource, ResourceExt,
+    runtime::events::{Event, EventType},
+};
+use std::future::Future;
+use tracing::*;
+
+#[derive(Clone, Default)]
+pub struct AuditTrail {
+    /// Publish an Event on the object for every change
+    events: bool,
+}
+
+impl AuditTrail {
+    pub fn from_env() -> Self {
+        Self {
+            events: std::env::var("CLOUDFLARE_AUDIT_EVENTS").is_ok_and(|v| v == "true"),
+        }
+    }
+
+    /// Run the reconcile of `obj`, then log the changes it made and publish them when configured to
+    ///
+    /// Changes made from tasks of their own, like the DNS record batches, are logged as they happen
+    /// without the object.
+    pub async fn record<K, T>(&self, obj: &K, recorder: &Events, reconcile: impl Future<Output = T>) -> T
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        let (result, changes) = audit::collect(reconcile).await;
+        let kind = K::kind(&());
+        let object = format!("{}/{}", obj.namespace().unwrap_or_default(), obj.name_any());
+        for change in &changes {
+            change.log(Some(&kind), Some(&object));
+            if self.events {
+                self.publish(obj, recorder, change).await;
+            }
+        }
+        result
+    }
+
+    async fn publish<K: Resource<DynamicType = ()>>(&self, obj: &K, recorder: &Events, change: &Change) {
+        let id = change.object_id.as_deref().unwrap_or("-");
+        let (type_, note) = match &change.error {
+            None => (
+                EventType::Normal,
+                format!(
+                    "{} {} (id {id}) with token {}",
+                    change.method, change.path, change.token
+                ),
+            ),
+            Some(error) => (
+                EventType::Warning,
+                format!(
+                    "{} {} (id {id}) with token {} failed: {error}",
+                    change.method, change.path, change.token
+                ),
+            ),
+        };
+        let event = Event {
+            type_,
+            reason: format!("Cloudflare{}", change.action()),
+            note: Some(note),
+            action: "Reconciling".into(),
+            secondary: None,
+        };
+        if let Err(e) = recorder.publish(&event, &obj.object_ref(&())).await {
+            warn!("failed to publish the audit event: {}", e);
+        }
+    }
+}
@@ -1,10 +1,52 @@
-use crate::{account::Account, cf_client::CloudflareClient, zone::Zone};
+use crate::{
+    Context,
+    account::Account,
+    cf_client::{
+        CloudflareApi, CloudflareClient, DEFAULT_MAX_IN_FLIGHT, DEFAULT_RATE_LIMIT, HttpSettings, Latency,
+        Quota, QuotaLabels, Retries,
+    },
+    conditions::{Condition, Conditions},
+    credentials::{CredentialsStore, NotSynced},
+    reconcile_policy::ReconcilePolicy,
+    status,
+    zone::Zone,
+};
 use async_recursion::async_recursion;
-use k8s_openapi::api::core::v1::{LocalObjectReference, Secret, SecretKeySelector};
-use kube::{Api, Client, ResourceExt};
-use std::{collections::HashMap, sync::Arc};
+use k8s_openapi::{
+    NamespaceResourceScope,
+    api::core::v1::{LocalObjectReference, Secret, SecretKeySelector},
+};
+use kube::{
+    Api, Client, Resource, ResourceExt,
+    core::object::HasStatus,
+    runtime::{
+        controller::Action,
+        events::{Event, EventType},
+    },
+};
+use prometheus_client::metrics::gauge::Gauge;
+use reqwest::Url;
+use serde::{Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::Arc,
+};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::*;
+use zeroize::{Zeroize, Zeroizing};
+
+pub mod audit;
+pub mod operator_token;
+pub mod preflight;
+pub mod rotation;
+
+pub use operator_token::{AllowedApiUrls, FallbackNamespaces, OperatorToken};
 
 #[derive(Debug, Error)]
 pub enum ProviderError {
@@ -20,11 +62,97 @@ pub enum ProviderError {
     TokenEncoding,
     #[error("Client creation error: {0}")]
     ClientCreation(String),
+    #[error("Token verification failed: {0}")]
+    Verification(String),
+    #[error("Token is not valid: {0}")]
+    TokenInvalid(String),
+    #[error("No credentials, the object names none and the operator has no token")]
+    NoOperatorToken,
+    #[error(
+        "No credentials, namespace {0} has to name its own secretRef, it may not use the operator token or CloudflareCredentials"
+    )]
+    OperatorTokenNotAllowed(String),
+    #[error("CloudflareCredentials are not synced yet")]
+    CredentialsNotSynced,
+    #[error("Invalid Cloudflare API URL {0}")]
+    InvalidApiUrl(String),
+    #[error(
+        "Cloudflare API URL {0} is only used with the object's own secretRef, other tokens only go to CLOUDFLARE_ALLOWED_API_URLS"
+    )]
+    ApiUrlNotAllowed(String),
+    #[error("No Origin CA key, originCAKeySecretRef is not set")]
+    NoOriginCaKey,
+    #[error("Credential references form a cycle: {0}")]
+    ReferenceCycle(String),
+    #[error("Credential references are nested too deep: {0}")]
+    ChainTooDeep(String),
     #[error("K8s error: {0}")]
     K8sError(#[from] kube::Error),
 }
 
+/// Objects walked looking for a token before giving up, resource → zone → account needs three
+const MAX_CHAIN_DEPTH: usize = 4;
+
+/// Condition naming where an object's token came from
+pub const CREDENTIALS_CONDITION: &str = "CredentialsResolved";
+
+/// Where the token of an object was found
+#[derive(Clone, Debug)]
+pub struct TokenSource {
+    /// `Secret`, `CloudflareCredentials` or `OperatorToken`
+    pub reason: &'static str,
+    pub message: String,
+    /// What the verify endpoint said about the token
+    pub verified: String,
+}
+
+impl TokenSource {
+    fn new(reason: &'static str, found: String, chain: &[String]) -> Self {
+        let message = match chain {
+            [_] => format!("Token from {found}"),
+            chain => format!("Token from {found}, through {}", chain.join(" → ")),
+        };
+        Self {
+            reason,
+            message,
+            verified: String::new(),
+        }
+    }
+
+    /// `CredentialsResolved=True`, keeping the transition time of the previous one
+    pub fn condition(&self, previous: &[Condition], generation: Option<i64>) -> Condition {
+        Condition::new(
+            CREDENTIALS_CONDITION,
+            "True",
+            self.reason,
+            Some(self.message.clone()),
+            previous,
+        )
+        .observed(generation)
+    }
+
+    /// `CredentialsValid=True`, the token was verified before use
+    pub fn validity(&self, previous: &[Condition], generation: Option<i64>) -> Condition {
+        preflight::condition(true, "Verified", self.verified.clone(), previous, generation)
+    }
+}
+
+/// A token and what it took to find it
+struct Resolved {
+    token: Zeroizing<String>,
+    /// API named by the closest object of the chain, the operator's when `None`
+    api_url: Option<Url>,
+    /// Secret the token was read from, `None` for the operator token
+    secret: Option<SecretKey>,
+    /// Cloudflare account of the object that named the token, account tokens only verify against it
+    account_id: Option<String>,
+    source: TokenSource,
+}
+
 pub trait CloudflareResource {
+    /// Token permissions managing the object takes, named after Cloudflare's permission groups
+    const PERMISSIONS: &'static [&'static str] = &[];
+
     fn secret_ref(&self) -> Option<&SecretKeySelector> {
         None
     }
@@ -36,107 +164,786 @@ pub trait CloudflareResource {
     fn account_ref(&self) -> Option<&LocalObjectReference> {
         None
     }
+
+    /// Cloudflare account id, used to look the token up in `CloudflareCredentials`
+    fn account_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// Cloudflare API the object is managed through instead of the operator's, objects referencing
+    /// it use it as well
+    fn api_url(&self) -> Option<&str> {
+        None
+    }
+
+    /// Secret holding the Origin CA key, for objects managing Origin CA certificates
+    fn origin_ca_key_secret_ref(&self) -> Option<&SecretKeySelector> {
+        None
+    }
+
+    fn reconcile_policy(&self) -> ReconcilePolicy {
+        ReconcilePolicy::Enforce
+    }
+
+    /// Whether differences are only reported, nothing may be written to Cloudflare for the object
+    fn report_only(&self) -> bool {
+        self.reconcile_policy() == ReconcilePolicy::DriftReportOnly
+    }
+}
+
+/// `namespace/name` of a Secret tokens are read from
+type SecretKey = String;
+
+/// What a credential authenticates as
+///
+/// API tokens work everywhere the token's permissions allow, Origin CA keys only on the Origin CA
+/// certificate endpoints and are sent in a header of their own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Credential {
+    ApiToken,
+    OriginCaKey,
+}
+
+impl Credential {
+    fn client(
+        self,
+        secret: &str,
+        api_url: Option<&Url>,
+        http: &HttpSettings,
+    ) -> anyhow::Result<CloudflareClient> {
+        match self {
+            Credential::ApiToken => CloudflareClient::new(secret, api_url, http),
+            Credential::OriginCaKey => CloudflareClient::with_origin_ca_key(secret, api_url, http),
+        }
+    }
+}
+
+/// SHA-256 of a token, what clients are cached under so tokens don't sit around as map keys
+///
+/// Only the token goes in, two Secrets holding the same token share a client and with it the limits.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TokenKey([u8; 32]);
+
+impl TokenKey {
+    pub fn of(token: &str) -> Self {
+        Self::of_credential(Credential::ApiToken, token)
+    }
+
+    /// Origin CA keys are hashed with a prefix, a token with the same text must not get their client
+    pub fn of_credential(credential: Credential, secret: &str) -> Self {
+        let mut hasher = Sha256::new();
+        if credential == Credential::OriginCaKey {
+            hasher.update(b"origin-ca-key:");
+        }
+        hasher.update(secret.as_bytes());
+        Self(hasher.finalize().into())
+    }
+
+    /// Start of the hash in hex, enough to tell tokens apart in metrics
+    pub fn label(&self) -> String {
+        self.0[..6].iter().map(|b| format!("{b:02x}")).collect()
+    }
 }
 
-type ClientCache = Arc<Mutex<HashMap<String, Arc<CloudflareClient>>>>;
+/// A token and the API it's used against, a token used on two APIs gets two clients
+type ClientKey = (TokenKey, Option<Url>);
+
+/// Kind, namespace and name of an object that was handed a client
+type User = (String, String, String);
+
+/// Clients kept when `CLOUDFLARE_CLIENT_CACHE_SIZE` isn't set
+const DEFAULT_CACHE_SIZE: usize = 256;
+
+/// Idle time after which a client is dropped when `CLOUDFLARE_CLIENT_CACHE_IDLE_SECONDS` isn't set
+const DEFAULT_CACHE_IDLE: Duration = Duration::from_secs(60 * 60);
+
+/// One client per token, each client carries the concurrency and throttling limits of its token
+///
+/// Shared by all controllers, so that a rotated Secret can be forgotten in one place. Holds at most
+/// `capacity` clients, the least recently used one makes room for a new token, and clients unused
+/// for `idle` are dropped. Reconciles still holding a dropped client finish with it.
+#[derive(Clone)]
+pub struct ClientCache {
+    clients: Arc<Mutex<HashMap<ClientKey, CachedClient>>>,
+    secrets: Arc<Mutex<HashMap<SecretKey, SecretUse>>>,
+    capacity: usize,
+    idle: Duration,
+    /// API used by objects that don't name one, `DEFAULT_API_URL` when unset
+    api_url: Option<Url>,
+    /// Number of cached clients, exported as a metric
+    size: Gauge,
+    /// Retries of every client, exported as a metric
+    retries: Retries,
+    /// Rate limit headroom of every client, exported as metrics
+    quota: Quota,
+    /// Request durations of every client, exported as a metric
+    latency: Latency,
+    /// Requests per five minutes each client may send, any number for `None`
+    rate_limit: Option<u32>,
+    /// Requests each client may have in flight at once
+    max_in_flight: usize,
+    /// Timeouts and pooling of every client
+    http: HttpSettings,
+    /// Log every Cloudflare request, bodies redacted
+    debug_http: bool,
+    /// How long clients reuse what they read of a zone, not at all for `None`
+    read_cache: Option<Duration>,
+}
+
+struct CachedClient {
+    client: Arc<CloudflareClient>,
+    last_used: Instant,
+}
+
+impl Default for ClientCache {
+    fn default() -> Self {
+        Self {
+            clients: Arc::default(),
+            secrets: Arc::default(),
+            capacity: DEFAULT_CACHE_SIZE,
+            idle: DEFAULT_CACHE_IDLE,
+            api_url: None,
+            size: Gauge::default(),
+            retries: Retries::default(),
+            quota: Quota::default(),
+            latency: Latency::default(),
+            rate_limit: Some(DEFAULT_RATE_LIMIT),
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            http: HttpSettings::default(),
+            debug_http: false,
+            read_cache: None,
+        }
+    }
+}
+
+/// What came out of a Secret: the tokens read from it and the objects using them
+#[derive(Default)]
+struct SecretUse {
+    tokens: HashSet<TokenKey>,
+    users: HashSet<User>,
+}
+
+impl ClientCache {
+    /// Limits from `CLOUDFLARE_CLIENT_CACHE_SIZE` and `CLOUDFLARE_CLIENT_CACHE_IDLE_SECONDS`, counted in `size`
+    ///
+    /// `CLOUDFLARE_API_URL` replaces the API clients talk to, a value that isn't a URL is ignored.
+    /// `CLOUDFLARE_RATE_LIMIT` is the number of requests per five minutes each token may send, 0 for
+    /// no limit, and `CLOUDFLARE_MAX_IN_FLIGHT` how many of them may be sent at once. Timeouts and
+    /// pooling come from `HttpSettings::from_env`. `CLOUDFLARE_DEBUG_HTTP=true` logs every request.
+    /// `CLOUDFLARE_READ_CACHE_SECONDS` lets clients reuse zone details, settings and DNS records read
+    /// that long ago, drift made outside the operator shows up that much later.
+    pub fn from_env(size: Gauge, retries: Retries, quota: Quota, latency: Latency) -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let defaults = Self::default();
+        Self {
+            // a cache of nothing would make a client per reconcile
+            capacity: var("CLOUDFLARE_CLIENT_CACHE_SIZE")
+                .filter(|&n| n > 0)
+                .map_or(defaults.capacity, |n| n as usize),
+            idle: var("CLOUDFLARE_CLIENT_CACHE_IDLE_SECONDS").map_or(defaults.idle, Duration::from_secs),
+            rate_limit: match var("CLOUDFLARE_RATE_LIMIT") {
+                Some(0) => None,
+                Some(n) => Some(n.try_into().unwrap_or(u32::MAX)),
+                None => defaults.rate_limit,
+            },
+            max_in_flight: var("CLOUDFLARE_MAX_IN_FLIGHT")
+                .filter(|&n| n > 0)
+                .map_or(defaults.max_in_flight, |n| n as usize),
+            api_url: std::env::var("CLOUDFLARE_API_URL")
+                .ok()
+                .and_then(|url| Url::parse(&url).ok()),
+            http: HttpSettings::from_env(),
+            debug_http: std::env::var("CLOUDFLARE_DEBUG_HTTP").is_ok_and(|v| v == "true"),
+            read_cache: var("CLOUDFLARE_READ_CACHE_SECONDS")
+                .filter(|&s| s > 0)
+                .map(Duration::from_secs),
+            size,
+            retries,
+            quota,
+            latency,
+            ..defaults
+        }
+    }
+
+    /// Talk to `api_url` unless an object names another API
+    pub fn with_api_url(self, api_url: Url) -> Self {
+        Self {
+            api_url: Some(api_url),
+            ..self
+        }
+    }
+
+    /// Log method, path, redacted body and outcome of every request
+    pub fn with_debug_http(self) -> Self {
+        Self {
+            debug_http: true,
+            ..self
+        }
+    }
+
+    /// Reach Cloudflare through `proxy`
+    pub fn with_proxy(self, proxy: Url) -> Self {
+        Self {
+            http: self.http.with_proxy(proxy),
+            ..self
+        }
+    }
+
+    /// The client of `token` on the operator's API, made when there is none
+    async fn client(&self, token: &str) -> Result<Arc<CloudflareClient>, ProviderError> {
+        self.client_of(Credential::ApiToken, token, None).await
+    }
+
+    /// The client of `secret` on `api_url`, or on the operator's API for `None`
+    async fn client_of(
+        &self,
+        credential: Credential,
+        secret: &str,
+        api_url: Option<&Url>,
+    ) -> Result<Arc<CloudflareClient>, ProviderError> {
+        let api_url = api_url.or(self.api_url.as_ref());
+        let key = (TokenKey::of_credential(credential, secret), api_url.cloned());
+        let now = Instant::now();
+        let mut clients = self.clients.lock().await;
+        clients.retain(|_, cached| now - cached.last_used < self.idle);
+
+        let client = match clients.get_mut(&key) {
+            Some(cached) => {
+                cached.last_used = now;
+                cached.client.clone()
+            }
+            None => {
+                let client = Arc::new(
+                    credential
+                        .client(secret, api_url, &self.http)
+                        .map(|client| {
+                            client
+                                .with_limits(self.rate_limit, self.max_in_flight)
+                                .with_retries(self.retries.clone())
+                                .with_debug_http(self.debug_http)
+                                .with_read_cache(self.read_cache)
+                                .with_quota(self.quota.clone(), key.0.label())
+                                .with_latency(self.latency.clone())
+                        })
+                        .map_err(|e| ProviderError::ClientCreation(e.to_string()))?,
+                );
+                if clients.len() >= self.capacity
+                    && let Some(lru) = clients
+                        .iter()
+                        .min_by_key(|(_, cached)| cached.last_used)
+                        .map(|(key, _)| key.clone())
+                {
+                    clients.remove(&lru);
+                }
+                clients.insert(key, CachedClient {
+                    client: client.clone(),
+                    last_used: now,
+                });
+                client
+            }
+        };
+        self.size.set(clients.len() as i64);
+        Ok(client)
+    }
+
+    async fn remember(&self, secret: SecretKey, token: TokenKey, user: User) {
+        let mut secrets = self.secrets.lock().await;
+        let used = secrets.entry(secret).or_default();
+        used.tokens.insert(token);
+        used.users.insert(user);
+    }
+
+    /// Drop the client of a token that's no longer in use, like a replaced operator token
+    pub async fn forget(&self, token: &str) {
+        let token = TokenKey::of(token);
+        let mut clients = self.clients.lock().await;
+        clients.retain(|(key, _), _| *key != token);
+        self.quota.forget(&QuotaLabels { token: token.label() });
+        self.size.set(clients.len() as i64);
+    }
+
+    /// Forget the clients of tokens the Secret no longer holds, `current` is `None` for a deleted Secret
+    ///
+    /// Returns the objects that used the old tokens, they have to resolve theirs again. A Secret whose
+    /// tokens are all still in place (only metadata changed) returns nothing.
+    pub async fn rotate(
+        &self,
+        namespace: &str,
+        name: &str,
+        current: Option<&HashSet<TokenKey>>,
+    ) -> Vec<User> {
+        let key = format!("{namespace}/{name}");
+        let mut secrets = self.secrets.lock().await;
+        let Some(used) = secrets.get(&key) else {
+            return vec![];
+        };
+        let stale: Vec<&TokenKey> = used
+            .tokens
+            .iter()
+            .filter(|token| current.is_none_or(|current| !current.contains(*token)))
+            .collect();
+        if stale.is_empty() {
+            return vec![];
+        }
+        let mut clients = self.clients.lock().await;
+        clients.retain(|(token, _), _| !stale.contains(&token));
+        self.size.set(clients.len() as i64);
+        info!("Secret {} changed, dropped the clients made from it", key);
+        secrets
+            .remove(&key)
+            .map(|used| used.users.into_iter().collect())
+            .unwrap_or_default()
+    }
+}
 
 #[derive(Clone)]
 pub struct CloudflareClientProvider {
     k8s_client: Client,
-    default_token: String,
+    default_token: OperatorToken,
+    /// Namespaces allowed to use `default_token` and `credentials`
+    fallback: FallbackNamespaces,
+    /// Tokens platform admins mapped to accounts
+    credentials: CredentialsStore,
+    /// APIs objects may name for tokens that aren't their own
+    allowed_api_urls: AllowedApiUrls,
     cache: ClientCache,
+    /// Answers every call of reconcilers using `get_api` instead of a client resolved per object
+    api: Option<Arc<dyn CloudflareApi>>,
 }
 
 impl CloudflareClientProvider {
-    pub fn new(k8s_client: Client, default_token: String) -> Self {
+    pub fn new(k8s_client: Client, default_token: OperatorToken) -> Self {
         Self {
             k8s_client,
             default_token,
-            cache: Arc::new(Mutex::new(HashMap::new())),
+            fallback: FallbackNamespaces::default(),
+            credentials: CredentialsStore::default(),
+            allowed_api_urls: AllowedApiUrls::default(),
+            cache: ClientCache::default(),
+            api: None,
+        }
+    }
+
+    /// Hand `api` to reconcilers going through `get_api` without resolving credentials, for tests
+    #[cfg(test)]
+    pub fn with_api(self, api: Arc<dyn CloudflareApi>) -> Self {
+        Self {
+            api: Some(api),
+            ..self
+        }
+    }
+
+    /// Only let objects in `fallback` namespaces use the operator token
+    pub fn with_fallback(self, fallback: FallbackNamespaces) -> Self {
+        Self { fallback, ..self }
+    }
+
+    /// Look tokens of accounts up in `credentials` before falling back to the operator token
+    pub fn with_credentials(self, credentials: CredentialsStore) -> Self {
+        Self { credentials, ..self }
+    }
+
+    /// Send tokens other than an object's own to the `apiUrl` it names only when `allowed` lists it
+    pub fn with_allowed_api_urls(self, allowed_api_urls: AllowedApiUrls) -> Self {
+        Self {
+            allowed_api_urls,
+            ..self
         }
     }
 
+    /// Share `cache` instead of keeping clients to this provider
+    pub fn with_cache(self, cache: ClientCache) -> Self {
+        Self { cache, ..self }
+    }
+
     pub async fn get_client<T>(
         &self,
         resource: &T,
         namespace: &str,
     ) -> Result<Arc<CloudflareClient>, ProviderError>
     where
-        T: CloudflareResource + ResourceExt + Sync + Send,
+        T: CloudflareResource + Resource<DynamicType = ()> + Sync + Send,
     {
-        let token = self.resolve_token(resource, namespace).await?;
-        self.get_client_from_cache(token).await
+        Ok(self.get_client_with_source(resource, namespace).await?.0)
     }
 
-    async fn get_client_from_cache(&self, token: String) -> Result<Arc<CloudflareClient>, ProviderError> {
-        let mut cache = self.cache.lock().await;
+    /// The client for `resource` and where its token came from, for the `CredentialsResolved` condition
+    pub async fn get_client_with_source<T>(
+        &self,
+        resource: &T,
+        namespace: &str,
+    ) -> Result<(Arc<CloudflareClient>, TokenSource), ProviderError>
+    where
+        T: CloudflareResource + Resource<DynamicType = ()> + Sync + Send,
+    {
+        let Resolved {
+            token,
+            api_url,
+            secret,
+            account_id,
+            mut source,
+        } = self.resolve(resource, namespace, &mut vec![]).await?;
+        if let Some(secret) = secret {
+            let user = (
+                T::kind(&()).into_owned(),
+                namespace.to_string(),
+                resource.name_any(),
+            );
+            self.cache.remember(secret, TokenKey::of(&token), user).await;
+        }
+        let client = self
+            .cache
+            .client_of(Credential::ApiToken, &token, api_url.as_ref())
+            .await?;
+        source.verified = preflight::verify(&client, account_id.as_deref()).await?;
+        Ok((client, source))
+    }
 
-        if let Some(client) = cache.get(&token) {
-            return Ok(client.clone());
+    /// Like `get_client_with_source`, or the API set with `with_api` when there is one
+    pub async fn get_api_with_source<T>(
+        &self,
+        resource: &T,
+        namespace: &str,
+    ) -> Result<(Arc<dyn CloudflareApi>, TokenSource), ProviderError>
+    where
+        T: CloudflareResource + Resource<DynamicType = ()> + Sync + Send,
+    {
+        if let Some(api) = &self.api {
+            let source = TokenSource {
+                reason: "Override",
+                message: "Cloudflare API replaced, no token used".into(),
+                verified: "No token to verify".into(),
+            };
+            return Ok((api.clone(), source));
         }
+        let (client, source) = self.get_client_with_source(resource, namespace).await?;
+        Ok((client, source))
+    }
 
-        let arc_client = Arc::new(
-            CloudflareClient::new(token.clone()).map_err(|e| ProviderError::ClientCreation(e.to_string()))?,
+    /// Like `get_client`, or the API set with `with_api` when there is one
+    pub async fn get_api<T>(
+        &self,
+        resource: &T,
+        namespace: &str,
+    ) -> Result<Arc<dyn CloudflareApi>, ProviderError>
+    where
+        T: CloudflareResource + Resource<DynamicType = ()> + Sync + Send,
+    {
+        Ok(self.get_api_with_source(resource, namespace).await?.0)
+    }
+
+    /// Client authenticating with the Origin CA key of `resource`, for the certificate endpoints
+    ///
+    /// There's no fallback, the operator token can't stand in for an Origin CA key.
+    pub async fn get_origin_ca_client<T>(
+        &self,
+        resource: &T,
+        namespace: &str,
+    ) -> Result<Arc<CloudflareClient>, ProviderError>
+    where
+        T: CloudflareResource + Resource<DynamicType = ()> + Sync + Send,
+    {
+        let s_ref = resource
+            .origin_ca_key_secret_ref()
+            .ok_or(ProviderError::NoOriginCaKey)?;
+        let key = self.fetch_secret(s_ref, namespace).await?;
+        let api_url = api_url(resource)?;
+        let user = (
+            T::kind(&()).into_owned(),
+            namespace.to_string(),
+            resource.name_any(),
         );
-        cache.insert(token, arc_client.clone());
+        self.cache
+            .remember(
+                format!("{namespace}/{}", s_ref.name),
+                TokenKey::of_credential(Credential::OriginCaKey, &key),
+                user,
+            )
+            .await;
+        self.cache
+            .client_of(Credential::OriginCaKey, &key, api_url.as_ref())
+            .await
+    }
+
+    /// Client for the operator token, for work that isn't done on behalf of an object
+    pub async fn default_client(&self) -> Result<Arc<CloudflareClient>, ProviderError> {
+        let token = self.default_token.get().ok_or(ProviderError::NoOperatorToken)?;
+        self.get_client_from_cache(token).await
+    }
+
+    /// The cached client of `token`, the token itself is wiped once it's been used
+    async fn get_client_from_cache(
+        &self,
+        token: Zeroizing<String>,
+    ) -> Result<Arc<CloudflareClient>, ProviderError> {
+        self.cache.client(&token).await
+    }
 
-        Ok(arc_client)
+    /// The token a resource is managed with, following its secret, zone and account references
+    pub async fn resolve_token<T>(
+        &self,
+        resource: &T,
+        namespace: &str,
+    ) -> Result<Zeroizing<String>, ProviderError>
+    where
+        T: CloudflareResource + Resource<DynamicType = ()> + Sync + Send,
+    {
+        Ok(self.resolve(resource, namespace, &mut vec![]).await?.token)
     }
 
+    /// Walk resource → zone → account → operator token until one of them names credentials
+    ///
+    /// `chain` holds the `Kind/name` of every object walked so far, an object showing up twice is a
+    /// reference cycle.
     #[async_recursion]
-    async fn resolve_token<T>(&self, resource: &T, namespace: &str) -> Result<String, ProviderError>
+    async fn resolve<T>(
+        &self,
+        resource: &T,
+        namespace: &str,
+        chain: &mut Vec<String>,
+    ) -> Result<Resolved, ProviderError>
     where
-        T: CloudflareResource + Sync + Send,
+        T: CloudflareResource + Resource<DynamicType = ()> + Sync + Send,
     {
+        let link = format!("{}/{}", T::kind(&()), resource.name_any());
+        let cycle = chain.contains(&link);
+        chain.push(link);
+        if cycle {
+            return Err(ProviderError::ReferenceCycle(chain.join(" → ")));
+        }
+        if chain.len() > MAX_CHAIN_DEPTH {
+            return Err(ProviderError::ChainTooDeep(chain.join(" → ")));
+        }
+
+        // the object may send its own token anywhere, anyone else's only where the operator allows
+        let own_api_url = api_url(resource)?;
         if let Some(s_ref) = resource.secret_ref() {
-            return self.fetch_secret::<T>(s_ref, namespace).await;
+            let token = self.fetch_secret(s_ref, namespace).await?;
+            let secret = format!("{namespace}/{}", s_ref.name);
+            return Ok(Resolved {
+                token,
+                api_url: own_api_url,
+                source: TokenSource::new("Secret", format!("Secret {secret}"), chain),
+                secret: Some(secret),
+                account_id: resource.account_id().map(String::from),
+            });
         }
 
         if let Some(z_ref) = resource.zone_ref() {
-            let zone: Api<Zone> = Api::namespaced(self.k8s_client.clone(), namespace);
-            return self
-                .resolve_token(
-                    &zone
-                        .get(&z_ref.name)
-                        .await
-                        .map_err(|_| ProviderError::ZoneNotFound(z_ref.name.clone()))?,
-                    &namespace,
-                )
-                .await;
-        }
-
-        if let Some(a_ref) = resource.zone_ref() {
-            let account: Api<Account> = Api::namespaced(self.k8s_client.clone(), namespace);
-            return self
-                .resolve_token(
-                    &account
-                        .get(&a_ref.name)
-                        .await
-                        .map_err(|_| ProviderError::AccountNotFound(a_ref.name.clone()))?,
-                    &namespace,
-                )
-                .await;
-        }
-
-        Ok(self.default_token.clone())
-    }
-
-    async fn fetch_secret<T>(
+            let zones: Api<Zone> = Api::namespaced(self.k8s_client.clone(), namespace);
+            let zone = zones
+                .get(&z_ref.name)
+                .await
+                .map_err(|_| ProviderError::ZoneNotFound(z_ref.name.clone()))?;
+            let resolved = self.resolve(&zone, namespace, chain).await?;
+            return Ok(Resolved {
+                api_url: self.allowed(own_api_url)?.or(resolved.api_url),
+                ..resolved
+            });
+        }
+
+        if let Some(a_ref) = resource.account_ref() {
+            let accounts: Api<Account> = Api::namespaced(self.k8s_client.clone(), namespace);
+            let account = accounts
+                .get(&a_ref.name)
+                .await
+                .map_err(|_| ProviderError::AccountNotFound(a_ref.name.clone()))?;
+            let resolved = self.resolve(&account, namespace, chain).await?;
+            return Ok(Resolved {
+                api_url: self.allowed(own_api_url)?.or(resolved.api_url),
+                ..resolved
+            });
+        }
+
+        // the account id is the object's to choose, so the central tokens are held to the same
+        // namespaces as the operator token
+        if !self.fallback.allows(namespace) {
+            return Err(ProviderError::OperatorTokenNotAllowed(namespace.to_string()));
+        }
+        if let Some(account_id) = resource.account_id()
+            && let Some(secret_ref) = self
+                .credentials
+                .find(account_id, namespace)
+                .await
+                .map_err(|NotSynced| ProviderError::CredentialsNotSynced)?
+        {
+            let selector = SecretKeySelector {
+                name: secret_ref.name,
+                key: secret_ref.key,
+                optional: None,
+            };
+            let token = self.fetch_secret(&selector, &secret_ref.namespace).await?;
+            let secret = format!("{}/{}", secret_ref.namespace, selector.name);
+            return Ok(Resolved {
+                token,
+                api_url: self.allowed(own_api_url)?,
+                source: TokenSource::new(
+                    "CloudflareCredentials",
+                    format!("Secret {secret} mapped to account {account_id}"),
+                    chain,
+                ),
+                secret: Some(secret),
+                account_id: Some(account_id.to_string()),
+            });
+        }
+
+        Ok(Resolved {
+            token: self.default_token.get().ok_or(ProviderError::NoOperatorToken)?,
+            api_url: self.allowed(own_api_url)?,
+            secret: None,
+            account_id: resource.account_id().map(String::from),
+            source: TokenSource::new("OperatorToken", "the operator token".into(), chain),
+        })
+    }
+
+    /// `api_url` if a token that isn't the naming object's own may go there
+    fn allowed(&self, api_url: Option<Url>) -> Result<Option<Url>, ProviderError> {
+        match api_url {
+            Some(url) if !self.allowed_api_urls.allows(&url) => {
+                Err(ProviderError::ApiUrlNotAllowed(url.to_string()))
+            }
+            api_url => Ok(api_url),
+        }
+    }
+
+    async fn fetch_secret(
         &self,
         secret_ref: &SecretKeySelector,
         namespace: &str,
-    ) -> Result<String, ProviderError> {
+    ) -> Result<Zeroizing<String>, ProviderError> {
         let secrets: Api<Secret> = Api::namespaced(self.k8s_client.clone(), namespace);
         let secret = secrets
             .get(&secret_ref.name)
             .await
             .map_err(|_| ProviderError::SecretNotFound(secret_ref.name.clone()))?;
 
-        if let Some(data) = secret.data {
-            if let Some(byte_token) = data.get(&secret_ref.key) {
-                return String::from_utf8(byte_token.0.clone()).map_err(|_| ProviderError::TokenEncoding);
-            }
+        let mut data = secret.data.unwrap_or_default();
+        let token = data.remove(&secret_ref.key);
+        // the other keys may hold credentials as well
+        for value in data.values_mut() {
+            value.0.zeroize();
+        }
+        match token {
+            Some(token) => String::from_utf8(token.0).map(Zeroizing::new).map_err(|e| {
+                e.into_bytes().zeroize();
+                ProviderError::TokenEncoding
+            }),
+            None => Err(ProviderError::SecretKeyMissing(secret_ref.key.clone())),
         }
-        Err(ProviderError::SecretKeyMissing(secret_ref.key.clone()))
+    }
+}
+
+/// The API `resource` names, if any
+fn api_url<T: CloudflareResource>(resource: &T) -> Result<Option<Url>, ProviderError> {
+    resource
+        .api_url()
+        .map(|url| Url::parse(url).map_err(|_| ProviderError::InvalidApiUrl(url.to_string())))
+        .transpose()
+}
+
+impl ProviderError {
+    /// Whether only a change to the object, its Secret or its credentials gets past it
+    pub fn is_permanent(&self) -> bool {
+        !matches!(
+            self,
+            ProviderError::K8sError(_)
+                | ProviderError::ClientCreation(_)
+                | ProviderError::Verification(_)
+                | ProviderError::CredentialsNotSynced
+        )
+    }
+}
+
+/// Record on the status that no client could be made for `obj`, publish a Warning and back off
+///
+/// The `Ready` condition reports it for every kind, `ready` and `error` are set where the status has them.
+pub async fn credentials_unavailable<K, S>(
+    obj: &K,
+    ctx: &Context,
+    error: ProviderError,
+) -> crate::Result<Action>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + HasStatus<Status = S>
+        + Clone
+        + DeserializeOwned
+        + Debug,
+    S: Conditions + Default + Clone + Serialize,
+{
+    let message = error.to_string();
+    warn!("{} \"{}\": {}", K::kind(&()), obj.name_any(), message);
+    let mut status = obj.status().cloned().unwrap_or_default();
+    let condition = match error {
+        // the token was found, Cloudflare just won't take it
+        ProviderError::TokenInvalid(_) => preflight::condition(
+            false,
+            "TokenInvalid",
+            message.clone(),
+            status.conditions(),
+            obj.meta().generation,
+        ),
+        _ => Condition::new(
+            CREDENTIALS_CONDITION,
+            "False",
+            "CredentialsUnavailable",
+            Some(message.clone()),
+            status.conditions(),
+        )
+        .observed(obj.meta().generation),
+    };
+    status.set_condition(condition);
+    if error.is_permanent() {
+        status.set_stalled("CredentialsUnavailable", message.clone(), obj.meta().generation);
+    } else {
+        status.set_reconciling("CredentialsUnavailable", message.clone(), obj.meta().generation);
+    }
+    status.set_failed(&message);
+    status::patch(obj, ctx.client.clone(), &status).await?;
+
+    let event = Event {
+        type_: EventType::Warning,
+        reason: "CredentialsUnavailable".into(),
+        note: Some(message),
+        action: "Reconciling".into(),
+        secondary: None,
+    };
+    if let Err(e) = ctx.recorder.publish(&event, &obj.object_ref(&())).await {
+        warn!("failed to publish CredentialsUnavailable event: {}", e);
+    }
+    Ok(Action::requeue(ctx.settings.retry))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ProviderError, credentials_unavailable};
+    use crate::{Context, cf_client::MockCloudflareApi, dns_record::DNSRecord};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn unavailable_credentials_stall_the_object() {
+        let (ctx, fakeserver) = Context::test(Arc::new(MockCloudflareApi::new()));
+        let doc = DNSRecord::test("example.com");
+        let served = fakeserver.serve(vec![], doc.clone());
+
+        credentials_unavailable(&doc, &ctx, ProviderError::NoOperatorToken)
+            .await
+            .expect("reconciler");
+
+        let status = served.last_status().expect("status written");
+        let condition = |type_: &str| {
+            status["conditions"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|c| c["type"] == type_)
+                .cloned()
+                .unwrap_or_default()
+        };
+        assert_eq!(condition("Ready")["status"], "False");
+        assert_eq!(condition("Ready")["reason"], "CredentialsUnavailable");
+        assert_eq!(condition("Stalled")["status"], "True");
+        assert_eq!(status["ready"], false);
+        assert_eq!(status["error"], ProviderError::NoOperatorToken.to_string());
+        assert_eq!(served.event_reasons(), ["CredentialsUnavailable"]);
     }
 }
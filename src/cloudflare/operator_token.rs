@@ -0,0 +1,208 @@
+//! The token objects without credentials of their own are managed with
+//!
+//! Either `CLOUDFLARE_API_TOKEN`, or a key of a Secret named with `--default-credentials-secret` (or
+//! `DEFAULT_CREDENTIALS_SECRET`) as `namespace/name/key`. The Secret is read at startup and followed
+//! afterwards, so rotating it doesn't need a restart. Without either, only objects that name their own
+//! credentials can be reconciled.
+//!
+//! `OPERATOR_TOKEN_NAMESPACES` limits which namespaces may fall back to it, or to the tokens of
+//! CloudflareCredentials, at all; in a shared cluster the others have to bring their own `secretRef`
+//! instead of inheriting the platform token.
+//!
+//! An object naming an `apiUrl` gets its own `secretRef` token sent there. Any other token, the operator
+//! token included, only goes to the https APIs `CLOUDFLARE_ALLOWED_API_URLS` lists, so nobody who can
+//! create a Zone or an Account gets it sent to a server of their choosing.
+use super::ProviderError;
+use k8s_openapi::api::core::v1::Secret;
+use kube::{Api, Client};
+use reqwest::Url;
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use zeroize::{Zeroize, Zeroizing};
+
+/// `namespace/name/key` of the Secret holding the operator token
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretLocation {
+    pub namespace: String,
+    pub name: String,
+    pub key: String,
+}
+
+impl FromStr for SecretLocation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split('/').collect::<Vec<_>>()[..] {
+            [namespace, name, key] if !namespace.is_empty() && !name.is_empty() && !key.is_empty() => {
+                Ok(Self {
+                    namespace: namespace.into(),
+                    name: name.into(),
+                    key: key.into(),
+                })
+            }
+            _ => Err(format!("{s} is not namespace/name/key")),
+        }
+    }
+}
+
+/// Namespaces whose objects may be managed with the operator token
+#[derive(Clone, Debug, Default)]
+pub struct FallbackNamespaces(Option<Vec<String>>);
+
+impl FallbackNamespaces {
+    /// From `OPERATOR_TOKEN_NAMESPACES`, comma separated; unset allows every namespace, empty none
+    pub fn from_env() -> Self {
+        Self(std::env::var("OPERATOR_TOKEN_NAMESPACES").ok().map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|ns| !ns.is_empty())
+                .map(String::from)
+                .collect()
+        }))
+    }
+
+    pub fn allows(&self, namespace: &str) -> bool {
+        self.0
+            .as_ref()
+            .is_none_or(|namespaces| namespaces.iter().any(|ns| ns == namespace))
+    }
+}
+
+/// Cloudflare APIs tokens other than an object's own may be sent to when the object names them
+#[derive(Clone, Debug, Default)]
+pub struct AllowedApiUrls(Vec<Url>);
+
+impl AllowedApiUrls {
+    /// From `CLOUDFLARE_ALLOWED_API_URLS`, comma separated; unset or empty allows none
+    pub fn from_env() -> Self {
+        Self::parse(&std::env::var("CLOUDFLARE_ALLOWED_API_URLS").unwrap_or_default())
+    }
+
+    /// Only https URLs count, the others are dropped
+    pub fn parse(list: &str) -> Self {
+        Self(
+            list.split(',')
+                .filter_map(|url| Url::parse(url.trim()).ok())
+                .filter(|url| url.scheme() == "https")
+                .collect(),
+        )
+    }
+
+    /// Whether `url` is on https and at or below one of the allowed URLs
+    pub fn allows(&self, url: &Url) -> bool {
+        url.scheme() == "https"
+            && self.0.iter().any(|allowed| {
+                allowed.origin() == url.origin()
+                    && url.path().starts_with(allowed.path().trim_end_matches('/'))
+            })
+    }
+
+    /// Why `api_url` can't be named by an object without a `secretRef` of its own, if it can't
+    pub fn problem(&self, api_url: &str, own_secret: bool) -> Option<String> {
+        let Ok(url) = Url::parse(api_url) else {
+            return Some(format!("apiUrl {api_url} is not a URL"));
+        };
+        (!own_secret && !self.allows(&url)).then(|| {
+            format!(
+                "apiUrl {api_url} needs a secretRef on the object, only CLOUDFLARE_ALLOWED_API_URLS get other tokens"
+            )
+        })
+    }
+}
+
+/// The operator token, shared by every controller and swapped in place when its Secret changes
+#[derive(Clone, Default)]
+pub struct OperatorToken {
+    token: Arc<RwLock<Option<Zeroizing<String>>>>,
+    secret: Option<SecretLocation>,
+}
+
+impl OperatorToken {
+    /// `CLOUDFLARE_API_TOKEN`, or no operator token when it's unset or empty
+    pub fn from_env() -> Self {
+        Self::fixed(std::env::var("CLOUDFLARE_API_TOKEN").ok())
+    }
+
+    /// A token that never changes, `None` or empty for none
+    pub fn fixed(token: Option<String>) -> Self {
+        Self {
+            token: Arc::new(RwLock::new(token.filter(|t| !t.is_empty()).map(Zeroizing::new))),
+            secret: None,
+        }
+    }
+
+    /// Read the token from `secret` instead, it takes over from `CLOUDFLARE_API_TOKEN`
+    pub fn from_secret(secret: SecretLocation) -> Self {
+        Self {
+            token: Arc::default(),
+            secret: Some(secret),
+        }
+    }
+
+    pub fn get(&self) -> Option<Zeroizing<String>> {
+        self.token.read().expect("token lock is never poisoned").clone()
+    }
+
+    /// The Secret the token is read from, if any
+    pub fn secret(&self) -> Option<&SecretLocation> {
+        self.secret.as_ref()
+    }
+
+    /// Take the token from the Secret's current content, `None` when it's gone
+    ///
+    /// Returns the token it replaced, if that was a different one.
+    pub fn update(&self, secret: Option<&mut Secret>) -> Option<Zeroizing<String>> {
+        let location = self.secret.as_ref()?;
+        let token = secret.and_then(|secret| {
+            let mut data = secret.data.take().unwrap_or_default();
+            let token = data.remove(&location.key);
+            for value in data.values_mut() {
+                value.0.zeroize();
+            }
+            String::from_utf8(token?.0).ok().map(Zeroizing::new)
+        });
+        let mut current = self.token.write().expect("token lock is never poisoned");
+        if *current == token {
+            return None;
+        }
+        std::mem::replace(&mut *current, token)
+    }
+
+    /// Read the Secret once, before the watch takes over
+    pub async fn load(&self, client: Client) -> Result<(), ProviderError> {
+        let Some(location) = &self.secret else {
+            return Ok(());
+        };
+        let secrets: Api<Secret> = Api::namespaced(client, &location.namespace);
+        let mut secret = secrets
+            .get_opt(&location.name)
+            .await?
+            .ok_or_else(|| ProviderError::SecretNotFound(location.name.clone()))?;
+        self.update(Some(&mut secret));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AllowedApiUrls;
+
+    #[test]
+    fn only_allowed_https_apis_get_other_tokens() {
+        let allowed = AllowedApiUrls::parse("https://api.cloudflare.com/client/v4, http://mock.local");
+        let url = |url: &str| url.parse().unwrap();
+        assert!(allowed.allows(&url("https://api.cloudflare.com/client/v4")));
+        assert!(allowed.allows(&url("https://api.cloudflare.com/client/v4/")));
+        assert!(!allowed.allows(&url("https://api.cloudflare.com.evil.example/client/v4")));
+        assert!(!allowed.allows(&url("https://api.cloudflare.com/other")));
+        assert!(!allowed.allows(&url("http://api.cloudflare.com/client/v4")));
+        // plain http is dropped from the list as well
+        assert!(!allowed.allows(&url("http://mock.local")));
+
+        assert!(allowed.problem("https://evil.example", true).is_none());
+        assert!(allowed.problem("https://evil.example", false).is_some());
+        assert!(allowed.problem("not a url", true).is_some());
+    }
+}
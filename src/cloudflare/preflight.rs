@@ -0,0 +1,113 @@
+//! Checks a token before it's used, and names the missing permission when Cloudflare refuses it
+//!
+//! The verify endpoint tells whether a token is active, it can't tell what the token may do. What an
+//! object needs is known per kind though, so a 403 in the middle of a reconcile is reported as the
+//! permission the kind needs instead of a bare Cloudflare error.
+use super::{CloudflareResource, ProviderError};
+use crate::{
+    Context, Result,
+    cf_client::{CloudflareClient, is_forbidden},
+    conditions::{Condition, Conditions},
+    status,
+};
+use k8s_openapi::NamespaceResourceScope;
+use kube::{
+    Resource, ResourceExt,
+    core::object::HasStatus,
+    runtime::{
+        controller::Action,
+        events::{Event, EventType},
+    },
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::fmt::Debug;
+use tracing::*;
+
+/// Condition telling whether the token is active and allowed what the object needs
+pub const CREDENTIALS_VALID_CONDITION: &str = "CredentialsValid";
+
+/// Verify the token of `client`, against `account` when it's known; a disabled or expired token is refused
+pub async fn verify(client: &CloudflareClient, account: Option<&str>) -> Result<String, ProviderError> {
+    let status = client
+        .verify(account)
+        .await
+        .map_err(|e| ProviderError::Verification(e.to_string()))?;
+    // disabled and expired tokens still verify, with that status
+    if status.status != "active" {
+        return Err(ProviderError::TokenInvalid(format!(
+            "token {} is {}",
+            status.id, status.status
+        )));
+    }
+    Ok(format!("Token {} is active", status.id))
+}
+
+/// `CredentialsValid`, keeping the transition time of the previous one
+pub fn condition(
+    valid: bool,
+    reason: &str,
+    message: String,
+    previous: &[Condition],
+    generation: Option<i64>,
+) -> Condition {
+    let status = if valid { "True" } else { "False" };
+    Condition::new(
+        CREDENTIALS_VALID_CONDITION,
+        status,
+        reason,
+        Some(message),
+        previous,
+    )
+    .observed(generation)
+}
+
+/// Pass the reconcile result on, recording `CredentialsValid=False` first when Cloudflare refused the token
+pub async fn forbidden<K, S>(obj: &K, ctx: &Context, result: Result<Action>) -> Result<Action>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + CloudflareResource
+        + HasStatus<Status = S>
+        + Clone
+        + DeserializeOwned
+        + Debug,
+    S: Conditions + Default + Clone + Serialize,
+{
+    let Err(error) = &result else {
+        return result;
+    };
+    let Some(cf_error) = error.cloudflare_error().filter(|e| is_forbidden(e)) else {
+        return result;
+    };
+    let message = format!(
+        "Cloudflare refused the token, a {} needs {}: {}",
+        K::kind(&()),
+        K::PERMISSIONS.join(", "),
+        cf_error
+    );
+    warn!("{} \"{}\": {}", K::kind(&()), obj.name_any(), message);
+    let mut status = obj.status().cloned().unwrap_or_default();
+    let valid = condition(
+        false,
+        "PermissionDenied",
+        message.clone(),
+        status.conditions(),
+        obj.meta().generation,
+    );
+    status.set_condition(valid);
+    status.set_stalled("PermissionDenied", message.clone(), obj.meta().generation);
+    if let Err(e) = status::patch(obj, ctx.client.clone(), &status).await {
+        warn!("failed to record the refused token: {}", e);
+    }
+
+    let event = Event {
+        type_: EventType::Warning,
+        reason: "PermissionDenied".into(),
+        note: Some(message),
+        action: "Reconciling".into(),
+        secondary: None,
+    };
+    if let Err(e) = ctx.recorder.publish(&event, &obj.object_ref(&())).await {
+        warn!("failed to publish PermissionDenied event: {}", e);
+    }
+    result
+}
@@ -0,0 +1,116 @@
+//! Follows the Secrets tokens are read from, so a rotated token takes effect without a restart
+use super::{Credential, TokenKey};
+use crate::{State, namespaces::scoped};
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::Secret;
+use kube::{
+    Api, Client, ResourceExt,
+    runtime::{WatchStreamExt, watcher},
+};
+use std::collections::HashSet;
+use tracing::*;
+use zeroize::Zeroize;
+
+/// Watch Secrets in the watched namespaces, dropping the cached clients of rotated or deleted ones
+///
+/// Objects that were handed a client for the old token are reconciled right away, they pick up the
+/// new token, or report that it's gone, without waiting for their requeue.
+pub async fn run(state: State) {
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    futures::future::join(
+        operator_token(state.clone(), client.clone()),
+        secrets(state, client),
+    )
+    .await;
+}
+
+/// Follow the Secret the operator token is read from, which may be outside the watched namespaces
+async fn operator_token(state: State, client: Client) {
+    let Some(location) = state.operator_token().secret().cloned() else {
+        return;
+    };
+    watcher(
+        Api::<Secret>::namespaced(client, &location.namespace),
+        watcher::Config::default().fields(&format!("metadata.name={}", location.name)),
+    )
+    .default_backoff()
+    .for_each(|event| {
+        let state = state.clone();
+        async move {
+            let replaced = match event {
+                Ok(watcher::Event::Apply(mut secret) | watcher::Event::InitApply(mut secret)) => {
+                    state.operator_token().update(Some(&mut secret))
+                }
+                Ok(watcher::Event::Delete(_)) => state.operator_token().update(None),
+                Ok(_) => return,
+                Err(e) => {
+                    warn!("operator token Secret watch failed: {e}");
+                    return;
+                }
+            };
+            if let Some(old) = replaced {
+                info!("operator token changed, dropping the client of the old one");
+                state.clients().forget(&old).await;
+            }
+        }
+    })
+    .await;
+}
+
+async fn secrets(state: State, client: Client) {
+    futures::future::join_all(state.namespaces().scopes().into_iter().map(|ns| {
+        let state = state.clone();
+        watcher(
+            scoped::<Secret>(client.clone(), ns.as_deref()),
+            watcher::Config::default(),
+        )
+        .default_backoff()
+        .for_each(move |event| {
+            let state = state.clone();
+            async move {
+                let (secret, deleted) = match event {
+                    // a relist after a broken watch catches rotations made in the meantime
+                    Ok(watcher::Event::Apply(secret) | watcher::Event::InitApply(secret)) => (secret, false),
+                    Ok(watcher::Event::Delete(secret)) => (secret, true),
+                    Ok(_) => return,
+                    Err(e) => {
+                        warn!("Secret watch failed: {e}");
+                        return;
+                    }
+                };
+                let mut secret = secret;
+                let tokens = (!deleted).then(|| tokens(&mut secret));
+                let namespace = secret.namespace().unwrap_or_default();
+                let users = state
+                    .clients()
+                    .rotate(&namespace, &secret.name_any(), tokens.as_ref())
+                    .await;
+                for (kind, namespace, name) in users {
+                    debug!(
+                        "Requeueing {} {}/{} after its Secret changed",
+                        kind, namespace, name
+                    );
+                    state.triggers().reconcile(&kind, &name, &namespace);
+                }
+            }
+        })
+    }))
+    .await;
+}
+
+/// Keys of every value of the Secret that could be a token or an Origin CA key, the values are wiped
+fn tokens(secret: &mut Secret) -> HashSet<TokenKey> {
+    secret
+        .data
+        .iter_mut()
+        .flatten()
+        .flat_map(|(_, value)| {
+            let keys = std::str::from_utf8(&value.0).ok().map(|value| {
+                [Credential::ApiToken, Credential::OriginCaKey]
+                    .map(|credential| TokenKey::of_credential(credential, value))
+            });
+            value.0.zeroize();
+            keys.into_iter().flatten()
+        })
+        .collect()
+}
@@ -0,0 +1,127 @@
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Condition as used by every status, mirrors the Kubernetes `metav1.Condition` layout
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Condition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// `True`, `False` or `Unknown`
+    pub status: String,
+    pub reason: String,
+    pub message: Option<String>,
+    pub last_transition_time: Option<String>,
+    /// Generation of the spec the condition was computed for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+}
+
+impl Condition {
+    /// Build a condition, keeping the transition time of `previous` when the status didn't change
+    pub fn new(
+        type_: &str,
+        status: &str,
+        reason: &str,
+        message: Option<String>,
+        previous: &[Condition],
+    ) -> Self {
+        let last_transition_time = previous
+            .iter()
+            .find(|c| c.type_ == type_ && c.status == status)
+            .and_then(|c| c.last_transition_time.clone())
+            .unwrap_or_else(|| Utc::now().to_rfc3339());
+        Self {
+            type_: type_.into(),
+            status: status.into(),
+            reason: reason.into(),
+            message,
+            last_transition_time: Some(last_transition_time),
+            observed_generation: None,
+        }
+    }
+
+    /// Record the generation of the object the condition was computed for
+    pub fn observed(self, generation: Option<i64>) -> Self {
+        Self {
+            observed_generation: generation,
+            ..self
+        }
+    }
+}
+
+/// A status reporting through conditions the way kstatus (Flux, Argo CD) reads them
+///
+/// `Ready` is always present, `Reconciling`, `Stalled` and `Paused` only while they are true. Statuses are built
+/// from the previous conditions, so that transition times survive a sync that changes nothing.
+pub trait Conditions {
+    fn conditions(&self) -> &[Condition];
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition>;
+
+    /// Replace the condition of the same type, or add it
+    fn set_condition(&mut self, condition: Condition) {
+        let conditions = self.conditions_mut();
+        match conditions.iter_mut().find(|c| c.type_ == condition.type_) {
+            Some(existing) => *existing = condition,
+            None => conditions.push(condition),
+        }
+    }
+
+    fn remove_condition(&mut self, type_: &str) {
+        self.conditions_mut().retain(|c| c.type_ != type_);
+    }
+
+    /// Record a failure in the `ready` and `error` fields, for kinds whose status has them next to
+    /// the conditions; the others only report it through `Ready`
+    fn set_failed(&mut self, _message: &str) {}
+
+    /// The object is in the state its spec asks for
+    fn set_ready(&mut self, reason: &str, generation: Option<i64>) {
+        let ready = Condition::new("Ready", "True", reason, None, self.conditions()).observed(generation);
+        self.set_condition(ready);
+        self.remove_condition("Reconciling");
+        self.remove_condition("Stalled");
+        self.remove_condition("Paused");
+    }
+
+    /// Not ready yet, but it will get there without the spec changing
+    fn set_reconciling(&mut self, reason: &str, message: String, generation: Option<i64>) {
+        set_not_ready(self, "Reconciling", reason, message, generation);
+        self.remove_condition("Stalled");
+    }
+
+    /// Not ready, and it won't be until the spec or something outside the operator changes
+    fn set_stalled(&mut self, reason: &str, message: String, generation: Option<i64>) {
+        set_not_ready(self, "Stalled", reason, message, generation);
+        self.remove_condition("Reconciling");
+    }
+
+    fn is_ready(&self) -> bool {
+        self.conditions()
+            .iter()
+            .any(|c| c.type_ == "Ready" && c.status == "True")
+    }
+}
+
+/// `Ready=False` next to `type_=True`, with the same reason and message
+fn set_not_ready<C: Conditions + ?Sized>(
+    status: &mut C,
+    type_: &str,
+    reason: &str,
+    message: String,
+    generation: Option<i64>,
+) {
+    let ready = Condition::new(
+        "Ready",
+        "False",
+        reason,
+        Some(message.clone()),
+        status.conditions(),
+    );
+    status.set_condition(ready.observed(generation));
+    let abnormal = Condition::new(type_, "True", reason, Some(message), status.conditions());
+    status.set_condition(abnormal.observed(generation));
+    status.remove_condition("Paused");
+}
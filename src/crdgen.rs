@@ -1,9 +1,93 @@
-use controller::{account::Account, dns_record::DNSRecord, zone::Zone};
-use kube::CustomResourceExt;
-fn main() {
-    print!("{}", serde_yaml::to_string(&DNSRecord::crd()).unwrap());
-    println!("---");
-    print!("{}", serde_yaml::to_string(&Account::crd()).unwrap());
-    println!("---");
-    print!("{}", serde_yaml::to_string(&Zone::crd()).unwrap());
+//! Prints the CRDs as one stream, or writes them to a directory
+//!
+//! - `--out-dir DIR` writes one file per CRD, named after it, instead of printing
+//! - `--format yaml|json` picks the encoding, YAML by default, printed JSON is a `List`
+//! - `--kustomization` also writes a `kustomization.yaml` listing the files (with `--out-dir`)
+//! - `--conversion-service namespace/name[:port]` points multi-version CRDs at the controller's
+//!   conversion webhook, `default/doc-controller` as installed by the chart, on port 443
+//! - `--conversion-ca-bundle FILE` or `--conversion-ca-from namespace/certificate` gives the API server the
+//!   CA to call the webhook with, a PEM file or the cert-manager Certificate (`default/doc-controller-webhook`
+//!   with the chart) to inject it from. Without either, versions are converted with strategy `None`
+use controller::crds::ConversionCa;
+use std::path::Path;
+
+/// Value of `--name value` or `--name=value`
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.strip_prefix(name) {
+            Some("") => args.get(i + 1).map(String::as_str),
+            Some(value) => value.strip_prefix('='),
+            None => None,
+        })
+}
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let service = flag(&args, "--conversion-service").unwrap_or("default/doc-controller");
+    let (service, port) = match service.split_once(':') {
+        Some((service, port)) => (service, Some(port.parse()?)),
+        None => (service, None),
+    };
+    let (namespace, name) = service
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("--conversion-service takes namespace/name"))?;
+    let ca = match (
+        flag(&args, "--conversion-ca-bundle"),
+        flag(&args, "--conversion-ca-from"),
+    ) {
+        (Some(_), Some(_)) => anyhow::bail!("use one of --conversion-ca-bundle and --conversion-ca-from"),
+        (Some(file), None) => Some(ConversionCa::Bundle(std::fs::read_to_string(file)?)),
+        (None, Some(certificate)) => Some(ConversionCa::InjectFrom(certificate.into())),
+        (None, None) => None,
+    };
+    let json = match flag(&args, "--format").unwrap_or("yaml") {
+        "yaml" => false,
+        "json" => true,
+        other => anyhow::bail!("unknown format {other}, use yaml or json"),
+    };
+
+    let mut crds = controller::crds::all();
+    for crd in &mut crds {
+        controller::crds::convert_through(crd, namespace, name, port, ca.as_ref());
+    }
+    let encode = |crd| -> anyhow::Result<String> {
+        Ok(if json {
+            serde_json::to_string_pretty(crd)? + "\n"
+        } else {
+            serde_yaml::to_string(crd)?
+        })
+    };
+
+    let Some(dir) = flag(&args, "--out-dir") else {
+        if json {
+            let list = serde_json::json!({ "apiVersion": "v1", "kind": "List", "items": crds });
+            println!("{}", serde_json::to_string_pretty(&list)?);
+        } else {
+            let documents = crds.iter().map(encode).collect::<anyhow::Result<Vec<_>>>()?;
+            print!("{}", documents.join("---\n"));
+        }
+        return Ok(());
+    };
+
+    let dir = Path::new(dir);
+    std::fs::create_dir_all(dir)?;
+    let extension = if json { "json" } else { "yaml" };
+    let mut files = vec![];
+    for crd in &crds {
+        let file = format!("{}.{extension}", crd.metadata.name.as_deref().unwrap_or_default());
+        std::fs::write(dir.join(&file), encode(crd)?)?;
+        files.push(file);
+    }
+    if args.iter().any(|arg| arg == "--kustomization") {
+        let resources: String = files.iter().map(|file| format!("- {file}\n")).collect();
+        std::fs::write(
+            dir.join("kustomization.yaml"),
+            format!(
+                "apiVersion: kustomize.config.k8s.io/v1beta1\nkind: Kustomization\nresources:\n{resources}"
+            ),
+        )?;
+    }
+    eprintln!("wrote {} CRDs to {}", files.len(), dir.display());
+    Ok(())
 }
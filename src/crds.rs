@@ -0,0 +1,324 @@
+use crate::{
+    account::Account, account_member::AccountMember, api_token::APIToken, credentials::CloudflareCredentials,
+    dns_record::DNSRecord, page_rule::PageRule, policy::CloudflarePolicy, tunnel::Tunnel, zone::Zone,
+    zone_binding::ZoneBinding, zone_set::ZoneSet,
+};
+use k8s_openapi::{
+    ByteString,
+    apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+        CustomResourceConversion, CustomResourceDefinition, ServiceReference, WebhookClientConfig,
+        WebhookConversion,
+    },
+};
+use kube::{CustomResourceExt, core::crd::merge_crds};
+
+/// Version the API server stores objects in, the one the controllers work with
+pub const STORED_VERSION: &str = "v1alpha1";
+
+/// Every CRD the operator serves, in the order they are installed
+///
+/// Kinds with several versions still need [`convert_through`] before they can be installed.
+pub fn all() -> Vec<CustomResourceDefinition> {
+    vec![
+        versions(vec![
+            DNSRecord::crd(),
+            crate::dns_record::v1beta1::DNSRecord::crd(),
+        ]),
+        Account::crd(),
+        versions(vec![Zone::crd(), crate::zone::v1beta1::Zone::crd()]),
+        CloudflarePolicy::crd(),
+        ZoneBinding::crd(),
+        CloudflareCredentials::crd(),
+        ZoneSet::crd(),
+        AccountMember::crd(),
+        APIToken::crd(),
+        PageRule::crd(),
+        Tunnel::crd(),
+    ]
+}
+
+/// One CRD serving every version of a kind
+fn versions(crds: Vec<CustomResourceDefinition>) -> CustomResourceDefinition {
+    merge_crds(crds, STORED_VERSION).expect("versions of a kind are generated alike")
+}
+
+/// Where the API server gets the CA to verify the conversion webhook's certificate from
+#[derive(Clone, Debug)]
+pub enum ConversionCa {
+    /// PEM encoded CA certificates, set as the `caBundle`
+    Bundle(String),
+    /// cert-manager Certificate `namespace/name`, its cainjector fills in the `caBundle`
+    InjectFrom(String),
+}
+
+/// Have the API server convert between the versions of `crd` through `/convert` on `service`
+///
+/// The API server only calls webhooks over HTTPS, `port` is where TLS is terminated for the controller
+/// and it has to trust the certificate served there. Without a `ca` the webhook couldn't be called, the
+/// versions are converted with strategy `None` instead, which only rewrites the `apiVersion`.
+pub fn convert_through(
+    crd: &mut CustomResourceDefinition,
+    namespace: &str,
+    service: &str,
+    port: Option<i32>,
+    ca: Option<&ConversionCa>,
+) {
+    if crd.spec.versions.len() < 2 {
+        return;
+    }
+    let Some(ca) = ca else {
+        crd.spec.conversion = Some(CustomResourceConversion {
+            strategy: "None".into(),
+            webhook: None,
+        });
+        return;
+    };
+    let ca_bundle = match ca {
+        ConversionCa::Bundle(pem) => Some(ByteString(pem.clone().into_bytes())),
+        ConversionCa::InjectFrom(certificate) => {
+            crd.metadata
+                .annotations
+                .get_or_insert_default()
+                .insert("cert-manager.io/inject-ca-from".into(), certificate.clone());
+            None
+        }
+    };
+    crd.spec.conversion = Some(CustomResourceConversion {
+        strategy: "Webhook".into(),
+        webhook: Some(WebhookConversion {
+            client_config: Some(WebhookClientConfig {
+                service: Some(ServiceReference {
+                    namespace: namespace.into(),
+                    name: service.into(),
+                    path: Some("/convert".into()),
+                    port,
+                }),
+                ca_bundle,
+                ..Default::default()
+            }),
+            conversion_review_versions: vec!["v1".into()],
+        }),
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConversionCa, all, convert_through};
+    use serde_json::Value;
+
+    /// Keywords that may only sit next to the `type` they belong to, not inside a junctor
+    const TYPED_KEYWORDS: [&str; 6] = [
+        "type",
+        "additionalProperties",
+        "default",
+        "nullable",
+        "description",
+        "title",
+    ];
+
+    /// Collect violations of the structural schema rules the API server enforces
+    ///
+    /// <https://kubernetes.io/docs/tasks/extend-kubernetes/custom-resources/custom-resource-definitions/#specifying-a-structural-schema>
+    fn structural_violations(path: &str, schema: &Value, in_junctor: bool, violations: &mut Vec<String>) {
+        let Some(schema) = schema.as_object() else {
+            violations.push(format!("{path}: schema is not an object"));
+            return;
+        };
+        let flag = |key: &str| schema.get(key).and_then(Value::as_bool).unwrap_or(false);
+
+        if schema.contains_key("$ref") || schema.contains_key("definitions") {
+            violations.push(format!("{path}: references are not allowed"));
+        }
+        if in_junctor {
+            for keyword in TYPED_KEYWORDS.iter().filter(|k| schema.contains_key(**k)) {
+                violations.push(format!(
+                    "{path}: `{keyword}` is not allowed inside anyOf/oneOf/allOf/not"
+                ));
+            }
+        } else if !schema.contains_key("type")
+            && !flag("x-kubernetes-int-or-string")
+            && !flag("x-kubernetes-preserve-unknown-fields")
+        {
+            violations.push(format!("{path}: missing type"));
+        }
+        if schema.contains_key("properties") && schema.get("additionalProperties").is_some_and(|a| a != false)
+        {
+            violations.push(format!(
+                "{path}: properties and additionalProperties are mutually exclusive"
+            ));
+        }
+
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (name, property) in properties {
+                structural_violations(&format!("{path}.{name}"), property, in_junctor, violations);
+            }
+        }
+        if let Some(items) = schema.get("items") {
+            structural_violations(&format!("{path}[]"), items, in_junctor, violations);
+        }
+        if let Some(additional) = schema.get("additionalProperties").filter(|a| a.is_object()) {
+            structural_violations(&format!("{path}{{}}"), additional, in_junctor, violations);
+        }
+        for junctor in ["anyOf", "oneOf", "allOf"] {
+            for (i, branch) in schema
+                .get(junctor)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .enumerate()
+            {
+                structural_violations(&format!("{path}/{junctor}[{i}]"), branch, true, violations);
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            structural_violations(&format!("{path}/not"), not, true, violations);
+        }
+    }
+
+    #[test]
+    fn crd_schemas_are_structural() {
+        let mut violations = vec![];
+        for crd in all() {
+            for version in &crd.spec.versions {
+                let schema = version
+                    .schema
+                    .as_ref()
+                    .and_then(|s| s.open_api_v3_schema.as_ref())
+                    .expect("every version has a schema");
+                let root = format!("{}/{}", crd.spec.names.kind, version.name);
+                let schema = serde_json::to_value(schema).unwrap();
+                structural_violations(&root, &schema, false, &mut violations);
+            }
+        }
+        assert!(
+            violations.is_empty(),
+            "non-structural schemas:\n{}",
+            violations.join("\n")
+        );
+    }
+
+    /// Fields `status::patch` and `status::record_failures` write whatever the kind
+    const WRITTEN_STATUS_FIELDS: [&str; 2] = ["observedGeneration", "consecutiveFailures"];
+
+    #[test]
+    fn written_status_fields_survive_pruning() {
+        for crd in all() {
+            for version in &crd.spec.versions {
+                let schema = serde_json::to_value(&version.schema).unwrap();
+                let status = &schema["openAPIV3Schema"]["properties"]["status"]["properties"];
+                if status.is_null() {
+                    // kinds without a status
+                    continue;
+                }
+                for field in WRITTEN_STATUS_FIELDS {
+                    assert!(
+                        status.get(field).is_some(),
+                        "{}/{} status has no {field}, the API server would prune it",
+                        crd.spec.names.kind,
+                        version.name
+                    );
+                }
+            }
+        }
+
+        let written = serde_json::json!({ "ready": true, "observedGeneration": 3, "consecutiveFailures": 2 });
+        let account: crate::account::AccountStatus = serde_json::from_value(written.clone()).unwrap();
+        assert_eq!(account.observed_generation, Some(3));
+        let record: crate::dns_record::DNSRecordStatus = serde_json::from_value(written).unwrap();
+        assert_eq!(record.observed_generation, Some(3));
+        assert_eq!(serde_json::to_value(&record).unwrap()["observedGeneration"], 3);
+    }
+
+    /// Short names taken by built-in kinds and common CRDs (Tekton's PipelineRun is `pr`)
+    const TAKEN_SHORTNAMES: [&str; 12] = [
+        "cm", "cs", "deploy", "ds", "ep", "ev", "ing", "ns", "po", "pr", "sa", "svc",
+    ];
+
+    #[test]
+    fn crds_share_a_category_and_distinct_shortnames() {
+        let mut seen = std::collections::HashSet::new();
+        for crd in all() {
+            let names = &crd.spec.names;
+            assert_eq!(
+                names.categories.as_deref(),
+                Some(&["cloudflare".to_string()][..]),
+                "{} is not in the cloudflare category",
+                names.kind
+            );
+            let shortnames = names.short_names.clone().unwrap_or_default();
+            assert!(
+                shortnames.iter().any(|s| s.starts_with("cf")),
+                "{} has no cf shortname",
+                names.kind
+            );
+            for shortname in shortnames {
+                assert!(
+                    !TAKEN_SHORTNAMES.contains(&shortname.as_str()),
+                    "{} shortname {shortname} is taken",
+                    names.kind
+                );
+                assert!(seen.insert(shortname.clone()), "{shortname} is used twice");
+            }
+        }
+    }
+
+    #[test]
+    fn conversion_webhook_needs_a_ca() {
+        let strategies = |ca: Option<&ConversionCa>| {
+            let mut crds = all();
+            for crd in &mut crds {
+                convert_through(crd, "default", "doc-controller", Some(443), ca);
+            }
+            crds.into_iter()
+                .filter(|crd| crd.spec.versions.len() > 1)
+                .map(|crd| serde_json::to_value(crd).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let without = strategies(None);
+        assert!(!without.is_empty());
+        for crd in &without {
+            assert_eq!(crd["spec"]["conversion"]["strategy"], "None");
+            assert!(crd["spec"]["conversion"]["webhook"].is_null());
+        }
+
+        let bundle = ConversionCa::Bundle("-----BEGIN CERTIFICATE-----".into());
+        for crd in strategies(Some(&bundle)) {
+            let config = &crd["spec"]["conversion"]["webhook"]["clientConfig"];
+            assert_eq!(crd["spec"]["conversion"]["strategy"], "Webhook");
+            assert_eq!(config["service"]["port"], 443);
+            assert_eq!(config["service"]["path"], "/convert");
+            assert!(config["caBundle"].is_string());
+        }
+
+        let injected = ConversionCa::InjectFrom("default/doc-controller-webhook".into());
+        for crd in strategies(Some(&injected)) {
+            assert_eq!(crd["spec"]["conversion"]["strategy"], "Webhook");
+            assert_eq!(
+                crd["metadata"]["annotations"]["cert-manager.io/inject-ca-from"],
+                "default/doc-controller-webhook"
+            );
+        }
+    }
+
+    // Lets the API server itself judge the schemas, point KUBECONFIG at a throwaway (kind) cluster
+    #[tokio::test]
+    #[ignore = "requires a cluster"]
+    async fn api_server_accepts_crds() {
+        use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+        use kube::api::{Api, Patch, PatchParams};
+
+        let client = kube::Client::try_default().await.unwrap();
+        let crds: Api<CustomResourceDefinition> = Api::all(client);
+        let params = PatchParams {
+            dry_run: true,
+            ..PatchParams::apply("crdgen").force()
+        };
+        for crd in all() {
+            let name = crd.metadata.name.clone().unwrap();
+            if let Err(e) = crds.patch(&name, &params, &Patch::Apply(&crd)).await {
+                panic!("API server rejected {name}: {e}");
+            }
+        }
+    }
+}
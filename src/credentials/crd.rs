@@ -0,0 +1,41 @@
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Cluster wide mapping of Cloudflare accounts to the secrets holding their tokens
+///
+/// Consulted when an object doesn't bring its own `secretRef`, before falling back to the operator token,
+/// and only for namespaces allowed to fall back to the operator token at all.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(Default))]
+#[kube(
+    kind = "CloudflareCredentials",
+    group = "cloudflare.com",
+    version = "v1alpha1"
+)]
+#[kube(shortname = "cfcreds", category = "cloudflare")]
+#[serde(rename_all = "camelCase")]
+pub struct CloudflareCredentialsSpec {
+    pub accounts: Vec<AccountCredentials>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountCredentials {
+    /// Cloudflare account id
+    pub account_id: String,
+    pub secret_ref: SecretKeyReference,
+    /// Namespaces whose objects may be managed with the token, `*` for every namespace
+    ///
+    /// The account id comes from the objects, so without this any namespace could claim the token.
+    pub allowed_namespaces: Vec<String>,
+}
+
+/// A key of a secret in any namespace
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretKeyReference {
+    pub name: String,
+    pub namespace: String,
+    pub key: String,
+}
@@ -0,0 +1,5 @@
+mod crd;
+mod store;
+
+pub use crd::{AccountCredentials, CloudflareCredentials, CloudflareCredentialsSpec, SecretKeyReference};
+pub use store::{CredentialsStore, NotSynced, run};
@@ -0,0 +1,96 @@
+use crate::{
+    State,
+    credentials::{CloudflareCredentials, SecretKeyReference},
+    namespaces,
+};
+use futures::StreamExt;
+use kube::{
+    Api, Client,
+    runtime::{
+        WatchStreamExt,
+        reflector::{self, Store, store::Writer},
+        watcher,
+    },
+};
+use std::sync::{Arc, Mutex};
+use tokio::time::Duration;
+use tracing::*;
+
+/// How long a lookup waits for the first list of CloudflareCredentials before giving up
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The CloudflareCredentials of the cluster, kept by a watch instead of listed on every lookup
+///
+/// The default store watches nothing and finds nothing.
+#[derive(Clone, Default)]
+pub struct CredentialsStore {
+    store: Option<Store<CloudflareCredentials>>,
+    /// Handed to the watch started by `run`
+    writer: Arc<Mutex<Option<Writer<CloudflareCredentials>>>>,
+}
+
+/// The first list of CloudflareCredentials didn't come in
+#[derive(Debug)]
+pub struct NotSynced;
+
+impl CredentialsStore {
+    /// A store filled once `run` watches the CloudflareCredentials
+    pub fn watched() -> Self {
+        let (store, writer) = reflector::store();
+        Self {
+            store: Some(store),
+            writer: Arc::new(Mutex::new(Some(writer))),
+        }
+    }
+
+    /// The secret mapped to `account_id` for objects in `namespace`
+    pub async fn find(
+        &self,
+        account_id: &str,
+        namespace: &str,
+    ) -> Result<Option<SecretKeyReference>, NotSynced> {
+        let Some(store) = &self.store else {
+            return Ok(None);
+        };
+        // until the first list is in, a missing mapping would hand out the operator token instead
+        match tokio::time::timeout(SYNC_TIMEOUT, store.wait_until_ready()).await {
+            Ok(Ok(())) => {}
+            _ => return Err(NotSynced),
+        }
+        Ok(store
+            .state()
+            .iter()
+            .flat_map(|c| &c.spec.accounts)
+            .find(|a| a.account_id == account_id && namespaces::selects(&a.allowed_namespaces, namespace))
+            .map(|a| a.secret_ref.clone()))
+    }
+}
+
+/// Watch the CloudflareCredentials into the store of the state
+pub async fn run(state: State) {
+    let writer = state
+        .credentials()
+        .writer
+        .lock()
+        .expect("writer lock is never poisoned")
+        .take();
+    let Some(writer) = writer else {
+        // nothing to watch, but we are raced against the controllers so never finish
+        return futures::future::pending().await;
+    };
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    reflector::reflector(
+        writer,
+        watcher(
+            Api::<CloudflareCredentials>::all(client),
+            watcher::Config::default(),
+        ),
+    )
+    .default_backoff()
+    .for_each(|event| async move {
+        if let Err(e) = event {
+            warn!("CloudflareCredentials watch failed: {e}");
+        }
+    })
+    .await;
+}
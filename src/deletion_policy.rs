@@ -0,0 +1,43 @@
+//! What happens on the Cloudflare side when an object goes away
+use kube::ResourceExt;
+use tracing::*;
+
+/// Set to `abandon` to leave the Cloudflare resource in place when the object is deleted, or `delete` to
+/// remove it even where the default is to leave it alone
+pub static DELETION_POLICY_ANNOTATION: &str = "cloudflare.com/deletion-policy";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeletionPolicy {
+    Delete,
+    Abandon,
+}
+
+impl DeletionPolicy {
+    /// The policy `obj` asks for through its annotation, `default` when it has none
+    ///
+    /// A value that is neither `delete` nor `abandon` abandons, a typo must not delete a production zone.
+    pub fn of<K: ResourceExt>(obj: &K, default: DeletionPolicy) -> Self {
+        match obj
+            .annotations()
+            .get(DELETION_POLICY_ANNOTATION)
+            .map(String::as_str)
+        {
+            None => default,
+            Some("delete") => DeletionPolicy::Delete,
+            Some("abandon") => DeletionPolicy::Abandon,
+            Some(other) => {
+                warn!(
+                    "Unknown deletion policy \"{}\" on {}, leaving the Cloudflare resource in place",
+                    other,
+                    obj.name_any()
+                );
+                DeletionPolicy::Abandon
+            }
+        }
+    }
+
+    /// Shorthand for objects whose Cloudflare resource is deleted unless asked otherwise
+    pub fn abandons<K: ResourceExt>(obj: &K) -> bool {
+        Self::of(obj, DeletionPolicy::Delete) == DeletionPolicy::Abandon
+    }
+}
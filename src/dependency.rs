@@ -0,0 +1,98 @@
+use crate::{Error, Result, account::Account, zone::Zone};
+use k8s_openapi::NamespaceResourceScope;
+use kube::{
+    Client, Error as KubeError, Resource, ResourceExt,
+    api::Api,
+    runtime::reflector::{ObjectRef, Store},
+};
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+
+/// An object other objects can wait for
+pub trait Dependency:
+    Resource<DynamicType = (), Scope = NamespaceResourceScope> + Clone + DeserializeOwned + Debug
+{
+    /// Whether dependents may go ahead
+    fn is_ready(&self) -> bool;
+}
+
+impl Dependency for Account {
+    fn is_ready(&self) -> bool {
+        self.status.as_ref().is_some_and(|s| s.ready)
+    }
+}
+
+impl Dependency for Zone {
+    fn is_ready(&self) -> bool {
+        self.status.as_ref().is_some_and(|s| s.ready && s.id.is_some())
+    }
+}
+
+/// The dependency that keeps an object from being reconciled
+#[derive(Clone, Debug)]
+pub struct Blocked {
+    pub kind: String,
+    pub name: String,
+    pub reason: &'static str,
+}
+
+impl Blocked {
+    pub fn message(&self) -> String {
+        format!(
+            "Dependency {}/{} {}",
+            self.kind.to_lowercase(),
+            self.name,
+            self.reason
+        )
+    }
+}
+
+/// Fetch the referenced object if it's ready, the inner `Err` says what's blocking otherwise
+///
+/// Pair it with [`wake_dependents`] on the controller, so that the blocked object is reconciled again
+/// as soon as the dependency becomes ready.
+pub async fn wait_for_dependency<D: Dependency>(
+    client: Client,
+    namespace: &str,
+    name: &str,
+) -> Result<Result<D, Blocked>> {
+    let blocked = |reason| Blocked {
+        kind: D::kind(&()).to_string(),
+        name: name.to_string(),
+        reason,
+    };
+    let api: Api<D> = Api::namespaced(client, namespace);
+    match api.get(name).await {
+        Ok(dependency) if dependency.is_ready() => Ok(Ok(dependency)),
+        Ok(_) => Ok(Err(blocked("is not ready"))),
+        Err(KubeError::Api(e)) if e.code == 404 => Ok(Err(blocked("not found"))),
+        Err(e) => Err(Error::KubeError(e)),
+    }
+}
+
+/// Mapper for `Controller::watches`, wakes up the objects referring to a dependency once it's ready
+///
+/// `reference` returns the name of the dependency an object refers to, references are namespace local.
+pub fn wake_dependents<K, D>(
+    dependents: Store<K>,
+    reference: fn(&K) -> Option<&str>,
+) -> impl Fn(D) -> Vec<ObjectRef<K>> + Send + Sync + 'static
+where
+    K: Resource<DynamicType = ()> + Clone + Send + Sync + 'static,
+    D: Dependency,
+{
+    move |dependency| {
+        if !dependency.is_ready() {
+            return vec![];
+        }
+        let name = dependency.name_any();
+        dependents
+            .state()
+            .into_iter()
+            .filter(|dependent| {
+                dependent.namespace() == dependency.namespace() && reference(dependent) == Some(name.as_str())
+            })
+            .map(|dependent| ObjectRef::from_obj(dependent.as_ref()))
+            .collect()
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{State, account::Account};
+use kube::{
+    Client,
+    api::{Api, Patch, PatchParams},
+};
+use serde_json::json;
+use tokio::time::Duration;
+use tracing::*;
+
+/// Label set on Account objects created by discovery
+pub static DISCOVERED_LABEL: &str = "cloudflare.com/discovered";
+/// Annotation carrying the Cloudflare name of a discovered account
+pub static ACCOUNT_NAME_ANNOTATION: &str = "cloudflare.com/account-name";
+
+/// How often accounts are listed when `CLOUDFLARE_DISCOVERY_INTERVAL` isn't set
+const DEFAULT_INTERVAL: u64 = 10 * 60;
+
+/// Keep an Account object for every account the operator token can see
+///
+/// Only runs when `CLOUDFLARE_DISCOVERY_NAMESPACE` names the namespace to create them in. Objects are
+/// named after the account id and only `spec.id` is owned by discovery, so they can be extended freely.
+pub async fn run(state: State) {
+    let Ok(namespace) = std::env::var("CLOUDFLARE_DISCOVERY_NAMESPACE") else {
+        // disabled, but we are raced against the controllers so never finish
+        return futures::future::pending().await;
+    };
+    let interval = std::env::var("CLOUDFLARE_DISCOVERY_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_INTERVAL);
+
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let ctx = state.to_context(client).await;
+    let accounts: Api<Account> = Api::namespaced(ctx.client.clone(), &namespace);
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    loop {
+        ticker.tick().await;
+        let cf_client = match ctx.provider.default_client().await {
+            Ok(cf_client) => cf_client,
+            Err(e) => {
+                warn!("account discovery has no client: {e}");
+                continue;
+            }
+        };
+        match cf_client.list_account().await {
+            Ok(found) => {
+                for account in found {
+                    if let Err(e) = apply(&accounts, &account.id, &account.name).await {
+                        warn!("failed to apply discovered account {}: {e}", account.id);
+                    }
+                }
+            }
+            Err(e) => warn!("account discovery failed: {e:?}"),
+        }
+    }
+}
+
+async fn apply(accounts: &Api<Account>, id: &str, name: &str) -> kube::Result<()> {
+    let patch = json!({
+        "apiVersion": "cloudflare.com/v1alpha1",
+        "kind": "Account",
+        "metadata": {
+            "name": id,
+            "labels": { DISCOVERED_LABEL: "true" },
+            "annotations": { ACCOUNT_NAME_ANNOTATION: name },
+        },
+        "spec": { "id": id },
+    });
+    accounts
+        .patch(id, &PatchParams::apply("discovery"), &Patch::Apply(patch))
+        .await?;
+    debug!("discovered account {} ({})", name, id);
+    Ok(())
+}
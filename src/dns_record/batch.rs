@@ -0,0 +1,130 @@
+use crate::cf_client::{BatchDnsRecordsParams, BatchRecord, CloudflareApi};
+use anyhow::anyhow;
+use std::{collections::HashMap, sync::Arc};
+use tokio::{
+    sync::{Mutex, oneshot},
+    time::Duration,
+};
+use tracing::*;
+
+type Reply = oneshot::Sender<Result<String, String>>;
+
+/// A zone as seen through one client, clients are cached per token so records resolved with different
+/// tokens never share a batch
+type BatchKey = (usize, String);
+
+#[derive(Default)]
+struct Pending {
+    posts: Vec<(BatchRecord, Reply)>,
+    puts: Vec<(BatchRecord, Reply)>,
+}
+
+/// Coalesces record changes for the same zone into a single batch call
+///
+/// Enabled by `CLOUDFLARE_DNS_BATCH_WINDOW` (in seconds), the first change for a zone opens a window
+/// and everything that arrives for that zone with the same client until it closes goes out together,
+/// sent with that client.
+#[derive(Clone, Default)]
+pub struct Batcher {
+    window: Option<Duration>,
+    pending: Arc<Mutex<HashMap<BatchKey, Pending>>>,
+}
+
+impl Batcher {
+    pub fn from_env() -> Self {
+        Self {
+            window: std::env::var("CLOUDFLARE_DNS_BATCH_WINDOW")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            pending: Arc::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.window.is_some()
+    }
+
+    /// Queue a record change and wait for the batch it ends up in, returns the id of the record
+    ///
+    /// Records with an `id` overwrite the existing record, the others are created.
+    pub async fn submit(
+        &self,
+        cf_client: Arc<dyn CloudflareApi>,
+        zone_id: &str,
+        record: BatchRecord,
+    ) -> anyhow::Result<String> {
+        let window = self
+            .window
+            .ok_or_else(|| anyhow!("DNS record batching is disabled"))?;
+        let (tx, rx) = oneshot::channel();
+        let key: BatchKey = (Arc::as_ptr(&cf_client) as *const () as usize, zone_id.to_string());
+        let opens_window = {
+            let mut pending = self.pending.lock().await;
+            let opens_window = !pending.contains_key(&key);
+            let batch = pending.entry(key.clone()).or_default();
+            match record.id {
+                Some(_) => batch.puts.push((record, tx)),
+                None => batch.posts.push((record, tx)),
+            }
+            opens_window
+        };
+
+        if opens_window {
+            let pending = self.pending.clone();
+            // the task holds on to the client, so its address isn't reused while the window is open
+            tokio::spawn(async move {
+                tokio::time::sleep(window).await;
+                let batch = pending.lock().await.remove(&key);
+                if let Some(batch) = batch {
+                    flush(cf_client.as_ref(), &key.1, batch).await;
+                }
+            });
+        }
+
+        rx.await
+            .map_err(|_| anyhow!("DNS record batch was dropped"))?
+            .map_err(|e| anyhow!(e))
+    }
+}
+
+async fn flush(cf_client: &dyn CloudflareApi, zone_id: &str, batch: Pending) {
+    let (posts, post_replies): (Vec<_>, Vec<_>) = batch.posts.into_iter().unzip();
+    let (puts, put_replies): (Vec<_>, Vec<_>) = batch.puts.into_iter().unzip();
+    info!(
+        "Sending batch of {} new and {} updated records for zone {}",
+        posts.len(),
+        puts.len(),
+        zone_id
+    );
+
+    let params = BatchDnsRecordsParams {
+        posts,
+        puts,
+        ..Default::default()
+    };
+    match cf_client.batch_dns_records(zone_id, params).await {
+        Ok(result) => {
+            reply_all(post_replies, result.posts.into_iter().map(|r| r.id));
+            reply_all(put_replies, result.puts.into_iter().map(|r| r.id));
+        }
+        // the batch is applied as a whole, so every change in it failed
+        Err(e) => {
+            warn!("DNS record batch for zone {} failed: {:?}", zone_id, e);
+            for reply in post_replies.into_iter().chain(put_replies) {
+                let _ = reply.send(Err(e.to_string()));
+            }
+        }
+    }
+}
+
+fn reply_all(replies: Vec<Reply>, ids: impl Iterator<Item = String>) {
+    let mut ids = ids.fuse();
+    for reply in replies {
+        let result = ids
+            .next()
+            .ok_or_else(|| "Cloudflare returned fewer records than were sent".to_string());
+        let _ = reply.send(result);
+    }
+}
@@ -3,7 +3,11 @@ use kube::CustomResource;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::cloudflare::CloudflareResource;
+use crate::{
+    cloudflare::CloudflareResource,
+    conditions::{Condition, Conditions},
+    reconcile_policy::ReconcilePolicy,
+};
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(test, derive(Default))]
@@ -13,7 +17,14 @@ use crate::cloudflare::CloudflareResource;
     version = "v1alpha1",
     namespaced
 )]
-#[kube(status = "DNSRecordStatus", shortname = "dns")]
+#[kube(
+    status = "DNSRecordStatus",
+    shortname = "dns",
+    shortname = "cfdns",
+    category = "cloudflare"
+)]
+#[kube(printcolumn = r#"{"name":"Ready", "type":"boolean", "jsonPath":".status.ready"}"#)]
+#[kube(printcolumn = r#"{"name":"Record", "type":"string", "jsonPath":".status.summary"}"#)]
 pub struct DNSRecordSpec {
     pub zone_ref: LocalObjectReference,
     pub name: String,
@@ -22,12 +33,62 @@ pub struct DNSRecordSpec {
     pub ttl: Option<u32>,
     pub priority: Option<u16>,
     pub proxied: Option<bool>,
+    /// `DriftReportOnly` to only compare the record with Cloudflare, `Enforce` by default
+    #[serde(rename = "reconcilePolicy")]
+    pub reconcile_policy: Option<ReconcilePolicy>,
+    /// `true` to also add the hostname as a Cloudflare for SaaS custom hostname, so Cloudflare issues
+    /// and serves a certificate for it
+    #[serde(rename = "customHostname", skip_serializing_if = "Option::is_none")]
+    pub custom_hostname: Option<bool>,
+}
+
+impl DNSRecord {
+    /// Fully qualified hostname of the record, the spec allows names relative to the zone
+    pub fn hostname(&self) -> String {
+        let name = self.spec.name.trim_end_matches('.');
+        let zone = self.spec.zone_ref.name.trim_end_matches('.');
+        if name == "@" {
+            zone.to_string()
+        } else if name == zone || name.ends_with(&format!(".{zone}")) {
+            name.to_string()
+        } else {
+            format!("{name}.{zone}")
+        }
+    }
+}
+
+impl DNSRecord {
+    /// One line description of the record, like `A app.example.com → 1.2.3.4 (proxied)`
+    pub fn summary(&self) -> String {
+        let content = match self.spec.priority {
+            Some(priority) => format!("{priority} {}", self.spec.content),
+            None => self.spec.content.clone(),
+        };
+        let proxied = if self.spec.proxied == Some(true) {
+            " (proxied)"
+        } else {
+            ""
+        };
+        format!(
+            "{} {} → {}{}",
+            self.spec.record_type,
+            self.hostname(),
+            content,
+            proxied
+        )
+    }
 }
 
 impl CloudflareResource for DNSRecord {
+    const PERMISSIONS: &'static [&'static str] = &["DNS:Edit"];
+
     fn zone_ref(&self) -> Option<&LocalObjectReference> {
         Some(&self.spec.zone_ref)
     }
+
+    fn reconcile_policy(&self) -> ReconcilePolicy {
+        self.spec.reconcile_policy.unwrap_or_default()
+    }
 }
 
 
@@ -35,4 +96,36 @@ impl CloudflareResource for DNSRecord {
 pub struct DNSRecordStatus {
     pub ready: bool,
     pub record_id: Option<String>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Zone, hostname and type the tracked record was created with, a change means a new record
+    pub zone_id: Option<String>,
+    pub hostname: Option<String>,
+    pub record_type: Option<String>,
+    /// Id of the custom hostname kept along with the record
+    pub custom_hostname_id: Option<String>,
+    /// What the record points at, for `kubectl get`
+    pub summary: Option<String>,
+    /// Generation of the spec the status was written for
+    #[serde(rename = "observedGeneration")]
+    pub observed_generation: Option<i64>,
+    /// Reconciles failed in a row, gone once one succeeds
+    #[serde(rename = "consecutiveFailures")]
+    pub consecutive_failures: Option<u32>,
+}
+
+impl Conditions for DNSRecordStatus {
+    fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition> {
+        &mut self.conditions
+    }
+
+    fn set_failed(&mut self, message: &str) {
+        self.ready = false;
+        self.error = Some(message.into());
+    }
 }
@@ -1,5 +1,8 @@
+mod batch;
 mod crd;
 mod reconcile;
+pub mod v1beta1;
 
+pub use batch::Batcher;
 pub use crd::{DNSRecord, DNSRecordSpec, DNSRecordStatus};
 pub use reconcile::{DOCUMENT_FINALIZER, run};
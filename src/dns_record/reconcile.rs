@@ -1,25 +1,39 @@
 use crate::{
     Context, Error, Result, State,
-    cf_client::{CreateDnsRecordParams, DnsContent},
+    cf_client::{
+        BatchRecord, CloudflareApi, CreateDnsRecordParams, DnsContent, DnsRecord as CfDnsRecord,
+        RECORD_EXISTS, UpdateDnsRecordParams,
+    },
+    cloudflare::{self, CloudflareResource, ProviderError},
+    conditions::{Condition, Conditions},
+    deletion_policy::DeletionPolicy,
+    dependency::{wait_for_dependency, wake_dependents},
     dns_record::{DNSRecord, DNSRecordStatus},
-    telemetry,
+    failures,
+    namespaces::scoped,
+    pause, policy,
+    reconcile_policy::Drift,
+    settings::ControllerSettings,
+    snapshot::CLOUDFLARE_ID_ANNOTATION,
+    status, telemetry,
     zone::Zone,
+    zone_binding,
 };
 use chrono::Utc;
 use futures::StreamExt;
 use kube::{
-    Error as KubeError, Resource,
-    api::{Api, ListParams, Patch, PatchParams, ResourceExt},
+    Resource,
+    api::{Api, ListParams, ResourceExt},
     client::Client,
     runtime::{
-        controller::{Action, Controller},
+        controller::Action,
         events::{Event, EventType},
         finalizer::{Event as Finalizer, finalizer},
         watcher::Config,
     },
 };
-use serde_json::json;
 use std::{
+    hash::{DefaultHasher, Hash, Hasher},
     net::{Ipv4Addr, Ipv6Addr},
     sync::Arc,
 };
@@ -33,26 +47,34 @@ async fn reconcile(doc: Arc<DNSRecord>, ctx: Arc<Context>) -> Result<Action> {
     if trace_id != opentelemetry::trace::TraceId::INVALID {
         Span::current().record("trace_id", field::display(&trace_id));
     }
-    let _timer = ctx.metrics.reconcile.count_and_measure(&trace_id);
+    let _timer = ctx.metrics.reconcile.count_and_measure(doc.as_ref(), &trace_id);
     ctx.diagnostics.write().await.last_event = Utc::now();
+    ctx.metrics.resources.track(doc.as_ref());
     let ns = doc.namespace().unwrap(); // doc is namespace scoped
     let docs: Api<DNSRecord> = Api::namespaced(ctx.client.clone(), &ns);
 
     info!("Reconciling DNSRecord \"{}\" in {}", doc.name_any(), ns);
-    finalizer(&docs, DOCUMENT_FINALIZER, doc, |event| async {
+    if pause::is_paused(doc.as_ref()) {
+        return pause::hold(doc.as_ref(), ctx.client.clone()).await;
+    }
+    let finalize = finalizer(&docs, DOCUMENT_FINALIZER, doc.clone(), |event| async {
         match event {
             Finalizer::Apply(doc) => doc.reconcile(ctx.clone()).await,
             Finalizer::Cleanup(doc) => doc.cleanup(ctx.clone()).await,
         }
-    })
-    .await
-    .map_err(|e| Error::FinalizerError(Box::new(e)))
+    });
+    let result = ctx
+        .audit
+        .record(doc.as_ref(), &ctx.recorder, finalize)
+        .await
+        .map_err(|e| Error::FinalizerError(Box::new(e)));
+    cloudflare::preflight::forbidden(doc.as_ref(), &ctx, result).await
 }
 
 fn error_policy(doc: Arc<DNSRecord>, error: &Error, ctx: Arc<Context>) -> Action {
     warn!("reconcile failed: {:?}", error);
     ctx.metrics.reconcile.set_failure(doc.as_ref(), error);
-    Action::requeue(Duration::from_secs(5 * 60))
+    failures::requeue(doc, error, &ctx)
 }
 
 impl DNSRecord {
@@ -63,8 +85,6 @@ impl DNSRecord {
         let ns = self.namespace().unwrap(); // we unwrap this, because it's probably impossible to
         // have no ns on the namespaced object
         let name = self.name_any();
-        let docs: Api<DNSRecord> = Api::namespaced(client.clone(), &ns);
-        let cf_client = ctx.provider.get_client(self, &ns).await.unwrap();
 
         if name == "illegal" {
             return Err(Error::IllegalDocument); // error names show up in metrics
@@ -90,71 +110,385 @@ impl DNSRecord {
             _ => return Err(Error::UnsupportedRecordType(self.spec.record_type.clone())),
         };
 
-        let dns_record_params = CreateDnsRecordParams {
-            ttl: self.spec.ttl,
-            priority: self.spec.priority,
-            proxied: self.spec.proxied,
-            name: self.spec.name.as_str(),
-            content,
+        let hostname = self.hostname();
+        let zone_name = &self.spec.zone_ref.name;
+        let denied =
+            match policy::check(client.clone(), &ns, zone_name, &self.spec.record_type, &hostname).await? {
+                Some(reason) => Some(reason),
+                None => zone_binding::check(client.clone(), &ns, Some(zone_name), &hostname).await?,
+            };
+        if let Some(reason) = denied {
+            // nothing the operator can do about it until the policy or the spec changes
+            self.set_not_ready(client.clone(), "Denied", reason, true).await?;
+            return Ok(Action::requeue(ctx.settings.requeue));
+        }
+
+        let zone_id = match wait_for_dependency::<Zone>(client.clone(), &ns, zone_name).await? {
+            // a ready zone always has an id
+            Ok(zone) => zone.status.and_then(|s| s.id).unwrap_or_default(),
+            Err(blocked) => {
+                self.publish(&ctx, "DependencyNotReady", blocked.message())
+                    .await?;
+                self.set_not_ready(client.clone(), "DependencyNotReady", blocked.message(), false)
+                    .await?;
+                // the Zone watch wakes us up once it's ready, this is just a fallback
+                return Ok(Action::requeue(ctx.settings.requeue));
+            }
         };
 
-        let zone_api: Api<Zone> = Api::namespaced(client.clone(), &ns);
-        match zone_api.get(&self.spec.zone_ref.name).await {
-            Ok(zone) => {
-                if let Some(z_status) = zone.status {
-                    if let Some(zone_id) = &z_status.id {
-                        let res = cf_client.create_dns_record(&zone_id, dns_record_params).await?;
-                        // always overwrite status object with what we saw
-                        let new_status = Patch::Apply(json!({
-                            "apiVersion": "cloudflare.com/v1alpha1",
-                            "kind": "DNSRecord",
-                            "status": DNSRecordStatus {
-                                ready: true,
-                                record_id: Some(res),
-                            }
-                        }));
-                        let ps = PatchParams::apply("cntrlr").force();
-                        let _o = docs
-                            .patch_status(&name, &ps, &new_status)
-                            .await
-                            .map_err(Error::KubeError)?;
-                    }
+        // the token is resolved through the zone, so only ask for a client once the zone is usable
+        let (cf_client, source) = match ctx.provider.get_api_with_source(self, &ns).await {
+            Ok(resolved) => resolved,
+            Err(e) => return cloudflare::credentials_unavailable(self, &ctx, e).await,
+        };
+        let mut tracked_id = self
+            .status
+            .as_ref()
+            .and_then(|s| s.record_id.clone())
+            // restored from a snapshot
+            .or_else(|| self.annotations().get(CLOUDFLARE_ID_ANNOTATION).cloned());
+        let report_only = self.report_only();
+        let retargeted_from = match self.previous_target(&zone_id, &hostname) {
+            Some((old_zone, old_id)) => {
+                if !report_only {
+                    self.release(&ctx, cf_client.as_ref(), &old_zone, &old_id).await?;
                 }
+                tracked_id = None;
+                Some(old_id)
             }
-            Err(KubeError::Api(e)) if e.code == 404 => {
-                eprintln!(
-                    "Zone '{}' not found in '{}' namespace",
-                    &self.spec.zone_ref.name, &ns
-                );
-                return Ok(Action::requeue(Duration::from_secs(30)));
+            None => None,
+        };
+        let existing = match &tracked_id {
+            Some(record_id) => cf_client.get_dns_record(&zone_id, record_id).await?,
+            // nothing gets created, so compare with whatever record already has the name
+            None if report_only => {
+                cf_client
+                    .find_dns_record(&zone_id, &hostname, &self.spec.record_type)
+                    .await?
             }
-            Err(e) => {
-                return Err(Error::KubeError(e));
+            None => None,
+        };
+
+        let mut drift = Drift::default();
+        let res = match existing {
+            Some(record) if self.in_sync(&record, &content) => Some(record.id),
+            Some(record) if report_only => {
+                let note = format!("Record `{}` differs from the spec", record.id);
+                drift.report(self, &ctx, note).await;
+                Some(record.id)
             }
-        }
+            None if report_only => {
+                let note = format!("No {} record for {hostname}", self.spec.record_type);
+                drift.report(self, &ctx, note).await;
+                None
+            }
+            Some(record) => {
+                let updated = if ctx.dns_batch.enabled() {
+                    let batched = self.batch_record(Some(record.id.clone()), content);
+                    ctx.dns_batch.submit(cf_client.clone(), &zone_id, batched).await?
+                } else {
+                    let params = UpdateDnsRecordParams {
+                        ttl: self.spec.ttl,
+                        proxied: self.spec.proxied,
+                        name: self.spec.name.as_str(),
+                        content,
+                    };
+                    cf_client
+                        .update_dns_record(&zone_id, &record.id, params)
+                        .await?
+                        .id
+                };
+                let note = format!("Updated record `{updated}` to match the spec");
+                if status::spec_changed(self) {
+                    self.publish(&ctx, "Updated", note).await?;
+                } else {
+                    self.drift_corrected(&ctx, note).await?;
+                }
+                Some(updated)
+            }
+            None => {
+                let created = if ctx.dns_batch.enabled() {
+                    let batched = self.batch_record(None, content);
+                    ctx.dns_batch.submit(cf_client.clone(), &zone_id, batched).await?
+                } else {
+                    let params = CreateDnsRecordParams {
+                        ttl: self.spec.ttl,
+                        priority: self.spec.priority,
+                        proxied: self.spec.proxied,
+                        name: self.spec.name.as_str(),
+                        content,
+                    };
+                    match cf_client.create_dns_record(&zone_id, params).await {
+                        Ok(created) => created,
+                        // the same record is already there, created before the status got written or by hand
+                        Err(e) if e.has_code(RECORD_EXISTS) => cf_client
+                            .find_dns_record(&zone_id, &hostname, &self.spec.record_type)
+                            .await?
+                            .map(|record| record.id)
+                            .ok_or(e)?,
+                        Err(e) => return Err(e.into()),
+                    }
+                };
+                if let Some(old_id) = &retargeted_from {
+                    self.publish(
+                        &ctx,
+                        "Retargeted",
+                        format!("Replaced record `{old_id}` with `{created}` after the spec moved it"),
+                    )
+                    .await?;
+                } else if let Some(old_id) = &tracked_id {
+                    self.drift_corrected(
+                        &ctx,
+                        format!(
+                            "Record `{old_id}` was deleted outside of the operator, recreated as `{created}`"
+                        ),
+                    )
+                    .await?;
+                } else {
+                    self.publish(&ctx, "Created", format!("Created record `{created}`"))
+                        .await?;
+                }
+                Some(created)
+            }
+        };
+
+        let custom_hostname_id = self
+            .custom_hostname(&ctx, cf_client.as_ref(), &zone_id, &hostname, report_only)
+            .await?;
 
+        // always overwrite status object with what we saw
+        let mut status = DNSRecordStatus {
+            observed_generation: None, // set by status::patch
+            consecutive_failures: None,
+            ready: drift.is_empty(),
+            record_id: res,
+            custom_hostname_id,
+            error: None,
+            conditions: self.conditions().to_vec(),
+            zone_id: Some(zone_id),
+            hostname: Some(hostname),
+            record_type: Some(self.spec.record_type.clone()),
+            summary: Some(self.summary()),
+        };
+        status.set_ready("Synced", self.meta().generation);
+        status.set_condition(source.condition(self.conditions(), self.meta().generation));
+        status.set_condition(source.validity(self.conditions(), self.meta().generation));
+        drift.apply(&mut status, self.meta().generation);
+        status::patch(self, client, &status).await?;
 
         // If no events were received, check back every 5 minutes
-        Ok(Action::requeue(Duration::from_secs(5 * 60)))
+        Ok(Action::requeue(self.resync_interval(ctx.settings.requeue)))
     }
 
-    // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
-    async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
-        let oref = self.object_ref(&());
-        // Document doesn't have any real cleanup, so we just publish an event
+    /// Id of the custom hostname the spec asks for, creating it when missing
+    ///
+    /// The one tracked so far is deleted once the spec no longer wants it or moved the record.
+    async fn custom_hostname(
+        &self,
+        ctx: &Context,
+        cf_client: &dyn CloudflareApi,
+        zone_id: &str,
+        hostname: &str,
+        report_only: bool,
+    ) -> Result<Option<String>> {
+        let status = self.status.clone().unwrap_or_default();
+        if report_only {
+            return Ok(status.custom_hostname_id);
+        }
+        let wanted = self.spec.custom_hostname == Some(true);
+        if let (Some(old_zone), Some(old_id)) = (&status.zone_id, &status.custom_hostname_id) {
+            let moved = old_zone != zone_id
+                || status
+                    .hostname
+                    .as_deref()
+                    .is_some_and(|old| !old.eq_ignore_ascii_case(hostname));
+            if moved || !wanted {
+                cf_client.delete_custom_hostname(old_zone, old_id).await?;
+                self.publish(ctx, "Deleted", format!("Deleted custom hostname `{old_id}`"))
+                    .await?;
+            }
+        }
+        if !wanted {
+            return Ok(None);
+        }
+        if let Some(existing) = cf_client.find_custom_hostname(zone_id, hostname).await? {
+            return Ok(Some(existing.id));
+        }
+        let created = cf_client.create_custom_hostname(zone_id, hostname).await?;
+        self.publish(
+            ctx,
+            "Created",
+            format!("Created custom hostname `{}` for {hostname}", created.id),
+        )
+        .await?;
+        Ok(Some(created.id))
+    }
+
+    /// `interval` plus up to a minute of jitter, stable per object
+    ///
+    /// Records created together (a mass rollout) would otherwise all resync in the same instant.
+    fn resync_interval(&self, interval: Duration) -> Duration {
+        let mut hasher = DefaultHasher::new();
+        self.uid().unwrap_or_else(|| self.name_any()).hash(&mut hasher);
+        interval + Duration::from_secs(hasher.finish() % 60)
+    }
+
+    fn batch_record(&self, id: Option<String>, content: DnsContent) -> BatchRecord {
+        BatchRecord {
+            id,
+            name: self.spec.name.clone(),
+            ttl: self.spec.ttl,
+            proxied: self.spec.proxied,
+            content,
+        }
+    }
+
+    /// Record why the record can't be applied right now, keeping the id we already track
+    ///
+    /// A `stalled` record waits for a change, any other one is expected to get there on a later sync.
+    async fn set_not_ready(
+        &self,
+        client: Client,
+        reason: &str,
+        message: String,
+        stalled: bool,
+    ) -> Result<()> {
+        warn!("DNSRecord \"{}\": {}", self.name_any(), message);
+        let mut status = DNSRecordStatus {
+            ready: false,
+            error: Some(message.clone()),
+            // keep tracking the record we already have
+            ..self.status.clone().unwrap_or_default()
+        };
+        if stalled {
+            status.set_stalled(reason, message, self.meta().generation);
+        } else {
+            status.set_reconciling(reason, message, self.meta().generation);
+        }
+        status::patch(self, client, &status).await?;
+        Ok(())
+    }
+
+    /// Whether the record on the Cloudflare side matches what the spec asks for
+    fn in_sync(&self, record: &CfDnsRecord, content: &DnsContent) -> bool {
+        // Cloudflare always reports the fully qualified name, the spec may use the short one
+        record.name.eq_ignore_ascii_case(&self.hostname())
+            && serde_json::to_value(&record.content).ok() == serde_json::to_value(content).ok()
+            && self.spec.ttl.is_none_or(|ttl| ttl == record.ttl)
+            && self.spec.proxied.is_none_or(|proxied| proxied == record.proxied)
+    }
+
+    /// Count and announce a repaired divergence between the spec and Cloudflare
+    async fn drift_corrected(&self, ctx: &Context, note: String) -> Result<()> {
+        warn!("DNSRecord \"{}\" drifted: {}", self.name_any(), note);
+        ctx.metrics.reconcile.set_drift(self);
+        self.publish(ctx, "DriftCorrected", note).await
+    }
+
+    async fn publish(&self, ctx: &Context, reason: &str, note: String) -> Result<()> {
         ctx.recorder
             .publish(
                 &Event {
                     type_: EventType::Normal,
-                    reason: "DeleteRequested".into(),
-                    note: Some(format!("Delete `{}`", self.name_any())),
-                    action: "Deleting".into(),
+                    reason: reason.into(),
+                    note: Some(note),
+                    action: "Reconciling".into(),
                     secondary: None,
                 },
-                &oref,
+                &self.object_ref(&()),
             )
             .await
-            .map_err(Error::KubeError)?;
+            .map_err(Error::KubeError)
+    }
+
+    /// Zone and id of the tracked record when the spec no longer points at it
+    ///
+    /// Moving a record to another zone, hostname or type makes it a different record. Statuses written
+    /// before the target was tracked are assumed to still match.
+    fn previous_target(&self, zone_id: &str, hostname: &str) -> Option<(String, String)> {
+        let status = self.status.as_ref()?;
+        let record_id = status.record_id.clone()?;
+        let old_zone = status.zone_id.clone()?;
+        let moved = old_zone != zone_id
+            || status
+                .hostname
+                .as_deref()
+                .is_some_and(|old| !old.eq_ignore_ascii_case(hostname))
+            || status
+                .record_type
+                .as_deref()
+                .is_some_and(|old| old != self.spec.record_type);
+        moved.then_some((old_zone, record_id))
+    }
+
+    /// Let go of a record the spec moved away from, deleting it unless the deletion policy says abandon
+    async fn release(
+        &self,
+        ctx: &Context,
+        cf_client: &dyn CloudflareApi,
+        zone_id: &str,
+        record_id: &str,
+    ) -> Result<()> {
+        if DeletionPolicy::abandons(self) {
+            let note =
+                format!("Left record `{record_id}` in zone `{zone_id}` as requested by the deletion policy");
+            return self.publish(ctx, "Abandoned", note).await;
+        }
+        cf_client.delete_dns_record(zone_id, record_id).await?;
+        self.publish(
+            ctx,
+            "Deleted",
+            format!("Deleted record `{record_id}` from zone `{zone_id}`, the spec no longer points at it"),
+        )
+        .await
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|s| s.conditions.as_slice())
+            .unwrap_or_default()
+    }
+
+    // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
+    async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
+        let status = self.status.clone().unwrap_or_default();
+        let (Some(zone_id), Some(record_id)) = (status.zone_id, status.record_id) else {
+            // nothing was ever created on the Cloudflare side
+            self.publish(&ctx, "DeleteRequested", format!("Delete `{}`", self.name_any()))
+                .await?;
+            return Ok(Action::await_change());
+        };
+        if DeletionPolicy::abandons(self) || self.report_only() {
+            let note = format!(
+                "Left record `{record_id}` in zone `{zone_id}` as requested by the deletion or reconcile policy"
+            );
+            self.publish(&ctx, "Abandoned", note).await?;
+            return Ok(Action::await_change());
+        }
+
+        let ns = self.namespace().unwrap(); // doc is namespace scoped
+        let cf_client = match ctx.provider.get_api(self, &ns).await {
+            Ok(cf_client) => cf_client,
+            // the token comes from the Zone, without it there is nobody to delete the record as
+            Err(ProviderError::ZoneNotFound(zone)) => {
+                let note = format!("Left record `{record_id}` in zone `{zone_id}`, Zone {zone} is gone");
+                self.publish(&ctx, "Abandoned", note).await?;
+                return Ok(Action::await_change());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // an error keeps the finalizer in place, so the object stays around until the delete goes through
+        if let Some(custom_id) = &status.custom_hostname_id {
+            cf_client.delete_custom_hostname(&zone_id, custom_id).await?;
+        }
+        cf_client.delete_dns_record(&zone_id, &record_id).await?;
+        self.publish(
+            &ctx,
+            "Deleted",
+            format!("Deleted record `{record_id}` from zone `{zone_id}`"),
+        )
+        .await?;
         Ok(Action::await_change())
     }
 }
@@ -162,126 +496,121 @@ impl DNSRecord {
 /// Initialize the controller and shared state (given the crd is installed)
 pub async fn run(state: State) {
     let client = Client::try_default().await.expect("failed to create kube Client");
-    let docs = Api::<DNSRecord>::all(client.clone());
+    let scopes = state.namespaces().scopes();
+    let docs: Api<DNSRecord> = scoped(client.clone(), scopes[0].as_deref());
     if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
         error!("CRD is not queryable; {e:?}. Is the CRD installed?");
         info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
         std::process::exit(1);
     }
 
-    let api_key =
-        std::env::var("CLOUDFLARE_API_TOKEN").expect("CLOUDFLARE_API_TOKEN environment variable must be set");
-    Controller::new(docs, Config::default().any_semantic())
-        .shutdown_on_signal()
-        .run(reconcile, error_policy, state.to_context(client, api_key).await)
-        .filter_map(|x| async move { std::result::Result::ok(x) })
-        .for_each(|_| futures::future::ready(()))
+    let settings = ControllerSettings::from_env("DNSRecord");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
         .await;
+    // one controller per watched namespace, they share the context and with it the Cloudflare clients
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        let controller = state
+            .controller("DNSRecord", scoped::<DNSRecord>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config());
+        let records = controller.store();
+        state.health().watch("DNSRecord", records.clone());
+        controller
+            // records waiting for their Zone go as soon as it becomes ready
+            .watches(
+                scoped::<Zone>(client.clone(), ns.as_deref()),
+                Config::default(),
+                wake_dependents(records, |record: &DNSRecord| {
+                    Some(record.spec.zone_ref.name.as_str())
+                }),
+            )
+            .reconcile_on(state.triggers().dns_record.stream())
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("DNSRecord", result))
+    }))
+    .await;
 }
 
-//TODO: reanimate tests
-// Mock tests relying on fixtures.rs and its primitive apiserver mocks
-/*#[cfg(test)]
+#[cfg(test)]
 mod test {
-    use super::{Context, DNSRecord, error_policy, reconcile};
     use crate::{
-        fixtures::{Scenario, timeout_after_1s},
-        metrics::ErrorLabels,
+        Context,
+        cf_client::MockCloudflareApi,
+        dns_record::{DNSRecord, DNSRecordStatus},
+        fixtures::object,
+        zone::Zone,
     };
     use std::sync::Arc;
 
     #[tokio::test]
-    async fn documents_without_finalizer_gets_a_finalizer() {
-        let (testctx, fakeserver) = Context::test();
-        let doc = DNSRecord::test();
-        let mocksrv = fakeserver.run(Scenario::FinalizerCreation(doc.clone()));
-        reconcile(Arc::new(doc), testctx).await.expect("reconciler");
-        timeout_after_1s(mocksrv).await;
-    }
+    async fn new_records_are_created_and_tracked() {
+        let api = Arc::new(MockCloudflareApi::new().with_zone("zone-1", "example.com", "account-1"));
+        let (ctx, fakeserver) = Context::test(api.clone());
+        let doc = DNSRecord::test("example.com");
+        let zone = Zone::test("example.com", "zone-1");
+        let served = fakeserver.serve(vec![object(&zone)], doc.clone());
 
-    #[tokio::test]
-    async fn finalized_doc_causes_status_patch() {
-        let (testctx, fakeserver) = Context::test();
-        let doc = DNSRecord::test().finalized();
-        let mocksrv = fakeserver.run(Scenario::StatusPatch(doc.clone()));
-        reconcile(Arc::new(doc), testctx).await.expect("reconciler");
-        timeout_after_1s(mocksrv).await;
+        doc.reconcile(ctx).await.expect("reconciler");
+
+        let records = api.records("zone-1");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name, "test.example.com");
+        assert_eq!(served.event_reasons(), ["Created"]);
+        let status = served.last_status().expect("status written");
+        assert_eq!(status["ready"], true);
+        assert_eq!(status["record_id"], records[0].id.as_str());
+        assert_eq!(status["zone_id"], "zone-1");
     }
 
     #[tokio::test]
-    async fn finalized_doc_with_hide_causes_event_and_hide_patch() {
-        let (testctx, fakeserver) = Context::test();
-        let doc = DNSRecord::test().finalized();
-        let scenario = Scenario::EventPublishThenStatusPatch("HideRequested".into(), doc.clone());
-        let mocksrv = fakeserver.run(scenario);
-        reconcile(Arc::new(doc), testctx).await.expect("reconciler");
-        timeout_after_1s(mocksrv).await;
+    async fn records_deleted_outside_are_recreated() {
+        let api = Arc::new(MockCloudflareApi::new().with_zone("zone-1", "example.com", "account-1"));
+        let (ctx, fakeserver) = Context::test(api.clone());
+        let doc = DNSRecord::test("example.com").with_status(DNSRecordStatus {
+            record_id: Some("gone".into()),
+            zone_id: Some("zone-1".into()),
+            ..DNSRecordStatus::default()
+        });
+        let zone = Zone::test("example.com", "zone-1");
+        let served = fakeserver.serve(vec![object(&zone)], doc.clone());
+
+        doc.reconcile(ctx).await.expect("reconciler");
+
+        let records = api.records("zone-1");
+        assert_eq!(records.len(), 1);
+        assert_ne!(records[0].id, "gone");
+        assert_eq!(served.event_reasons(), ["DriftCorrected"]);
     }
 
     #[tokio::test]
-    async fn finalized_doc_with_delete_timestamp_causes_delete() {
-        let (testctx, fakeserver) = Context::test();
-        let doc = DNSRecord::test().finalized().needs_delete();
-        let mocksrv = fakeserver.run(Scenario::Cleanup("DeleteRequested".into(), doc.clone()));
-        reconcile(Arc::new(doc), testctx).await.expect("reconciler");
-        timeout_after_1s(mocksrv).await;
+    async fn records_wait_for_their_zone() {
+        let api = Arc::new(MockCloudflareApi::new());
+        let (ctx, fakeserver) = Context::test(api.clone());
+        let doc = DNSRecord::test("example.com");
+        let served = fakeserver.serve(vec![], doc.clone());
+
+        doc.reconcile(ctx).await.expect("reconciler");
+
+        assert!(api.calls().is_empty(), "no Cloudflare call without a zone");
+        assert_eq!(served.event_reasons(), ["DependencyNotReady"]);
+        assert_eq!(served.last_status().expect("status written")["ready"], false);
     }
 
     #[tokio::test]
-    async fn illegal_doc_reconcile_errors_which_bumps_failure_metric() {
-        let (testctx, fakeserver) = Context::test();
-        let doc = Arc::new(DNSRecord::illegal().finalized());
-        let mocksrv = fakeserver.run(Scenario::RadioSilence);
-        let res = reconcile(doc.clone(), testctx.clone()).await;
-        timeout_after_1s(mocksrv).await;
-        assert!(res.is_err(), "apply reconciler fails on illegal doc");
-        let err = res.unwrap_err();
-        assert!(err.to_string().contains("IllegalDocument"));
-        // calling error policy with the reconciler error should cause the correct metric to be set
-        error_policy(doc.clone(), &err, testctx.clone());
-        let err_labels = ErrorLabels {
-            instance: "illegal".into(),
-            error: "finalizererror(applyfailed(illegaldocument))".into(),
-        };
-        let metrics = &testctx.metrics.reconcile;
-        let failures = metrics.failures.get_or_create(&err_labels).get();
-        assert_eq!(failures, 1);
-    }
+    async fn cleanup_deletes_the_record() {
+        let api = Arc::new(MockCloudflareApi::new().with_zone("zone-1", "example.com", "account-1"));
+        let (ctx, fakeserver) = Context::test(api.clone());
+        let doc = DNSRecord::test("example.com");
+        let zone = Zone::test("example.com", "zone-1");
+        let served = fakeserver.serve(vec![object(&zone)], doc.clone());
+        doc.reconcile(ctx.clone()).await.expect("reconciler");
+        let status: DNSRecordStatus =
+            serde_json::from_value(served.last_status().expect("status written")).unwrap();
 
-    // Integration test without mocks
-    //    use kube::api::{Api, ListParams, Patch, PatchParams};
-    //    #[tokio::test]
-    //    #[ignore = "uses k8s current-context"]
-    //    async fn integration_reconcile_should_set_status_and_send_event() {
-    //        let client = kube::Client::try_default().await.unwrap();
-    //        let ctx = super::State::default().to_context(client.clone()).await;
-    //
-    //        // create a test doc
-    //        let doc = DNSRecord::test().finalized();
-    //        let docs: Api<DNSRecord> = Api::namespaced(client.clone(), "default");
-    //        let ssapply = PatchParams::apply("ctrltest");
-    //        let patch = Patch::Apply(doc.clone());
-    //        docs.patch("test", &ssapply, &patch).await.unwrap();
-    //
-    //        // reconcile it (as if it was just applied to the cluster like this)
-    //        reconcile(Arc::new(doc), ctx).await.unwrap();
-    //
-    //        // verify side-effects happened
-    //        let output = docs.get_status("test").await.unwrap();
-    //        assert!(output.status.is_some());
-    //        // verify hide event was found
-    //        let events: Api<k8s_openapi::api::core::v1::Event> = Api::all(client.clone());
-    //        let opts = ListParams::default().fields("involvedObject.kind=Document,involvedObject.name=test");
-    //        let event = events
-    //            .list(&opts)
-    //            .await
-    //            .unwrap()
-    //            .into_iter()
-    //            .filter(|e| e.reason.as_deref() == Some("HideRequested"))
-    //            .last()
-    //            .unwrap();
-    //        dbg!("got ev: {:?}", &event);
-    //        assert_eq!(event.action.as_deref(), Some("Hiding"));
-    //    }
+        doc.with_status(status).cleanup(ctx).await.expect("cleanup");
+
+        assert!(api.records("zone-1").is_empty());
+        assert_eq!(served.event_reasons(), ["Created", "Deleted"]);
+    }
 }
-*/
@@ -0,0 +1,106 @@
+//! DNSRecord v1beta1, camelCase fields and a typed record type
+//!
+//! v1alpha1 stays the stored version the controller works with, the conversion webhook translates
+//! between the two.
+use k8s_openapi::api::core::v1::LocalObjectReference;
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::crd::{self, DNSRecordStatus};
+use crate::reconcile_policy::ReconcilePolicy;
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(kind = "DNSRecord", group = "cloudflare.com", version = "v1beta1", namespaced)]
+#[kube(
+    status = "DNSRecordStatus",
+    shortname = "dns",
+    shortname = "cfdns",
+    category = "cloudflare"
+)]
+#[kube(printcolumn = r#"{"name":"Ready", "type":"boolean", "jsonPath":".status.ready"}"#)]
+#[kube(printcolumn = r#"{"name":"Record", "type":"string", "jsonPath":".status.summary"}"#)]
+#[serde(rename_all = "camelCase")]
+pub struct DNSRecordSpec {
+    pub zone_ref: LocalObjectReference,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: RecordType,
+    pub content: String,
+    pub ttl: Option<u32>,
+    /// Only used by MX records
+    pub priority: Option<u16>,
+    pub proxied: Option<bool>,
+    /// `DriftReportOnly` to only compare the record with Cloudflare, `Enforce` by default
+    pub reconcile_policy: Option<ReconcilePolicy>,
+    /// `true` to also add the hostname as a Cloudflare for SaaS custom hostname, so Cloudflare issues
+    /// and serves a certificate for it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_hostname: Option<bool>,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum RecordType {
+    A,
+    AAAA,
+    CNAME,
+    MX,
+    TXT,
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RecordType::A => "A",
+            RecordType::AAAA => "AAAA",
+            RecordType::CNAME => "CNAME",
+            RecordType::MX => "MX",
+            RecordType::TXT => "TXT",
+        };
+        f.write_str(name)
+    }
+}
+
+impl TryFrom<crd::DNSRecordSpec> for DNSRecordSpec {
+    type Error = String;
+
+    fn try_from(spec: crd::DNSRecordSpec) -> Result<Self, Self::Error> {
+        let record_type = match spec.record_type.as_str() {
+            "A" => RecordType::A,
+            "AAAA" => RecordType::AAAA,
+            "CNAME" => RecordType::CNAME,
+            "MX" => RecordType::MX,
+            "TXT" => RecordType::TXT,
+            other => return Err(format!("record type {other:?} has no v1beta1 equivalent")),
+        };
+        Ok(DNSRecordSpec {
+            zone_ref: spec.zone_ref,
+            name: spec.name,
+            record_type,
+            content: spec.content,
+            ttl: spec.ttl,
+            priority: spec.priority,
+            proxied: spec.proxied,
+            reconcile_policy: spec.reconcile_policy,
+            custom_hostname: spec.custom_hostname,
+        })
+    }
+}
+
+impl From<DNSRecordSpec> for crd::DNSRecordSpec {
+    fn from(spec: DNSRecordSpec) -> Self {
+        crd::DNSRecordSpec {
+            zone_ref: spec.zone_ref,
+            name: spec.name,
+            record_type: spec.record_type.to_string(),
+            content: spec.content,
+            ttl: spec.ttl,
+            priority: spec.priority,
+            proxied: spec.proxied,
+            reconcile_policy: spec.reconcile_policy,
+            custom_hostname: spec.custom_hostname,
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Events with the repeats collapsed into a count
+//!
+//! An object failing the same way would get the same Warning every backoff cycle. The first event goes
+//! out, identical ones following within `EVENT_DEDUP_SECONDS` (300 by default, 0 publishes everything)
+//! are only counted, and the first one after the window says how many were held back.
+use k8s_openapi::api::core::v1::ObjectReference;
+use kube::runtime::events::{Event, EventType, Recorder};
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// The object, type, reason and note of an event, repeats share it
+#[derive(PartialEq, Eq, Hash)]
+struct Key {
+    object: (Option<String>, Option<String>, Option<String>),
+    type_: EventType,
+    reason: String,
+    note: Option<String>,
+}
+
+struct Seen {
+    /// When the event last went out
+    published: Instant,
+    /// Repeats held back since
+    repeats: u32,
+}
+
+/// Publishes through a [`Recorder`], holding back repeats
+#[derive(Clone)]
+pub struct Events {
+    recorder: Recorder,
+    window: Duration,
+    seen: Arc<Mutex<HashMap<Key, Seen>>>,
+}
+
+impl Events {
+    pub fn new(recorder: Recorder, window: Duration) -> Self {
+        Self {
+            recorder,
+            window,
+            seen: Arc::default(),
+        }
+    }
+
+    pub fn from_env(recorder: Recorder) -> Self {
+        let window = std::env::var("EVENT_DEDUP_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(300);
+        Self::new(recorder, Duration::from_secs(window))
+    }
+
+    /// Publish `ev` on `reference` unless it already went out within the window
+    pub async fn publish(&self, ev: &Event, reference: &ObjectReference) -> Result<(), kube::Error> {
+        if self.window.is_zero() {
+            return self.recorder.publish(ev, reference).await;
+        }
+        let key = Key {
+            object: (
+                reference.kind.clone(),
+                reference.namespace.clone(),
+                reference.name.clone(),
+            ),
+            type_: ev.type_,
+            reason: ev.reason.clone(),
+            note: ev.note.clone(),
+        };
+        let now = Instant::now();
+        let repeats = {
+            let mut seen = self.seen.lock().unwrap();
+            // repeats of an event that stopped within twice the window are dropped along with it
+            seen.retain(|_, seen| now - seen.published < self.window * 2);
+            match seen.get_mut(&key) {
+                Some(seen) if now - seen.published < self.window => {
+                    seen.repeats += 1;
+                    return Ok(());
+                }
+                Some(seen) => {
+                    seen.published = now;
+                    mem::take(&mut seen.repeats)
+                }
+                None => {
+                    seen.insert(key, Seen {
+                        published: now,
+                        repeats: 0,
+                    });
+                    0
+                }
+            }
+        };
+        if repeats == 0 {
+            return self.recorder.publish(ev, reference).await;
+        }
+        let held_back = format!("{repeats} more in the last {}s", self.window.as_secs());
+        let ev = Event {
+            type_: ev.type_,
+            reason: ev.reason.clone(),
+            note: Some(match &ev.note {
+                Some(note) => format!("{note} ({held_back})"),
+                None => held_back,
+            }),
+            action: ev.action.clone(),
+            secondary: ev.secondary.clone(),
+        };
+        self.recorder.publish(&ev, reference).await
+    }
+}
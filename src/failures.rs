@@ -0,0 +1,119 @@
+//! Failures in a row, telling a flapping object from a broken one
+//!
+//! Every failed retry of an object counts, in `status.consecutiveFailures` and the
+//! `doc_ctrl_reconcile_consecutive_failures` metric, until a reconcile succeeds. After
+//! `CONTROLLER_STALL_AFTER_FAILURES` (5 by default, 0 never) the object is marked `Stalled=True` with the
+//! last error and retried every `CONTROLLER_STALLED_RETRY_SECONDS` (an hour by default) instead. A reconcile
+//! failing before its retry was due, woken by the status write or by a change, doesn't count.
+use crate::{Context, Error, conditions::Conditions, status};
+use k8s_openapi::NamespaceResourceScope;
+use kube::{Resource, ResourceExt, core::object::HasStatus, runtime::controller::Action};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+use tokio::time::{Duration, Instant};
+use tracing::*;
+
+struct Streak {
+    count: u32,
+    /// When the next failure counts
+    due: Instant,
+}
+
+/// Failure streaks of every object, by `Kind namespace/name`
+#[derive(Clone, Default)]
+pub struct Failures {
+    streaks: Arc<Mutex<HashMap<String, Streak>>>,
+}
+
+impl Failures {
+    /// Failures of `key` in a row and the wait before the next retry
+    pub fn get(&self, key: &str) -> Option<(u32, Duration)> {
+        let streaks = self.streaks.lock().unwrap();
+        streaks
+            .get(key)
+            .map(|streak| (streak.count, streak.due.saturating_duration_since(Instant::now())))
+    }
+
+    /// Note that a reconcile of `key` succeeded
+    pub fn clear(&self, key: &str) {
+        self.streaks.lock().unwrap().remove(key);
+    }
+
+    /// Count a failure of `key` unless its retry wasn't due, `recorded` is the count in the status
+    ///
+    /// Hands back the streak, whether this failure counted, and the wait before the next retry.
+    fn fail(
+        &self,
+        key: String,
+        recorded: u32,
+        retry_after: impl Fn(u32) -> Duration,
+    ) -> (u32, bool, Duration) {
+        let now = Instant::now();
+        let mut streaks = self.streaks.lock().unwrap();
+        if let Some(streak) = streaks.get(&key)
+            && streak.due > now
+        {
+            return (streak.count, false, streak.due - now);
+        }
+        // after a restart the status still knows
+        let count = streaks.get(&key).map_or(recorded, |streak| streak.count) + 1;
+        let wait = retry_after(count);
+        streaks.insert(key, Streak {
+            count,
+            due: now + wait,
+        });
+        (count, true, wait)
+    }
+}
+
+/// What the diagnostics and the streaks call an object
+pub fn key(kind: &str, namespace: Option<&str>, name: &str) -> String {
+    format!("{kind} {}/{name}", namespace.unwrap_or_default())
+}
+
+/// Count the failed reconcile of `doc` and say when to retry it, the error policy of every controller
+pub fn requeue<K>(doc: Arc<K>, error: &Error, ctx: &Context) -> Action
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + HasStatus
+        + Clone
+        + DeserializeOwned
+        + Debug
+        + Send
+        + Sync
+        + 'static,
+    K::Status: Conditions + Serialize + Clone + Default + Send,
+{
+    let recorded = doc
+        .status()
+        .and_then(|status| serde_json::to_value(status).ok())
+        .and_then(|status| status["consecutiveFailures"].as_u64())
+        .unwrap_or_default() as u32;
+    let key = key(&K::kind(&()), doc.namespace().as_deref(), &doc.name_any());
+    let (failures, counted, wait) = ctx
+        .failures
+        .fail(key, recorded, |count| ctx.settings.retry_after(count));
+    if !counted {
+        return Action::requeue(wait);
+    }
+    ctx.metrics
+        .reconcile
+        .set_consecutive_failures(doc.as_ref(), failures);
+    let stalled = ctx.settings.stalls(failures).then(|| {
+        format!(
+            "{failures} reconciles in a row failed, retrying every {}s: {error}",
+            wait.as_secs()
+        )
+    });
+    let client = ctx.client.clone();
+    tokio::spawn(async move {
+        if let Err(e) = status::record_failures(doc.as_ref(), client, failures, stalled).await {
+            warn!("failed to record the failures of \"{}\": {}", doc.name_any(), e);
+        }
+    });
+    Action::requeue(wait)
+}
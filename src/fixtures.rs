@@ -1,230 +1,217 @@
 //! Helper methods only available for tests
 use crate::{
-    Result, controller::Context, controller::DOCUMENT_FINALIZER, crds::DNSRecord, crds::DNSRecordSpec,
-    crds::DNSRecordStatus,
+    Context, Diagnostics,
+    cf_client::CloudflareApi,
+    cloudflare::{CloudflareClientProvider, OperatorToken},
+    dns_record::{DNSRecord, DNSRecordSpec, DNSRecordStatus},
+    events::Events,
+    zone::{Zone, ZoneSpec, ZoneStatus},
+};
+use http::{Method, Request, Response, StatusCode};
+use k8s_openapi::api::core::v1::LocalObjectReference;
+use kube::{Client, Resource, client::Body, runtime::events::Recorder};
+use serde_json::{Value, json};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
 };
-use assert_json_diff::assert_json_include;
-use http::{Request, Response};
-use kube::{Client, Resource, ResourceExt, client::Body, runtime::events::Recorder};
-use std::sync::Arc;
 
 impl DNSRecord {
-    /// A document that will cause the reconciler to fail
-    pub fn illegal() -> Self {
+    /// A record `test.<zone>` pointing at 1.2.3.4
+    pub fn test(zone: &str) -> Self {
         let spec = DNSRecordSpec {
-            zone_id: "test-zone".to_string(),
-            name: "illegal".to_string(),
-            record_type: "A".to_string(),
-            content: "1.2.3.4".to_string(),
-        };
-        let mut d = DNSRecord::new("illegal", spec);
-        d.meta_mut().namespace = Some("default".into());
-        d
-    }
-
-    /// A normal test document
-    pub fn test() -> Self {
-        let spec = DNSRecordSpec {
-            zone_id: "test-zone".to_string(),
-            name: "test".to_string(),
-            record_type: "A".to_string(),
-            content: "1.2.3.4".to_string(),
+            zone_ref: LocalObjectReference { name: zone.into() },
+            name: "test".into(),
+            record_type: "A".into(),
+            content: "1.2.3.4".into(),
+            ttl: None,
+            priority: None,
+            proxied: None,
+            reconcile_policy: None,
+            custom_hostname: None,
         };
         let mut d = DNSRecord::new("test", spec);
         d.meta_mut().namespace = Some("default".into());
+        d.meta_mut().generation = Some(1);
         d
     }
-    /// Modify document to set a deletion timestamp
-    pub fn needs_delete(mut self) -> Self {
-        use chrono::prelude::{DateTime, TimeZone, Utc};
-        let now: DateTime<Utc> = Utc.with_ymd_and_hms(2017, 04, 02, 12, 50, 32).unwrap();
-        use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
-        self.meta_mut().deletion_timestamp = Some(Time(now));
-        self
-    }
 
-    /// Modify a document to have the expected finalizer
-    pub fn finalized(mut self) -> Self {
-        self.finalizers_mut().push(DOCUMENT_FINALIZER.to_string());
-        self
-    }
-
-    /// Modify a document to have an expected status
+    /// Modify a record to have an expected status
     pub fn with_status(mut self, status: DNSRecordStatus) -> Self {
         self.status = Some(status);
         self
     }
 }
 
+impl Zone {
+    /// A ready zone `name` with Cloudflare id `id`
+    pub fn test(name: &str, id: &str) -> Self {
+        let mut zone = Zone::new(name, ZoneSpec::default());
+        zone.meta_mut().namespace = Some("default".into());
+        zone.status = Some(ZoneStatus {
+            ready: true,
+            id: Some(id.into()),
+            ..ZoneStatus::default()
+        });
+        zone
+    }
+}
+
 // We wrap tower_test::mock::Handle
 type ApiServerHandle = tower_test::mock::Handle<Request<Body>, Response<Body>>;
 pub struct ApiServerVerifier(ApiServerHandle);
 
-/// Scenarios we test for in ApiServerVerifier
-pub enum Scenario {
-    /// objects without finalizers will get a finalizer applied (and not call the apply loop)
-    FinalizerCreation(DNSRecord),
-    /// objects that do not fail and do not cause publishes will only patch
-    StatusPatch(DNSRecord),
-    /// finalized objects with hide set causes both an event and then a hide patch
-    EventPublishThenStatusPatch(String, DNSRecord),
-    /// finalized objects "with errors" (i.e. the "illegal" object) will short circuit the apply loop
-    RadioSilence,
-    /// objects with a deletion timestamp will run the cleanup loop sending event and removing the finalizer
-    Cleanup(String, DNSRecord),
+/// A request the fake API server answered
+#[derive(Clone, Debug)]
+pub struct Served {
+    pub method: Method,
+    pub path: String,
+    pub body: Value,
 }
 
-pub async fn timeout_after_1s(handle: tokio::task::JoinHandle<()>) {
-    tokio::time::timeout(std::time::Duration::from_secs(1), handle)
-        .await
-        .expect("timeout on mock apiserver")
-        .expect("scenario succeeded")
+/// Requests answered so far, in order
+#[derive(Clone, Default)]
+pub struct ServedRequests(Arc<Mutex<Vec<Served>>>);
+
+impl ServedRequests {
+    /// Reasons of the events published
+    pub fn event_reasons(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|served| served.method == Method::POST && served.path.contains("/events"))
+            .filter_map(|served| served.body["reason"].as_str().map(String::from))
+            .collect()
+    }
+
+    /// The last status written through a server-side apply
+    pub fn last_status(&self) -> Option<Value> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|served| served.method == Method::PATCH && served.path.contains("/status"))
+            .map(|served| served.body["status"].clone())
+    }
 }
 
 impl ApiServerVerifier {
-    /// Tests only get to run specific scenarios that has matching handlers
-    ///
-    /// This setup makes it easy to handle multiple requests by chaining handlers together.
-    ///
-    /// NB: If the controller is making more calls than we are handling in the scenario,
-    /// you then typically see a `KubeError(Service(Closed(())))` from the reconciler.
+    /// Answer the calls of the reconcilers in the background, with `objects` as the cluster content
     ///
-    /// You should await the `JoinHandle` (with a timeout) from this function to ensure that the
-    /// scenario runs to completion (i.e. all expected calls were responded to),
-    /// using the timeout to catch missing api calls to Kubernetes.
-    pub fn run(self, scenario: Scenario) -> tokio::task::JoinHandle<()> {
+    /// Gets of objects are answered from `objects` (404 otherwise), lists of kinds not in there are
+    /// empty, events and status patches are accepted as sent. Unlike a scenario of expected calls this
+    /// leaves the order of the calls to the reconciler, assert on the `ServedRequests` instead.
+    pub fn serve<K>(mut self, objects: Vec<Value>, patched: K) -> ServedRequests
+    where
+        K: Resource<DynamicType = ()> + serde::Serialize + Send + 'static,
+    {
+        let served = ServedRequests::default();
+        let log = served.clone();
         tokio::spawn(async move {
-            // moving self => one scenario per test
-            match scenario {
-                Scenario::FinalizerCreation(doc) => self.handle_finalizer_creation(doc).await,
-                Scenario::StatusPatch(doc) => self.handle_status_patch(doc).await,
-                Scenario::EventPublishThenStatusPatch(reason, doc) => {
-                    self.handle_event_create(reason)
-                        .await
-                        .unwrap()
-                        .handle_status_patch(doc)
-                        .await
-                }
-                Scenario::RadioSilence => Ok(self),
-                Scenario::Cleanup(reason, doc) => {
-                    self.handle_event_create(reason)
-                        .await
-                        .unwrap()
-                        .handle_finalizer_removal(doc)
-                        .await
-                }
+            let patched = serde_json::to_value(&patched).unwrap();
+            while let Some((request, send)) = self.0.next_request().await {
+                let method = request.method().clone();
+                let path = request.uri().path().to_string();
+                let bytes = request.into_body().collect_bytes().await.unwrap();
+                let body: Value = serde_json::from_slice(&bytes).unwrap_or_default();
+                let (status, response) = answer(&method, &path, &body, &objects, &patched);
+                log.0.lock().unwrap().push(Served { method, path, body });
+                let response = Response::builder()
+                    .status(status)
+                    .body(Body::from(serde_json::to_vec(&response).unwrap()))
+                    .unwrap();
+                send.send_response(response);
             }
-            .expect("scenario completed without errors");
-        })
+        });
+        served
     }
+}
 
-    // chainable scenario handlers
-
-    async fn handle_finalizer_creation(mut self, doc: DNSRecord) -> Result<Self> {
-        let (request, send) = self.0.next_request().await.expect("service not called");
-        // We expect a json patch to the specified document adding our finalizer
-        assert_eq!(request.method(), http::Method::PATCH);
-        assert_eq!(
-            request.uri().to_string(),
-            format!(
-                "/apis/kube.rs/v1/namespaces/default/documents/{}?",
-                doc.name_any()
-            )
-        );
-        let expected_patch = serde_json::json!([
-            { "op": "test", "path": "/metadata/finalizers", "value": null },
-            { "op": "add", "path": "/metadata/finalizers", "value": vec![DOCUMENT_FINALIZER] }
-        ]);
-        let req_body = request.into_body().collect_bytes().await.unwrap();
-        let runtime_patch: serde_json::Value =
-            serde_json::from_slice(&req_body).expect("valid document from runtime");
-        assert_json_include!(actual: runtime_patch, expected: expected_patch);
-
-        let response = serde_json::to_vec(&doc.finalized()).unwrap(); // respond as the apiserver would have
-        send.send_response(Response::builder().body(Body::from(response)).unwrap());
-        Ok(self)
-    }
-
-    async fn handle_finalizer_removal(mut self, doc: DNSRecord) -> Result<Self> {
-        let (request, send) = self.0.next_request().await.expect("service not called");
-        // We expect a json patch to the specified document removing our finalizer (at index 0)
-        assert_eq!(request.method(), http::Method::PATCH);
-        assert_eq!(
-            request.uri().to_string(),
-            format!(
-                "/apis/kube.rs/v1/namespaces/default/documents/{}?",
-                doc.name_any()
-            )
-        );
-        let expected_patch = serde_json::json!([
-            { "op": "test", "path": "/metadata/finalizers/0", "value": DOCUMENT_FINALIZER },
-            { "op": "remove", "path": "/metadata/finalizers/0", "path": "/metadata/finalizers/0" }
-        ]);
-        let req_body = request.into_body().collect_bytes().await.unwrap();
-        let runtime_patch: serde_json::Value =
-            serde_json::from_slice(&req_body).expect("valid document from runtime");
-        assert_json_include!(actual: runtime_patch, expected: expected_patch);
-
-        let response = serde_json::to_vec(&doc).unwrap(); // respond as the apiserver would have
-        send.send_response(Response::builder().body(Body::from(response)).unwrap());
-        Ok(self)
-    }
+/// Where the object at `path` would be, `/apis/<group>/<version>/namespaces/<ns>/<plural>/<name>`
+fn matches(object: &Value, path: &str) -> bool {
+    let name = object["metadata"]["name"].as_str().unwrap_or_default();
+    let namespace = object["metadata"]["namespace"].as_str().unwrap_or_default();
+    let plural = format!("{}s", object["kind"].as_str().unwrap_or_default().to_lowercase());
+    path.ends_with(&format!("/namespaces/{namespace}/{plural}/{name}"))
+}
 
-    async fn handle_event_create(mut self, reason: String) -> Result<Self> {
-        let (request, send) = self.0.next_request().await.expect("service not called");
-        assert_eq!(request.method(), http::Method::POST);
-        assert_eq!(
-            request.uri().to_string(),
-            format!("/apis/events.k8s.io/v1/namespaces/default/events?")
-        );
-        // verify the event reason matches the expected
-        let req_body = request.into_body().collect_bytes().await.unwrap();
-        let postdata: serde_json::Value =
-            serde_json::from_slice(&req_body).expect("valid event from runtime");
-        dbg!("postdata for event: {}", postdata.clone());
-        assert_eq!(
-            postdata.get("reason").unwrap().as_str().map(String::from),
-            Some(reason)
-        );
-        // then pass through the body
-        send.send_response(Response::builder().body(Body::from(req_body)).unwrap());
-        Ok(self)
-    }
+/// Whether `path` names one object rather than listing a kind, past `/api/<v>` or `/apis/<group>/<v>`
+/// that's `<plural>/<name>` with or without `namespaces/<ns>/` in front
+fn names_object(path: &str) -> bool {
+    let segments: Vec<_> = path.trim_start_matches('/').split('/').collect();
+    let skip = if segments.first() == Some(&"api") { 2 } else { 3 };
+    segments.len().saturating_sub(skip) % 2 == 0
+}
 
-    async fn handle_status_patch(mut self, doc: DNSRecord) -> Result<Self> {
-        let (request, send) = self.0.next_request().await.expect("service not called");
-        assert_eq!(request.method(), http::Method::PATCH);
-        assert_eq!(
-            request.uri().to_string(),
-            format!(
-                "/apis/kube.rs/v1/namespaces/default/documents/{}/status?&force=true&fieldManager=cntrlr",
-                doc.name_any()
-            )
-        );
-        let req_body = request.into_body().collect_bytes().await.unwrap();
-        let json: serde_json::Value = serde_json::from_slice(&req_body).expect("patch_status object is json");
-        let status_json = json.get("status").expect("status object").clone();
-        let status: DNSRecordStatus = serde_json::from_value(status_json).expect("valid status");
-        //assert_eq!(status.hidden, doc.spec.hide, "status.hidden iff doc.spec.hide");
-        let response = serde_json::to_vec(&doc.with_status(status)).unwrap();
-        // pass through document "patch accepted"
-        send.send_response(Response::builder().body(Body::from(response)).unwrap());
-        Ok(self)
+fn answer(
+    method: &Method,
+    path: &str,
+    body: &Value,
+    objects: &[Value],
+    patched: &Value,
+) -> (StatusCode, Value) {
+    match *method {
+        Method::GET => match objects.iter().find(|object| matches(object, path)) {
+            Some(object) => (StatusCode::OK, object.clone()),
+            None if names_object(path) => (
+                StatusCode::NOT_FOUND,
+                json!({
+                    "kind": "Status",
+                    "apiVersion": "v1",
+                    "status": "Failure",
+                    "reason": "NotFound",
+                    "message": format!("{path} not found"),
+                    "code": 404
+                }),
+            ),
+            None => (
+                StatusCode::OK,
+                json!({
+                    "apiVersion": "v1",
+                    "kind": "List",
+                    "metadata": {},
+                    "items": []
+                }),
+            ),
+        },
+        Method::PATCH if path.ends_with("/status") => {
+            let mut object = patched.clone();
+            object["status"] = body["status"].clone();
+            (StatusCode::OK, object)
+        }
+        _ => (StatusCode::OK, body.clone()),
     }
 }
 
 impl Context {
-    // Create a test context with a mocked kube client, locally registered metrics and default diagnostics
-    pub fn test() -> (Arc<Self>, ApiServerVerifier) {
+    /// A test context with a mocked kube client, handing `api` to the reconcilers instead of Cloudflare
+    pub fn test(api: Arc<dyn CloudflareApi>) -> (Arc<Self>, ApiServerVerifier) {
         let (mock_service, handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
         let mock_client = Client::new(mock_service, "default");
-        let mock_recorder = Recorder::new(mock_client.clone(), "doc-ctrl-test".into());
+        let mock_recorder = Recorder::new(mock_client.clone(), Diagnostics::default().reporter);
         let ctx = Self {
-            client: mock_client,
-            metrics: Arc::default(),
+            client: mock_client.clone(),
+            recorder: Events::new(mock_recorder, Duration::ZERO),
             diagnostics: Arc::default(),
-            recorder: mock_recorder,
+            metrics: Arc::default(),
+            triggers: Default::default(),
+            dns_batch: Default::default(),
+            provider: CloudflareClientProvider::new(mock_client, OperatorToken::default()).with_api(api),
+            settings: Default::default(),
+            namespaces: Default::default(),
+            audit: Default::default(),
+            failures: Default::default(),
         };
         (Arc::new(ctx), ApiServerVerifier(handle))
     }
 }
+
+/// The object as the API server would hand it out
+pub fn object<K: Resource<DynamicType = ()> + serde::Serialize>(obj: &K) -> Value {
+    let mut value = serde_json::to_value(obj).unwrap();
+    value["apiVersion"] = json!(K::api_version(&()));
+    value["kind"] = json!(K::kind(&()));
+    value
+}
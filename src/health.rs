@@ -0,0 +1,248 @@
+//! Liveness and readiness of the operator, what `/healthz` and `/readyz` answer
+//!
+//! Ready means the API server answers and the CRDs are established, with `HEALTH_VERIFY_TOKEN=true`
+//! also that Cloudflare accepts the operator token. Alive means the controllers are running and get
+//! through what they watch: a controller is stalled when its objects changed more than
+//! `HEALTH_STALL_SECONDS` (600 by default) ago and it hasn't reconciled anything since.
+use crate::{crds, status};
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::{Api, Client, Resource, ResourceExt, core::object::HasStatus, runtime::reflector::Store};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// What a controller's store holds
+#[derive(Default)]
+struct Snapshot {
+    objects: usize,
+    pending: usize,
+    /// Changes whenever an object does
+    fingerprint: u64,
+}
+
+type Snapshotter = Box<dyn Fn() -> Snapshot + Send + Sync>;
+
+/// A controller as the diagnostics show it
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ControllerDiagnostics {
+    /// Objects watched
+    pub objects: usize,
+    /// Objects whose spec changed since their status was last written, kube-runtime keeps its queue
+    /// to itself
+    pub pending: usize,
+    /// Seconds since the objects changed with nothing reconciled since
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub waiting_seconds: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct Health {
+    /// Progress of every controller, by kind
+    controllers: Arc<Mutex<HashMap<&'static str, Progress>>>,
+    stall_after: Duration,
+    verify_token: bool,
+    /// Account owning the operator token, when it's an account token
+    verify_account: Option<String>,
+    /// Set once every CRD was seen established, they don't go back
+    established: Arc<AtomicBool>,
+    /// Set once the controllers returned
+    stopped: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct Progress {
+    /// One per watched namespace
+    stores: Vec<Snapshotter>,
+    /// Fingerprint of the objects at the last check
+    seen: u64,
+    /// When the objects were first seen changed with no reconcile since
+    changed: Option<Instant>,
+}
+
+impl Progress {
+    /// Look at the stores, noting when the objects changed
+    fn refresh(&mut self, now: Instant) -> Snapshot {
+        let snapshot = self
+            .stores
+            .iter()
+            .map(|store| store())
+            .fold(Snapshot::default(), |sum, store| Snapshot {
+                objects: sum.objects + store.objects,
+                pending: sum.pending + store.pending,
+                fingerprint: sum.fingerprint.wrapping_add(store.fingerprint),
+            });
+        if snapshot.fingerprint != self.seen {
+            self.seen = snapshot.fingerprint;
+            self.changed.get_or_insert(now);
+        }
+        snapshot
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            controllers: Arc::default(),
+            stall_after: Duration::from_secs(600),
+            verify_token: false,
+            verify_account: None,
+            established: Arc::default(),
+            stopped: Arc::default(),
+        }
+    }
+}
+
+impl Health {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            stall_after: std::env::var("HEALTH_STALL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|secs| *secs > 0)
+                .map_or(defaults.stall_after, Duration::from_secs),
+            verify_token: std::env::var("HEALTH_VERIFY_TOKEN").is_ok_and(|v| v == "true"),
+            verify_account: std::env::var("HEALTH_VERIFY_ACCOUNT_ID")
+                .ok()
+                .filter(|id| !id.is_empty()),
+            ..defaults
+        }
+    }
+
+    /// Whether readiness includes verifying the operator token
+    pub fn verifies_token(&self) -> bool {
+        self.verify_token
+    }
+
+    /// Account the operator token is verified against, as a user's token without one
+    pub fn verified_account(&self) -> Option<&str> {
+        self.verify_account.as_deref()
+    }
+
+    /// Follow the objects of a `kind` controller in `store`
+    pub fn watch<K>(&self, kind: &'static str, store: Store<K>)
+    where
+        K: Resource<DynamicType = ()> + HasStatus + Clone + Send + Sync + 'static,
+        K::Status: Serialize,
+    {
+        let snapshot = move || {
+            let objects = store.state();
+            Snapshot {
+                objects: objects.len(),
+                pending: objects
+                    .iter()
+                    .filter(|obj| status::spec_changed(obj.as_ref()))
+                    .count(),
+                // order independent, the store hands its objects out in any order
+                fingerprint: objects.iter().fold(0u64, |sum, obj| {
+                    let mut hasher = DefaultHasher::new();
+                    (obj.uid(), obj.resource_version()).hash(&mut hasher);
+                    sum.wrapping_add(hasher.finish())
+                }),
+            }
+        };
+        let mut controllers = self.controllers.lock().unwrap();
+        controllers
+            .entry(kind)
+            .or_default()
+            .stores
+            .push(Box::new(snapshot));
+    }
+
+    /// Note that the `kind` controller reconciled an object, whatever the outcome
+    pub fn processed(&self, kind: &'static str) {
+        if let Some(progress) = self.controllers.lock().unwrap().get_mut(kind) {
+            progress.changed = None;
+        }
+    }
+
+    /// Note that the controllers returned, nothing gets reconciled anymore
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Why the operator isn't alive, nothing when it is
+    pub fn liveness(&self) -> Vec<String> {
+        if self.stopped.load(Ordering::Relaxed) {
+            return vec!["the controllers stopped".into()];
+        }
+        let now = Instant::now();
+        let mut controllers = self.controllers.lock().unwrap();
+        let mut stalled = vec![];
+        for (kind, progress) in controllers.iter_mut() {
+            progress.refresh(now);
+            if let Some(changed) = progress.changed
+                && now - changed > self.stall_after
+            {
+                stalled.push(format!(
+                    "the {kind} controller reconciled nothing for {}s after its objects changed",
+                    (now - changed).as_secs()
+                ));
+            }
+        }
+        stalled.sort();
+        stalled
+    }
+
+    /// Every controller's objects and how far behind it is, by kind
+    pub fn controllers(&self) -> BTreeMap<String, ControllerDiagnostics> {
+        let now = Instant::now();
+        let mut controllers = self.controllers.lock().unwrap();
+        controllers
+            .iter_mut()
+            .map(|(kind, progress)| {
+                let snapshot = progress.refresh(now);
+                let diagnostics = ControllerDiagnostics {
+                    objects: snapshot.objects,
+                    pending: snapshot.pending,
+                    waiting_seconds: progress.changed.map(|changed| (now - changed).as_secs()),
+                };
+                (kind.to_string(), diagnostics)
+            })
+            .collect()
+    }
+
+    /// Why the operator isn't ready as far as Kubernetes goes, nothing when it is
+    pub async fn readiness(&self, client: &Client) -> Vec<String> {
+        if let Err(e) = client.apiserver_version().await {
+            return vec![format!("the API server doesn't answer: {e}")];
+        }
+        match self.crds_established(client).await {
+            Ok(()) => vec![],
+            Err(problem) => vec![problem],
+        }
+    }
+
+    async fn crds_established(&self, client: &Client) -> Result<(), String> {
+        if self.established.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let api: Api<CustomResourceDefinition> = Api::all(client.clone());
+        for crd in crds::all() {
+            let name = crd.name_any();
+            let crd = api
+                .get(&name)
+                .await
+                .map_err(|e| format!("CRD {name} can't be read: {e}"))?;
+            let established = crd
+                .status
+                .and_then(|status| status.conditions)
+                .unwrap_or_default()
+                .iter()
+                .any(|c| c.type_ == "Established" && c.status == "True");
+            if !established {
+                return Err(format!("CRD {name} is not established"));
+            }
+        }
+        self.established.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
@@ -1,16 +1,29 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
 
-use serde::Serialize;
+use serde::{Serialize, de::DeserializeOwned};
 use thiserror::Error;
 
 use chrono::{DateTime, Utc};
+use futures::StreamExt;
 use kube::{
+    Api, Resource,
     client::Client,
-    runtime::events::{Recorder, Reporter},
+    runtime::{
+        Controller, WatchStreamExt,
+        controller::{self, Action},
+        events::{Recorder, Reporter},
+        reflector::{self, ObjectRef},
+        watcher,
+    },
 };
 
-use cloudflare::CloudflareClientProvider;
+use cloudflare::{AllowedApiUrls, ClientCache, CloudflareClientProvider, FallbackNamespaces, OperatorToken};
+use failures::Failures;
+use health::{ControllerDiagnostics, Health};
+use namespaces::WatchNamespaces;
+use settings::ControllerSettings;
 use tokio::sync::RwLock;
+use triggers::Triggers;
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("SerializationError: {0}")]
@@ -34,7 +47,14 @@ pub enum Error {
     UnsupportedRecordType(String),
 
     #[error("Cloudflare API error: {0}")]
-    CloudflareApiError(#[from] anyhow::Error),
+    CloudflareApiError(#[from] cf_client::CfApiError),
+
+    /// Cloudflare state the operator can't work with: a missing zone, a refused operation
+    #[error("Cloudflare error: {0}")]
+    CloudflareError(#[from] anyhow::Error),
+
+    #[error("Credentials error: {0}")]
+    CredentialsError(#[from] cloudflare::ProviderError),
 }
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -42,6 +62,20 @@ impl Error {
     pub fn metric_label(&self) -> String {
         format!("{self:?}").to_lowercase()
     }
+
+    /// The Cloudflare error behind this one, looking through the finalizer
+    pub fn cloudflare_error(&self) -> Option<&cf_client::CfApiError> {
+        use kube::runtime::finalizer::Error as Finalizer;
+        match self {
+            Error::CloudflareApiError(e) => Some(e),
+            Error::CloudflareError(e) => e.downcast_ref(),
+            Error::FinalizerError(e) => match e.as_ref() {
+                Finalizer::ApplyFailed(e) | Finalizer::CleanupFailed(e) => e.cloudflare_error(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 }
 
 /// Diagnostics to be exposed by the web server
@@ -49,6 +83,15 @@ impl Error {
 pub struct Diagnostics {
     #[serde(deserialize_with = "from_ts")]
     pub last_event: DateTime<Utc>,
+    /// Objects watched and how far behind the controller is, by kind
+    pub controllers: BTreeMap<String, ControllerDiagnostics>,
+    /// Objects whose last reconcile failed
+    pub failing_objects: usize,
+    /// The same by `Kind namespace/name`, left out of the diagnostics of callers without the token
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub failing: BTreeMap<String, Failure>,
+    /// Cloudflare clients cached, one per token
+    pub cloudflare_clients: i64,
     #[serde(skip)]
     pub reporter: Reporter,
 }
@@ -56,10 +99,39 @@ impl Default for Diagnostics {
     fn default() -> Self {
         Self {
             last_event: Utc::now(),
+            controllers: BTreeMap::new(),
+            failing_objects: 0,
+            failing: BTreeMap::new(),
+            cloudflare_clients: 0,
             reporter: "doc-controller".into(),
         }
     }
 }
+
+impl Diagnostics {
+    /// Without the names and errors of the failing objects, for anonymous callers
+    pub fn redacted(self) -> Self {
+        Self {
+            failing: BTreeMap::new(),
+            ..self
+        }
+    }
+}
+
+/// The last failed reconcile of an object
+#[derive(Clone, Debug, Serialize)]
+pub struct Failure {
+    pub error: String,
+    pub at: DateTime<Utc>,
+    /// Failed retries in a row
+    pub consecutive: u32,
+    /// When the controller tries again, unless the object changes before
+    pub retry_at: DateTime<Utc>,
+}
+
+/// What a controller's stream yields for every reconcile
+pub type ReconcileResult<K> =
+    std::result::Result<(ObjectRef<K>, Action), controller::Error<Error, watcher::Error>>;
 impl Diagnostics {
     fn recorder(&self, client: Client) -> Recorder {
         Recorder::new(client, self.reporter.clone())
@@ -73,38 +145,247 @@ pub struct State {
     diagnostics: Arc<RwLock<Diagnostics>>,
     /// Metrics
     metrics: Arc<Metrics>,
+    /// Requests for immediate reconciles
+    triggers: Triggers,
+    /// Namespaces the controllers are limited to
+    namespaces: WatchNamespaces,
+    /// Cloudflare clients of every controller, by token
+    clients: ClientCache,
+    /// Token for objects without credentials of their own
+    operator_token: OperatorToken,
+    /// Namespaces allowed to use the operator token
+    fallback: FallbackNamespaces,
+    /// APIs objects may name for tokens that aren't their own
+    allowed_api_urls: AllowedApiUrls,
+    /// Tokens platform admins mapped to accounts
+    credentials: credentials::CredentialsStore,
+    /// Liveness and readiness
+    health: Health,
+    /// Failures in a row of every object
+    failures: Failures,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// State wrapper around the controller outputs for the web server
 impl State {
     pub fn new() -> Self {
+        let metrics = Arc::<Metrics>::default();
         Self {
             diagnostics: Arc::default(),
-            metrics: Arc::default(),
+            clients: ClientCache::from_env(
+                metrics.client.cached.clone(),
+                metrics.client.retries.clone(),
+                metrics.client.quota.clone(),
+                metrics.client.latency.clone(),
+            ),
+            metrics,
+            triggers: Triggers::default(),
+            namespaces: WatchNamespaces::from_env(),
+            operator_token: OperatorToken::from_env(),
+            fallback: FallbackNamespaces::from_env(),
+            allowed_api_urls: AllowedApiUrls::from_env(),
+            credentials: credentials::CredentialsStore::watched(),
+            health: Health::from_env(),
+            failures: Failures::default(),
+        }
+    }
+
+    /// Limit the controllers to `namespaces` instead of what `WATCH_NAMESPACES` says
+    pub fn with_namespaces(self, namespaces: WatchNamespaces) -> Self {
+        Self { namespaces, ..self }
+    }
+
+    /// Talk to `api_url` instead of what `CLOUDFLARE_API_URL` says
+    pub fn with_api_url(self, api_url: reqwest::Url) -> Self {
+        Self {
+            clients: self.clients.with_api_url(api_url),
+            ..self
+        }
+    }
+
+    /// Log every Cloudflare request, whatever `CLOUDFLARE_DEBUG_HTTP` says
+    pub fn with_debug_http(self) -> Self {
+        Self {
+            clients: self.clients.with_debug_http(),
+            ..self
+        }
+    }
+
+    /// Reach Cloudflare through `proxy` instead of what `CLOUDFLARE_PROXY_URL` or `HTTPS_PROXY` say
+    pub fn with_proxy(self, proxy: reqwest::Url) -> Self {
+        Self {
+            clients: self.clients.with_proxy(proxy),
+            ..self
+        }
+    }
+
+    /// Manage objects without credentials with `operator_token` instead of `CLOUDFLARE_API_TOKEN`
+    pub fn with_operator_token(self, operator_token: OperatorToken) -> Self {
+        Self {
+            operator_token,
+            ..self
         }
     }
 
     /// Metrics getter
     pub fn metrics(&self) -> String {
+        for (kind, controller) in self.health.controllers() {
+            self.metrics
+                .controller
+                .pending
+                .get_or_create(&metrics::ControllerLabels { kind })
+                .set(controller.pending as i64);
+        }
         let mut buffer = String::new();
         let registry = &*self.metrics.registry;
         prometheus_client::encoding::text::encode(&mut buffer, registry).unwrap();
         buffer
     }
 
-    /// State getter
+    /// State getter, with the controllers and clients as they are now
     pub async fn diagnostics(&self) -> Diagnostics {
-        self.diagnostics.read().await.clone()
+        let mut diagnostics = self.diagnostics.read().await.clone();
+        diagnostics.controllers = self.health.controllers();
+        diagnostics.cloudflare_clients = self.metrics.client.cached.get();
+        diagnostics.failing_objects = diagnostics.failing.len();
+        diagnostics
+    }
+
+    /// Note how a reconcile of the `kind` controller went
+    pub async fn reconciled<K>(&self, kind: &'static str, result: ReconcileResult<K>)
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        self.health.processed(kind);
+        let mut diagnostics = self.diagnostics.write().await;
+        match result {
+            Ok((obj, _)) => {
+                let key = failures::key(kind, obj.namespace.as_deref(), &obj.name);
+                diagnostics.failing.remove(&key);
+                self.failures.clear(&key);
+                self.metrics
+                    .reconcile
+                    .clear_consecutive_failures(kind, obj.namespace.as_deref(), &obj.name);
+            }
+            Err(controller::Error::ReconcilerFailed(error, obj)) => {
+                let key = failures::key(kind, obj.namespace.as_deref(), &obj.name);
+                let (consecutive, retry) = self.failures.get(&key).unwrap_or_default();
+                let at = Utc::now();
+                let failure = Failure {
+                    error: error.to_string(),
+                    at,
+                    consecutive,
+                    retry_at: at + chrono::Duration::from_std(retry).unwrap_or_default(),
+                };
+                diagnostics.failing.insert(key, failure);
+            }
+            // watch and queue trouble, kube-runtime logs it and there's no object to blame
+            Err(_) => {}
+        }
+    }
+
+    /// Triggers getter
+    pub fn triggers(&self) -> &Triggers {
+        &self.triggers
+    }
+
+    /// Namespaces getter
+    pub fn namespaces(&self) -> &WatchNamespaces {
+        &self.namespaces
+    }
+
+    /// Getter of the APIs objects may name for tokens that aren't their own
+    pub fn allowed_api_urls(&self) -> &AllowedApiUrls {
+        &self.allowed_api_urls
+    }
+
+    /// Client cache getter
+    pub fn clients(&self) -> &ClientCache {
+        &self.clients
+    }
+
+    /// Operator token getter
+    pub fn operator_token(&self) -> &OperatorToken {
+        &self.operator_token
+    }
+
+    /// CloudflareCredentials getter
+    pub fn credentials(&self) -> &credentials::CredentialsStore {
+        &self.credentials
+    }
+
+    /// Controller of the `kind` objects `api` lists, counting its lists in the relist metric
+    pub fn controller<K>(&self, kind: &str, api: Api<K>) -> Controller<K>
+    where
+        K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug + Send + Sync + 'static,
+    {
+        let relists = self
+            .metrics
+            .controller
+            .relists
+            .get_or_create(&metrics::ControllerLabels { kind: kind.into() })
+            .clone();
+        let (reader, writer) = reflector::store();
+        let watch = watcher(api, watcher::Config::default().any_semantic()).inspect(move |event| {
+            if let Ok(watcher::Event::Init) = event {
+                relists.inc();
+            }
+        });
+        Controller::for_stream(reflector::reflector(writer, watch).applied_objects(), reader)
+    }
+
+    /// Health getter
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    /// Why the operator isn't ready, nothing when it is
+    pub async fn readiness(&self, client: Client) -> Vec<String> {
+        let mut problems = self.health.readiness(&client).await;
+        if problems.is_empty() && self.health.verifies_token() {
+            let provider = CloudflareClientProvider::new(client, self.operator_token.clone())
+                .with_cache(self.clients.clone());
+            match provider.default_client().await {
+                Ok(cf_client) => {
+                    if let Err(e) = cf_client.verify(self.health.verified_account()).await {
+                        problems.push(format!("Cloudflare refused the operator token: {e}"));
+                    }
+                }
+                Err(e) => problems.push(format!("no operator token to verify: {e}")),
+            }
+        }
+        problems
     }
 
     // Create a Controller Context that can update State
-    pub async fn to_context(&self, client: Client, token: String) -> Arc<Context> {
+    pub async fn to_context(&self, client: Client) -> Arc<Context> {
+        self.to_controller_context(client, ControllerSettings::default())
+            .await
+    }
+
+    /// Context for a controller, carrying its queue settings
+    pub async fn to_controller_context(&self, client: Client, settings: ControllerSettings) -> Arc<Context> {
         Arc::new(Context {
             client: client.clone(),
-            recorder: self.diagnostics.read().await.recorder(client.clone()),
+            recorder: events::Events::from_env(self.diagnostics.read().await.recorder(client.clone())),
             metrics: self.metrics.clone(),
             diagnostics: self.diagnostics.clone(),
-            provider: CloudflareClientProvider::new(client, token),
+            triggers: self.triggers.clone(),
+            dns_batch: dns_record::Batcher::from_env(),
+            provider: CloudflareClientProvider::new(client, self.operator_token.clone())
+                .with_cache(self.clients.clone())
+                .with_fallback(self.fallback.clone())
+                .with_allowed_api_urls(self.allowed_api_urls.clone())
+                .with_credentials(self.credentials.clone()),
+            settings,
+            namespaces: self.namespaces.clone(),
+            audit: cloudflare::audit::AuditTrail::from_env(),
+            failures: self.failures.clone(),
         })
     }
 }
@@ -114,22 +395,56 @@ impl State {
 pub struct Context {
     /// Kubernetes client
     pub client: Client,
-    /// Event recorder
-    pub recorder: Recorder,
+    /// Event recorder, repeats held back
+    pub recorder: events::Events,
     /// Diagnostics read by the web server
     pub diagnostics: Arc<RwLock<Diagnostics>>,
     /// Prometheus metrics
     pub metrics: Arc<Metrics>,
+    /// Requests for immediate reconciles
+    pub triggers: Triggers,
+    /// Coalesces DNS record changes per zone when a batch window is configured
+    pub dns_batch: dns_record::Batcher,
     pub provider: CloudflareClientProvider,
+    /// Queue settings of the controller the context belongs to
+    pub settings: ControllerSettings,
+    /// Namespaces the operator is limited to, lists go through it instead of across the cluster
+    pub namespaces: WatchNamespaces,
+    /// Logs the changes made on the Cloudflare side
+    pub audit: cloudflare::audit::AuditTrail,
+    /// Failures in a row of every object, shared with the state
+    pub failures: Failures,
 }
 
 pub async fn run(state: State) {
+    if let Ok(client) = Client::try_default().await
+        && let Err(e) = state.operator_token.load(client).await
+    {
+        // objects with credentials of their own still work, the watch picks the Secret up once it's there
+        tracing::warn!("failed to read the operator token: {e}");
+    }
     tokio::select! {
         _ = dns_record::run(state.clone()) => {}
         _ = zone::run(state.clone()) => {}
         _ = account::run(state.clone()) => {}
+        _ = account_member::run(state.clone()) => {}
+        _ = api_token::run(state.clone()) => {}
+        _ = page_rule::run(state.clone()) => {}
+        _ = zone_set::run(state.clone()) => {}
+        _ = tunnel::run(state.clone()) => {}
+        _ = sources::ingress::run(state.clone()) => {}
+        _ = sources::service::run(state.clone()) => {}
+        _ = sources::gateway::run(state.clone()) => {}
+        _ = sources::httproute::run(state.clone()) => {}
+        _ = analytics::run(state.clone()) => {}
+        _ = audit_log::run(state.clone()) => {}
+        _ = discovery::run(state.clone()) => {}
+        _ = snapshot::run(state.clone()) => {}
+        _ = cloudflare::rotation::run(state.clone()) => {}
+        _ = credentials::run(state.clone()) => {}
         // in future we could run other workers here future: _ = worker::run(state.clone()) => {},
     }
+    state.health.stop();
 }
 /// Log and trace integrations
 pub mod telemetry;
@@ -138,11 +453,38 @@ pub mod telemetry;
 mod metrics;
 pub use metrics::Metrics;
 pub mod account;
+pub mod account_member;
+pub mod analytics;
+pub mod api_token;
+pub mod audit_log;
 pub mod cf_client;
 pub mod cloudflare;
+pub mod conditions;
+pub mod crds;
+pub mod credentials;
+pub mod deletion_policy;
+pub mod dependency;
+pub mod discovery;
 pub mod dns_record;
+pub mod events;
+pub mod failures;
+pub mod health;
+pub mod namespaces;
+pub mod page_rule;
+pub mod pause;
+pub mod policy;
+pub mod reconcile_policy;
+pub mod settings;
+pub mod snapshot;
+pub mod sources;
+pub mod status;
+pub mod token_scope;
+pub mod triggers;
+pub mod tunnel;
+pub mod webhook;
 pub mod zone;
+pub mod zone_binding;
+pub mod zone_set;
+pub mod zonefile;
 
-//TODO: reanimate tests
-//#[cfg(test)]
-//pub mod fixtures;
+#[cfg(test)] pub mod fixtures;
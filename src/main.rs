@@ -1,6 +1,17 @@
 #![allow(unused_imports, unused_variables)]
-use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, get, middleware, web::Data};
-pub use controller::{self, State, telemetry};
+use actix_web::{
+    App, HttpRequest, HttpResponse, HttpServer, Responder, get,
+    http::header::AUTHORIZATION,
+    middleware, post, put,
+    web::{Data, Json, Path},
+};
+pub use controller::{
+    self, State, cloudflare::OperatorToken, namespaces::WatchNamespaces, telemetry, webhook, zonefile,
+};
+use kube::{
+    Client,
+    core::{DynamicObject, admission::AdmissionReview, conversion::ConversionReview},
+};
 
 #[get("/metrics")]
 async fn metrics(c: Data<State>, _req: HttpRequest) -> impl Responder {
@@ -15,30 +26,276 @@ async fn health(_: HttpRequest) -> impl Responder {
     HttpResponse::Ok().json("healthy")
 }
 
+/// Liveness, fails once a controller stopped or stalled
+#[get("/healthz")]
+async fn healthz(c: Data<State>) -> impl Responder {
+    let problems = c.health().liveness();
+    if problems.is_empty() {
+        HttpResponse::Ok().json("alive")
+    } else {
+        HttpResponse::ServiceUnavailable().json(problems)
+    }
+}
+
+/// Readiness, fails while the API server, the CRDs or the operator token aren't usable
+#[get("/readyz")]
+async fn readyz(c: Data<State>, client: Data<Client>) -> impl Responder {
+    let problems = c.readiness(client.get_ref().clone()).await;
+    if problems.is_empty() {
+        HttpResponse::Ok().json("ready")
+    } else {
+        HttpResponse::ServiceUnavailable().json(problems)
+    }
+}
+
+/// Records of a zone as a BIND zonefile, behind the same bearer token as `/reconcile`
+#[get("/zonefile/{namespace}/{zone}")]
+async fn zonefile_export(
+    client: Data<Client>,
+    token: Data<ReconcileToken>,
+    req: HttpRequest,
+    path: Path<(String, String)>,
+) -> impl Responder {
+    if let Some(refused) = refusal(&token, &req) {
+        return refused;
+    }
+    let (namespace, zone) = path.into_inner();
+    match zonefile::export(client.get_ref().clone(), &namespace, &zone).await {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/dns; charset=utf-8")
+            .body(body),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Validating admission webhook for the Cloudflare kinds
+#[post("/validate")]
+async fn validate(
+    c: Data<State>,
+    client: Data<Client>,
+    review: Json<AdmissionReview<DynamicObject>>,
+) -> impl Responder {
+    HttpResponse::Ok().json(webhook::review(&c, &client, review.into_inner()).await)
+}
+
+/// Defaulting admission webhook for the Cloudflare kinds
+#[post("/mutate")]
+async fn mutate(review: Json<AdmissionReview<DynamicObject>>) -> impl Responder {
+    HttpResponse::Ok().json(webhook::defaults::review(review.into_inner()))
+}
+
+/// Conversion webhook between the versions of the Cloudflare kinds
+#[post("/convert")]
+async fn convert(review: Json<ConversionReview>) -> impl Responder {
+    HttpResponse::Ok().json(webhook::convert::review(review.into_inner()))
+}
+
+/// Bearer token callers of `/reconcile` and the other admin endpoints have to present, they are off without one
+#[derive(Clone)]
+struct ReconcileToken(Option<String>);
+
+/// Reconcile an object right away, for CI to call after applying manifests
+#[post("/reconcile/{kind}/{namespace}/{name}")]
+async fn reconcile(
+    c: Data<State>,
+    token: Data<ReconcileToken>,
+    req: HttpRequest,
+    path: Path<(String, String, String)>,
+) -> impl Responder {
+    if let Some(refused) = refusal(&token, &req) {
+        return refused;
+    }
+
+    let (kind, namespace, name) = path.into_inner();
+    if c.triggers().reconcile(&kind, &name, &namespace) {
+        HttpResponse::Accepted().json(format!("queued {kind} {namespace}/{name}"))
+    } else {
+        HttpResponse::NotFound().json(format!("no controller for {kind}"))
+    }
+}
+
+/// Log filter in effect, in `RUST_LOG` syntax
+#[get("/log-level")]
+async fn show_log_level(level: Data<telemetry::LogLevel>) -> impl Responder {
+    HttpResponse::Ok().body(level.current())
+}
+
+/// Change the log filter until the next change or SIGHUP, the body is in `RUST_LOG` syntax
+#[put("/log-level")]
+async fn set_log_level(
+    level: Data<telemetry::LogLevel>,
+    token: Data<ReconcileToken>,
+    req: HttpRequest,
+    directives: String,
+) -> impl Responder {
+    if let Some(refused) = refusal(&token, &req) {
+        return refused;
+    }
+    match level.set(directives.trim()) {
+        Ok(()) => HttpResponse::Ok().body(level.current()),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+/// Answer for callers not presenting the token, the endpoints are off without one
+fn refusal(token: &ReconcileToken, req: &HttpRequest) -> Option<HttpResponse> {
+    let Some(expected) = &token.0 else {
+        return Some(HttpResponse::NotFound().finish());
+    };
+    let presented = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !presented.is_some_and(|presented| constant_time_eq(presented.as_bytes(), expected.as_bytes())) {
+        return Some(HttpResponse::Unauthorized().finish());
+    }
+    None
+}
+
+/// Compare without bailing at the first difference, so timing doesn't leak the token
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Diagnostics, the failing objects and their errors only for callers presenting the token
 #[get("/")]
-async fn index(c: Data<State>, _req: HttpRequest) -> impl Responder {
+async fn index(c: Data<State>, token: Data<ReconcileToken>, req: HttpRequest) -> impl Responder {
     let d = c.diagnostics().await;
+    let d = match refusal(&token, &req) {
+        Some(_) => d.redacted(),
+        None => d,
+    };
     HttpResponse::Ok().json(&d)
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    telemetry::init().await;
+/// Value of `--name value` or `--name=value`
+fn flag(args: &[String], name: &str) -> Option<String> {
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.strip_prefix(name) {
+            Some("") => args.get(i + 1).cloned(),
+            Some(value) => value.strip_prefix('=').map(String::from),
+            None => None,
+        })
+}
+
+fn main() -> anyhow::Result<()> {
+    // `--cf-proxy-url` takes precedence over CLOUDFLARE_PROXY_URL, and both over HTTPS_PROXY
+    let args: Vec<String> = std::env::args().collect();
+    let proxy = flag(&args, "--cf-proxy-url")
+        .or_else(|| std::env::var("CLOUDFLARE_PROXY_URL").ok())
+        .filter(|url| !url.is_empty())
+        .map(|url| url.parse::<reqwest::Url>())
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("--cf-proxy-url: {e}"))?;
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(serve(args, proxy))
+}
+
+async fn serve(args: Vec<String>, proxy: Option<reqwest::Url>) -> anyhow::Result<()> {
+    // `--otlp-endpoint`, `--otlp-protocol` and `--trace-sampling-ratio` take precedence over the
+    // OTEL_* variables, `--no-tracing` turns exporting off
+    let mut tracing = telemetry::TracingConfig::from_env()?;
+    if let Some(endpoint) = flag(&args, "--otlp-endpoint") {
+        tracing.endpoint = Some(endpoint);
+    }
+    if let Some(protocol) = flag(&args, "--otlp-protocol") {
+        tracing.protocol = protocol.parse()?;
+    }
+    if let Some(ratio) = flag(&args, "--trace-sampling-ratio") {
+        tracing.sampling_ratio = telemetry::parse_ratio(&ratio)?;
+    }
+    if args.iter().any(|arg| arg == "--no-tracing") {
+        tracing.disabled = true;
+    }
+    // `--log-format json|text` likewise over LOG_FORMAT
+    let log_format = match flag(&args, "--log-format") {
+        Some(format) => format.parse()?,
+        None => telemetry::LogFormat::from_env()?,
+    };
+    let log_level = telemetry::init(tracing, log_format).await?;
+    tokio::spawn(log_level.clone().reset_on_sighup());
 
     // Initiatilize Kubernetes controller state
-    let state = State::new();
+    let mut state = State::new();
+    // `--namespaces a,b` takes precedence over WATCH_NAMESPACES
+    if let Some(list) = flag(&args, "--namespaces") {
+        state = state.with_namespaces(WatchNamespaces::parse(&list));
+    }
+    // `--default-credentials-secret ns/name/key` likewise over DEFAULT_CREDENTIALS_SECRET, and both
+    // over CLOUDFLARE_API_TOKEN
+    let default_secret = flag(&args, "--default-credentials-secret").or_else(|| {
+        std::env::var("DEFAULT_CREDENTIALS_SECRET")
+            .ok()
+            .filter(|s| !s.is_empty())
+    });
+    if let Some(location) = default_secret {
+        let location = location
+            .parse()
+            .map_err(|e| anyhow::anyhow!("--default-credentials-secret: {e}"))?;
+        state = state.with_operator_token(OperatorToken::from_secret(location));
+    }
+    // `--cloudflare-api-url` likewise over CLOUDFLARE_API_URL, for the China network or a mock server
+    if let Some(url) = flag(&args, "--cloudflare-api-url") {
+        let url = url
+            .parse()
+            .map_err(|e| anyhow::anyhow!("--cloudflare-api-url: {e}"))?;
+        state = state.with_api_url(url);
+    }
+    if let Some(url) = proxy {
+        state = state.with_proxy(url);
+    }
+    // `--cf-debug-http` logs every Cloudflare request, like CLOUDFLARE_DEBUG_HTTP=true
+    if args.iter().any(|arg| arg == "--cf-debug-http") {
+        state = state.with_debug_http();
+    }
     let controller = controller::run(state.clone());
+    let client = Client::try_default().await?;
+    // the API server only calls the webhooks over TLS
+    let webhook_tls = match webhook::tls::from_env()? {
+        Some((dir, port)) => Some((webhook::tls::server_config(&dir)?, port)),
+        None => None,
+    };
+    let reconcile_token = ReconcileToken(
+        std::env::var("RECONCILE_WEBHOOK_TOKEN")
+            .ok()
+            .filter(|t| !t.is_empty()),
+    );
 
     // Start web server
     let server = HttpServer::new(move || {
         App::new()
             .app_data(Data::new(state.clone()))
-            .wrap(middleware::Logger::default().exclude("/health"))
+            .app_data(Data::new(client.clone()))
+            .app_data(Data::new(reconcile_token.clone()))
+            .app_data(Data::new(log_level.clone()))
+            .wrap(
+                middleware::Logger::default()
+                    .exclude("/health")
+                    .exclude("/healthz")
+                    .exclude("/readyz"),
+            )
             .service(index)
             .service(health)
+            .service(healthz)
+            .service(readyz)
             .service(metrics)
+            .service(zonefile_export)
+            .service(reconcile)
+            .service(show_log_level)
+            .service(set_log_level)
+            .service(validate)
+            .service(mutate)
+            .service(convert)
     })
-    .bind("0.0.0.0:8080")?
+    .bind("0.0.0.0:8080")?;
+    let server = match webhook_tls {
+        Some((config, port)) => server.bind_rustls_0_23(("0.0.0.0", port), config)?,
+        None => server,
+    }
     .shutdown_timeout(5);
 
     // Both runtimes implements graceful shutdown, so poll until both are done
@@ -1,27 +1,51 @@
-use crate::Error;
-use kube::ResourceExt;
+use crate::{
+    Error,
+    cf_client::{Latency, Quota, Retries},
+    conditions::Conditions,
+};
+use kube::{Resource, ResourceExt, core::object::HasStatus};
 use opentelemetry::trace::TraceId;
 use prometheus_client::{
     encoding::EncodeLabelSet,
-    metrics::{counter::Counter, exemplar::HistogramWithExemplars, family::Family},
+    metrics::{counter::Counter, exemplar::HistogramWithExemplars, family::Family, gauge::Gauge},
     registry::{Registry, Unit},
 };
-use std::sync::Arc;
+use std::sync::{Arc, atomic::AtomicU64};
 use tokio::time::Instant;
 
 #[derive(Clone)]
 pub struct Metrics {
     pub reconcile: ReconcileMetrics,
+    pub analytics: AnalyticsMetrics,
+    pub account: AccountMetrics,
+    pub client: ClientMetrics,
+    pub resources: ResourceMetrics,
+    pub controller: ControllerMetrics,
     pub registry: Arc<Registry>,
 }
 
 impl Default for Metrics {
     fn default() -> Self {
-        let mut registry = Registry::with_prefix("doc_ctrl_reconcile");
-        let reconcile = ReconcileMetrics::default().register(&mut registry);
+        let mut registry = Registry::default();
+        let controller = registry.sub_registry_with_prefix("doc_ctrl_reconcile");
+        let reconcile = ReconcileMetrics::default().register(controller);
+        let analytics =
+            AnalyticsMetrics::default().register(controller.sub_registry_with_prefix("analytics"));
+        let account = AccountMetrics::default().register(controller.sub_registry_with_prefix("account"));
+        let client = ClientMetrics::default().register(controller.sub_registry_with_prefix("client"));
+        let runtime =
+            ControllerMetrics::default().register(controller.sub_registry_with_prefix("controller"));
+        // named like kube-state-metrics, alerts on it don't need to know the operator
+        let resources =
+            ResourceMetrics::default().register(registry.sub_registry_with_prefix("cloudflare_resource"));
         Self {
             registry: Arc::new(registry),
             reconcile,
+            analytics,
+            account,
+            client,
+            resources,
+            controller: runtime,
         }
     }
 }
@@ -43,29 +67,64 @@ impl TryFrom<&TraceId> for TraceLabel {
     }
 }
 
+type DurationHistogram = HistogramWithExemplars<TraceLabel>;
+
 #[derive(Clone)]
 pub struct ReconcileMetrics {
-    pub runs: Counter,
+    pub runs: Family<KindLabels, Counter>,
     pub failures: Family<ErrorLabels, Counter>,
-    pub duration: HistogramWithExemplars<TraceLabel>,
+    pub drift_detected: Family<InstanceLabels, Counter>,
+    pub consecutive_failures: Family<InstanceLabels, Gauge>,
+    pub in_flight: Family<KindLabels, Gauge>,
+    pub duration: Family<KindLabels, DurationHistogram, fn() -> DurationHistogram>,
 }
 
 impl Default for ReconcileMetrics {
     fn default() -> Self {
         Self {
-            runs: Counter::default(),
+            runs: Family::<KindLabels, Counter>::default(),
             failures: Family::<ErrorLabels, Counter>::default(),
-            duration: HistogramWithExemplars::new([0.01, 0.1, 0.25, 0.5, 1., 5., 15., 60.].into_iter()),
+            drift_detected: Family::<InstanceLabels, Counter>::default(),
+            consecutive_failures: Family::<InstanceLabels, Gauge>::default(),
+            in_flight: Family::<KindLabels, Gauge>::default(),
+            duration: Family::new_with_constructor(|| {
+                HistogramWithExemplars::new([0.01, 0.1, 0.25, 0.5, 1., 5., 15., 60.].into_iter())
+            }),
+        }
+    }
+}
+
+/// Kind and namespace of the reconciled object
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct KindLabels {
+    pub kind: String,
+    pub namespace: String,
+}
+
+impl KindLabels {
+    fn of<K: Resource<DynamicType = ()>>(doc: &K) -> Self {
+        Self {
+            kind: K::kind(&()).to_string(),
+            namespace: doc.namespace().unwrap_or_default(),
         }
     }
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 pub struct ErrorLabels {
+    pub kind: String,
+    pub namespace: String,
     pub instance: String,
     pub error: String,
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct InstanceLabels {
+    pub kind: String,
+    pub namespace: String,
+    pub instance: String,
+}
+
 impl ReconcileMetrics {
     /// Register API metrics to start tracking them.
     pub fn register(self, r: &mut Registry) -> Self {
@@ -77,27 +136,85 @@ impl ReconcileMetrics {
         );
         r.register("failures", "reconciliation errors", self.failures.clone());
         r.register("runs", "reconciliations", self.runs.clone());
+        r.register("in_flight", "reconciles running", self.in_flight.clone());
+        r.register(
+            "drift_detected",
+            "remote objects found out of sync with their spec",
+            self.drift_detected.clone(),
+        );
+        r.register(
+            "consecutive_failures",
+            "reconciles failed in a row, until one succeeds",
+            self.consecutive_failures.clone(),
+        );
         self
     }
 
     pub fn set_failure<K>(&self, doc: &K, e: &Error)
     where
-        K: ResourceExt,
+        K: Resource<DynamicType = ()>,
     {
+        let KindLabels { kind, namespace } = KindLabels::of(doc);
         self.failures
             .get_or_create(&ErrorLabels {
+                kind,
+                namespace,
                 instance: doc.name_any(),
                 error: e.metric_label(),
             })
             .inc();
     }
 
-    pub fn count_and_measure(&self, trace_id: &TraceId) -> ReconcileMeasurer {
-        self.runs.inc();
+    pub fn set_consecutive_failures<K>(&self, doc: &K, failures: u32)
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        let KindLabels { kind, namespace } = KindLabels::of(doc);
+        self.consecutive_failures
+            .get_or_create(&InstanceLabels {
+                kind,
+                namespace,
+                instance: doc.name_any(),
+            })
+            .set(failures.into());
+    }
+
+    /// Drop the failure streak of the `kind` object `name` in `namespace`, a reconcile of it succeeded
+    pub fn clear_consecutive_failures(&self, kind: &str, namespace: Option<&str>, name: &str) {
+        self.consecutive_failures.remove(&InstanceLabels {
+            kind: kind.to_string(),
+            namespace: namespace.unwrap_or_default().to_string(),
+            instance: name.to_string(),
+        });
+    }
+
+    pub fn set_drift<K>(&self, doc: &K)
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        let KindLabels { kind, namespace } = KindLabels::of(doc);
+        self.drift_detected
+            .get_or_create(&InstanceLabels {
+                kind,
+                namespace,
+                instance: doc.name_any(),
+            })
+            .inc();
+    }
+
+    pub fn count_and_measure<K>(&self, doc: &K, trace_id: &TraceId) -> ReconcileMeasurer
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        let labels = KindLabels::of(doc);
+        self.runs.get_or_create(&labels).inc();
+        let in_flight = self.in_flight.get_or_create(&labels).clone();
+        in_flight.inc();
         ReconcileMeasurer {
             start: Instant::now(),
             labels: trace_id.try_into().ok(),
-            metric: self.duration.clone(),
+            metric: self.duration.get_or_create(&labels).clone(),
+            in_flight,
         }
     }
 }
@@ -108,7 +225,8 @@ impl ReconcileMetrics {
 pub struct ReconcileMeasurer {
     start: Instant,
     labels: Option<TraceLabel>,
-    metric: HistogramWithExemplars<TraceLabel>,
+    metric: DurationHistogram,
+    in_flight: Gauge,
 }
 
 impl Drop for ReconcileMeasurer {
@@ -118,5 +236,202 @@ impl Drop for ReconcileMeasurer {
         let labels = self.labels.take();
         self.metric
             .observe(duration, labels, Some(std::time::SystemTime::now()));
+        self.in_flight.dec();
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ZoneLabels {
+    pub namespace: String,
+    pub zone: String,
+}
+
+/// Zone traffic as reported by the Cloudflare analytics API over the last polling window
+#[derive(Clone, Default)]
+pub struct AnalyticsMetrics {
+    pub requests: Family<ZoneLabels, Gauge>,
+    pub cached_requests: Family<ZoneLabels, Gauge>,
+    pub threats: Family<ZoneLabels, Gauge>,
+    pub origin_errors: Family<ZoneLabels, Gauge>,
+    pub bytes: Family<ZoneLabels, Gauge>,
+    pub cache_ratio: Family<ZoneLabels, Gauge<f64, AtomicU64>>,
+}
+
+impl AnalyticsMetrics {
+    /// Register analytics metrics to start exposing them.
+    pub fn register(self, r: &mut Registry) -> Self {
+        r.register("requests", "requests served by the zone", self.requests.clone());
+        r.register(
+            "cached_requests",
+            "requests served from the Cloudflare cache",
+            self.cached_requests.clone(),
+        );
+        r.register("threats", "requests identified as threats", self.threats.clone());
+        r.register(
+            "origin_errors",
+            "requests answered with a 5xx status",
+            self.origin_errors.clone(),
+        );
+        r.register_with_unit(
+            "transferred",
+            "bytes served by the zone",
+            Unit::Bytes,
+            self.bytes.clone(),
+        );
+        r.register(
+            "cache_ratio",
+            "share of requests served from cache",
+            self.cache_ratio.clone(),
+        );
+        self
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct AccountLabels {
+    pub namespace: String,
+    pub account: String,
+}
+
+/// Usage of the Cloudflare accounts, refreshed on every Account resync
+#[derive(Clone, Default)]
+pub struct AccountMetrics {
+    pub zones: Family<AccountLabels, Gauge>,
+    pub zone_quota: Family<AccountLabels, Gauge>,
+    pub members: Family<AccountLabels, Gauge>,
+}
+
+impl AccountMetrics {
+    /// Register account metrics to start exposing them.
+    pub fn register(self, r: &mut Registry) -> Self {
+        r.register("zones", "zones in the account", self.zones.clone());
+        r.register(
+            "zone_quota",
+            "zones the account may hold, when a quota is configured",
+            self.zone_quota.clone(),
+        );
+        r.register("members", "members of the account", self.members.clone());
+        self
+    }
+}
+
+/// State of the shared Cloudflare client cache and the requests of its clients
+#[derive(Clone, Default)]
+pub struct ClientMetrics {
+    pub cached: Gauge,
+    pub retries: Retries,
+    pub quota: Quota,
+    pub latency: Latency,
+}
+
+impl ClientMetrics {
+    /// Register client metrics to start exposing them.
+    pub fn register(self, r: &mut Registry) -> Self {
+        r.register(
+            "cached",
+            "Cloudflare clients cached, one per token",
+            self.cached.clone(),
+        );
+        r.register(
+            "retries",
+            "Cloudflare requests sent again after a transient failure, by reason",
+            self.retries.clone(),
+        );
+        r.register(
+            "rate_limit_remaining",
+            "Requests a token has left in Cloudflare's five minute window, by token hash",
+            self.quota.remaining.clone(),
+        );
+        r.register(
+            "rate_limit_reset_seconds",
+            "Seconds until a token has its full Cloudflare quota back, by token hash",
+            self.quota.reset_seconds.clone(),
+        );
+        r.register(
+            "rate_limited",
+            "Cloudflare requests answered with 429, by token hash",
+            self.quota.rate_limited.clone(),
+        );
+        r.register_with_unit(
+            "request_duration",
+            "Duration of every attempt at a Cloudflare request, by method and route",
+            Unit::Seconds,
+            self.latency.durations.clone(),
+        );
+        self
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ResourceLabels {
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+}
+
+/// Readiness of every managed object, as its status last said
+#[derive(Clone, Default)]
+pub struct ResourceMetrics {
+    pub ready: Family<ResourceLabels, Gauge>,
+}
+
+impl ResourceMetrics {
+    /// Register resource metrics to start exposing them.
+    pub fn register(self, r: &mut Registry) -> Self {
+        r.register(
+            "ready",
+            "whether the Cloudflare object has Ready=True, by kind, namespace and name",
+            self.ready.clone(),
+        );
+        self
+    }
+
+    /// Record whether `doc` is ready, objects being deleted are dropped
+    pub fn track<K, S>(&self, doc: &K)
+    where
+        K: Resource<DynamicType = ()> + HasStatus<Status = S>,
+        S: Conditions,
+    {
+        let labels = ResourceLabels {
+            kind: K::kind(&()).to_string(),
+            namespace: doc.namespace().unwrap_or_default(),
+            name: doc.name_any(),
+        };
+        if doc.meta().deletion_timestamp.is_some() {
+            self.ready.remove(&labels);
+            return;
+        }
+        let ready = doc.status().is_some_and(|status| status.is_ready());
+        self.ready.get_or_create(&labels).set(i64::from(ready));
+    }
+}
+
+/// Kind of objects a controller reconciles
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ControllerLabels {
+    pub kind: String,
+}
+
+/// The work queue and watch of every controller
+#[derive(Clone, Default)]
+pub struct ControllerMetrics {
+    pub pending: Family<ControllerLabels, Gauge>,
+    pub relists: Family<ControllerLabels, Counter>,
+}
+
+impl ControllerMetrics {
+    /// Register controller metrics to start exposing them.
+    pub fn register(self, r: &mut Registry) -> Self {
+        r.register(
+            "pending",
+            "objects whose spec changed since their status was last written, by kind",
+            self.pending.clone(),
+        );
+        r.register(
+            "relists",
+            "full lists of the watched objects, the first one and every one after the watch broke",
+            self.relists.clone(),
+        );
+        self
     }
 }
@@ -0,0 +1,81 @@
+//! Limiting the operator to some namespaces
+use k8s_openapi::NamespaceResourceScope;
+use kube::{
+    Client, Resource,
+    api::{Api, ListParams},
+};
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+
+/// Namespaces the controllers watch, the whole cluster when empty
+///
+/// With namespaces, every controller runs once per namespace on namespaced watches, so the operator
+/// gets by with a Role in each of them instead of cluster-wide RBAC. The cluster scoped kinds
+/// (CloudflarePolicy, ZoneBinding and CloudflareCredentials) are still read across the cluster, which
+/// takes one read-only ClusterRole.
+#[derive(Clone, Debug, Default)]
+pub struct WatchNamespaces(Vec<String>);
+
+impl WatchNamespaces {
+    /// Comma separated namespaces, blanks are ignored
+    pub fn parse(list: &str) -> Self {
+        let mut namespaces: Vec<String> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|ns| !ns.is_empty())
+            .map(String::from)
+            .collect();
+        namespaces.sort();
+        namespaces.dedup();
+        Self(namespaces)
+    }
+
+    /// From `WATCH_NAMESPACES`
+    pub fn from_env() -> Self {
+        std::env::var("WATCH_NAMESPACES")
+            .map(|list| Self::parse(&list))
+            .unwrap_or_default()
+    }
+
+    pub fn is_cluster_wide(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// What each controller instance watches, `None` standing for the whole cluster
+    pub fn scopes(&self) -> Vec<Option<String>> {
+        if self.is_cluster_wide() {
+            vec![None]
+        } else {
+            self.0.iter().cloned().map(Some).collect()
+        }
+    }
+
+    /// Objects of every watched namespace
+    pub async fn list<K>(&self, client: &Client) -> kube::Result<Vec<K>>
+    where
+        K: Resource<DynamicType = (), Scope = NamespaceResourceScope> + Clone + DeserializeOwned + Debug,
+    {
+        let mut objects = vec![];
+        for scope in self.scopes() {
+            let api: Api<K> = scoped(client.clone(), scope.as_deref());
+            objects.extend(api.list(&ListParams::default()).await?.items);
+        }
+        Ok(objects)
+    }
+}
+
+/// Api on `namespace`, or on the whole cluster for `None`
+pub fn scoped<K>(client: Client, namespace: Option<&str>) -> Api<K>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>,
+{
+    match namespace {
+        Some(ns) => Api::namespaced(client, ns),
+        None => Api::all(client),
+    }
+}
+
+/// Whether a namespace list of a cluster scoped object names `namespace`, `*` names every namespace
+pub fn selects(list: &[String], namespace: &str) -> bool {
+    list.iter().any(|ns| ns == "*" || ns == namespace)
+}
@@ -0,0 +1,194 @@
+use k8s_openapi::api::core::v1::{LocalObjectReference, SecretKeySelector};
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    cf_client,
+    cloudflare::CloudflareResource,
+    conditions::{Condition, Conditions},
+    reconcile_policy::ReconcilePolicy,
+};
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(Default))]
+#[kube(kind = "PageRule", group = "cloudflare.com", version = "v1alpha1", namespaced)]
+#[kube(status = "PageRuleStatus", shortname = "cfpr", category = "cloudflare")]
+#[kube(
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#
+)]
+#[kube(printcolumn = r#"{"name":"Target", "type":"string", "jsonPath":".spec.target"}"#)]
+#[kube(printcolumn = r#"{"name":"Priority", "type":"integer", "jsonPath":".status.priority"}"#)]
+#[kube(printcolumn = r#"{"name":"Status", "type":"string", "jsonPath":".status.state"}"#)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRuleSpec {
+    /// Zone the rule lives in, the id is looked up once the Zone is ready
+    pub zone_ref: Option<LocalObjectReference>,
+    /// Id of a zone the operator doesn't manage, used when there is no `zoneRef`
+    pub zone_id: Option<String>,
+    pub secret_ref: Option<SecretKeySelector>,
+    pub account_ref: Option<LocalObjectReference>,
+    /// URL pattern the rule applies to, `*` matches any run of characters
+    pub target: String,
+    pub actions: Vec<PageRuleAction>,
+    /// Rules with a higher priority win when several match a URL, ties are broken by namespace and name
+    ///
+    /// Only the order matters, rules with a priority are kept in that order above the other rules of the
+    /// zone, whatever numbers Cloudflare gives them.
+    pub priority: Option<i32>,
+    /// Keep the rule in place but stop applying it
+    pub disabled: Option<bool>,
+    /// `DriftReportOnly` to only compare the rule with Cloudflare, `Enforce` by default
+    pub reconcile_policy: Option<ReconcilePolicy>,
+}
+
+/// A setting the rule changes, the value goes in the field matching its type
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRuleAction {
+    /// Setting to change, like `cache_level`, `forwarding_url` or `always_use_https`
+    pub id: String,
+    /// Value of settings that take a word, like `bypass` for `cache_level` or `on`
+    pub value: Option<String>,
+    /// Value of settings that take a number, like the seconds of `edge_cache_ttl`
+    pub number: Option<i64>,
+    /// Value of `forwarding_url`
+    pub forwarding_url: Option<ForwardingUrl>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardingUrl {
+    pub url: String,
+    /// `301` or `302`
+    pub status_code: u16,
+}
+
+impl From<&PageRuleAction> for cf_client::PageRuleAction {
+    fn from(action: &PageRuleAction) -> Self {
+        let value = match (&action.value, action.number, &action.forwarding_url) {
+            (_, _, Some(forward)) => Some(json!({ "url": forward.url, "status_code": forward.status_code })),
+            (_, Some(number), _) => Some(number.into()),
+            (Some(value), _, _) => Some(value.as_str().into()),
+            _ => None,
+        };
+        cf_client::PageRuleAction {
+            id: action.id.clone(),
+            value,
+        }
+    }
+}
+
+impl PageRule {
+    /// Host part of the target, wildcards included
+    pub fn hostname(&self) -> &str {
+        let target = self.spec.target.trim();
+        let target = target
+            .strip_prefix("https://")
+            .or_else(|| target.strip_prefix("http://"))
+            .unwrap_or(target);
+        target.split('/').next().unwrap_or_default()
+    }
+
+    /// The rule as the page rules API takes it
+    pub fn params(&self) -> cf_client::PageRuleParams {
+        cf_client::PageRuleParams {
+            targets: vec![cf_client::PageRuleTarget {
+                target: "url".into(),
+                constraint: cf_client::PageRuleConstraint {
+                    operator: "matches".into(),
+                    value: self.spec.target.clone(),
+                },
+            }],
+            actions: self.spec.actions.iter().map(Into::into).collect(),
+            // placed by the ordering pass, Cloudflare renumbers rules as they move
+            priority: None,
+            status: if self.spec.disabled == Some(true) {
+                "disabled".into()
+            } else {
+                "active".into()
+            },
+        }
+    }
+
+    /// The fields of `live` that differ from the spec, or `None` when the rule matches
+    ///
+    /// Priority is left to the ordering pass, which sees every rule of the zone.
+    pub fn drift(&self, live: &cf_client::PageRule) -> Option<Vec<&'static str>> {
+        let desired = self.params();
+        let mut differs = vec![];
+        if desired.targets != live.targets {
+            differs.push("target");
+        }
+        // Cloudflare doesn't keep the order actions were sent in
+        let by_id = |actions: &[cf_client::PageRuleAction]| {
+            let mut actions = actions.to_vec();
+            actions.sort_by(|a, b| a.id.cmp(&b.id));
+            actions
+        };
+        if by_id(&desired.actions) != by_id(&live.actions) {
+            differs.push("actions");
+        }
+        if desired.status != live.status {
+            differs.push("disabled");
+        }
+        (!differs.is_empty()).then_some(differs)
+    }
+}
+
+impl CloudflareResource for PageRule {
+    const PERMISSIONS: &'static [&'static str] = &["Page Rules:Edit"];
+
+    fn secret_ref(&self) -> Option<&SecretKeySelector> {
+        self.spec.secret_ref.as_ref()
+    }
+
+    fn zone_ref(&self) -> Option<&LocalObjectReference> {
+        self.spec.zone_ref.as_ref()
+    }
+
+    fn account_ref(&self) -> Option<&LocalObjectReference> {
+        self.spec.account_ref.as_ref()
+    }
+
+    fn reconcile_policy(&self) -> ReconcilePolicy {
+        self.spec.reconcile_policy.unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRuleStatus {
+    /// `Ready`, plus `Reconciling` while a retry is pending and `Stalled` when only a spec change helps
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Generation of the spec the status was written for
+    pub observed_generation: Option<i64>,
+    /// Reconciles failed in a row, gone once one succeeds
+    pub consecutive_failures: Option<u32>,
+    pub rule_id: Option<String>,
+    /// Zone the tracked rule was created in, a change means a new rule
+    pub zone_id: Option<String>,
+    pub error: Option<String>,
+    /// Priority Cloudflare gave the rule
+    pub priority: Option<i32>,
+    /// `active` or `disabled`
+    pub state: Option<String>,
+    pub created_on: Option<String>,
+    pub modified_on: Option<String>,
+}
+
+impl Conditions for PageRuleStatus {
+    fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition> {
+        &mut self.conditions
+    }
+
+    fn set_failed(&mut self, message: &str) {
+        self.error = Some(message.into());
+    }
+}
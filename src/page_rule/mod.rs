@@ -0,0 +1,5 @@
+mod crd;
+mod reconcile;
+
+pub use crd::{ForwardingUrl, PageRule, PageRuleAction, PageRuleSpec, PageRuleStatus};
+pub use reconcile::{DOCUMENT_FINALIZER, run};
@@ -0,0 +1,463 @@
+use crate::{
+    Context, Error, Result, State,
+    cf_client::{CloudflareClient, PageRule as CfPageRule},
+    cloudflare::{self, CloudflareResource},
+    conditions::{Condition, Conditions},
+    deletion_policy::DeletionPolicy,
+    dependency::{wait_for_dependency, wake_dependents},
+    failures,
+    namespaces::scoped,
+    page_rule::{PageRule, PageRuleStatus},
+    pause,
+    reconcile_policy::Drift,
+    settings::ControllerSettings,
+    snapshot::CLOUDFLARE_ID_ANNOTATION,
+    status, telemetry,
+    zone::Zone,
+    zone_binding,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use kube::{
+    Resource,
+    api::{Api, ListParams, ResourceExt},
+    client::Client,
+    runtime::{
+        controller::Action,
+        events::{Event, EventType},
+        finalizer::{Event as Finalizer, finalizer},
+        watcher::Config,
+    },
+};
+use std::{collections::HashMap, sync::Arc};
+use tracing::*;
+pub static DOCUMENT_FINALIZER: &str = "pagerule.cloudflare.com";
+
+#[instrument(skip(ctx, doc), fields(trace_id))]
+async fn reconcile(doc: Arc<PageRule>, ctx: Arc<Context>) -> Result<Action> {
+    let trace_id = telemetry::get_trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        Span::current().record("trace_id", field::display(&trace_id));
+    }
+    let _timer = ctx.metrics.reconcile.count_and_measure(doc.as_ref(), &trace_id);
+    ctx.diagnostics.write().await.last_event = Utc::now();
+    ctx.metrics.resources.track(doc.as_ref());
+    let ns = doc.namespace().unwrap(); // doc is namespace scoped
+    let docs: Api<PageRule> = Api::namespaced(ctx.client.clone(), &ns);
+
+    info!("Reconciling PageRule \"{}\" in {}", doc.name_any(), ns);
+    if pause::is_paused(doc.as_ref()) {
+        return pause::hold(doc.as_ref(), ctx.client.clone()).await;
+    }
+    let finalize = finalizer(&docs, DOCUMENT_FINALIZER, doc.clone(), |event| async {
+        match event {
+            Finalizer::Apply(doc) => doc.reconcile(ctx.clone()).await,
+            Finalizer::Cleanup(doc) => doc.cleanup(ctx.clone()).await,
+        }
+    });
+    let result = ctx
+        .audit
+        .record(doc.as_ref(), &ctx.recorder, finalize)
+        .await
+        .map_err(|e| Error::FinalizerError(Box::new(e)));
+    cloudflare::preflight::forbidden(doc.as_ref(), &ctx, result).await
+}
+
+fn error_policy(doc: Arc<PageRule>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}", error);
+    ctx.metrics.reconcile.set_failure(doc.as_ref(), error);
+    failures::requeue(doc, error, &ctx)
+}
+
+impl PageRule {
+    // Reconcile (for non-finalizer related changes)
+    async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action> {
+        let ns = self.namespace().unwrap(); // doc is namespace scoped
+        let name = self.name_any();
+
+        let zone_name = self.spec.zone_ref.as_ref().map(|z_ref| z_ref.name.as_str());
+        if let Some(reason) = zone_binding::check(ctx.client.clone(), &ns, zone_name, self.hostname()).await?
+        {
+            warn!("PageRule \"{}\": {}", name, reason);
+            // nothing the operator can do about it until the bindings or the spec change
+            let mut status = PageRuleStatus {
+                error: Some(reason.clone()),
+                ..self.status.clone().unwrap_or_default()
+            };
+            status.set_stalled("Denied", reason, self.meta().generation);
+            status::patch(self, ctx.client.clone(), &status).await?;
+            return Ok(Action::requeue(ctx.settings.requeue));
+        }
+
+        let zone_id = match &self.spec.zone_ref {
+            Some(z_ref) => match wait_for_dependency::<Zone>(ctx.client.clone(), &ns, &z_ref.name).await? {
+                // a ready zone always has an id
+                Ok(zone) => zone.status.and_then(|s| s.id).unwrap_or_default(),
+                Err(blocked) => {
+                    warn!("PageRule \"{}\": {}", name, blocked.message());
+                    self.publish(&ctx, "DependencyNotReady", blocked.message()).await;
+                    let mut status = PageRuleStatus {
+                        error: Some(blocked.message()),
+                        ..self.status.clone().unwrap_or_default()
+                    };
+                    status.set_reconciling("DependencyNotReady", blocked.message(), self.meta().generation);
+                    status::patch(self, ctx.client.clone(), &status).await?;
+                    // the Zone watch wakes us up once it's ready, this is just a fallback
+                    return Ok(Action::requeue(ctx.settings.requeue));
+                }
+            },
+            None => match &self.spec.zone_id {
+                Some(zone_id) => zone_id.clone(),
+                None => {
+                    let message = "Either zoneRef or zoneId has to be set".to_string();
+                    let mut status = PageRuleStatus {
+                        error: Some(message.clone()),
+                        ..self.status.clone().unwrap_or_default()
+                    };
+                    status.set_stalled("MissingZone", message, self.meta().generation);
+                    status::patch(self, ctx.client.clone(), &status).await?;
+                    return Ok(Action::await_change());
+                }
+            },
+        };
+
+        // the token may be resolved through the zone, so only ask for a client once the zone is usable
+        let (cf_client, source) = match ctx.provider.get_client_with_source(self, &ns).await {
+            Ok(resolved) => resolved,
+            Err(e) => return cloudflare::credentials_unavailable(self, &ctx, e).await,
+        };
+        let mut drift = Drift::default();
+        let converged = if self.report_only() {
+            self.observe(&ctx, &cf_client, &zone_id, &mut drift).await
+        } else {
+            match self.converge(&ctx, &cf_client, &zone_id).await {
+                Ok(rule) => self.order_zone(&ctx, &cf_client, &zone_id, rule).await.map(Some),
+                Err(e) => Err(e),
+            }
+        };
+        let mut status = match converged {
+            Ok(None) => PageRuleStatus {
+                conditions: self.conditions().to_vec(),
+                zone_id: Some(zone_id),
+                ..Default::default()
+            },
+            Ok(Some(rule)) => PageRuleStatus {
+                conditions: self.conditions().to_vec(),
+                observed_generation: None, // set by status::patch
+                consecutive_failures: None,
+                rule_id: Some(rule.id),
+                zone_id: Some(zone_id),
+                error: None,
+                priority: Some(rule.priority),
+                state: Some(rule.status),
+                created_on: rule.created_on.map(|at| at.to_rfc3339()),
+                modified_on: rule.modified_on.map(|at| at.to_rfc3339()),
+            },
+            Err(e) => {
+                warn!("PageRule \"{}\": {:?}", name, e);
+                PageRuleStatus {
+                    error: Some(e.to_string()),
+                    // keep tracking the rule we already have
+                    ..self.status.clone().unwrap_or_default()
+                }
+            }
+        };
+        match &status.error {
+            // retried on the next requeue
+            Some(error) => status.set_reconciling("SyncFailed", error.clone(), self.meta().generation),
+            None => status.set_ready("Synced", self.meta().generation),
+        }
+        status.set_condition(source.condition(self.conditions(), self.meta().generation));
+        status.set_condition(source.validity(self.conditions(), self.meta().generation));
+        drift.apply(&mut status, self.meta().generation);
+        status::patch(self, ctx.client.clone(), &status).await?;
+
+        Ok(Action::requeue(ctx.settings.requeue))
+    }
+
+    /// Create the rule, or bring the one we created before back to the spec when it was edited elsewhere
+    async fn converge(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        zone_id: &str,
+    ) -> anyhow::Result<CfPageRule> {
+        let status = self.status.clone().unwrap_or_default();
+        let tracked_id = match (&status.zone_id, &status.rule_id) {
+            // the zone was recreated or the spec points elsewhere, the old rule is not ours to update
+            (Some(tracked_zone), Some(rule_id)) if tracked_zone != zone_id => {
+                self.release(ctx, cf_client, tracked_zone, rule_id).await?;
+                None
+            }
+            (_, rule_id) => rule_id.as_deref(),
+        };
+        let tracked_id = tracked_id
+            // restored from a snapshot
+            .or_else(|| {
+                self.annotations()
+                    .get(CLOUDFLARE_ID_ANNOTATION)
+                    .map(String::as_str)
+            });
+        let existing = match tracked_id {
+            Some(rule_id) => cf_client.get_page_rule(zone_id, rule_id).await?,
+            None => None,
+        };
+        match existing {
+            Some(rule) => {
+                let Some(drifted) = self.drift(&rule) else {
+                    return Ok(rule);
+                };
+                let note = format!(
+                    "Updated page rule `{}` {} to match the spec",
+                    rule.id,
+                    drifted.join(", ")
+                );
+                let updated = cf_client
+                    .update_page_rule(zone_id, &rule.id, self.params())
+                    .await?;
+                if status::spec_changed(self) {
+                    self.publish(ctx, "Updated", note).await;
+                } else {
+                    warn!("PageRule \"{}\" drifted: {}", self.name_any(), note);
+                    ctx.metrics.reconcile.set_drift(self);
+                    self.publish(ctx, "DriftCorrected", note).await;
+                }
+                Ok(updated)
+            }
+            None => {
+                let rule = cf_client.create_page_rule(zone_id, self.params()).await?;
+                info!("Created page rule {} in zone {}", rule.id, zone_id);
+                self.publish(ctx, "Created", format!("Created page rule `{}`", rule.id))
+                    .await;
+                Ok(rule)
+            }
+        }
+    }
+
+    /// Compare the rule with the spec without touching it, for `DriftReportOnly`
+    ///
+    /// Without a tracked rule, the one of the zone with the spec's target is compared. Order isn't
+    /// checked, that's a property of the whole zone.
+    async fn observe(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        zone_id: &str,
+        drift: &mut Drift,
+    ) -> anyhow::Result<Option<CfPageRule>> {
+        let status = self.status.clone().unwrap_or_default();
+        let tracked_id = status
+            .rule_id
+            .filter(|_| status.zone_id.as_deref() == Some(zone_id))
+            .or_else(|| self.annotations().get(CLOUDFLARE_ID_ANNOTATION).cloned());
+        let existing = match tracked_id {
+            Some(rule_id) => cf_client.get_page_rule(zone_id, &rule_id).await?,
+            None => {
+                let targets = self.params().targets;
+                cf_client
+                    .list_page_rules(zone_id)
+                    .await?
+                    .into_iter()
+                    .find(|rule| rule.targets == targets)
+            }
+        };
+        match &existing {
+            Some(rule) => {
+                if let Some(drifted) = self.drift(rule) {
+                    let note = format!(
+                        "Page rule `{}` {} differ from the spec",
+                        rule.id,
+                        drifted.join(", ")
+                    );
+                    drift.report(self, ctx, note).await;
+                }
+            }
+            None => {
+                let note = format!("No page rule for {} in zone `{zone_id}`", self.spec.target);
+                drift.report(self, ctx, note).await;
+            }
+        }
+        Ok(existing)
+    }
+
+    /// Remove a rule from the zone the spec no longer points at, unless the deletion policy keeps it
+    async fn release(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        zone_id: &str,
+        rule_id: &str,
+    ) -> anyhow::Result<()> {
+        if DeletionPolicy::abandons(self) {
+            info!("Leaving page rule {} in zone {} behind", rule_id, zone_id);
+            return Ok(());
+        }
+        // a rule of a deleted zone is already gone, which counts as deleted
+        cf_client.delete_page_rule(zone_id, rule_id).await?;
+        self.publish(
+            ctx,
+            "Retargeted",
+            format!("Deleted page rule `{rule_id}` from zone `{zone_id}` after the spec moved it"),
+        )
+        .await;
+        Ok(())
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|s| s.conditions.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Keep the rules of the zone that declare a priority in that order, above the rules made elsewhere
+    ///
+    /// Every PageRule of the zone runs this pass, so the zone ends up ordered whichever of them syncs
+    /// first. Returns `rule` as it is after the pass.
+    async fn order_zone(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        zone_id: &str,
+        rule: CfPageRule,
+    ) -> anyhow::Result<CfPageRule> {
+        let mut declared: Vec<(i32, String, String)> = ctx
+            .namespaces
+            .list::<PageRule>(&ctx.client)
+            .await?
+            .iter()
+            .filter(|sibling| sibling.status.as_ref().and_then(|s| s.zone_id.as_deref()) == Some(zone_id))
+            .filter_map(|sibling| {
+                let key = format!(
+                    "{}/{}",
+                    sibling.namespace().unwrap_or_default(),
+                    sibling.name_any()
+                );
+                let rule_id = sibling.status.as_ref()?.rule_id.clone()?;
+                Some((sibling.spec.priority?, key, rule_id))
+            })
+            .collect();
+        // our own status may not carry the rule id yet
+        if let Some(priority) = self.spec.priority
+            && !declared.iter().any(|(_, _, id)| *id == rule.id)
+        {
+            let key = format!("{}/{}", self.namespace().unwrap_or_default(), self.name_any());
+            declared.push((priority, key, rule.id.clone()));
+        }
+        declared.sort();
+
+        let live = cf_client.list_page_rules(zone_id).await?;
+        let live_priority: HashMap<&str, i32> = live.iter().map(|r| (r.id.as_str(), r.priority)).collect();
+        declared.retain(|(_, _, id)| live_priority.contains_key(id.as_str()));
+
+        // in order when the declared rules hold the top priorities, highest declared on top
+        let top = live.len() as i32;
+        let in_order = declared
+            .iter()
+            .rev()
+            .zip((1..=top).rev())
+            .all(|((_, _, id), expected)| live_priority[id.as_str()] == expected);
+        if in_order {
+            return Ok(rule);
+        }
+
+        // moving each rule to the top, lowest first, leaves them stacked in declared order
+        info!("Reordering {} page rules of zone {}", declared.len(), zone_id);
+        for (_, _, rule_id) in &declared {
+            cf_client.set_page_rule_priority(zone_id, rule_id, top).await?;
+        }
+        self.publish(
+            ctx,
+            "Reordered",
+            format!(
+                "Restored the declared order of {} page rules in the zone",
+                declared.len()
+            ),
+        )
+        .await;
+        // later moves shift the earlier ones down, so read the final priority back
+        Ok(cf_client.get_page_rule(zone_id, &rule.id).await?.unwrap_or(rule))
+    }
+
+    async fn publish(&self, ctx: &Context, reason: &str, note: String) {
+        let event = Event {
+            type_: EventType::Normal,
+            reason: reason.into(),
+            note: Some(note),
+            action: "Reconciling".into(),
+            secondary: None,
+        };
+        if let Err(e) = ctx.recorder.publish(&event, &self.object_ref(&())).await {
+            warn!("failed to publish {} event: {}", reason, e);
+        }
+    }
+
+    // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
+    async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
+        let status = self.status.clone().unwrap_or_default();
+        let (Some(zone_id), Some(rule_id)) = (status.zone_id, status.rule_id) else {
+            // nothing was ever created on the Cloudflare side
+            return Ok(Action::await_change());
+        };
+        if DeletionPolicy::abandons(self) || self.report_only() {
+            self.publish(
+                &ctx,
+                "Abandoned",
+                format!("Left page rule `{rule_id}` in Cloudflare as requested by the deletion or reconcile policy"),
+            )
+            .await;
+            return Ok(Action::await_change());
+        }
+
+        let ns = self.namespace().unwrap(); // doc is namespace scoped
+        let cf_client = ctx.provider.get_client(self, &ns).await?;
+        // an error keeps the finalizer in place, so the object stays around until the delete goes through
+        cf_client.delete_page_rule(&zone_id, &rule_id).await?;
+        self.publish(
+            &ctx,
+            "Deleted",
+            format!("Deleted page rule `{rule_id}` from Cloudflare"),
+        )
+        .await;
+        Ok(Action::await_change())
+    }
+}
+
+/// Initialize the controller and shared state (given the crd is installed)
+pub async fn run(state: State) {
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let scopes = state.namespaces().scopes();
+    let docs: Api<PageRule> = scoped(client.clone(), scopes[0].as_deref());
+    if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
+        error!("CRD is not queryable; {e:?}. Is the CRD installed?");
+        info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
+        std::process::exit(1);
+    }
+
+    let settings = ControllerSettings::from_env("PageRule");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
+        .await;
+    // one controller per watched namespace, they share the context and with it the Cloudflare clients
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        let controller = state
+            .controller("PageRule", scoped::<PageRule>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config());
+        let rules = controller.store();
+        state.health().watch("PageRule", rules.clone());
+        controller
+            // rules waiting for their Zone go as soon as it becomes ready instead of on the next requeue
+            .watches(
+                scoped::<Zone>(client.clone(), ns.as_deref()),
+                Config::default(),
+                wake_dependents(rules, |rule: &PageRule| {
+                    rule.spec.zone_ref.as_ref().map(|z_ref| z_ref.name.as_str())
+                }),
+            )
+            .reconcile_on(state.triggers().page_rule.stream())
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("PageRule", result))
+    }))
+    .await;
+}
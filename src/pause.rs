@@ -0,0 +1,47 @@
+//! Freezing objects for maintenance windows and incidents
+use crate::{
+    Result,
+    conditions::{Condition, Conditions},
+    status,
+};
+use k8s_openapi::NamespaceResourceScope;
+use kube::{Client, Resource, ResourceExt, core::object::HasStatus, runtime::controller::Action};
+use serde::{Serialize, de::DeserializeOwned};
+use std::fmt::Debug;
+use tracing::*;
+
+/// While present, whatever its value, nothing is changed on the Cloudflare side for the object
+///
+/// This includes deleting it, a paused object that is deleted stays around until the annotation goes.
+pub static PAUSED_ANNOTATION: &str = "cloudflare.com/paused";
+
+pub fn is_paused<K: ResourceExt>(obj: &K) -> bool {
+    obj.annotations().contains_key(PAUSED_ANNOTATION)
+}
+
+/// Record `Paused=True` on the status and wait, removing the annotation triggers the next reconcile
+pub async fn hold<K, S>(obj: &K, client: Client) -> Result<Action>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + HasStatus<Status = S>
+        + Clone
+        + DeserializeOwned
+        + Debug,
+    S: Conditions + Default + Clone + Serialize,
+{
+    info!("{} \"{}\" is paused", K::kind(&()), obj.name_any());
+    let mut status = obj.status().cloned().unwrap_or_default();
+    let paused = Condition::new(
+        "Paused",
+        "True",
+        "Annotated",
+        Some(format!(
+            "Reconciliation is paused by the {PAUSED_ANNOTATION} annotation"
+        )),
+        status.conditions(),
+    );
+    status.set_condition(paused.observed(obj.meta().generation));
+
+    status::patch(obj, client, &status).await?;
+    Ok(Action::await_change())
+}
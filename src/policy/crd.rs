@@ -0,0 +1,26 @@
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Restricts what Cloudflare objects may be managed from the namespaces it names
+///
+/// Cluster scoped, so the tenants it restricts can't edit it. Every policy naming a namespace has to
+/// allow an object, an empty list doesn't restrict anything.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(Default))]
+#[kube(kind = "CloudflarePolicy", group = "cloudflare.com", version = "v1alpha1")]
+#[kube(shortname = "cfpolicy", category = "cloudflare")]
+#[serde(rename_all = "camelCase")]
+pub struct CloudflarePolicySpec {
+    /// Namespaces the policy restricts, `*` for every namespace
+    pub namespaces: Vec<String>,
+    /// Names of the Zone objects that may be referenced
+    #[serde(default)]
+    pub allowed_zones: Vec<String>,
+    /// DNS record types that may be managed, e.g. `A` or `CNAME`
+    #[serde(default)]
+    pub allowed_record_types: Vec<String>,
+    /// Hostnames that may be managed, `*.example.com` matches any subdomain of example.com
+    #[serde(default)]
+    pub allowed_hostnames: Vec<String>,
+}
@@ -0,0 +1,64 @@
+use crate::{Error, Result, namespaces, policy::CloudflarePolicy};
+use kube::{
+    Client, ResourceExt,
+    api::{Api, ListParams},
+};
+
+/// Check an object in `namespace` against all policies naming it
+///
+/// Returns the reason of the first denial, `None` when the object is allowed.
+pub async fn check(
+    client: Client,
+    namespace: &str,
+    zone: &str,
+    record_type: &str,
+    hostname: &str,
+) -> Result<Option<String>> {
+    let policies: Api<CloudflarePolicy> = Api::all(client);
+    let policies = policies
+        .list(&ListParams::default())
+        .await
+        .map_err(Error::KubeError)?;
+    Ok(policies
+        .iter()
+        .filter(|policy| namespaces::selects(&policy.spec.namespaces, namespace))
+        .find_map(|policy| violation(policy, zone, record_type, hostname)))
+}
+
+fn violation(policy: &CloudflarePolicy, zone: &str, record_type: &str, hostname: &str) -> Option<String> {
+    let spec = &policy.spec;
+    let reason = if !spec.allowed_zones.is_empty() && !spec.allowed_zones.iter().any(|z| z == zone) {
+        format!("zone {zone} is not allowed")
+    } else if !spec.allowed_record_types.is_empty()
+        && !spec
+            .allowed_record_types
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(record_type))
+    {
+        format!("record type {record_type} is not allowed")
+    } else if !spec.allowed_hostnames.is_empty()
+        && !spec
+            .allowed_hostnames
+            .iter()
+            .any(|p| hostname_matches(p, hostname))
+    {
+        format!("hostname {hostname} is not allowed")
+    } else {
+        return None;
+    };
+    Some(format!(
+        "Denied by cloudflarepolicy/{}: {reason}",
+        policy.name_any()
+    ))
+}
+
+/// Match a hostname against an exact name or a `*.` wildcard pattern (`*` alone matches everything)
+pub fn hostname_matches(pattern: &str, hostname: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    let hostname = hostname.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix('*') {
+        Some("") => true,
+        Some(suffix) if suffix.starts_with('.') => hostname.ends_with(suffix),
+        _ => pattern == hostname,
+    }
+}
@@ -0,0 +1,5 @@
+mod crd;
+mod evaluate;
+
+pub use crd::{CloudflarePolicy, CloudflarePolicySpec};
+pub use evaluate::{check, hostname_matches};
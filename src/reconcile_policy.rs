@@ -0,0 +1,62 @@
+//! Objects the operator only compares with Cloudflare, for onboarding existing resources read-only
+use crate::{Context, conditions::Conditions};
+use kube::{
+    Resource, ResourceExt,
+    runtime::events::{Event, EventType},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::*;
+
+/// What the controller may do about differences between the spec and Cloudflare
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum ReconcilePolicy {
+    /// Create, update and delete on the Cloudflare side to match the spec
+    #[default]
+    Enforce,
+    /// Only look, differences are reported and nothing is written to Cloudflare, not even on deletion
+    DriftReportOnly,
+}
+
+/// Differences an object's policy keeps the controller from correcting
+#[derive(Debug, Default)]
+pub struct Drift(Vec<String>);
+
+impl Drift {
+    /// Count the difference in the drift metric and announce it as a `DriftDetected` Warning
+    pub async fn report<K>(&mut self, obj: &K, ctx: &Context, note: String)
+    where
+        K: Resource<DynamicType = ()> + ResourceExt,
+    {
+        warn!(
+            "{} \"{}\" drifted, not corrected: {}",
+            K::kind(&()),
+            obj.name_any(),
+            note
+        );
+        ctx.metrics.reconcile.set_drift(obj);
+        let event = Event {
+            type_: EventType::Warning,
+            reason: "DriftDetected".into(),
+            note: Some(note.clone()),
+            action: "Reconciling".into(),
+            secondary: None,
+        };
+        if let Err(e) = ctx.recorder.publish(&event, &obj.object_ref(&())).await {
+            warn!("failed to publish DriftDetected event: {}", e);
+        }
+        self.0.push(note);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `Ready=False` with reason `DriftDetected` when anything was reported, it stays until Cloudflare or
+    /// the spec changes
+    pub fn apply<S: Conditions>(&self, status: &mut S, generation: Option<i64>) {
+        if !self.is_empty() {
+            status.set_stalled("DriftDetected", self.0.join("; "), generation);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Queue settings of the controllers
+use kube::runtime::controller::Config;
+use tokio::time::Duration;
+
+/// How a controller schedules its reconciles
+///
+/// Read from `CONTROLLER_<SETTING>`, a controller takes `CONTROLLER_<KIND>_<SETTING>` over it, like
+/// `CONTROLLER_DNSRECORD_CONCURRENCY`. Durations are in seconds.
+#[derive(Clone, Debug)]
+pub struct ControllerSettings {
+    /// Objects reconciled at the same time, 0 for no limit
+    pub concurrency: u16,
+    /// Quiet period after a change before it's reconciled, changes arriving in it are reconciled once
+    pub debounce: Duration,
+    /// Wait before looking at an object that is in sync again
+    pub requeue: Duration,
+    /// Wait before retrying an object whose reconcile failed
+    pub retry: Duration,
+    /// Failures in a row after which an object is marked `Stalled`, 0 never
+    pub stall_after: u32,
+    /// Wait before retrying a stalled object instead of `retry`
+    pub stalled_retry: Duration,
+}
+
+impl Default for ControllerSettings {
+    fn default() -> Self {
+        Self {
+            concurrency: 0,
+            debounce: Duration::ZERO,
+            requeue: Duration::from_secs(5 * 60),
+            retry: Duration::from_secs(5 * 60),
+            stall_after: 5,
+            stalled_retry: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl ControllerSettings {
+    pub fn from_env(kind: &str) -> Self {
+        let var = |setting: &str| {
+            std::env::var(format!("CONTROLLER_{}_{setting}", kind.to_uppercase()))
+                .or_else(|_| std::env::var(format!("CONTROLLER_{setting}")))
+                .ok()
+        };
+        let seconds = |setting: &str| {
+            var(setting)
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs)
+        };
+        let defaults = Self::default();
+        Self {
+            concurrency: var("CONCURRENCY")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.concurrency),
+            debounce: seconds("DEBOUNCE_SECONDS").unwrap_or(defaults.debounce),
+            // a zero interval would spin on every object
+            requeue: seconds("REQUEUE_SECONDS")
+                .filter(|d| !d.is_zero())
+                .unwrap_or(defaults.requeue),
+            retry: seconds("RETRY_SECONDS")
+                .filter(|d| !d.is_zero())
+                .unwrap_or(defaults.retry),
+            stall_after: var("STALL_AFTER_FAILURES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.stall_after),
+            stalled_retry: seconds("STALLED_RETRY_SECONDS")
+                .filter(|d| !d.is_zero())
+                .unwrap_or(defaults.stalled_retry),
+        }
+    }
+
+    /// Whether `failures` in a row make an object stalled
+    pub fn stalls(&self, failures: u32) -> bool {
+        self.stall_after > 0 && failures >= self.stall_after
+    }
+
+    /// Wait before retrying an object that failed `failures` times in a row
+    pub fn retry_after(&self, failures: u32) -> Duration {
+        if self.stalls(failures) {
+            self.stalled_retry
+        } else {
+            self.retry
+        }
+    }
+
+    /// Runtime config to hand to `Controller::with_config`
+    pub fn controller_config(&self) -> Config {
+        Config::default()
+            .concurrency(self.concurrency)
+            .debounce(self.debounce)
+    }
+}
@@ -0,0 +1,309 @@
+//! Snapshots of everything the operator manages, to rebuild a lost cluster without touching Cloudflare
+//!
+//! Each object is written back as a plain manifest carrying the id of its Cloudflare counterpart, so
+//! applying a snapshot to a fresh cluster adopts the existing resources instead of creating new ones.
+use crate::{
+    State,
+    account::Account,
+    account_member::AccountMember,
+    deletion_policy::DELETION_POLICY_ANNOTATION,
+    dns_record::DNSRecord,
+    namespaces::WatchNamespaces,
+    page_rule::PageRule,
+    tunnel::Tunnel,
+    zone::Zone,
+    zone_set::{ZONE_SET_LABEL, ZoneSet},
+};
+use chrono::Utc;
+use k8s_openapi::{NamespaceResourceScope, api::core::v1::ConfigMap};
+use kube::{
+    Client, Resource, ResourceExt,
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use std::{collections::BTreeMap, fmt::Debug};
+use tokio::time::Duration;
+use tracing::*;
+
+/// Id of the Cloudflare resource an object was managing, picked up when the object has no status yet
+pub static CLOUDFLARE_ID_ANNOTATION: &str = "cloudflare.com/id";
+
+/// Name of the first ConfigMap the periodic snapshot is written to, the others get `-1`, `-2`, ... appended
+pub static SNAPSHOT_CONFIGMAP: &str = "cloudflare-operator-snapshot";
+
+/// Label on every ConfigMap of the periodic snapshot, parts left over from a larger one are deleted by it
+pub static SNAPSHOT_LABEL: &str = "cloudflare.com/snapshot";
+
+/// Bytes of manifests a ConfigMap of the snapshot takes, the API server refuses objects above 1 MiB
+const CONFIGMAP_BUDGET: usize = 900 * 1024;
+
+/// How often a snapshot is taken when `CLOUDFLARE_SNAPSHOT_INTERVAL` isn't set
+const DEFAULT_INTERVAL: u64 = 60 * 60;
+
+/// Write a snapshot to ConfigMaps at a fixed interval
+///
+/// Only runs when `CLOUDFLARE_SNAPSHOT_NAMESPACE` names the namespace for the ConfigMaps, which keep one
+/// key per kind and namespace, spread over as many ConfigMaps as their size takes. Copy them out of the
+/// cluster (or use `cfctl snapshot`) for the snapshot to survive the cluster.
+pub async fn run(state: State) {
+    let Ok(namespace) = std::env::var("CLOUDFLARE_SNAPSHOT_NAMESPACE") else {
+        // disabled, but we are raced against the controllers so never finish
+        return futures::future::pending().await;
+    };
+    let interval = std::env::var("CLOUDFLARE_SNAPSHOT_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_INTERVAL);
+
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    loop {
+        ticker.tick().await;
+        if let Err(e) = write(client.clone(), state.namespaces(), &namespace).await {
+            warn!("snapshot failed: {e:?}");
+        }
+    }
+}
+
+async fn write(client: Client, namespaces: &WatchNamespaces, namespace: &str) -> anyhow::Result<()> {
+    let parts = pack(documents(client.clone(), namespaces).await?, CONFIGMAP_BUDGET);
+    let api = Api::<ConfigMap>::namespaced(client, namespace);
+    let taken = Utc::now().to_rfc3339();
+    let mut names = vec![];
+    for (i, data) in parts.iter().enumerate() {
+        let name = match i {
+            0 => SNAPSHOT_CONFIGMAP.to_string(),
+            i => format!("{SNAPSHOT_CONFIGMAP}-{i}"),
+        };
+        let config_map = json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": name,
+                "labels": { SNAPSHOT_LABEL: "true" },
+                "annotations": {
+                    "cloudflare.com/snapshot-time": taken,
+                    "cloudflare.com/snapshot-parts": parts.len().to_string(),
+                },
+            },
+            "data": data,
+        });
+        api.patch(
+            &name,
+            &PatchParams::apply("snapshot").force(),
+            &Patch::Apply(config_map),
+        )
+        .await?;
+        names.push(name);
+    }
+    // a smaller snapshot than the last one leaves parts of the last one behind
+    let labelled = api
+        .list(&ListParams::default().labels(&format!("{SNAPSHOT_LABEL}=true")))
+        .await?;
+    for stale in labelled.iter().filter(|cm| !names.contains(&cm.name_any())) {
+        api.delete(&stale.name_any(), &DeleteParams::default()).await?;
+    }
+    info!("wrote snapshot to {} ConfigMaps in {}", names.len(), namespace);
+    Ok(())
+}
+
+/// Manifests of every managed object in `namespaces`, as multi-document YAML keyed by
+/// `<kind>.<namespace>.yaml`
+///
+/// They can be applied in any order, objects wait for the ones they depend on.
+pub async fn take(client: Client, namespaces: &WatchNamespaces) -> anyhow::Result<BTreeMap<String, String>> {
+    Ok(documents(client, namespaces)
+        .await?
+        .into_iter()
+        .map(|(stem, docs)| (format!("{stem}.yaml"), docs.join("---\n")))
+        .collect())
+}
+
+/// YAML documents of every managed object, by `<kind>.<namespace>`
+async fn documents(
+    client: Client,
+    namespaces: &WatchNamespaces,
+) -> anyhow::Result<BTreeMap<String, Vec<String>>> {
+    let mut snapshot: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut add = |kind: &str, manifests: Vec<Value>| -> anyhow::Result<()> {
+        for manifest in &manifests {
+            let namespace = manifest["metadata"]["namespace"].as_str().unwrap_or_default();
+            snapshot
+                .entry(format!("{}.{namespace}", kind.to_lowercase()))
+                .or_default()
+                .push(serde_yaml::to_string(manifest)?);
+        }
+        Ok(())
+    };
+
+    add(
+        "Account",
+        manifests::<Account>(&client, namespaces, |_| None).await?,
+    )?;
+    add(
+        "ZoneSet",
+        manifests::<ZoneSet>(&client, namespaces, |_| None).await?,
+    )?;
+    let zones: Vec<Value> = namespaces
+        .list::<Zone>(&client)
+        .await?
+        .iter()
+        // recreated by their set
+        .filter(|zone| !zone.labels().contains_key(ZONE_SET_LABEL))
+        .map(zone_manifest)
+        .collect();
+    add("Zone", zones)?;
+    add(
+        "DNSRecord",
+        manifests::<DNSRecord>(&client, namespaces, |r| r.status.as_ref()?.record_id.clone()).await?,
+    )?;
+    add(
+        "PageRule",
+        manifests::<PageRule>(&client, namespaces, |r| r.status.as_ref()?.rule_id.clone()).await?,
+    )?;
+    // members are found again by email
+    add(
+        "AccountMember",
+        manifests::<AccountMember>(&client, namespaces, |_| None).await?,
+    )?;
+    // tunnels are found again by name
+    add(
+        "Tunnel",
+        manifests::<Tunnel>(&client, namespaces, |_| None).await?,
+    )?;
+    Ok(snapshot)
+}
+
+/// Spread the documents over ConfigMap data of at most `budget` bytes each
+///
+/// Documents of one key that don't fit go on under `<stem>.1.yaml`, `<stem>.2.yaml`, ... in the next one.
+fn pack(documents: BTreeMap<String, Vec<String>>, budget: usize) -> Vec<BTreeMap<String, String>> {
+    let mut parts = vec![BTreeMap::new()];
+    let mut size = 0;
+    for (stem, docs) in documents {
+        let mut chunk = 0;
+        let mut value = String::new();
+        for doc in docs {
+            if size + value.len() + doc.len() > budget && size + value.len() > 0 {
+                if !value.is_empty() {
+                    let key = chunk_key(&stem, chunk);
+                    parts
+                        .last_mut()
+                        .expect("there is a part")
+                        .insert(key, std::mem::take(&mut value));
+                    chunk += 1;
+                }
+                parts.push(BTreeMap::new());
+                size = 0;
+            }
+            if !value.is_empty() {
+                value.push_str("---\n");
+            }
+            value.push_str(&doc);
+        }
+        if !value.is_empty() {
+            size += value.len();
+            parts
+                .last_mut()
+                .expect("there is a part")
+                .insert(chunk_key(&stem, chunk), value);
+        }
+    }
+    parts
+}
+
+fn chunk_key(stem: &str, chunk: usize) -> String {
+    match chunk {
+        0 => format!("{stem}.yaml"),
+        chunk => format!("{stem}.{chunk}.yaml"),
+    }
+}
+
+async fn manifests<K>(
+    client: &Client,
+    namespaces: &WatchNamespaces,
+    cloudflare_id: fn(&K) -> Option<String>,
+) -> anyhow::Result<Vec<Value>>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + DeserializeOwned
+        + Serialize
+        + Clone
+        + Debug,
+{
+    Ok(namespaces
+        .list::<K>(client)
+        .await?
+        .iter()
+        .map(|obj| manifest(obj, cloudflare_id(obj)))
+        .collect())
+}
+
+/// The object without its status and server-set metadata
+fn manifest<K>(obj: &K, cloudflare_id: Option<String>) -> Value
+where
+    K: Resource<DynamicType = ()> + Serialize,
+{
+    let mut annotations = obj.annotations().clone();
+    // the last applied configuration would undo the changes made for the restore
+    annotations.remove("kubectl.kubernetes.io/last-applied-configuration");
+    if let Some(id) = cloudflare_id {
+        annotations.insert(CLOUDFLARE_ID_ANNOTATION.into(), id);
+    }
+    let spec = serde_json::to_value(obj)
+        .ok()
+        .and_then(|mut value| value.get_mut("spec").map(Value::take))
+        .unwrap_or_default();
+    json!({
+        "apiVersion": K::api_version(&()),
+        "kind": K::kind(&()),
+        "metadata": {
+            "name": obj.name_any(),
+            "namespace": obj.namespace(),
+            "labels": obj.labels(),
+            "annotations": annotations,
+        },
+        "spec": spec,
+    })
+}
+
+/// Zones are bound to their id, which would flip their default deletion policy to abandon, so it's pinned
+fn zone_manifest(zone: &Zone) -> Value {
+    let mut value = manifest(zone, None);
+    let Some(id) = zone.status.as_ref().and_then(|s| s.id.clone()) else {
+        return value;
+    };
+    if zone.spec.zone_id.is_none() && !zone.annotations().contains_key(DELETION_POLICY_ANNOTATION) {
+        value["metadata"]["annotations"][DELETION_POLICY_ANNOTATION] = "delete".into();
+    }
+    value["spec"]["zoneId"] = id.into();
+    value
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn large_snapshots_are_split() {
+        let doc = "x".repeat(40);
+        let documents = BTreeMap::from([
+            ("account.a".to_string(), vec![doc.clone()]),
+            ("dnsrecord.a".to_string(), vec![doc.clone(); 5]),
+        ]);
+        let parts = pack(documents, 100);
+        let keys: Vec<Vec<&String>> = parts.iter().map(|part| part.keys().collect()).collect();
+        assert_eq!(keys, vec![
+            vec!["account.a.yaml", "dnsrecord.a.yaml"],
+            vec!["dnsrecord.a.1.yaml"],
+            vec!["dnsrecord.a.2.yaml"],
+        ]);
+        assert!(
+            parts
+                .iter()
+                .flat_map(|part| part.values())
+                .all(|value| value.len() <= 100)
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! DNSRecords for annotated Gateway API Gateways, pointing their listener hostnames at the Gateway
+//!
+//! Only the fields the sources read are modelled, the Gateway API CRDs come with the Gateway controller.
+use super::{Annotations, targets};
+use crate::{
+    Context, Error, Result, State, dns_record::DNSRecord, namespaces::scoped, settings::ControllerSettings,
+    telemetry,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use kube::{
+    CustomResource, Resource, ResourceExt,
+    client::Client,
+    runtime::{controller::Action, watcher::Config},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+use tracing::*;
+
+/// Listener TLS option asking for a Cloudflare for SaaS custom hostname, so Cloudflare serves the
+/// certificate of the listener hostname
+pub static CUSTOM_HOSTNAME_OPTION: &str = "cloudflare.com/custom-hostname";
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug)]
+#[kube(
+    kind = "Gateway",
+    group = "gateway.networking.k8s.io",
+    version = "v1",
+    namespaced,
+    status = "GatewayStatus",
+    schema = "disabled"
+)]
+pub struct GatewaySpec {
+    #[serde(default)]
+    pub listeners: Vec<Listener>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Listener {
+    pub name: String,
+    /// Any hostname when left out, may start with a `*.` wildcard
+    pub hostname: Option<String>,
+    pub tls: Option<ListenerTls>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ListenerTls {
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct GatewayStatus {
+    #[serde(default)]
+    pub addresses: Vec<GatewayAddress>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GatewayAddress {
+    /// `IPAddress` when left out, `Hostname` or implementation specific
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    pub value: String,
+}
+
+impl Gateway {
+    /// Hostnames of the listeners
+    pub fn hostnames(&self) -> BTreeSet<String> {
+        self.spec
+            .listeners
+            .iter()
+            .filter_map(|listener| listener.hostname.as_ref())
+            .map(|hostname| hostname.trim_end_matches('.').to_lowercase())
+            .collect()
+    }
+
+    /// Where the Gateway is reachable at from its status, as `(ip, hostname)` pairs
+    pub fn addresses(&self) -> impl Iterator<Item = (Option<&str>, Option<&str>)> {
+        self.status
+            .iter()
+            .flat_map(|status| &status.addresses)
+            .filter_map(|address| match address.type_.as_deref() {
+                None | Some("IPAddress") => Some((Some(address.value.as_str()), None)),
+                Some("Hostname") => Some((None, Some(address.value.as_str()))),
+                // nothing DNS can point at
+                Some(_) => None,
+            })
+    }
+
+    /// Whether a listener serving `hostname` asks for a custom hostname
+    pub fn custom_hostname(&self, hostname: &str) -> bool {
+        self.spec
+            .listeners
+            .iter()
+            .filter(|listener| {
+                listener
+                    .tls
+                    .as_ref()
+                    .and_then(|tls| tls.options.get(CUSTOM_HOSTNAME_OPTION))
+                    .is_some_and(|option| option.trim().eq_ignore_ascii_case("true"))
+            })
+            .any(|listener| {
+                listener
+                    .hostname
+                    .as_deref()
+                    .is_none_or(|served| matches(served, hostname))
+            })
+    }
+}
+
+/// Whether the listener hostname `served` covers `hostname`, a wildcard covers one label or more
+fn matches(served: &str, hostname: &str) -> bool {
+    let served = served.trim_end_matches('.');
+    match served.strip_prefix("*.") {
+        Some(parent) => hostname
+            .strip_suffix(parent)
+            .is_some_and(|labels| labels.len() > 1 && labels.ends_with('.')),
+        None => served.eq_ignore_ascii_case(hostname),
+    }
+}
+
+#[instrument(skip(ctx, gateway), fields(trace_id))]
+async fn reconcile(gateway: Arc<Gateway>, ctx: Arc<Context>) -> Result<Action> {
+    let trace_id = telemetry::get_trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        Span::current().record("trace_id", field::display(&trace_id));
+    }
+    let _timer = ctx
+        .metrics
+        .reconcile
+        .count_and_measure(gateway.as_ref(), &trace_id);
+    ctx.diagnostics.write().await.last_event = Utc::now();
+    if gateway.meta().deletion_timestamp.is_some() {
+        // the records are owned, the garbage collector takes them along
+        return Ok(Action::await_change());
+    }
+
+    // a Gateway that lost its annotation, or never had one, keeps no records
+    let records = match Annotations::of(gateway.as_ref()) {
+        Some(annotations) => {
+            let hostnames: BTreeSet<String> = match &annotations.hostnames {
+                Some(hostnames) => hostnames.iter().cloned().collect(),
+                None => gateway.hostnames(),
+            };
+            let mut records = annotations
+                .records(
+                    ctx.client.clone(),
+                    &gateway.namespace().unwrap_or_default(),
+                    &hostnames,
+                    &targets(gateway.addresses()),
+                )
+                .await?;
+            for record in &mut records {
+                record.custom_hostname = gateway.custom_hostname(&record.name).then_some(true);
+            }
+            records
+        }
+        None => vec![],
+    };
+    let keeps_records = !records.is_empty();
+    super::sync(gateway.as_ref(), ctx.client.clone(), records).await?;
+    if keeps_records {
+        // puts back what the owner watch missed, new listeners and addresses come from the Gateway watch
+        Ok(Action::requeue(ctx.settings.requeue))
+    } else {
+        Ok(Action::await_change())
+    }
+}
+
+fn error_policy(gateway: Arc<Gateway>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}", error);
+    ctx.metrics.reconcile.set_failure(gateway.as_ref(), error);
+    Action::requeue(ctx.settings.retry)
+}
+
+/// Initialize the controller when `DNS_SOURCES` lists `gateway`
+pub async fn run(state: State) {
+    if !super::enabled("gateway") {
+        // disabled, but we are raced against the controllers so never finish
+        return futures::future::pending().await;
+    }
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let scopes = state.namespaces().scopes();
+    let settings = ControllerSettings::from_env("Gateway");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
+        .await;
+    // one controller per watched namespace, they share the context
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        state
+            .controller("Gateway", scoped::<Gateway>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config())
+            // records deleted or edited by hand are put back
+            .owns(
+                scoped::<DNSRecord>(client.clone(), ns.as_deref()),
+                Config::default(),
+            )
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("Gateway", result))
+    }))
+    .await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wildcards_cover_subdomains_only() {
+        assert!(matches("*.example.com", "app.example.com"));
+        assert!(matches("*.example.com", "a.b.example.com"));
+        assert!(!matches("*.example.com", "example.com"));
+        assert!(!matches("*.example.com", "badexample.com"));
+        assert!(matches("App.example.com.", "app.example.com"));
+    }
+}
@@ -0,0 +1,183 @@
+//! DNSRecords for annotated HTTPRoutes, pointing their hostnames at the Gateways they attach to
+use super::{Annotations, gateway::Gateway, targets};
+use crate::{
+    Context, Error, Result, State, dns_record::DNSRecord, namespaces::scoped, settings::ControllerSettings,
+    telemetry,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use kube::{
+    CustomResource, Resource, ResourceExt,
+    api::Api,
+    client::Client,
+    runtime::{controller::Action, reflector::ObjectRef, watcher::Config},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, sync::Arc};
+use tracing::*;
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug)]
+#[kube(
+    kind = "HTTPRoute",
+    group = "gateway.networking.k8s.io",
+    version = "v1",
+    namespaced,
+    schema = "disabled"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRouteSpec {
+    #[serde(default)]
+    pub hostnames: Vec<String>,
+    #[serde(default)]
+    pub parent_refs: Vec<ParentReference>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ParentReference {
+    /// `gateway.networking.k8s.io` when left out
+    pub group: Option<String>,
+    /// `Gateway` when left out
+    pub kind: Option<String>,
+    /// The namespace of the route when left out
+    pub namespace: Option<String>,
+    pub name: String,
+}
+
+impl HTTPRoute {
+    /// Namespace and name of the Gateways the route attaches to
+    fn gateways(&self) -> BTreeSet<(String, String)> {
+        let ns = self.namespace().unwrap_or_default();
+        self.spec
+            .parent_refs
+            .iter()
+            .filter(|parent| {
+                parent
+                    .group
+                    .as_deref()
+                    .is_none_or(|group| group == "gateway.networking.k8s.io")
+                    && parent.kind.as_deref().is_none_or(|kind| kind == "Gateway")
+            })
+            .map(|parent| {
+                (
+                    parent.namespace.clone().unwrap_or_else(|| ns.clone()),
+                    parent.name.clone(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[instrument(skip(ctx, route), fields(trace_id))]
+async fn reconcile(route: Arc<HTTPRoute>, ctx: Arc<Context>) -> Result<Action> {
+    let trace_id = telemetry::get_trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        Span::current().record("trace_id", field::display(&trace_id));
+    }
+    let _timer = ctx.metrics.reconcile.count_and_measure(route.as_ref(), &trace_id);
+    ctx.diagnostics.write().await.last_event = Utc::now();
+    if route.meta().deletion_timestamp.is_some() {
+        // the records are owned, the garbage collector takes them along
+        return Ok(Action::await_change());
+    }
+
+    // a route that lost its annotation, or never had one, keeps no records
+    let records = match Annotations::of(route.as_ref()) {
+        Some(annotations) => {
+            let hostnames: BTreeSet<String> = match &annotations.hostnames {
+                Some(hostnames) => hostnames.iter().cloned().collect(),
+                None => route
+                    .spec
+                    .hostnames
+                    .iter()
+                    .map(|hostname| hostname.trim_end_matches('.').to_lowercase())
+                    .collect(),
+            };
+            let mut gateways = vec![];
+            for (ns, name) in route.gateways() {
+                let api: Api<Gateway> = Api::namespaced(ctx.client.clone(), &ns);
+                match api.get_opt(&name).await.map_err(Error::KubeError)? {
+                    Some(gateway) => gateways.push(gateway),
+                    // the Gateway watch brings it in once it shows up
+                    None => warn!("Gateway {ns}/{name} of \"{}\" not found", route.name_any()),
+                }
+            }
+            // the addresses of every Gateway, an IP of one wins over a hostname of another
+            let addresses = targets(gateways.iter().flat_map(Gateway::addresses));
+            let mut records = annotations
+                .records(
+                    ctx.client.clone(),
+                    &route.namespace().unwrap_or_default(),
+                    &hostnames,
+                    &addresses,
+                )
+                .await?;
+            for record in &mut records {
+                let custom_hostname = gateways
+                    .iter()
+                    .any(|gateway| gateway.custom_hostname(&record.name));
+                record.custom_hostname = custom_hostname.then_some(true);
+            }
+            records
+        }
+        None => vec![],
+    };
+    let keeps_records = !records.is_empty();
+    super::sync(route.as_ref(), ctx.client.clone(), records).await?;
+    if keeps_records {
+        // puts back what the owner watch missed, Gateway address changes come from the Gateway watch
+        Ok(Action::requeue(ctx.settings.requeue))
+    } else {
+        Ok(Action::await_change())
+    }
+}
+
+fn error_policy(route: Arc<HTTPRoute>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}", error);
+    ctx.metrics.reconcile.set_failure(route.as_ref(), error);
+    Action::requeue(ctx.settings.retry)
+}
+
+/// Initialize the controller when `DNS_SOURCES` lists `httproute`
+pub async fn run(state: State) {
+    if !super::enabled("httproute") {
+        // disabled, but we are raced against the controllers so never finish
+        return futures::future::pending().await;
+    }
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let scopes = state.namespaces().scopes();
+    let settings = ControllerSettings::from_env("HTTPRoute");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
+        .await;
+    // one controller per watched namespace, they share the context
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        let controller = state
+            .controller("HTTPRoute", scoped::<HTTPRoute>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config());
+        let routes = controller.store();
+        controller
+            // records deleted or edited by hand are put back
+            .owns(
+                scoped::<DNSRecord>(client.clone(), ns.as_deref()),
+                Config::default(),
+            )
+            // routes follow the addresses and listeners of their Gateways
+            .watches(
+                scoped::<Gateway>(client.clone(), ns.as_deref()),
+                Config::default(),
+                move |gateway: Gateway| {
+                    let parent = (gateway.namespace().unwrap_or_default(), gateway.name_any());
+                    routes
+                        .state()
+                        .into_iter()
+                        .filter(|route| route.gateways().contains(&parent))
+                        .map(|route| ObjectRef::from_obj(route.as_ref()))
+                        .collect::<Vec<_>>()
+                },
+            )
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("HTTPRoute", result))
+    }))
+    .await;
+}
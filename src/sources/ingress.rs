@@ -0,0 +1,116 @@
+//! DNSRecords for annotated Ingresses, pointing their hosts at the Ingress load balancer
+use super::{Annotations, targets};
+use crate::{
+    Context, Error, Result, State, dns_record::DNSRecord, namespaces::scoped, settings::ControllerSettings,
+    telemetry,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use k8s_openapi::api::networking::v1::Ingress;
+use kube::{
+    Resource, ResourceExt,
+    client::Client,
+    runtime::{controller::Action, watcher::Config},
+};
+use std::{collections::BTreeSet, sync::Arc};
+use tracing::*;
+
+#[instrument(skip(ctx, ingress), fields(trace_id))]
+async fn reconcile(ingress: Arc<Ingress>, ctx: Arc<Context>) -> Result<Action> {
+    let trace_id = telemetry::get_trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        Span::current().record("trace_id", field::display(&trace_id));
+    }
+    let _timer = ctx
+        .metrics
+        .reconcile
+        .count_and_measure(ingress.as_ref(), &trace_id);
+    ctx.diagnostics.write().await.last_event = Utc::now();
+    if ingress.meta().deletion_timestamp.is_some() {
+        // the records are owned, the garbage collector takes them along
+        return Ok(Action::await_change());
+    }
+
+    // an Ingress that lost its annotation, or never had one, keeps no records
+    let records = match Annotations::of(ingress.as_ref()) {
+        Some(annotations) => {
+            let hostnames: BTreeSet<String> = match &annotations.hostnames {
+                Some(hostnames) => hostnames.iter().cloned().collect(),
+                None => hosts(&ingress),
+            };
+            let addresses = ingress
+                .status
+                .as_ref()
+                .and_then(|status| status.load_balancer.as_ref())
+                .and_then(|lb| lb.ingress.as_ref())
+                .into_iter()
+                .flatten()
+                .map(|address| (address.ip.as_deref(), address.hostname.as_deref()));
+            annotations
+                .records(
+                    ctx.client.clone(),
+                    &ingress.namespace().unwrap_or_default(),
+                    &hostnames,
+                    &targets(addresses),
+                )
+                .await?
+        }
+        None => vec![],
+    };
+    let keeps_records = !records.is_empty();
+    super::sync(ingress.as_ref(), ctx.client.clone(), records).await?;
+    if keeps_records {
+        // puts back what the owner watch missed, new hosts and addresses come from the Ingress watch
+        Ok(Action::requeue(ctx.settings.requeue))
+    } else {
+        Ok(Action::await_change())
+    }
+}
+
+fn error_policy(ingress: Arc<Ingress>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}", error);
+    ctx.metrics.reconcile.set_failure(ingress.as_ref(), error);
+    Action::requeue(ctx.settings.retry)
+}
+
+/// Hosts of the rules of `ingress`
+fn hosts(ingress: &Ingress) -> BTreeSet<String> {
+    ingress
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.rules.as_ref())
+        .into_iter()
+        .flatten()
+        .filter_map(|rule| rule.host.as_ref())
+        .map(|host| host.trim_end_matches('.').to_lowercase())
+        .collect()
+}
+
+/// Initialize the controller when `DNS_SOURCES` lists `ingress`
+pub async fn run(state: State) {
+    if !super::enabled("ingress") {
+        // disabled, but we are raced against the controllers so never finish
+        return futures::future::pending().await;
+    }
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let scopes = state.namespaces().scopes();
+    let settings = ControllerSettings::from_env("Ingress");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
+        .await;
+    // one controller per watched namespace, they share the context
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        state
+            .controller("Ingress", scoped::<Ingress>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config())
+            // records deleted or edited by hand are put back
+            .owns(
+                scoped::<DNSRecord>(client.clone(), ns.as_deref()),
+                Config::default(),
+            )
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("Ingress", result))
+    }))
+    .await;
+}
@@ -0,0 +1,279 @@
+//! DNS records kept from annotations on workloads, without DNSRecord manifests
+//!
+//! Sources listed in `DNS_SOURCES` (comma separated, like `ingress,service,gateway,httproute`) watch their objects, and
+//! those with a `cloudflare.com/dns-zone-ref` or `cloudflare.com/hostname` annotation get a DNSRecord for
+//! every hostname and address they are reachable at. The records are owned by the object, so the garbage
+//! collector removes them along with it, and records no longer wanted are deleted on the way. The
+//! annotations:
+//!
+//! - `cloudflare.com/hostname`: comma separated hostnames, by default the ones the object names (rule
+//!   hosts of an Ingress, listener hostnames of a Gateway, hostnames of an HTTPRoute)
+//! - `cloudflare.com/dns-zone-ref`: the Zone the records go in, by default the Zone of the object's
+//!   namespace whose name the hostname ends with
+//! - `cloudflare.com/dns-proxied`: `true` to proxy the records through Cloudflare
+//! - `cloudflare.com/dns-ttl`: TTL in seconds, automatic by default
+pub mod gateway;
+pub mod httproute;
+pub mod ingress;
+pub mod service;
+
+use crate::{
+    Error, Result,
+    dns_record::{DNSRecord, DNSRecordSpec},
+    zone::Zone,
+};
+use k8s_openapi::{NamespaceResourceScope, api::core::v1::LocalObjectReference};
+use kube::{
+    Resource, ResourceExt,
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams},
+    client::Client,
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+    str::FromStr,
+};
+use tracing::*;
+
+pub static ZONE_REF_ANNOTATION: &str = "cloudflare.com/dns-zone-ref";
+pub static HOSTNAME_ANNOTATION: &str = "cloudflare.com/hostname";
+pub static PROXIED_ANNOTATION: &str = "cloudflare.com/dns-proxied";
+pub static TTL_ANNOTATION: &str = "cloudflare.com/dns-ttl";
+/// Label on the DNSRecords of a source object, holding the uid of the object
+pub static SOURCE_LABEL: &str = "cloudflare.com/dns-source";
+
+/// Whether `source` is listed in `DNS_SOURCES`
+pub fn enabled(source: &str) -> bool {
+    std::env::var("DNS_SOURCES")
+        .unwrap_or_default()
+        .split(',')
+        .any(|listed| listed.trim().eq_ignore_ascii_case(source))
+}
+
+/// What the annotations of an object ask for
+pub struct Annotations {
+    pub zone_ref: Option<String>,
+    pub hostnames: Option<Vec<String>>,
+    pub proxied: Option<bool>,
+    pub ttl: Option<u32>,
+}
+
+impl Annotations {
+    /// The annotations of `obj`, nothing when it names neither a zone nor a hostname
+    pub fn of<K: ResourceExt>(obj: &K) -> Option<Self> {
+        let annotations = obj.annotations();
+        let zone_ref = annotations
+            .get(ZONE_REF_ANNOTATION)
+            .map(|zone_ref| zone_ref.trim().to_string());
+        if zone_ref.is_none() && !annotations.contains_key(HOSTNAME_ANNOTATION) {
+            return None;
+        }
+        Some(Self {
+            zone_ref,
+            hostnames: annotations.get(HOSTNAME_ANNOTATION).map(|hostnames| {
+                hostnames
+                    .split(',')
+                    .map(|hostname| hostname.trim().trim_end_matches('.').to_lowercase())
+                    .filter(|hostname| !hostname.is_empty())
+                    .collect()
+            }),
+            proxied: parse(obj, PROXIED_ANNOTATION),
+            ttl: parse(obj, TTL_ANNOTATION),
+        })
+    }
+
+    /// A record for every hostname and target, hostnames outside the Zones of `ns` are left out
+    pub async fn records(
+        &self,
+        client: Client,
+        ns: &str,
+        hostnames: &BTreeSet<String>,
+        targets: &[Target],
+    ) -> Result<Vec<DNSRecordSpec>> {
+        let zones = match &self.zone_ref {
+            Some(zone_ref) => vec![zone_ref.clone()],
+            None => Api::<Zone>::namespaced(client, ns)
+                .list(&ListParams::default())
+                .await
+                .map_err(Error::KubeError)?
+                .iter()
+                .map(ResourceExt::name_any)
+                .collect(),
+        };
+        let mut records = vec![];
+        for hostname in hostnames {
+            let Some(zone) = self.zone_for(hostname, &zones) else {
+                warn!("no Zone in {ns} for {hostname}, leaving it out");
+                continue;
+            };
+            records.extend(targets.iter().map(|target| DNSRecordSpec {
+                zone_ref: LocalObjectReference { name: zone.clone() },
+                name: hostname.clone(),
+                record_type: target.record_type.to_string(),
+                content: target.content.clone(),
+                ttl: self.ttl,
+                priority: None,
+                proxied: self.proxied,
+                reconcile_policy: None,
+                custom_hostname: None,
+            }));
+        }
+        Ok(records)
+    }
+
+    /// The zone of `hostname`, the annotated one or the longest of `zones` it ends with
+    fn zone_for(&self, hostname: &str, zones: &[String]) -> Option<String> {
+        if let Some(zone_ref) = &self.zone_ref {
+            return Some(zone_ref.clone());
+        }
+        zones
+            .iter()
+            .filter(|zone| hostname == zone.as_str() || hostname.ends_with(&format!(".{zone}")))
+            .max_by_key(|zone| zone.len())
+            .cloned()
+    }
+}
+
+/// The value of `annotation` on `obj`, a bad one is left out instead of holding up the records
+fn parse<K: ResourceExt, T: FromStr>(obj: &K, annotation: &str) -> Option<T> {
+    let value = obj.annotations().get(annotation)?;
+    let parsed = value.trim().parse().ok();
+    if parsed.is_none() {
+        warn!("ignoring {annotation}={value} on \"{}\"", obj.name_any());
+    }
+    parsed
+}
+
+/// An address the object is reachable at
+pub struct Target {
+    pub record_type: &'static str,
+    pub content: String,
+}
+
+/// The targets of the load balancer `addresses`, as `(ip, hostname)` pairs
+///
+/// Every IP gets an A or AAAA record. A hostname can only be a CNAME, which can't share the name with
+/// anything, so the first one is used when there is no IP.
+pub fn targets<'a>(addresses: impl IntoIterator<Item = (Option<&'a str>, Option<&'a str>)>) -> Vec<Target> {
+    let (ips, hostnames): (Vec<_>, Vec<_>) = addresses.into_iter().partition(|(ip, _)| ip.is_some());
+    let ips: BTreeSet<IpAddr> = ips.iter().filter_map(|(ip, _)| (*ip)?.parse().ok()).collect();
+    if ips.is_empty() {
+        return hostnames
+            .iter()
+            .find_map(|(_, hostname)| *hostname)
+            .map(|hostname| Target {
+                record_type: "CNAME",
+                content: hostname.trim_end_matches('.').to_string(),
+            })
+            .into_iter()
+            .collect();
+    }
+    ips.into_iter()
+        .map(|ip| Target {
+            record_type: if ip.is_ipv4() { "A" } else { "AAAA" },
+            content: ip.to_string(),
+        })
+        .collect()
+}
+
+/// Apply `records` as the DNSRecords of `owner`, deleting the ones it had that aren't among them
+pub async fn sync<K>(owner: &K, client: Client, records: Vec<DNSRecordSpec>) -> Result<()>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>,
+{
+    let ns = owner.namespace().unwrap_or_default();
+    let uid = owner.uid().unwrap_or_default();
+    let api: Api<DNSRecord> = Api::namespaced(client, &ns);
+    let mut wanted = BTreeSet::new();
+    let mut per_hostname = BTreeMap::<String, usize>::new();
+    for spec in records {
+        let index = per_hostname.entry(spec.name.clone()).or_default();
+        let name = record_name(owner, &spec.name, *index);
+        *index += 1;
+        let record = json!({
+            "apiVersion": "cloudflare.com/v1alpha1",
+            "kind": "DNSRecord",
+            "metadata": {
+                "name": name,
+                "labels": { SOURCE_LABEL: uid },
+                "ownerReferences": [owner.controller_owner_ref(&()).unwrap()],
+            },
+            "spec": spec,
+        });
+        api.patch(
+            &name,
+            &PatchParams::apply("dns-source").force(),
+            &Patch::Apply(record),
+        )
+        .await
+        .map_err(Error::KubeError)?;
+        wanted.insert(name);
+    }
+    let owned = api
+        .list(&ListParams::default().labels(&format!("{SOURCE_LABEL}={uid}")))
+        .await
+        .map_err(Error::KubeError)?;
+    for record in owned.iter().filter(|record| !wanted.contains(&record.name_any())) {
+        info!(
+            "Removing DNSRecord {} of {} {}",
+            record.name_any(),
+            K::kind(&()),
+            owner.name_any()
+        );
+        api.delete(&record.name_any(), &DeleteParams::default())
+            .await
+            .map_err(Error::KubeError)?;
+    }
+    Ok(())
+}
+
+/// Name of the `index`th DNSRecord of `owner` for `hostname`
+///
+/// A target that changes, like a new load balancer IP, updates the record instead of replacing it.
+fn record_name<K: Resource<DynamicType = ()>>(owner: &K, hostname: &str, index: usize) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}/{hostname}/{index}", K::kind(&())));
+    let digest = hasher.finalize();
+    let suffix: String = digest[..4].iter().map(|byte| format!("{byte:02x}")).collect();
+    // room for the suffix within the 253 characters of a name
+    let prefix: String = owner
+        .meta()
+        .name
+        .clone()
+        .unwrap_or_default()
+        .chars()
+        .take(240)
+        .collect();
+    format!("{}-{suffix}", prefix.trim_end_matches(['-', '.']))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ips_win_over_hostnames() {
+        let targets = targets([
+            (None, Some("lb.example.net.")),
+            (Some("2001:db8::1"), None),
+            (Some("192.0.2.1"), None),
+        ]);
+        let targets: Vec<_> = targets
+            .iter()
+            .map(|t| (t.record_type, t.content.as_str()))
+            .collect();
+        assert_eq!(targets, [("A", "192.0.2.1"), ("AAAA", "2001:db8::1")]);
+    }
+
+    #[test]
+    fn a_hostname_is_a_single_cname() {
+        let targets = targets([(None, Some("a.example.net.")), (None, Some("b.example.net"))]);
+        let targets: Vec<_> = targets
+            .iter()
+            .map(|t| (t.record_type, t.content.as_str()))
+            .collect();
+        assert_eq!(targets, [("CNAME", "a.example.net")]);
+    }
+}
@@ -0,0 +1,105 @@
+//! DNSRecords for annotated LoadBalancer Services, following the addresses the load balancer gets
+use super::{Annotations, targets};
+use crate::{
+    Context, Error, Result, State, dns_record::DNSRecord, namespaces::scoped, settings::ControllerSettings,
+    telemetry,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::Service;
+use kube::{
+    Resource, ResourceExt,
+    client::Client,
+    runtime::{controller::Action, watcher::Config},
+};
+use std::{collections::BTreeSet, sync::Arc};
+use tracing::*;
+
+#[instrument(skip(ctx, service), fields(trace_id))]
+async fn reconcile(service: Arc<Service>, ctx: Arc<Context>) -> Result<Action> {
+    let trace_id = telemetry::get_trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        Span::current().record("trace_id", field::display(&trace_id));
+    }
+    let _timer = ctx
+        .metrics
+        .reconcile
+        .count_and_measure(service.as_ref(), &trace_id);
+    ctx.diagnostics.write().await.last_event = Utc::now();
+    if service.meta().deletion_timestamp.is_some() {
+        // the records are owned, the garbage collector takes them along
+        return Ok(Action::await_change());
+    }
+
+    // a Service that stopped being a load balancer or lost its annotation keeps no records
+    let load_balancer = service
+        .spec
+        .as_ref()
+        .is_some_and(|spec| spec.type_.as_deref() == Some("LoadBalancer"));
+    let records = match Annotations::of(service.as_ref()) {
+        Some(annotations) if load_balancer => {
+            // a Service names no hostnames of its own
+            let hostnames: BTreeSet<String> = annotations.hostnames.iter().flatten().cloned().collect();
+            let addresses = service
+                .status
+                .as_ref()
+                .and_then(|status| status.load_balancer.as_ref())
+                .and_then(|lb| lb.ingress.as_ref())
+                .into_iter()
+                .flatten()
+                .map(|address| (address.ip.as_deref(), address.hostname.as_deref()));
+            annotations
+                .records(
+                    ctx.client.clone(),
+                    &service.namespace().unwrap_or_default(),
+                    &hostnames,
+                    &targets(addresses),
+                )
+                .await?
+        }
+        _ => vec![],
+    };
+    let keeps_records = !records.is_empty();
+    super::sync(service.as_ref(), ctx.client.clone(), records).await?;
+    if keeps_records {
+        // puts back what the owner watch missed, address changes come from the Service watch
+        Ok(Action::requeue(ctx.settings.requeue))
+    } else {
+        Ok(Action::await_change())
+    }
+}
+
+fn error_policy(service: Arc<Service>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}", error);
+    ctx.metrics.reconcile.set_failure(service.as_ref(), error);
+    Action::requeue(ctx.settings.retry)
+}
+
+/// Initialize the controller when `DNS_SOURCES` lists `service`
+pub async fn run(state: State) {
+    if !super::enabled("service") {
+        // disabled, but we are raced against the controllers so never finish
+        return futures::future::pending().await;
+    }
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let scopes = state.namespaces().scopes();
+    let settings = ControllerSettings::from_env("Service");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
+        .await;
+    // one controller per watched namespace, they share the context
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        state
+            .controller("Service", scoped::<Service>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config())
+            // records deleted or edited by hand are put back
+            .owns(
+                scoped::<DNSRecord>(client.clone(), ns.as_deref()),
+                Config::default(),
+            )
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("Service", result))
+    }))
+    .await;
+}
@@ -0,0 +1,85 @@
+//! Writing the status of the operator's own objects
+use crate::{Error, Result, conditions::Conditions};
+use k8s_openapi::NamespaceResourceScope;
+use kube::{
+    Client, Resource, ResourceExt,
+    api::{Api, Patch, PatchParams},
+    core::object::HasStatus,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use std::fmt::Debug;
+
+/// Server-side apply `status` as the whole status of `obj`
+///
+/// Fields left out are dropped, so pass everything that should stay. `observedGeneration` is set to the
+/// generation of `obj`, which tells clients the status was computed for that spec. A write is a
+/// reconcile getting somewhere, so `consecutiveFailures` is dropped too.
+pub async fn patch<K, S>(obj: &K, client: Client, status: &S) -> Result<()>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope> + Clone + DeserializeOwned + Debug,
+    S: Serialize,
+{
+    let mut status = serde_json::to_value(status).map_err(Error::SerializationError)?;
+    status["observedGeneration"] = json!(obj.meta().generation);
+    if let Some(fields) = status.as_object_mut() {
+        fields.remove("consecutiveFailures");
+    }
+    apply(obj, client, status).await
+}
+
+/// Record `failures` in a row on the latest status of `obj`, with `stalled` also `Stalled=True` with it
+///
+/// The reconcile may have written the status since `obj` was read, so it's read again. The generation
+/// observed stays, the failures didn't get the spec anywhere.
+pub async fn record_failures<K>(obj: &K, client: Client, failures: u32, stalled: Option<String>) -> Result<()>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + HasStatus
+        + Clone
+        + DeserializeOwned
+        + Debug,
+    K::Status: Conditions + Serialize + Clone + Default,
+{
+    let docs: Api<K> = Api::namespaced(client.clone(), &obj.namespace().unwrap_or_default());
+    let latest = docs.get_status(&obj.name_any()).await.map_err(Error::KubeError)?;
+    let mut status = latest.status().cloned().unwrap_or_default();
+    if let Some(message) = stalled {
+        status.set_stalled("ReconcileFailing", message, latest.meta().generation);
+    }
+    let mut status = serde_json::to_value(status).map_err(Error::SerializationError)?;
+    status["consecutiveFailures"] = json!(failures);
+    apply(&latest, client, status).await
+}
+
+async fn apply<K>(obj: &K, client: Client, status: Value) -> Result<()>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope> + Clone + DeserializeOwned + Debug,
+{
+    let docs: Api<K> = Api::namespaced(client, &obj.namespace().unwrap_or_default());
+    docs.patch_status(
+        &obj.name_any(),
+        &PatchParams::apply("cntrlr").force(),
+        &Patch::Apply(json!({
+            "apiVersion": K::api_version(&()),
+            "kind": K::kind(&()),
+            "status": status
+        })),
+    )
+    .await
+    .map_err(Error::KubeError)?;
+    Ok(())
+}
+
+/// Whether the spec changed since the status was last written, which tells an update from a drift
+pub fn spec_changed<K>(obj: &K) -> bool
+where
+    K: Resource + HasStatus,
+    K::Status: Serialize,
+{
+    let observed = obj
+        .status()
+        .and_then(|status| serde_json::to_value(status).ok())
+        .and_then(|status| status["observedGeneration"].as_i64());
+    observed != obj.meta().generation
+}
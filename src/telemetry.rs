@@ -2,7 +2,7 @@
 use opentelemetry::trace::{TraceId, TracerProvider};
 use opentelemetry_sdk::{Resource, trace as sdktrace};
 use sdktrace::{SdkTracer, SdkTracerProvider};
-use tracing_subscriber::{EnvFilter, Registry, prelude::*};
+use tracing_subscriber::{EnvFilter, Registry, prelude::*, reload};
 
 ///  Fetch an opentelemetry::trace::TraceId as hex through the full tracing stack
 pub fn get_trace_id() -> TraceId {
@@ -15,50 +15,255 @@ pub fn get_trace_id() -> TraceId {
         .trace_id()
 }
 
+/// W3C `traceparent` of the current span, `None` outside of a trace
+pub fn traceparent() -> Option<String> {
+    use opentelemetry::trace::TraceContextExt as _;
+    use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    span_context.is_valid().then(|| {
+        format!(
+            "00-{}-{}-{:02x}",
+            span_context.trace_id(),
+            span_context.span_id(),
+            span_context.trace_flags().to_u8()
+        )
+    })
+}
+
+/// How log lines are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Compact lines for people
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of the event and its spans
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => anyhow::bail!("unknown log format {other}, expected text or json"),
+        }
+    }
+}
+
+impl LogFormat {
+    /// `LOG_FORMAT`, text when unset
+    pub fn from_env() -> anyhow::Result<Self> {
+        std::env::var("LOG_FORMAT")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map_or(Ok(Self::default()), |v| v.parse())
+    }
+}
+
+/// The log filter of the running operator, changed without a restart
+#[derive(Clone)]
+pub struct LogLevel {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Directives the operator started with, from `RUST_LOG`
+    initial: String,
+}
+
+impl LogLevel {
+    /// Directives in effect, like `info,controller=debug`
+    pub fn current(&self) -> String {
+        self.handle
+            .with_current(|filter| filter.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Filter with `directives` from now on, in `RUST_LOG` syntax
+    pub fn set(&self, directives: &str) -> anyhow::Result<()> {
+        let filter = EnvFilter::try_new(directives)?;
+        self.handle.reload(filter)?;
+        tracing::info!("log level set to {directives}");
+        Ok(())
+    }
+
+    /// Go back to what `RUST_LOG` said at startup
+    pub fn reset(&self) -> anyhow::Result<()> {
+        self.set(&self.initial)
+    }
+
+    /// Reset the level on every SIGHUP, undoing changes made at runtime
+    pub async fn reset_on_sighup(self) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+            let Ok(mut hangups) = signal(SignalKind::hangup()) else {
+                tracing::warn!("can't listen for SIGHUP, the log level is only reset over HTTP");
+                return;
+            };
+            while hangups.recv().await.is_some() {
+                if let Err(e) = self.reset() {
+                    tracing::warn!("failed to reset the log level: {e}");
+                }
+            }
+        }
+    }
+}
+
+/// How traces reach the collector
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// OTLP over gRPC, usually port 4317
+    #[default]
+    Grpc,
+    /// OTLP protobuf over HTTP, usually port 4318
+    HttpProtobuf,
+}
+
+impl std::str::FromStr for Protocol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grpc" => Ok(Self::Grpc),
+            "http/protobuf" | "http" => Ok(Self::HttpProtobuf),
+            other => anyhow::bail!("unknown OTLP protocol {other}, expected grpc or http/protobuf"),
+        }
+    }
+}
+
+/// Where and how traces are exported, only used by builds with the `telemetry` feature
+///
+/// Read from the standard OpenTelemetry variables: `OTEL_EXPORTER_OTLP_ENDPOINT` (or
+/// `OPENTELEMETRY_ENDPOINT_URL`), `OTEL_EXPORTER_OTLP_PROTOCOL`, `OTEL_TRACES_SAMPLER_ARG`,
+/// `OTEL_SERVICE_NAME` and `OTEL_SDK_DISABLED`. `OTEL_RESOURCE_ATTRIBUTES` is read by the SDK itself.
+#[derive(Clone, Debug)]
+pub struct TracingConfig {
+    /// Collector traces go to, for `http/protobuf` the full URL ending in `/v1/traces`; no tracing
+    /// without one
+    pub endpoint: Option<String>,
+    pub protocol: Protocol,
+    /// Share of new traces kept, traces started upstream follow the caller's decision
+    pub sampling_ratio: f64,
+    pub service_name: String,
+    /// Export nothing, whatever else is configured
+    pub disabled: bool,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            protocol: Protocol::default(),
+            sampling_ratio: 1.0,
+            service_name: env!("CARGO_PKG_NAME").into(),
+            disabled: false,
+        }
+    }
+}
+
+impl TracingConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let defaults = Self::default();
+        Ok(Self {
+            endpoint: var("OTEL_EXPORTER_OTLP_ENDPOINT").or_else(|| var("OPENTELEMETRY_ENDPOINT_URL")),
+            protocol: var("OTEL_EXPORTER_OTLP_PROTOCOL")
+                .map(|p| p.parse())
+                .transpose()?
+                .unwrap_or(defaults.protocol),
+            sampling_ratio: var("OTEL_TRACES_SAMPLER_ARG")
+                .map(|ratio| parse_ratio(&ratio))
+                .transpose()?
+                .unwrap_or(defaults.sampling_ratio),
+            service_name: var("OTEL_SERVICE_NAME").unwrap_or(defaults.service_name),
+            disabled: var("OTEL_SDK_DISABLED").is_some_and(|v| v == "true"),
+        })
+    }
+}
+
+/// A sampling ratio between 0 and 1
+pub fn parse_ratio(ratio: &str) -> anyhow::Result<f64> {
+    match ratio.parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => anyhow::bail!("sampling ratio {ratio} is not a number between 0 and 1"),
+    }
+}
+
 #[cfg(feature = "telemetry")]
-fn resource() -> Resource {
+fn resource(config: &TracingConfig) -> Resource {
     use opentelemetry::KeyValue;
     Resource::builder()
-        .with_service_name(env!("CARGO_PKG_NAME"))
+        .with_service_name(config.service_name.clone())
         .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
         .build()
 }
 
 #[cfg(feature = "telemetry")]
-fn init_tracer() -> SdkTracer {
+fn init_tracer(config: &TracingConfig, endpoint: &str) -> anyhow::Result<SdkTracer> {
     use opentelemetry_otlp::{SpanExporter, WithExportConfig};
-    let endpoint = std::env::var("OPENTELEMETRY_ENDPOINT_URL").expect("Needs an otel collector");
-    let exporter = SpanExporter::builder()
-        .with_tonic()
-        .with_endpoint(endpoint)
-        .build()
-        .unwrap();
+    use sdktrace::Sampler;
+    let exporter = match config.protocol {
+        Protocol::Grpc => SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?,
+        Protocol::HttpProtobuf => SpanExporter::builder()
+            .with_http()
+            .with_protocol(opentelemetry_otlp::Protocol::HttpBinary)
+            .with_endpoint(endpoint)
+            .build()?,
+    };
 
     let provider = SdkTracerProvider::builder()
-        .with_resource(resource())
+        .with_resource(resource(config))
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sampling_ratio,
+        ))))
         .with_batch_exporter(exporter)
         .build();
 
-    provider.tracer("tracing-otel-subscriber")
+    Ok(provider.tracer("tracing-otel-subscriber"))
 }
 
-/// Initialize tracing
-pub async fn init() {
+/// Initialize tracing, logging in `format`; the returned handle changes the level later
+pub async fn init(config: TracingConfig, format: LogFormat) -> anyhow::Result<LogLevel> {
     // Setup tracing layers
     #[cfg(feature = "telemetry")]
-    let otel = tracing_opentelemetry::OpenTelemetryLayer::new(init_tracer());
+    let otel = match (&config.endpoint, config.disabled) {
+        (Some(endpoint), false) => Some(tracing_opentelemetry::OpenTelemetryLayer::new(init_tracer(
+            &config, endpoint,
+        )?)),
+        _ => None,
+    };
 
-    let logger = tracing_subscriber::fmt::layer().compact();
-    let env_filter = EnvFilter::try_from_default_env()
-        .or(EnvFilter::try_new("info"))
-        .unwrap();
+    let initial = std::env::var("RUST_LOG")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "info".into());
+    let (env_filter, handle) = reload::Layer::new(EnvFilter::try_new(&initial)?);
+    let text = (format == LogFormat::Text).then(|| tracing_subscriber::fmt::layer().compact());
+    let json =
+        (format == LogFormat::Json).then(|| tracing_subscriber::fmt::layer().json().flatten_event(true));
 
     // Decide on layers
     let reg = Registry::default();
     #[cfg(feature = "telemetry")]
-    reg.with(env_filter).with(logger).with(otel).init();
+    {
+        let exporting = otel.is_some();
+        reg.with(env_filter).with(text).with(json).with(otel).init();
+        if !exporting && !config.disabled {
+            tracing::warn!("no OTLP endpoint configured, traces are not exported");
+        }
+    }
     #[cfg(not(feature = "telemetry"))]
-    reg.with(env_filter).with(logger).init();
+    {
+        // built without an exporter, there is nothing to configure
+        let _ = config;
+        reg.with(env_filter).with(text).with(json).init();
+    }
+    Ok(LogLevel { handle, initial })
 }
 
 #[cfg(test)]
@@ -70,7 +275,9 @@ mod test {
     #[ignore = "requires a trace exporter"]
     async fn get_trace_id_returns_valid_traces() {
         use super::*;
-        super::init().await;
+        super::init(TracingConfig::from_env().unwrap(), LogFormat::Text)
+            .await
+            .unwrap();
         #[tracing::instrument(name = "test_span")] // need to be in an instrumented fn
         fn test_trace_id() -> TraceId {
             get_trace_id()
@@ -0,0 +1,172 @@
+//! Works out the smallest set of token permissions the objects managed with a token need
+use crate::{
+    Error, Result,
+    account::Account,
+    account_member::AccountMember,
+    cloudflare::{CloudflareClientProvider, CloudflareResource},
+    dns_record::DNSRecord,
+    tunnel::Tunnel,
+    zone::Zone,
+};
+use kube::{
+    Client, Resource, ResourceExt,
+    api::{Api, ListParams},
+};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    Account,
+    Zone,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    Read,
+    Edit,
+}
+
+/// Permissions by Cloudflare permission group, with the accounts or zones they are needed on
+#[derive(Default, Debug)]
+pub struct Report {
+    grants: BTreeMap<(Scope, &'static str, Access), BTreeSet<String>>,
+    /// Objects whose token couldn't be resolved, they may or may not use the token
+    pub unresolved: Vec<String>,
+}
+
+impl Report {
+    fn grant(&mut self, scope: Scope, group: &'static str, access: Access, resource: String) {
+        self.grants
+            .entry((scope, group, access))
+            .or_default()
+            .insert(resource);
+    }
+
+    /// Permissions needed, edit access covers reading the same resource
+    pub fn permissions(&self) -> impl Iterator<Item = (Scope, &'static str, Access, Vec<&str>)> {
+        self.grants
+            .iter()
+            .filter_map(|((scope, group, access), resources)| {
+                let covered = match access {
+                    Access::Read => self.grants.get(&(*scope, *group, Access::Edit)),
+                    Access::Edit => None,
+                };
+                let resources: Vec<_> = resources
+                    .iter()
+                    .filter(|r| !covered.is_some_and(|c| c.contains(*r)))
+                    .map(String::as_str)
+                    .collect();
+                (!resources.is_empty()).then_some((*scope, *group, *access, resources))
+            })
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (scope, group, access, resources) in self.permissions() {
+            let scope = match scope {
+                Scope::Account => "accounts",
+                Scope::Zone => "zones",
+            };
+            writeln!(f, "{group}:{access:?} on {scope} {}", resources.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Look at every object managed with `token` and collect the permissions the operator needs for them
+pub async fn report(client: Client, provider: &CloudflareClientProvider, token: &str) -> Result<Report> {
+    let mut report = Report::default();
+
+    let accounts = using_token::<Account>(&client, provider, token, &mut report).await?;
+    for account in &accounts {
+        let id = account.spec.id.clone();
+        report.grant(Scope::Account, "Account Settings", Access::Read, id.clone());
+        // usage reporting lists the plans of the account
+        report.grant(Scope::Account, "Billing", Access::Read, id.clone());
+        if account.spec.settings.is_some() {
+            report.grant(Scope::Account, "Account Settings", Access::Edit, id);
+        }
+    }
+
+    // zones and members point at Accounts, which may be managed with another token
+    let account_ids: HashMap<_, _> = Api::<Account>::all(client.clone())
+        .list(&ListParams::default())
+        .await
+        .map_err(Error::KubeError)?
+        .into_iter()
+        .map(|a| ((a.namespace().unwrap_or_default(), a.name_any()), a.spec.id))
+        .collect();
+    let account_id = |ns: String, name: &str| account_ids.get(&(ns, name.to_string())).cloned();
+
+    for zone in using_token::<Zone>(&client, provider, token, &mut report).await? {
+        let name = zone.name_any();
+        report.grant(Scope::Zone, "Zone", Access::Edit, name.clone());
+        if zone.spec.settings.is_some() {
+            report.grant(Scope::Zone, "Zone Settings", Access::Edit, name);
+        }
+        // zones that aren't bound by id may have to be created in the account
+        if zone.spec.zone_id.is_none()
+            && let Some(a_ref) = &zone.spec.account_ref
+            && let Some(id) = account_id(zone.namespace().unwrap_or_default(), &a_ref.name)
+        {
+            report.grant(Scope::Account, "Zone", Access::Edit, id);
+        }
+    }
+
+    for record in using_token::<DNSRecord>(&client, provider, token, &mut report).await? {
+        report.grant(Scope::Zone, "DNS", Access::Edit, record.spec.zone_ref.name);
+    }
+
+    for member in using_token::<AccountMember>(&client, provider, token, &mut report).await? {
+        if let Some(id) = account_id(
+            member.namespace().unwrap_or_default(),
+            &member.spec.account_ref.name,
+        ) {
+            report.grant(Scope::Account, "Memberships", Access::Edit, id);
+        }
+    }
+
+    for tunnel in using_token::<Tunnel>(&client, provider, token, &mut report).await? {
+        if let Some(id) = account_id(
+            tunnel.namespace().unwrap_or_default(),
+            &tunnel.spec.account_ref.name,
+        ) {
+            report.grant(Scope::Account, "Cloudflare Tunnel", Access::Edit, id);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Objects of kind `K` in any namespace that resolve to `token`
+async fn using_token<K>(
+    client: &Client,
+    provider: &CloudflareClientProvider,
+    token: &str,
+    report: &mut Report,
+) -> Result<Vec<K>>
+where
+    K: Resource<DynamicType = ()> + CloudflareResource + DeserializeOwned + Clone + fmt::Debug + Send + Sync,
+{
+    let mut matching = vec![];
+    let objects = Api::<K>::all(client.clone())
+        .list(&ListParams::default())
+        .await
+        .map_err(Error::KubeError)?;
+    for object in objects {
+        let ns = object.namespace().unwrap_or_default();
+        match provider.resolve_token(&object, &ns).await {
+            Ok(resolved) if resolved.as_str() == token => matching.push(object),
+            Ok(_) => {}
+            Err(e) => report
+                .unresolved
+                .push(format!("{}/{}/{}: {}", K::kind(&()), ns, object.name_any(), e)),
+        }
+    }
+    Ok(matching)
+}
@@ -0,0 +1,88 @@
+use crate::{
+    account::Account, account_member::AccountMember, api_token::APIToken, dns_record::DNSRecord,
+    page_rule::PageRule, tunnel::Tunnel, zone::Zone, zone_set::ZoneSet,
+};
+use futures::{Stream, stream};
+use kube::{Resource, runtime::reflector::ObjectRef};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+/// Capacity of each trigger channel, lagging receivers skip the oldest triggers
+const CAPACITY: usize = 1024;
+
+/// Requests to reconcile a specific object right away, outside of what the watches pick up
+pub struct Trigger<K: Resource<DynamicType = ()>> {
+    tx: broadcast::Sender<ObjectRef<K>>,
+}
+
+impl<K: Resource<DynamicType = ()>> Clone for Trigger<K> {
+    fn clone(&self) -> Self {
+        Self { tx: self.tx.clone() }
+    }
+}
+
+impl<K: Resource<DynamicType = ()>> Default for Trigger<K> {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl<K> Trigger<K>
+where
+    K: Resource<DynamicType = ()> + Send + Sync + 'static,
+{
+    /// Queue a reconcile of `name` in `namespace`
+    pub fn reconcile(&self, name: &str, namespace: &str) {
+        // no receivers only means the controller isn't running (yet), nothing to do then
+        let _ = self.tx.send(ObjectRef::new(name).within(namespace));
+    }
+
+    /// Stream of triggered objects, meant for `Controller::reconcile_on`
+    pub fn stream(&self) -> impl Stream<Item = ObjectRef<K>> + Send + 'static {
+        stream::unfold(self.tx.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(obj) => return Some((obj, rx)),
+                    Err(RecvError::Lagged(skipped)) => warn!("dropped {skipped} reconcile triggers"),
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+/// Triggers for every kind with a controller
+#[derive(Clone, Default)]
+pub struct Triggers {
+    pub dns_record: Trigger<DNSRecord>,
+    pub zone: Trigger<Zone>,
+    pub account: Trigger<Account>,
+    pub account_member: Trigger<AccountMember>,
+    pub api_token: Trigger<APIToken>,
+    pub page_rule: Trigger<PageRule>,
+    pub zone_set: Trigger<ZoneSet>,
+    pub tunnel: Trigger<Tunnel>,
+}
+
+impl Triggers {
+    /// Queue a reconcile by kind, given as kind or resource name (`DNSRecord`, `dnsrecords`)
+    ///
+    /// Returns false when no controller handles the kind.
+    pub fn reconcile(&self, kind: &str, name: &str, namespace: &str) -> bool {
+        let kind = kind.to_lowercase();
+        match kind.strip_suffix('s').unwrap_or(&kind) {
+            "dnsrecord" => self.dns_record.reconcile(name, namespace),
+            "zone" => self.zone.reconcile(name, namespace),
+            "account" => self.account.reconcile(name, namespace),
+            "accountmember" => self.account_member.reconcile(name, namespace),
+            "apitoken" => self.api_token.reconcile(name, namespace),
+            "pagerule" => self.page_rule.reconcile(name, namespace),
+            "zoneset" => self.zone_set.reconcile(name, namespace),
+            "tunnel" => self.tunnel.reconcile(name, namespace),
+            _ => return false,
+        }
+        true
+    }
+}
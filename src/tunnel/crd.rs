@@ -0,0 +1,162 @@
+use k8s_openapi::api::core::v1::LocalObjectReference;
+use kube::{CustomResource, ResourceExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    cloudflare::CloudflareResource,
+    conditions::{Condition, Conditions},
+};
+
+/// A Cloudflare Tunnel, with the token `cloudflared` runs it with kept in a Secret next to this object
+///
+/// The tunnel is configured through the API, so the token is all a connector needs. It is deleted along
+/// with the object, unless the `cloudflare.com/deletion-policy` annotation is `abandon`.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(Default))]
+#[kube(kind = "Tunnel", group = "cloudflare.com", version = "v1alpha1", namespaced)]
+#[kube(status = "TunnelStatus", shortname = "cftunnel", category = "cloudflare")]
+#[kube(printcolumn = r#"{"name":"Ready", "type":"boolean", "jsonPath":".status.ready"}"#)]
+#[kube(printcolumn = r#"{"name":"Connector", "type":"string", "jsonPath":".status.connectorStatus"}"#)]
+#[kube(printcolumn = r#"{"name":"Connections", "type":"integer", "jsonPath":".status.connections"}"#)]
+#[serde(rename_all = "camelCase")]
+pub struct TunnelSpec {
+    pub account_ref: LocalObjectReference,
+    /// Name of the tunnel in Cloudflare, `<namespace>-<name>` by default, a tunnel already going by it is
+    /// a conflict
+    pub tunnel_name: Option<String>,
+    /// Secret the tunnel token is written to, under the `token` key; a Secret of that name the Tunnel didn't
+    /// create is a conflict
+    pub secret_name: String,
+    /// Where the tunnel sends requests, in order, pushed as the tunnel configuration. Left alone when
+    /// unset, a catch-all answering 404 is added when the last rule has a hostname.
+    pub ingress: Option<Vec<IngressRule>>,
+    /// Run `cloudflared` for the tunnel in a Deployment next to this object
+    pub cloudflared: Option<Cloudflared>,
+}
+
+/// A rule of the tunnel configuration, the fields are those of the cloudflared config file
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct IngressRule {
+    /// Any hostname when left out, may start with a `*.` wildcard
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Regular expression the path has to match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Where matching requests go, like `http://web.default.svc:80` or `http_status:404`
+    pub service: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin_request: Option<OriginRequest>,
+}
+
+/// How `cloudflared` talks to the service of a rule
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OriginRequest {
+    /// Seconds to wait for a connection to the service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u32>,
+    /// Skip verifying the certificate of the service
+    #[serde(rename = "noTLSVerify", skip_serializing_if = "Option::is_none")]
+    pub no_tls_verify: Option<bool>,
+    /// `Host` header sent to the service instead of the requested hostname
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_host_header: Option<String>,
+    /// Hostname the certificate of the service is checked against
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin_server_name: Option<String>,
+    /// Talk HTTP/2 to the service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http2_origin: Option<bool>,
+}
+
+/// The `cloudflared` Deployment running the tunnel with the token from the Secret
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Cloudflared {
+    /// 2 by default, every replica is a connector of the tunnel
+    pub replicas: Option<i32>,
+    /// Image to run, a pinned cloudflared release by default
+    pub image: Option<String>,
+}
+
+impl Tunnel {
+    /// What the tunnel is called in Cloudflare
+    pub fn tunnel_name(&self) -> String {
+        self.spec
+            .tunnel_name
+            .clone()
+            .unwrap_or_else(|| format!("{}-{}", self.namespace().unwrap_or_default(), self.name_any()))
+    }
+}
+
+impl Tunnel {
+    /// The tunnel configuration `ingress` asks for, with the catch-all cloudflared wants last
+    pub fn ingress_config(&self) -> Option<serde_json::Value> {
+        let mut rules = self.spec.ingress.clone()?;
+        if rules
+            .last()
+            .is_none_or(|rule| rule.hostname.is_some() || rule.path.is_some())
+        {
+            rules.push(IngressRule {
+                hostname: None,
+                path: None,
+                service: "http_status:404".into(),
+                origin_request: None,
+            });
+        }
+        Some(json!({ "ingress": rules }))
+    }
+
+    /// Name of the cloudflared Deployment
+    pub fn deployment_name(&self) -> String {
+        format!("{}-cloudflared", self.name_any())
+    }
+}
+
+impl CloudflareResource for Tunnel {
+    const PERMISSIONS: &'static [&'static str] = &["Cloudflare Tunnel:Edit"];
+
+    fn account_ref(&self) -> Option<&LocalObjectReference> {
+        Some(&self.spec.account_ref)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TunnelStatus {
+    /// Set once the tunnel exists and its token is in the Secret, connectors may still be missing
+    pub ready: bool,
+    pub tunnel_id: Option<String>,
+    /// `inactive` until a connector ran, then `healthy`, `degraded` or `down`
+    pub connector_status: Option<String>,
+    /// Connections of the connectors to Cloudflare data centers
+    pub connections: Option<u32>,
+    /// Version of the tunnel configuration last pushed or found in line with `ingress`
+    pub config_version: Option<i64>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Generation of the spec the status was written for
+    pub observed_generation: Option<i64>,
+    /// Reconciles failed in a row, gone once one succeeds
+    pub consecutive_failures: Option<u32>,
+}
+
+impl Conditions for TunnelStatus {
+    fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition> {
+        &mut self.conditions
+    }
+
+    fn set_failed(&mut self, message: &str) {
+        self.ready = false;
+        self.error = Some(message.into());
+    }
+}
@@ -0,0 +1,5 @@
+mod crd;
+mod reconcile;
+
+pub use crd::{Cloudflared, IngressRule, OriginRequest, Tunnel, TunnelSpec, TunnelStatus};
+pub use reconcile::{DOCUMENT_FINALIZER, SECRET_KEY, TUNNEL_ID_KEY, run};
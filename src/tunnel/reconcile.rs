@@ -0,0 +1,580 @@
+use crate::{
+    Context, Error, Result, State,
+    account::Account,
+    cf_client::{CloudflareClient, Tunnel as CfTunnel},
+    cloudflare,
+    conditions::{Condition, Conditions},
+    deletion_policy::DeletionPolicy,
+    dependency::{wait_for_dependency, wake_dependents},
+    failures,
+    namespaces::scoped,
+    pause,
+    settings::ControllerSettings,
+    status, telemetry,
+    tunnel::{Tunnel, TunnelStatus},
+};
+use chrono::Utc;
+use futures::StreamExt;
+use k8s_openapi::api::{apps::v1::Deployment, core::v1::Secret};
+use kube::{
+    Resource,
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams, ResourceExt},
+    client::Client,
+    runtime::{
+        controller::Action,
+        events::{Event, EventType},
+        finalizer::{Event as Finalizer, finalizer},
+        watcher::Config,
+    },
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::*;
+pub static DOCUMENT_FINALIZER: &str = "tunnel.cloudflare.com";
+/// Key of the target Secret holding the tunnel token
+pub static SECRET_KEY: &str = "token";
+/// Key of the target Secret holding the id of the tunnel the token belongs to
+pub static TUNNEL_ID_KEY: &str = "tunnel-id";
+/// Pod annotation of the cloudflared Deployment, a new tunnel rolls the pods onto its token
+pub static TUNNEL_ID_ANNOTATION: &str = "cloudflare.com/tunnel-id";
+/// cloudflared release the Deployment runs unless the spec names an image
+pub static DEFAULT_CLOUDFLARED_IMAGE: &str = "cloudflare/cloudflared:2025.4.0";
+
+#[instrument(skip(ctx, doc), fields(trace_id))]
+async fn reconcile(doc: Arc<Tunnel>, ctx: Arc<Context>) -> Result<Action> {
+    let trace_id = telemetry::get_trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        Span::current().record("trace_id", field::display(&trace_id));
+    }
+    let _timer = ctx.metrics.reconcile.count_and_measure(doc.as_ref(), &trace_id);
+    ctx.diagnostics.write().await.last_event = Utc::now();
+    ctx.metrics.resources.track(doc.as_ref());
+    let ns = doc.namespace().unwrap(); // doc is namespace scoped
+    let docs: Api<Tunnel> = Api::namespaced(ctx.client.clone(), &ns);
+
+    info!("Reconciling Tunnel \"{}\" in {}", doc.name_any(), ns);
+    if pause::is_paused(doc.as_ref()) {
+        return pause::hold(doc.as_ref(), ctx.client.clone()).await;
+    }
+    let finalize = finalizer(&docs, DOCUMENT_FINALIZER, doc.clone(), |event| async {
+        match event {
+            Finalizer::Apply(doc) => doc.reconcile(ctx.clone()).await,
+            Finalizer::Cleanup(doc) => doc.cleanup(ctx.clone()).await,
+        }
+    });
+    let result = ctx
+        .audit
+        .record(doc.as_ref(), &ctx.recorder, finalize)
+        .await
+        .map_err(|e| Error::FinalizerError(Box::new(e)));
+    cloudflare::preflight::forbidden(doc.as_ref(), &ctx, result).await
+}
+
+fn error_policy(doc: Arc<Tunnel>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}", error);
+    ctx.metrics.reconcile.set_failure(doc.as_ref(), error);
+    failures::requeue(doc, error, &ctx)
+}
+
+impl Tunnel {
+    // Reconcile (for non-finalizer related changes)
+    async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action> {
+        let client = ctx.client.clone();
+        let ns = self.namespace().unwrap(); // doc is namespace scoped
+        let name = self.name_any();
+
+        let account =
+            match wait_for_dependency::<Account>(client.clone(), &ns, &self.spec.account_ref.name).await? {
+                Ok(account) => account,
+                Err(blocked) => {
+                    warn!("Tunnel \"{}\": {}", name, blocked.message());
+                    self.publish(&ctx, "DependencyNotReady", blocked.message()).await;
+                    let mut status = TunnelStatus {
+                        ready: false,
+                        error: Some(blocked.message()),
+                        ..self.status.clone().unwrap_or_default()
+                    };
+                    status.set_reconciling("DependencyNotReady", blocked.message(), self.meta().generation);
+                    status::patch(self, ctx.client.clone(), &status).await?;
+                    // the Account watch wakes us up once it's ready, this is just a fallback
+                    return Ok(Action::requeue(ctx.settings.requeue));
+                }
+            };
+
+        // the token of the account, tunnels are managed on its behalf
+        let (cf_client, source) = match ctx.provider.get_client_with_source(&account, &ns).await {
+            Ok(resolved) => resolved,
+            Err(e) => return cloudflare::credentials_unavailable(self, &ctx, e).await,
+        };
+        let secrets: Api<Secret> = Api::namespaced(client.clone(), &ns);
+        let deployments: Api<Deployment> = Api::namespaced(client, &ns);
+        // the id of a created tunnel is recorded right away, it has to survive a failure further on
+        let mut current = self.status.clone().unwrap_or_default();
+        let converged = match self
+            .converge(&ctx, &cf_client, &account.spec.id, &secrets, &mut current)
+            .await
+        {
+            Ok(tunnel) => match self
+                .configure(&ctx, &cf_client, &account.spec.id, &tunnel.id)
+                .await
+            {
+                Ok(config_version) => self
+                    .run_cloudflared(&ctx, &deployments, &tunnel.id)
+                    .await
+                    .map(|()| (tunnel, config_version)),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        let mut status = match converged {
+            Ok((tunnel, config_version)) => {
+                let mut status = TunnelStatus {
+                    observed_generation: None, // set by status::patch
+                    consecutive_failures: None,
+                    ready: true,
+                    tunnel_id: Some(tunnel.id),
+                    connector_status: tunnel.status,
+                    connections: Some(tunnel.connections.len() as u32),
+                    config_version,
+                    error: None,
+                    conditions: self.conditions().to_vec(),
+                };
+                status.set_ready("Created", self.meta().generation);
+                status
+            }
+            Err(e) => {
+                warn!("Tunnel \"{}\": {:?}", name, e);
+                let mut status = TunnelStatus {
+                    ready: false,
+                    error: Some(e.to_string()),
+                    ..current
+                };
+                status.set_reconciling("SyncFailed", e.to_string(), self.meta().generation);
+                status
+            }
+        };
+        status.set_condition(source.condition(self.conditions(), self.meta().generation));
+        status.set_condition(source.validity(self.conditions(), self.meta().generation));
+        status::patch(self, ctx.client.clone(), &status).await?;
+
+        // connectors come and go without a change to the object
+        Ok(Action::requeue(ctx.settings.requeue))
+    }
+
+    /// Find the tunnel, creating it when there is none, and make sure its token sits in the Secret
+    ///
+    /// Only the tunnel recorded in the status belongs to this object. Another one going by the same name
+    /// may serve someone else, so it's a conflict instead of being adopted along with its token.
+    async fn converge(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        account_id: &str,
+        secrets: &Api<Secret>,
+        status: &mut TunnelStatus,
+    ) -> anyhow::Result<CfTunnel> {
+        let known = match status.tunnel_id.as_deref() {
+            Some(tunnel_id) => cf_client.get_tunnel(account_id, tunnel_id).await?,
+            None => None,
+        };
+        let tunnel = match known {
+            Some(tunnel) => tunnel,
+            None => {
+                let name = self.tunnel_name();
+                if let Some(taken) = cf_client.find_tunnel(account_id, &name).await? {
+                    anyhow::bail!(
+                        "tunnel `{name}` already exists as `{}` and wasn't created for this object, delete it \
+                         or pick another `tunnelName`",
+                        taken.id
+                    );
+                }
+                let tunnel = cf_client.create_tunnel(account_id, name).await?;
+                status.tunnel_id = Some(tunnel.id.clone());
+                status::patch(self, ctx.client.clone(), status).await?;
+                self.publish(
+                    ctx,
+                    "Created",
+                    format!("Created tunnel `{}` as `{}`", tunnel.id, tunnel.name),
+                )
+                .await;
+                tunnel
+            }
+        };
+
+        let existing = secrets.get_opt(&self.spec.secret_name).await?;
+        // a Secret of the same name someone else wrote is left alone, like a Deployment
+        if let Some(secret) = &existing
+            && !self.owns(secret)
+        {
+            anyhow::bail!(
+                "Secret `{}` exists and isn't owned by this Tunnel, remove it or pick another `secretName`",
+                self.spec.secret_name
+            );
+        }
+        // the token of a tunnel doesn't change, so it's only fetched for an empty or outdated Secret
+        let in_secret = existing.and_then(|secret| secret.data).is_some_and(|data| {
+            data.contains_key(SECRET_KEY)
+                && data
+                    .get(TUNNEL_ID_KEY)
+                    .is_some_and(|id| id.0 == tunnel.id.as_bytes())
+        });
+        if !in_secret {
+            let token = cf_client.tunnel_token(account_id, &tunnel.id).await?;
+            self.write_secret(secrets, &tunnel.id, &token).await?;
+            self.publish(
+                ctx,
+                "TokenWritten",
+                format!(
+                    "Wrote the token of tunnel `{}` to Secret `{}`",
+                    tunnel.id, self.spec.secret_name
+                ),
+            )
+            .await;
+        }
+        Ok(tunnel)
+    }
+
+    /// Push the ingress rules of the spec unless the tunnel configuration already has them
+    ///
+    /// Returns the version of the configuration, `None` when the spec leaves it alone.
+    async fn configure(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        account_id: &str,
+        tunnel_id: &str,
+    ) -> anyhow::Result<Option<i64>> {
+        let Some(desired) = self.ingress_config() else {
+            return Ok(None);
+        };
+        let current = cf_client.get_tunnel_configuration(account_id, tunnel_id).await?;
+        if current
+            .config
+            .as_ref()
+            .is_some_and(|config| covers(config, &desired))
+        {
+            return Ok(current.version);
+        }
+        let updated = cf_client
+            .update_tunnel_configuration(account_id, tunnel_id, desired)
+            .await?;
+        let note = format!(
+            "Pushed version {} of the configuration of tunnel `{tunnel_id}`",
+            updated.version.unwrap_or_default()
+        );
+        if status::spec_changed(self) || current.config.is_none() {
+            self.publish(ctx, "Configured", note).await;
+        } else {
+            ctx.metrics.reconcile.set_drift(self);
+            self.publish(ctx, "DriftCorrected", note).await;
+        }
+        Ok(updated.version)
+    }
+
+    /// Apply the cloudflared Deployment the spec asks for, or remove the one it no longer wants
+    async fn run_cloudflared(
+        &self,
+        ctx: &Context,
+        deployments: &Api<Deployment>,
+        tunnel_id: &str,
+    ) -> anyhow::Result<()> {
+        let Some(cloudflared) = &self.spec.cloudflared else {
+            if self.stop_cloudflared(deployments).await? {
+                let note = format!("Removed Deployment `{}`", self.deployment_name());
+                self.publish(ctx, "Removed", note).await;
+            }
+            return Ok(());
+        };
+        let name = self.deployment_name();
+        // a Deployment of the same name someone else runs is left alone
+        if let Some(existing) = deployments.get_opt(&name).await?
+            && !self.owns(&existing)
+        {
+            anyhow::bail!(
+                "Deployment `{name}` exists and isn't owned by this Tunnel, remove it or drop `cloudflared`"
+            );
+        }
+        let labels = json!({
+            "app.kubernetes.io/name": "cloudflared",
+            "app.kubernetes.io/instance": self.name_any(),
+        });
+        // owned by the Tunnel, so the Deployment goes away with it
+        let deployment = json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": {
+                "name": name,
+                "labels": labels,
+                "ownerReferences": [self.controller_owner_ref(&()).unwrap()],
+            },
+            "spec": {
+                "replicas": cloudflared.replicas.unwrap_or(2),
+                "selector": { "matchLabels": labels },
+                "template": {
+                    "metadata": {
+                        "labels": labels,
+                        "annotations": { TUNNEL_ID_ANNOTATION: tunnel_id },
+                    },
+                    "spec": {
+                        "containers": [{
+                            "name": "cloudflared",
+                            "image": cloudflared.image.as_deref().unwrap_or(DEFAULT_CLOUDFLARED_IMAGE),
+                            "args": ["tunnel", "--no-autoupdate", "--metrics", "0.0.0.0:2000", "run"],
+                            "env": [{
+                                "name": "TUNNEL_TOKEN",
+                                "valueFrom": {
+                                    "secretKeyRef": { "name": self.spec.secret_name, "key": SECRET_KEY },
+                                },
+                            }],
+                            "ports": [{ "name": "metrics", "containerPort": 2000 }],
+                            // ready once connected to Cloudflare, losing the connections is no reason
+                            // for a restart, cloudflared reconnects on its own
+                            "readinessProbe": {
+                                "httpGet": { "path": "/ready", "port": 2000 },
+                                "initialDelaySeconds": 10,
+                                "periodSeconds": 10,
+                                "failureThreshold": 3,
+                            },
+                            "livenessProbe": {
+                                "tcpSocket": { "port": 2000 },
+                                "initialDelaySeconds": 10,
+                                "periodSeconds": 10,
+                                "failureThreshold": 3,
+                            },
+                        }],
+                    },
+                },
+            },
+        });
+        deployments
+            .patch(
+                &name,
+                &PatchParams::apply("cntrlr").force(),
+                &Patch::Apply(deployment),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Delete the cloudflared Deployment of the tunnel, says whether there was one
+    async fn stop_cloudflared(&self, deployments: &Api<Deployment>) -> kube::Result<bool> {
+        let name = self.deployment_name();
+        // a Deployment of the same name someone else runs is left alone
+        let ours = deployments
+            .get_opt(&name)
+            .await?
+            .is_some_and(|deployment| self.owns(&deployment));
+        if ours {
+            deployments.delete(&name, &DeleteParams::default()).await?;
+        }
+        Ok(ours)
+    }
+
+    /// Whether `obj` was made for this Tunnel
+    fn owns(&self, obj: &impl Resource) -> bool {
+        obj.owner_references()
+            .iter()
+            .any(|owner| Some(&owner.uid) == self.uid().as_ref())
+    }
+
+    async fn write_secret(&self, secrets: &Api<Secret>, tunnel_id: &str, token: &str) -> anyhow::Result<()> {
+        // owned by the Tunnel, so the Secret goes away with it
+        let secret = json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": {
+                "name": self.spec.secret_name,
+                "ownerReferences": [self.controller_owner_ref(&()).unwrap()],
+            },
+            "type": "Opaque",
+            "stringData": { SECRET_KEY: token, TUNNEL_ID_KEY: tunnel_id },
+        });
+        secrets
+            .patch(
+                &self.spec.secret_name,
+                &PatchParams::apply("cntrlr").force(),
+                &Patch::Apply(secret),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn publish(&self, ctx: &Context, reason: &str, note: String) {
+        let event = Event {
+            type_: EventType::Normal,
+            reason: reason.into(),
+            note: Some(note),
+            action: "Reconciling".into(),
+            secondary: None,
+        };
+        if let Err(e) = ctx.recorder.publish(&event, &self.object_ref(&())).await {
+            warn!("failed to publish {} event: {}", reason, e);
+        }
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|s| s.conditions.as_slice())
+            .unwrap_or_default()
+    }
+
+    // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
+    async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
+        let Some(tunnel_id) = self.status.as_ref().and_then(|s| s.tunnel_id.clone()) else {
+            // nothing was ever created on the Cloudflare side
+            return Ok(Action::await_change());
+        };
+        if DeletionPolicy::abandons(self) {
+            self.publish(
+                &ctx,
+                "Abandoned",
+                format!("Left tunnel `{tunnel_id}` in Cloudflare as requested by the deletion policy"),
+            )
+            .await;
+            return Ok(Action::await_change());
+        }
+
+        let ns = self.namespace().unwrap(); // doc is namespace scoped
+        let accounts: Api<Account> = Api::namespaced(ctx.client.clone(), &ns);
+        // without the Account there's no telling which account to delete it from
+        let Some(account) = accounts
+            .get_opt(&self.spec.account_ref.name)
+            .await
+            .map_err(Error::KubeError)?
+        else {
+            warn!(
+                "Account {} is gone, leaving tunnel {} in place",
+                self.spec.account_ref.name, tunnel_id
+            );
+            return Ok(Action::await_change());
+        };
+        let cf_client = ctx.provider.get_client(&account, &ns).await?;
+        // connectors would reconnect as soon as their connections are dropped
+        let deployments: Api<Deployment> = Api::namespaced(ctx.client.clone(), &ns);
+        self.stop_cloudflared(&deployments)
+            .await
+            .map_err(Error::KubeError)?;
+        // an error keeps the finalizer in place, so the object stays around until the delete goes through
+        cf_client.delete_tunnel(&account.spec.id, &tunnel_id).await?;
+        self.publish(
+            &ctx,
+            "Deleted",
+            format!("Deleted tunnel `{tunnel_id}` from Cloudflare"),
+        )
+        .await;
+        Ok(Action::await_change())
+    }
+}
+
+/// Initialize the controller and shared state (given the crd is installed)
+pub async fn run(state: State) {
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let scopes = state.namespaces().scopes();
+    let docs: Api<Tunnel> = scoped(client.clone(), scopes[0].as_deref());
+    if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
+        error!("CRD is not queryable; {e:?}. Is the CRD installed?");
+        info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
+        std::process::exit(1);
+    }
+
+    let settings = ControllerSettings::from_env("Tunnel");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
+        .await;
+    // one controller per watched namespace, they share the context and with it the Cloudflare clients
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        let controller = state
+            .controller("Tunnel", scoped::<Tunnel>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config());
+        let tunnels = controller.store();
+        state.health().watch("Tunnel", tunnels.clone());
+        controller
+            .watches(
+                scoped::<Account>(client.clone(), ns.as_deref()),
+                Config::default(),
+                wake_dependents(tunnels, |tunnel: &Tunnel| {
+                    Some(tunnel.spec.account_ref.name.as_str())
+                }),
+            )
+            // a deleted or emptied Secret gets the token again right away
+            .owns(scoped::<Secret>(client.clone(), ns.as_deref()), Config::default())
+            // and an edited or deleted cloudflared Deployment is put back
+            .owns(
+                scoped::<Deployment>(client.clone(), ns.as_deref()),
+                Config::default(),
+            )
+            .reconcile_on(state.triggers().tunnel.stream())
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("Tunnel", result))
+    }))
+    .await;
+}
+
+/// Whether `current` has everything `desired` sets, fields Cloudflare adds on its own don't count
+fn covers(current: &serde_json::Value, desired: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (current, desired) {
+        (Value::Object(current), Value::Object(desired)) => desired
+            .iter()
+            .all(|(key, value)| current.get(key).is_some_and(|current| covers(current, value))),
+        (Value::Array(current), Value::Array(desired)) => {
+            current.len() == desired.len() && current.iter().zip(desired).all(|(c, d)| covers(c, d))
+        }
+        _ => current == desired,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tunnel::{IngressRule, TunnelSpec};
+
+    #[test]
+    fn a_catch_all_closes_the_rules() {
+        let rule = |hostname: Option<&str>| IngressRule {
+            hostname: hostname.map(String::from),
+            path: None,
+            service: "http://web.default.svc:80".into(),
+            origin_request: None,
+        };
+        let tunnel = |ingress| {
+            Tunnel::new("edge", TunnelSpec {
+                ingress,
+                ..Default::default()
+            })
+        };
+        let config = tunnel(Some(vec![rule(Some("app.example.com"))]))
+            .ingress_config()
+            .unwrap();
+        assert_eq!(config["ingress"][1], json!({ "service": "http_status:404" }));
+        let config = tunnel(Some(vec![rule(None)])).ingress_config().unwrap();
+        assert_eq!(config["ingress"].as_array().unwrap().len(), 1);
+        assert_eq!(tunnel(None).ingress_config(), None);
+    }
+
+    #[test]
+    fn added_fields_still_cover_the_rules() {
+        let desired = json!({ "ingress": [{ "hostname": "a.example.com", "service": "http://a" }] });
+        let current = json!({
+            "ingress": [{ "hostname": "a.example.com", "service": "http://a", "originRequest": {} }],
+            "warp-routing": { "enabled": false },
+        });
+        assert!(covers(&current, &desired));
+        let reordered = json!({ "ingress": [{ "service": "http://a" }, { "hostname": "a.example.com" }] });
+        assert!(!covers(&reordered, &desired));
+    }
+
+    #[test]
+    fn only_secrets_made_for_the_tunnel_are_owned() {
+        let mut tunnel = Tunnel::new("edge", TunnelSpec::default());
+        tunnel.meta_mut().uid = Some("3f1c".into());
+        let mut secret = Secret::default();
+        assert!(!tunnel.owns(&secret));
+        secret.metadata.owner_references = Some(vec![tunnel.controller_owner_ref(&()).unwrap()]);
+        assert!(tunnel.owns(&secret));
+        secret.metadata.owner_references.as_mut().unwrap()[0].uid = "9a2e".into();
+        assert!(!tunnel.owns(&secret));
+    }
+}
@@ -0,0 +1,124 @@
+//! Conversion webhook between the served versions of a kind
+//!
+//! Only the spec differs between versions, metadata and status are passed through.
+use crate::{
+    dns_record::{self, DNSRecordSpec},
+    zone::{self, ZoneSpec},
+};
+use kube::core::{
+    Status,
+    conversion::{ConversionRequest, ConversionResponse, ConversionReview},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use tracing::*;
+
+/// Answer a review, failing it as a whole when one of its objects doesn't convert
+pub fn review(review: ConversionReview) -> ConversionReview {
+    let request = match ConversionRequest::from_review(review) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Invalid conversion review: {e}");
+            return ConversionResponse::invalid(Status::failure(&e.to_string(), "InvalidRequest"))
+                .into_review();
+        }
+    };
+    let desired = request.desired_api_version.clone();
+    let converted: Result<Vec<_>, String> = request
+        .objects
+        .iter()
+        .cloned()
+        .map(|object| convert(object, &desired))
+        .collect();
+
+    let response = ConversionResponse::for_request(request);
+    match converted {
+        Ok(objects) => response.success(objects),
+        Err(e) => {
+            warn!("Failed to convert to {desired}: {e}");
+            response.failure(Status::failure(&e, "ConversionFailed"))
+        }
+    }
+    .into_review()
+}
+
+/// `object` as `desired`, an `apiVersion` like `cloudflare.com/v1beta1`
+fn convert(object: Value, desired: &str) -> Result<Value, String> {
+    if object["apiVersion"] == desired {
+        return Ok(object);
+    }
+    let kind = object["kind"].as_str().unwrap_or_default().to_string();
+    let version = desired.rsplit('/').next().unwrap_or_default();
+    match (kind.as_str(), version) {
+        ("DNSRecord", "v1beta1") => convert_spec(
+            object,
+            desired,
+            <dns_record::v1beta1::DNSRecordSpec as TryFrom<DNSRecordSpec>>::try_from,
+        ),
+        ("DNSRecord", "v1alpha1") => {
+            convert_spec(object, desired, |spec: dns_record::v1beta1::DNSRecordSpec| {
+                Ok(DNSRecordSpec::from(spec))
+            })
+        }
+        ("Zone", "v1beta1") => convert_spec(
+            object,
+            desired,
+            <zone::v1beta1::ZoneSpec as TryFrom<ZoneSpec>>::try_from,
+        ),
+        ("Zone", "v1alpha1") => convert_spec(object, desired, |spec: zone::v1beta1::ZoneSpec| {
+            Ok(ZoneSpec::from(spec))
+        }),
+        _ => Err(format!("no conversion of {kind} to {desired}")),
+    }
+}
+
+fn convert_spec<A, B>(
+    mut object: Value,
+    desired: &str,
+    convert: impl FnOnce(A) -> Result<B, String>,
+) -> Result<Value, String>
+where
+    A: DeserializeOwned,
+    B: Serialize,
+{
+    let name = object["metadata"]["name"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let spec = serde_json::from_value(object["spec"].take()).map_err(|e| format!("{name}: {e}"))?;
+    let spec = convert(spec).map_err(|e| format!("{name}: {e}"))?;
+    object["spec"] = serde_json::to_value(spec).map_err(|e| format!("{name}: {e}"))?;
+    object["apiVersion"] = desired.into();
+    Ok(object)
+}
+
+#[cfg(test)]
+mod test {
+    use super::convert;
+    use serde_json::json;
+
+    #[test]
+    fn dns_records_round_trip() {
+        let alpha = json!({
+            "apiVersion": "cloudflare.com/v1alpha1",
+            "kind": "DNSRecord",
+            "metadata": { "name": "www", "namespace": "default" },
+            "spec": {
+                "zone_ref": { "name": "example.com" },
+                "name": "www",
+                "record_type": "AAAA",
+                "content": "2001:db8::1",
+                "ttl": 300,
+                "priority": null,
+                "proxied": true,
+                "reconcilePolicy": null
+            },
+            "status": { "ready": true }
+        });
+        let beta = convert(alpha.clone(), "cloudflare.com/v1beta1").unwrap();
+        assert_eq!(beta["spec"]["type"], "AAAA");
+        assert_eq!(beta["spec"]["zoneRef"]["name"], "example.com");
+        assert_eq!(beta["status"], alpha["status"]);
+        assert_eq!(convert(beta, "cloudflare.com/v1alpha1").unwrap(), alpha);
+    }
+}
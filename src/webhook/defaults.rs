@@ -0,0 +1,71 @@
+//! Defaulting webhook writing the implied spec values into the object
+//!
+//! With the defaults spelled out, `kubectl get -o yaml` shows what the controller acts on and GitOps
+//! tools don't see a diff between the applied manifest and a spec the API server filled in.
+use kube::core::{
+    DynamicObject,
+    admission::{AdmissionRequest, AdmissionResponse, AdmissionReview, Operation},
+};
+use serde_json::{Value, json};
+use tracing::*;
+
+/// Spec fields left out and the value they stand for, per kind
+fn defaults(kind: &str) -> Vec<(&'static str, Value)> {
+    match kind {
+        // 1 is Cloudflare's automatic TTL
+        "DNSRecord" => vec![("ttl", json!(1)), ("proxied", json!(false))],
+        "PageRule" => vec![("disabled", json!(false))],
+        "Zone" => vec![("type", json!("full"))],
+        _ => vec![],
+    }
+}
+
+/// JSON patch adding the defaults `spec` is missing
+pub fn patch(kind: &str, spec: &Value) -> Vec<Value> {
+    defaults(kind)
+        .into_iter()
+        .filter(|(field, _)| spec.get(field).is_none_or(Value::is_null))
+        .map(|(field, value)| json!({ "op": "add", "path": format!("/spec/{field}"), "value": value }))
+        .collect()
+}
+
+/// Answer a review, creates and updates of kinds with defaults get a patch
+pub fn review(review: AdmissionReview<DynamicObject>) -> AdmissionReview<DynamicObject> {
+    let request: AdmissionRequest<DynamicObject> = match review.try_into() {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Invalid admission review: {e}");
+            return AdmissionResponse::invalid(e.to_string()).into_review();
+        }
+    };
+    let response = AdmissionResponse::from(&request);
+    let Some(spec) = request
+        .object
+        .as_ref()
+        .filter(|_| request.operation != Operation::Delete)
+        .and_then(|object| object.data.get("spec"))
+    else {
+        return response.into_review();
+    };
+
+    let operations = patch(&request.kind.kind, spec);
+    if operations.is_empty() {
+        return response.into_review();
+    }
+    debug!(
+        "Defaulting {} fields of {} \"{}\"",
+        operations.len(),
+        request.kind.kind,
+        request.name
+    );
+    let patched = serde_json::from_value(Value::Array(operations))
+        .map_err(|e| e.to_string())
+        .and_then(|patch| response.clone().with_patch(patch).map_err(|e| e.to_string()));
+    match patched {
+        Ok(response) => response.into_review(),
+        Err(e) => {
+            warn!("Failed to build the defaulting patch: {e}");
+            response.into_review()
+        }
+    }
+}
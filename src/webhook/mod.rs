@@ -0,0 +1,145 @@
+//! Admission webhook turning away objects the reconcilers would only fail on
+//!
+//! The API server posts an `AdmissionReview` to `/validate` for creates and updates, a denial comes
+//! back to `kubectl apply` right away instead of minutes later through the status. It only does so over
+//! HTTPS, see [`tls`].
+use crate::{
+    State, account::Account, dns_record::DNSRecord, page_rule::PageRule, policy, zone::Zone, zone_binding,
+    zone_set::ZoneSet,
+};
+use kube::{
+    Client, ResourceExt,
+    core::{
+        DynamicObject,
+        admission::{AdmissionRequest, AdmissionResponse, AdmissionReview, Operation},
+    },
+};
+use tracing::*;
+
+pub mod convert;
+pub mod defaults;
+pub mod tls;
+pub mod validate;
+
+/// Answer a review, objects of kinds without checks are allowed
+pub async fn review(
+    state: &State,
+    client: &Client,
+    review: AdmissionReview<DynamicObject>,
+) -> AdmissionReview<DynamicObject> {
+    let request: AdmissionRequest<DynamicObject> = match review.try_into() {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Invalid admission review: {e}");
+            return AdmissionResponse::invalid(e.to_string()).into_review();
+        }
+    };
+    let response = AdmissionResponse::from(&request);
+    let Some(object) = request.object.filter(|_| request.operation != Operation::Delete) else {
+        return response.into_review();
+    };
+
+    let problems = match problems(state, client, &request.kind.kind, object).await {
+        Ok(problems) => problems,
+        Err(e) => vec![format!("{} doesn't match its schema: {e}", request.kind.kind)],
+    };
+    if problems.is_empty() {
+        response.into_review()
+    } else {
+        info!(
+            "Denied {} \"{}\": {}",
+            request.kind.kind,
+            request.name,
+            problems.join("; ")
+        );
+        response.deny(problems.join("; ")).into_review()
+    }
+}
+
+async fn problems(
+    state: &State,
+    client: &Client,
+    kind: &str,
+    object: DynamicObject,
+) -> Result<Vec<String>, kube::core::dynamic::ParseDynamicObjectError> {
+    Ok(match kind {
+        "DNSRecord" => {
+            let record = object.try_parse::<DNSRecord>()?;
+            let mut problems = validate::dns_record(&record);
+            let namespace = record.namespace().unwrap_or_default();
+            let hostname = record.hostname();
+            // the reconciler checks again, an outage shouldn't block every apply
+            match policy::check(
+                client.clone(),
+                &namespace,
+                &record.spec.zone_ref.name,
+                &record.spec.record_type,
+                &hostname,
+            )
+            .await
+            {
+                Ok(denied) => problems.extend(denied),
+                Err(e) => warn!("Failed to list CloudflarePolicies, skipping the policy check: {e}"),
+            }
+            problems.extend(bindings(client, &namespace, Some(&record.spec.zone_ref.name), &hostname).await);
+            problems
+        }
+        "Zone" => {
+            let spec = object.data.get("spec");
+            let mut problems = validate::zone_settings(spec.and_then(|spec| spec.get("settings")));
+            problems.extend(api_url(state, spec));
+            object.try_parse::<Zone>()?;
+            problems
+        }
+        "Account" => {
+            let problems = api_url(state, object.data.get("spec")).into_iter().collect();
+            object.try_parse::<Account>()?;
+            problems
+        }
+        "ZoneSet" => {
+            let template = object.data.get("spec").and_then(|spec| spec.get("template"));
+            let problems = api_url(state, template).into_iter().collect();
+            object.try_parse::<ZoneSet>()?;
+            problems
+        }
+        "PageRule" => {
+            let rule = object.try_parse::<PageRule>()?;
+            // the check is only as good as the list, an outage shouldn't block every apply
+            let others = state
+                .namespaces()
+                .list::<PageRule>(client)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to list PageRules, skipping the priority check: {e}");
+                    vec![]
+                });
+            let mut problems = validate::page_rule(&rule, &others);
+            let zone = rule.spec.zone_ref.as_ref().map(|z_ref| z_ref.name.as_str());
+            let namespace = rule.namespace().unwrap_or_default();
+            problems.extend(bindings(client, &namespace, zone, rule.hostname()).await);
+            problems
+        }
+        _ => vec![],
+    })
+}
+
+/// Why the `apiUrl` of `spec` can't be used, if it can't
+///
+/// Read from the JSON, so it holds for every version of the kind.
+fn api_url(state: &State, spec: Option<&serde_json::Value>) -> Option<String> {
+    let spec = spec?;
+    let api_url = spec.get("apiUrl")?.as_str()?;
+    let own_secret = spec.get("secretRef").is_some_and(|s_ref| !s_ref.is_null());
+    state.allowed_api_urls().problem(api_url, own_secret)
+}
+
+/// Why the ZoneBindings deny a hostname, if they do
+async fn bindings(client: &Client, namespace: &str, zone: Option<&str>, hostname: &str) -> Option<String> {
+    // the reconcilers check again
+    zone_binding::check(client.clone(), namespace, zone, hostname)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to list ZoneBindings, skipping the binding check: {e}");
+            None
+        })
+}
@@ -0,0 +1,91 @@
+//! TLS for the admission and conversion webhooks
+//!
+//! The API server only calls webhooks over HTTPS. With `WEBHOOK_CERT_DIR` set, the `tls.crt` and `tls.key`
+//! in it (a mounted Secret, as cert-manager writes them) are served on `WEBHOOK_PORT`, 8443 by default.
+//! They are read again every minute, a renewed certificate is picked up without a restart.
+use rustls::{
+    ServerConfig,
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tracing::*;
+
+/// Port the webhooks are served on over TLS when `WEBHOOK_PORT` isn't set
+pub const DEFAULT_PORT: u16 = 8443;
+
+/// How often the certificate is read again
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Directory and port from `WEBHOOK_CERT_DIR` and `WEBHOOK_PORT`, `None` without a directory
+pub fn from_env() -> anyhow::Result<Option<(PathBuf, u16)>> {
+    let Some(dir) = std::env::var("WEBHOOK_CERT_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+    else {
+        return Ok(None);
+    };
+    let port = match std::env::var("WEBHOOK_PORT") {
+        Ok(port) => port.parse().map_err(|e| anyhow::anyhow!("WEBHOOK_PORT: {e}"))?,
+        Err(_) => DEFAULT_PORT,
+    };
+    Ok(Some((dir.into(), port)))
+}
+
+/// Server configuration presenting the certificate in `dir`, kept up to date in the background
+pub fn server_config(dir: &Path) -> anyhow::Result<ServerConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let current = load(dir, &provider)?;
+    let resolver = Arc::new(Reloading {
+        dir: dir.to_path_buf(),
+        provider: provider.clone(),
+        current: RwLock::new(current),
+    });
+    tokio::spawn(resolver.clone().reload());
+    Ok(ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver))
+}
+
+fn load(dir: &Path, provider: &CryptoProvider) -> anyhow::Result<Arc<CertifiedKey>> {
+    let chain = CertificateDer::pem_file_iter(dir.join("tls.crt"))?.collect::<Result<Vec<_>, _>>()?;
+    let key = PrivateKeyDer::from_pem_file(dir.join("tls.key"))?;
+    let key = provider.key_provider.load_private_key(key)?;
+    Ok(Arc::new(CertifiedKey::new(chain, key)))
+}
+
+/// The certificate of a directory, as last read
+#[derive(Debug)]
+struct Reloading {
+    dir: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl Reloading {
+    async fn reload(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(RELOAD_INTERVAL);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match load(&self.dir, &self.provider) {
+                Ok(certified) => *self.current.write().unwrap() = certified,
+                // the previous one stays, it may well still be valid
+                Err(e) => warn!("failed to read the webhook certificate: {e}"),
+            }
+        }
+    }
+}
+
+impl ResolvesServerCert for Reloading {
+    fn resolve(&self, _hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
@@ -0,0 +1,176 @@
+//! Checks an object has to pass to be admitted, each returns what is wrong with it
+use crate::{dns_record::DNSRecord, page_rule::PageRule};
+use kube::ResourceExt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Record types the DNSRecord reconciler knows how to write
+pub const RECORD_TYPES: [&str; 5] = ["A", "AAAA", "CNAME", "MX", "TXT"];
+
+/// Keys of `spec.settings` on a Zone
+pub const ZONE_SETTINGS: [&str; 12] = [
+    "ssl",
+    "alwaysUseHttps",
+    "minTlsVersion",
+    "tls13",
+    "automaticHttpsRewrites",
+    "opportunisticEncryption",
+    "http3",
+    "brotli",
+    "ipv6",
+    "websockets",
+    "alwaysOnline",
+    "developmentMode",
+];
+
+/// Settings a page rule action can change
+pub const PAGE_RULE_ACTIONS: [&str; 27] = [
+    "always_use_https",
+    "automatic_https_rewrites",
+    "browser_cache_ttl",
+    "browser_check",
+    "bypass_cache_on_cookie",
+    "cache_by_device_type",
+    "cache_deception_armor",
+    "cache_key_fields",
+    "cache_level",
+    "cache_on_cookie",
+    "disable_apps",
+    "disable_performance",
+    "disable_security",
+    "disable_zaraz",
+    "edge_cache_ttl",
+    "email_obfuscation",
+    "explicit_cache_control",
+    "forwarding_url",
+    "host_header_override",
+    "ip_geolocation",
+    "mirage",
+    "opportunistic_encryption",
+    "origin_error_page_pass_thru",
+    "resolve_override",
+    "rocket_loader",
+    "security_level",
+    "ssl",
+];
+
+/// Cloudflare takes 1 for automatic, otherwise between a minute and a day
+fn valid_ttl(ttl: u32) -> bool {
+    ttl == 1 || (60..=86400).contains(&ttl)
+}
+
+pub fn dns_record(record: &DNSRecord) -> Vec<String> {
+    let spec = &record.spec;
+    let mut problems = vec![];
+    match spec.record_type.as_str() {
+        "A" if spec.content.parse::<Ipv4Addr>().is_err() => {
+            problems.push(format!("content {:?} is not an IPv4 address", spec.content))
+        }
+        "AAAA" if spec.content.parse::<Ipv6Addr>().is_err() => {
+            problems.push(format!("content {:?} is not an IPv6 address", spec.content))
+        }
+        t if !RECORD_TYPES.contains(&t) => problems.push(format!(
+            "recordType {t:?} is not supported, use one of {}",
+            RECORD_TYPES.join(", ")
+        )),
+        _ => {}
+    }
+    if let Some(ttl) = spec.ttl.filter(|ttl| !valid_ttl(*ttl)) {
+        problems.push(format!(
+            "ttl {ttl} is out of range, use 1 for automatic or 60 to 86400"
+        ));
+    }
+    if spec.priority.is_some() && spec.record_type != "MX" {
+        problems.push(format!(
+            "priority only applies to MX records, not {}",
+            spec.record_type
+        ));
+    }
+    problems
+}
+
+/// Keys of the raw `spec.settings`, the typed Zone drops the ones it doesn't know
+pub fn zone_settings(settings: Option<&serde_json::Value>) -> Vec<String> {
+    let Some(settings) = settings.and_then(|s| s.as_object()) else {
+        return vec![];
+    };
+    settings
+        .keys()
+        .filter(|key| !ZONE_SETTINGS.contains(&key.as_str()))
+        .map(|key| format!("settings.{key} is not a known zone setting"))
+        .collect()
+}
+
+/// `others` are the PageRules already in the cluster, the rule itself among them when it's updated
+pub fn page_rule(rule: &PageRule, others: &[PageRule]) -> Vec<String> {
+    let mut problems = vec![];
+    match (&rule.spec.zone_ref, &rule.spec.zone_id) {
+        (None, None) => problems.push("one of zoneRef or zoneId is required".to_string()),
+        (Some(_), Some(_)) => problems.push("zoneRef and zoneId can't both be set".to_string()),
+        _ => {}
+    }
+    for action in &rule.spec.actions {
+        if !PAGE_RULE_ACTIONS.contains(&action.id.as_str()) {
+            problems.push(format!("action {:?} is not a page rule setting", action.id));
+        }
+    }
+
+    if let Some(priority) = rule.spec.priority {
+        let same_zone = |other: &PageRule| match (&rule.spec.zone_ref, &other.spec.zone_ref) {
+            (Some(a), Some(b)) => a.name == b.name && rule.namespace() == other.namespace(),
+            _ => rule.spec.zone_id.is_some() && rule.spec.zone_id == other.spec.zone_id,
+        };
+        let taken = others.iter().find(|other| {
+            (other.namespace(), other.name_any()) != (rule.namespace(), rule.name_any())
+                && other.spec.priority == Some(priority)
+                && same_zone(other)
+        });
+        if let Some(other) = taken {
+            problems.push(format!(
+                "priority {priority} is already taken by {}/{} in the same zone",
+                other.namespace().unwrap_or_default(),
+                other.name_any()
+            ));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{dns_record::DNSRecordSpec, page_rule::PageRuleSpec};
+    use k8s_openapi::api::core::v1::LocalObjectReference;
+
+    #[test]
+    fn rejects_malformed_records() {
+        let record = |record_type: &str, content: &str, ttl| {
+            DNSRecord::new("test", DNSRecordSpec {
+                record_type: record_type.into(),
+                content: content.into(),
+                ttl,
+                ..Default::default()
+            })
+        };
+        assert!(dns_record(&record("A", "192.0.2.1", Some(300))).is_empty());
+        assert!(dns_record(&record("AAAA", "2001:db8::1", Some(1))).is_empty());
+        assert_eq!(dns_record(&record("A", "2001:db8::1", None)).len(), 1);
+        assert_eq!(dns_record(&record("SRV", "x", Some(30))).len(), 2);
+    }
+
+    #[test]
+    fn rejects_taken_priorities() {
+        let rule = |name: &str, zone: &str, priority| {
+            let mut rule = PageRule::new(name, PageRuleSpec {
+                zone_ref: Some(LocalObjectReference { name: zone.into() }),
+                priority: Some(priority),
+                ..Default::default()
+            });
+            rule.metadata.namespace = Some("default".into());
+            rule
+        };
+        let existing = vec![rule("a", "example.com", 1), rule("b", "example.org", 2)];
+        assert!(page_rule(&rule("a", "example.com", 1), &existing).is_empty());
+        assert!(page_rule(&rule("c", "example.com", 2), &existing).is_empty());
+        assert_eq!(page_rule(&rule("c", "example.com", 1), &existing).len(), 1);
+    }
+}
@@ -3,19 +3,160 @@ use kube::CustomResource;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::cloudflare::CloudflareResource;
+use crate::{
+    cf_client::{self, Toggle, ZoneSettingValue},
+    cloudflare::CloudflareResource,
+    conditions::{Condition, Conditions},
+    reconcile_policy::ReconcilePolicy,
+};
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(test, derive(Default))]
 #[kube(kind = "Zone", group = "cloudflare.com", version = "v1alpha1", namespaced)]
-#[kube(status = "ZoneStatus", shortname = "zone")]
+#[kube(
+    status = "ZoneStatus",
+    shortname = "zone",
+    shortname = "cfzone",
+    category = "cloudflare"
+)]
+#[kube(printcolumn = r#"{"name":"Ready", "type":"boolean", "jsonPath":".status.ready"}"#)]
+#[kube(printcolumn = r#"{"name":"Activation", "type":"string", "jsonPath":".status.activationStatus"}"#)]
+#[kube(printcolumn = r#"{"name":"Summary", "type":"string", "jsonPath":".status.summary"}"#)]
+#[kube(
+    printcolumn = r#"{"name":"Nameservers", "type":"string", "jsonPath":".status.assignedNameservers", "priority":1}"#
+)]
+#[kube(printcolumn = r#"{"name":"Plan", "type":"string", "jsonPath":".status.plan", "priority":1}"#)]
+#[kube(printcolumn = r#"{"name":"Paused", "type":"boolean", "jsonPath":".status.paused", "priority":1}"#)]
 #[serde(rename_all = "camelCase")]
 pub struct ZoneSpec {
     pub account_ref: Option<LocalObjectReference>,
     pub secret_ref: Option<SecretKeySelector>,
+    /// Bind to this existing Cloudflare zone instead of looking it up by name or creating it
+    pub zone_id: Option<String>,
+    /// How DNS for the zone is set up, only used when the zone is created
+    #[serde(rename = "type")]
+    pub zone_type: Option<ZoneType>,
+    /// Rate plan id of the zone subscription (`free`, `pro`, `business`, `enterprise`)
+    pub plan: Option<String>,
+    /// Scan for existing DNS records when the zone is created
+    pub jump_start: Option<bool>,
+    /// Pause Cloudflare for the domain, traffic goes straight to the origin while paused
+    pub paused: Option<bool>,
+    /// Custom nameservers to hand out instead of the assigned ones, business and enterprise plans only
+    pub vanity_name_servers: Option<Vec<String>>,
+    /// Zone settings to enforce, settings left out are not touched
+    pub settings: Option<ZoneSettings>,
+    /// `DriftReportOnly` to only compare the zone with Cloudflare, `Enforce` by default
+    pub reconcile_policy: Option<ReconcilePolicy>,
+    /// Cloudflare API to manage the zone through instead of the operator's (the China network, a mock
+    /// server), records of the zone follow it; without a `secretRef` only `CLOUDFLARE_ALLOWED_API_URLS`
+    pub api_url: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneSettings {
+    pub ssl: Option<SslMode>,
+    pub always_use_https: Option<bool>,
+    pub min_tls_version: Option<TlsVersion>,
+    pub tls_1_3: Option<bool>,
+    pub automatic_https_rewrites: Option<bool>,
+    pub opportunistic_encryption: Option<bool>,
+    pub http3: Option<bool>,
+    pub brotli: Option<bool>,
+    pub ipv6: Option<bool>,
+    pub websockets: Option<bool>,
+    pub always_online: Option<bool>,
+    pub development_mode: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SslMode {
+    Off,
+    Flexible,
+    Full,
+    Strict,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum TlsVersion {
+    #[serde(rename = "1.0")]
+    Tls10,
+    #[serde(rename = "1.1")]
+    Tls11,
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl ZoneSettings {
+    /// The settings that are set, as Cloudflare settings
+    pub fn desired(&self) -> Vec<ZoneSettingValue> {
+        use ZoneSettingValue as Setting;
+        fn name<T: Serialize>(value: T) -> String {
+            serde_json::to_value(value)
+                .ok()
+                .and_then(|v| v.as_str().map(String::from))
+                .unwrap_or_default()
+        }
+        let toggles = [
+            (
+                Setting::AlwaysUseHttps as fn(Toggle) -> Setting,
+                self.always_use_https,
+            ),
+            (Setting::Tls13, self.tls_1_3),
+            (Setting::AutomaticHttpsRewrites, self.automatic_https_rewrites),
+            (Setting::OpportunisticEncryption, self.opportunistic_encryption),
+            (Setting::Http3, self.http3),
+            (Setting::Brotli, self.brotli),
+            (Setting::Ipv6, self.ipv6),
+            (Setting::Websockets, self.websockets),
+            (Setting::AlwaysOnline, self.always_online),
+            (Setting::DevelopmentMode, self.development_mode),
+        ];
+
+        let mut desired = vec![];
+        if let Some(ssl) = self.ssl {
+            desired.push(Setting::Ssl(name(ssl)));
+        }
+        if let Some(version) = self.min_tls_version {
+            desired.push(Setting::MinTlsVersion(name(version)));
+        }
+        desired.extend(
+            toggles
+                .into_iter()
+                .filter_map(|(setting, on)| Some(setting(on?.into()))),
+        );
+        desired
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ZoneType {
+    /// Cloudflare is the authoritative DNS provider
+    Full,
+    /// CNAME setup, DNS stays with another provider
+    Partial,
+    /// Cloudflare serves the zone transferred from a primary
+    Secondary,
+}
+
+impl From<ZoneType> for cf_client::ZoneType {
+    fn from(zone_type: ZoneType) -> Self {
+        match zone_type {
+            ZoneType::Full => cf_client::ZoneType::Full,
+            ZoneType::Partial => cf_client::ZoneType::Partial,
+            ZoneType::Secondary => cf_client::ZoneType::Secondary,
+        }
+    }
 }
 
 impl CloudflareResource for Zone {
+    const PERMISSIONS: &'static [&'static str] = &["Zone:Edit", "Zone Settings:Edit"];
+
     fn secret_ref(&self) -> Option<&SecretKeySelector> {
         self.spec.secret_ref.as_ref()
     }
@@ -23,11 +164,69 @@ impl CloudflareResource for Zone {
     fn account_ref(&self) -> Option<&LocalObjectReference> {
         self.spec.account_ref.as_ref()
     }
+
+    fn api_url(&self) -> Option<&str> {
+        self.spec.api_url.as_deref()
+    }
+
+    fn reconcile_policy(&self) -> ReconcilePolicy {
+        self.spec.reconcile_policy.unwrap_or_default()
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct ZoneStatus {
     pub ready: bool,
     pub id: Option<String>,
     pub error: Option<String>,
+    /// Nameservers Cloudflare assigned, the ones to delegate the domain to
+    #[serde(default)]
+    pub assigned_nameservers: Vec<String>,
+    /// Nameservers the domain was delegated to before moving to Cloudflare
+    #[serde(default)]
+    pub original_nameservers: Vec<String>,
+    /// `pending` until Cloudflare sees the delegation, `active` afterwards
+    pub activation_status: Option<String>,
+    /// When the operator last asked Cloudflare to recheck the delegation of a pending zone
+    pub last_activation_check: Option<String>,
+    #[serde(default)]
+    pub paused: bool,
+    pub plan: Option<String>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Activation, plan and pause state in one line, for `kubectl get`
+    pub summary: Option<String>,
+    /// Generation of the spec the status was written for
+    pub observed_generation: Option<i64>,
+    /// Reconciles failed in a row, gone once one succeeds
+    pub consecutive_failures: Option<u32>,
+}
+
+impl Conditions for ZoneStatus {
+    fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition> {
+        &mut self.conditions
+    }
+
+    fn set_failed(&mut self, message: &str) {
+        self.ready = false;
+        self.error = Some(message.into());
+    }
+}
+
+impl ZoneStatus {
+    /// Describe the zone, like `example.com: active, Free Website, paused`
+    pub fn summarize(&self, name: &str) -> String {
+        let mut parts: Vec<&str> = vec![];
+        parts.extend(self.activation_status.as_deref());
+        parts.extend(self.plan.as_deref());
+        if self.paused {
+            parts.push("paused");
+        }
+        format!("{name}: {}", parts.join(", "))
+    }
 }
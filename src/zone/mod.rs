@@ -1,5 +1,6 @@
 mod crd;
 mod reconcile;
+pub mod v1beta1;
 
-pub use crd::{Zone, ZoneSpec, ZoneStatus};
+pub use crd::{SslMode, TlsVersion, Zone, ZoneSettings, ZoneSpec, ZoneStatus, ZoneType};
 pub use reconcile::{DOCUMENT_FINALIZER, run};
@@ -1,55 +1,91 @@
 use crate::{
     Context, Error, Result, State,
     account::Account,
-    cf_client::CreateZoneParams,
-    telemetry,
+    cf_client::{
+        CloudflareClient, CreateZoneParams, EditZoneParams, Plan, ZONE_EXISTS, Zone as CfZone, is_not_found,
+    },
+    cloudflare::{self, CloudflareResource},
+    conditions::{Condition, Conditions},
+    deletion_policy::DeletionPolicy,
+    dependency::{wait_for_dependency, wake_dependents},
+    failures,
+    namespaces::scoped,
+    pause,
+    reconcile_policy::Drift,
+    settings::ControllerSettings,
+    status, telemetry,
     zone::{Zone, ZoneStatus},
 };
-use chrono::Utc;
+use anyhow::anyhow;
+use chrono::{DateTime, TimeDelta, Utc};
 use futures::StreamExt;
 use kube::{
-    Error as KubeError, Resource,
-    api::{Api, ListParams, Patch, PatchParams, ResourceExt},
+    Resource,
+    api::{Api, ListParams, ResourceExt},
     client::Client,
     runtime::{
-        controller::{Action, Controller},
+        controller::Action,
         events::{Event, EventType},
         finalizer::{Event as Finalizer, finalizer},
         watcher::Config,
     },
 };
-use serde_json::json;
 use std::sync::Arc;
 use tokio::time::Duration;
 use tracing::*;
 pub static DOCUMENT_FINALIZER: &str = "zone.cloudflare.com";
 
+/// The account can't take another zone, checked before creating one
+#[derive(Debug, thiserror::Error)]
+#[error("Account {account} is at its quota of {quota} zones")]
+struct QuotaExceeded {
+    account: String,
+    quota: u32,
+}
+
+/// Cloudflare rate limits activation checks, so don't ask more often than this
+const ACTIVATION_CHECK_INTERVAL: TimeDelta = TimeDelta::minutes(10);
+
+/// Whether `spec.vanityNameServers` are in place, only set when the spec has them
+const VANITY_NAME_SERVERS_CONDITION: &str = "VanityNameServers";
+
+/// Whether `spec.settings` are in place, only set when the spec has them
+const SETTINGS_APPLIED_CONDITION: &str = "SettingsApplied";
+
 #[instrument(skip(ctx, doc), fields(trace_id))]
 async fn reconcile(doc: Arc<Zone>, ctx: Arc<Context>) -> Result<Action> {
     let trace_id = telemetry::get_trace_id();
     if trace_id != opentelemetry::trace::TraceId::INVALID {
         Span::current().record("trace_id", field::display(&trace_id));
     }
-    let _timer = ctx.metrics.reconcile.count_and_measure(&trace_id);
+    let _timer = ctx.metrics.reconcile.count_and_measure(doc.as_ref(), &trace_id);
     ctx.diagnostics.write().await.last_event = Utc::now();
+    ctx.metrics.resources.track(doc.as_ref());
     let ns = doc.namespace().unwrap(); // doc is namespace scoped
     let docs: Api<Zone> = Api::namespaced(ctx.client.clone(), &ns);
 
     info!("Reconciling Zone \"{}\" in {}", doc.name_any(), ns);
-    finalizer(&docs, DOCUMENT_FINALIZER, doc, |event| async {
+    if pause::is_paused(doc.as_ref()) {
+        return pause::hold(doc.as_ref(), ctx.client.clone()).await;
+    }
+    let finalize = finalizer(&docs, DOCUMENT_FINALIZER, doc.clone(), |event| async {
         match event {
             Finalizer::Apply(doc) => doc.reconcile(ctx.clone()).await,
             Finalizer::Cleanup(doc) => doc.cleanup(ctx.clone()).await,
         }
-    })
-    .await
-    .map_err(|e| Error::FinalizerError(Box::new(e)))
+    });
+    let result = ctx
+        .audit
+        .record(doc.as_ref(), &ctx.recorder, finalize)
+        .await
+        .map_err(|e| Error::FinalizerError(Box::new(e)));
+    cloudflare::preflight::forbidden(doc.as_ref(), &ctx, result).await
 }
 
 fn error_policy(doc: Arc<Zone>, error: &Error, ctx: Arc<Context>) -> Action {
     warn!("reconcile failed: {:?}", error);
     ctx.metrics.reconcile.set_failure(doc.as_ref(), error);
-    Action::requeue(Duration::from_secs(5 * 60))
+    failures::requeue(doc, error, &ctx)
 }
 
 impl Zone {
@@ -59,159 +95,594 @@ impl Zone {
         let ns = self.namespace().unwrap(); // we unwrap this, because it's probably impossible to
         // have no ns on the namespaced object
         let name = self.name_any();
-        let docs: Api<Zone> = Api::namespaced(client.clone(), &ns);
-        let acc_api: Api<Account> = Api::namespaced(client, &ns);
         if let Some(a_ref) = &self.spec.account_ref {
-            match acc_api.get(&a_ref.name).await {
-                Ok(acc) => {
-                    if let Some(a_status) = acc.status.as_ref()
-                        && a_status.ready
+            let acc = match wait_for_dependency::<Account>(client, &ns, &a_ref.name).await? {
+                Ok(acc) => acc,
+                Err(blocked) => {
+                    warn!("Zone \"{}\": {}", name, blocked.message());
+                    self.publish(&ctx, "DependencyNotReady", blocked.message()).await;
+                    let mut status = ZoneStatus {
+                        ready: false,
+                        error: Some(blocked.message()),
+                        // cleanup deletes the zone by the id, it has to outlive a failed reconcile
+                        ..self.status.clone().unwrap_or_default()
+                    };
+                    status.set_reconciling("DependencyNotReady", blocked.message(), self.meta().generation);
+                    status::patch(self, ctx.client.clone(), &status).await?;
+                    // the Account watch wakes us up once it's ready, this is just a fallback
+                    return Ok(Action::requeue(ctx.settings.requeue));
+                }
+            };
+
+            let (cf_client, source) = match ctx.provider.get_client_with_source(self, &ns).await {
+                Ok(resolved) => resolved,
+                Err(e) => return cloudflare::credentials_unavailable(self, &ctx, e).await,
+            };
+            let mut drift = Drift::default();
+            match self.converge(&ctx, &cf_client, &acc, &mut drift).await {
+                Ok(zone) => {
+                    let mut status = ZoneStatus {
+                        conditions: self.conditions().to_vec(),
+                        ..zone_status(&zone)
+                    };
+                    // only reported while the spec asks for them
+                    status.remove_condition(VANITY_NAME_SERVERS_CONDITION);
+                    status.remove_condition(SETTINGS_APPLIED_CONDITION);
+                    if let Some(condition) = self
+                        .sync_vanity_name_servers(&ctx, &cf_client, &zone, &mut drift)
+                        .await
                     {
-                        let create_zone = CreateZoneParams {
-                            name: &name,
-                            account: &acc.spec.id,
-                            jump_start: None,
-                            zone_type: None,
-                        };
-
-                        match ctx
-                            .provider
-                            .get_client(self, &ns)
-                            .await
-                            .unwrap() // @FIXME: We need poscess it
-                            .create_zone(create_zone)
-                            .await
-                        {
-                            Ok(zone_id) => {
-                                docs.patch_status(
-                                    &name,
-                                    &PatchParams::apply("cntrlr").force(),
-                                    &Patch::Apply(json!({
-                                        "apiVersion": "cloudflare.com/v1alpha1",
-                                        "kind": "Zone",
-                                        "status": ZoneStatus {
-                                            ready: true,
-                                            id: Some(zone_id),
-                                            error: None,
-                                        }
-                                    })),
-                                )
-                                .await
-                                .map_err(Error::KubeError)?;
-
-                                return Ok(Action::requeue(Duration::from_secs(5 * 60)));
-                            }
-                            Err(e) => {
-                                eprintln!("Error happend: {}", e);
-                                docs.patch_status(
-                                    &name,
-                                    &PatchParams::apply("cntrlr").force(),
-                                    &Patch::Apply(json!({
-                                        "apiVersion": "cloudflare.com/v1alpha1",
-                                        "kind": "Zone",
-                                        "status": ZoneStatus {
-                                            ready: false,
-                                            id: None,
-                                            error: Some(e.to_string()),
-                                        }
-                                    })),
-                                )
-                                .await
-                                .map_err(Error::KubeError)?;
-                                return Ok(Action::requeue(Duration::from_secs(60)));
-                            }
+                        status.set_condition(condition);
+                    }
+                    if let Some(condition) = self.sync_settings(&ctx, &cf_client, &zone.id, &mut drift).await
+                    {
+                        status.set_condition(condition);
+                    }
+                    status.set_condition(source.condition(self.conditions(), self.meta().generation));
+                    status.set_condition(source.validity(self.conditions(), self.meta().generation));
+                    let requeue = self.track_activation(&ctx, &cf_client, &mut status).await?;
+                    drift.apply(&mut status, self.meta().generation);
+                    status.summary = Some(status.summarize(&zone.name));
+                    status::patch(self, ctx.client.clone(), &status).await?;
+
+                    return Ok(Action::requeue(requeue));
+                }
+                Err(e) => {
+                    eprintln!("Error happend: {}", e);
+                    let mut status = ZoneStatus {
+                        ready: false,
+                        error: Some(e.to_string()),
+                        ..self.status.clone().unwrap_or_default()
+                    };
+                    match e.downcast_ref::<QuotaExceeded>() {
+                        // stays this way until zones are removed or the plan grows
+                        Some(quota) => {
+                            status.set_stalled("QuotaExceeded", quota.to_string(), self.meta().generation)
                         }
-                    } else {
-                        docs.patch_status(
-                            &name,
-                            &PatchParams::apply("cntrlr").force(),
-                            &Patch::Apply(json!({
-                                "apiVersion": "cloudflare.com/v1alpha1",
-                                "kind": "Zone",
-                                "status": ZoneStatus {
-                                    ready: false,
-                                    id: None,
-                                    error: Some(format!("Dependency account/{} is not ready", acc.name_any())),
-                                }
-                            })),
-                        )
-                        .await
-                        .map_err(Error::KubeError)?;
-                        return Ok(Action::requeue(Duration::from_secs(60)));
+                        None => status.set_reconciling("SyncFailed", e.to_string(), self.meta().generation),
                     }
+                    status.set_condition(source.condition(self.conditions(), self.meta().generation));
+                    status.set_condition(source.validity(self.conditions(), self.meta().generation));
+                    status::patch(self, ctx.client.clone(), &status).await?;
+                    return Ok(Action::requeue(Duration::from_secs(60)));
                 }
-                Err(KubeError::Api(e)) if e.code == 404 => {
-                    eprintln!("Account '{}' not found in '{}' namespace", &a_ref.name, &ns);
-                    docs.patch_status(
-                        &name,
-                        &PatchParams::apply("cntrlr").force(),
-                        &Patch::Apply(json!({
-                            "apiVersion": "cloudflare.com/v1alpha1",
-                            "kind": "Zone",
-                            "status": ZoneStatus {
-                                ready: false,
-                                id: None,
-                                error: Some(format!("Dependency account/{} not found", a_ref.name)),
-                            }
-                        })),
+            }
+        }
+
+        if name == "illegal" {
+            return Err(Error::IllegalDocument); // error names show up in metrics
+        }
+
+        // If no events were received, check back every 5 minutes
+        Ok(Action::requeue(ctx.settings.requeue))
+    }
+
+    /// Bring the Cloudflare zone in line with the spec, returns the zone as it is afterwards
+    ///
+    /// Under `DriftReportOnly` differences end up in `drift` instead of being corrected.
+    async fn converge(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        account: &Account,
+        drift: &mut Drift,
+    ) -> anyhow::Result<CfZone> {
+        let zone = self.ensure_zone(ctx, cf_client, account).await?;
+        let zone = self.sync_plan(ctx, cf_client, zone, drift).await?;
+        self.sync_paused(ctx, cf_client, zone, drift).await
+    }
+
+    /// Find the Cloudflare zone this object stands for, creating it only when it doesn't exist yet
+    async fn ensure_zone(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        account: &Account,
+    ) -> anyhow::Result<CfZone> {
+        let name = self.name_any();
+        let account_id = account.spec.id.as_str();
+        // an explicitly bound zone is never created, it has to exist already
+        if let Some(zone_id) = self.spec.zone_id.as_deref() {
+            return match cf_client.get_zone(zone_id).await {
+                Ok(zone) if zone.account.id != account_id => Err(anyhow!(
+                    "Zone {} ({}) belongs to account {}, not {}",
+                    zone.name,
+                    zone_id,
+                    zone.account.id,
+                    account_id
+                )),
+                Ok(zone) => Ok(zone),
+                Err(e) if is_not_found(&e) => Err(anyhow!("Zone {zone_id} does not exist in Cloudflare")),
+                Err(e) => Err(e.into()),
+            };
+        }
+
+        if let Some(zone_id) = self.status.as_ref().and_then(|s| s.id.as_deref()) {
+            match cf_client.get_zone(zone_id).await {
+                Ok(zone) => return Ok(zone),
+                // deleted behind our back, go through lookup and creation again
+                Err(e) if is_not_found(&e) => warn!("Zone {} ({}) is gone from Cloudflare", name, zone_id),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if let Some(zone) = cf_client.find_zone(&name, account_id).await? {
+            info!("Adopting existing zone {} ({})", name, zone.id);
+            self.publish(ctx, "Adopted", format!("Adopted existing zone `{}`", zone.id))
+                .await;
+            return Ok(zone);
+        }
+        if self.report_only() {
+            return Err(anyhow!(
+                "Zone {name} does not exist in Cloudflare and the reconcile policy doesn't allow creating it"
+            ));
+        }
+
+        if let Some(status) = &account.status
+            && status.at_zone_quota()
+        {
+            return Err(QuotaExceeded {
+                account: account.name_any(),
+                quota: status.zone_quota.unwrap_or_default(),
+            }
+            .into());
+        }
+
+        // type and jump start only apply to a fresh zone, an existing one keeps what it was created with
+        let zone = match cf_client
+            .create_zone(CreateZoneParams {
+                name: &name,
+                account: account_id,
+                jump_start: self.spec.jump_start,
+                zone_type: self.spec.zone_type.map(Into::into),
+            })
+            .await
+        {
+            Ok(zone) => zone,
+            // not in this account, the lookup above would have found it
+            Err(e) if e.has_code(ZONE_EXISTS) => {
+                return Err(anyhow!(
+                    "Zone {name} already exists in another Cloudflare account"
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        self.publish(ctx, "Created", format!("Created zone `{}`", zone.id))
+            .await;
+        Ok(zone)
+    }
+
+    /// Move the zone to the plan from the spec, unless it's already on it or the change is pending
+    async fn sync_plan(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        zone: CfZone,
+        drift: &mut Drift,
+    ) -> anyhow::Result<CfZone> {
+        let Some(plan) = self.spec.plan.as_deref() else {
+            return Ok(zone);
+        };
+        let on_plan = |p: &Option<Plan>| p.as_ref().is_some_and(|p| p.legacy_id == plan || p.id == plan);
+        if on_plan(&zone.plan) || on_plan(&zone.plan_pending) {
+            return Ok(zone);
+        }
+        if self.report_only() {
+            drift
+                .report(self, ctx, format!("Zone `{}` is not on the {plan} plan", zone.id))
+                .await;
+            return Ok(zone);
+        }
+        info!("Moving zone {} ({}) to the {} plan", zone.name, zone.id, plan);
+        cf_client.update_zone_plan(&zone.id, plan).await?;
+        self.changed(ctx, format!("Moved zone `{}` to the {plan} plan", zone.id))
+            .await;
+        Ok(zone)
+    }
+
+    /// Pause or resume Cloudflare for the domain when `spec.paused` disagrees with the zone
+    async fn sync_paused(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        zone: CfZone,
+        drift: &mut Drift,
+    ) -> anyhow::Result<CfZone> {
+        match self.spec.paused {
+            Some(paused) if paused != zone.paused && self.report_only() => {
+                let state = if zone.paused { "paused" } else { "not paused" };
+                drift
+                    .report(self, ctx, format!("Zone `{}` is {state}", zone.id))
+                    .await;
+                Ok(zone)
+            }
+            Some(paused) if paused != zone.paused => {
+                let verb = if paused { "Pausing" } else { "Resuming" };
+                info!("{} zone {} ({})", verb, zone.name, zone.id);
+                let params = EditZoneParams {
+                    paused: Some(paused),
+                    ..Default::default()
+                };
+                let zone = cf_client.edit_zone(&zone.id, params).await?;
+                let verb = if paused { "Paused" } else { "Resumed" };
+                self.changed(ctx, format!("{verb} zone `{}`", zone.id)).await;
+                Ok(zone)
+            }
+            _ => Ok(zone),
+        }
+    }
+
+    /// Follow the zone until Cloudflare sees the delegation, returns when to look again
+    ///
+    /// `status.ready` only says the zone exists, the `Ready` condition says whether it's active. A pending
+    /// zone gets an activation check every [`ACTIVATION_CHECK_INTERVAL`] and is looked at more often.
+    async fn track_activation(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        status: &mut ZoneStatus,
+    ) -> Result<Duration> {
+        let previous = self.status.as_ref();
+        status.last_activation_check = previous.and_then(|s| s.last_activation_check.clone());
+
+        if status.activation_status.as_deref() == Some("active") {
+            status.set_ready("Active", self.meta().generation);
+            if previous.and_then(|s| s.activation_status.as_deref()) == Some("pending") {
+                ctx.recorder
+                    .publish(
+                        &Event {
+                            type_: EventType::Normal,
+                            reason: "ZoneActivated".into(),
+                            note: Some(format!("Zone `{}` is active", self.name_any())),
+                            action: "Reconciling".into(),
+                            secondary: None,
+                        },
+                        &self.object_ref(&()),
                     )
                     .await
                     .map_err(Error::KubeError)?;
+            }
+            return Ok(Duration::from_secs(5 * 60));
+        }
 
-                    return Ok(Action::requeue(Duration::from_secs(30)));
-                }
+        let message = format!(
+            "Waiting for the domain to be delegated to {}",
+            status.assigned_nameservers.join(", ")
+        );
+        status.set_reconciling("PendingActivation", message, self.meta().generation);
+
+        let due = status
+            .last_activation_check
+            .as_deref()
+            .and_then(|last| DateTime::parse_from_rfc3339(last).ok())
+            .is_none_or(|last| Utc::now() - last.with_timezone(&Utc) >= ACTIVATION_CHECK_INTERVAL);
+        if let Some(zone_id) = status.id.as_deref()
+            && due
+        {
+            info!(
+                "Requesting an activation check for zone {} ({})",
+                self.name_any(),
+                zone_id
+            );
+            match cf_client.check_zone_activation(zone_id).await {
+                Ok(()) => status.last_activation_check = Some(Utc::now().to_rfc3339()),
+                Err(e) => warn!("Activation check of zone {} failed: {}", zone_id, e),
+            }
+        }
+        Ok(Duration::from_secs(60))
+    }
+
+    /// Hand out `spec.vanityNameServers` for the zone, reported through the `VanityNameServers` condition
+    async fn sync_vanity_name_servers(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        zone: &CfZone,
+        drift: &mut Drift,
+    ) -> Option<Condition> {
+        let desired = self.spec.vanity_name_servers.as_ref()?;
+
+        let invalid: Vec<_> = desired.iter().filter(|ns| !is_hostname(ns)).cloned().collect();
+        if desired.is_empty() || !invalid.is_empty() {
+            let message = if desired.is_empty() {
+                "At least one nameserver is required".to_string()
+            } else {
+                format!("Not valid hostnames: {}", invalid.join(", "))
+            };
+            return Some(self.condition(VANITY_NAME_SERVERS_CONDITION, "False", "Invalid", Some(message)));
+        }
+
+        let normalize = |names: &[String]| {
+            let mut names: Vec<_> = names
+                .iter()
+                .map(|ns| ns.trim_end_matches('.').to_ascii_lowercase())
+                .collect();
+            names.sort();
+            names
+        };
+        let current = zone.vanity_name_servers.as_deref().unwrap_or_default();
+        if normalize(desired) != normalize(current) && self.report_only() {
+            let message = format!("Zone `{}` hands out {}", zone.id, current.join(", "));
+            drift.report(self, ctx, message.clone()).await;
+            return Some(self.condition(VANITY_NAME_SERVERS_CONDITION, "False", "Drifted", Some(message)));
+        }
+        if normalize(desired) != normalize(current) {
+            info!(
+                "Setting vanity nameservers of zone {} to {}",
+                zone.name,
+                desired.join(", ")
+            );
+            let params = EditZoneParams {
+                vanity_name_servers: Some(desired.clone()),
+                ..Default::default()
+            };
+            if let Err(e) = cf_client.edit_zone(&zone.id, params).await {
+                warn!("Failed to set vanity nameservers of zone {}: {}", zone.name, e);
+                return Some(self.condition(
+                    VANITY_NAME_SERVERS_CONDITION,
+                    "False",
+                    "ApplyFailed",
+                    Some(e.to_string()),
+                ));
+            }
+            self.changed(ctx, format!("Set the vanity nameservers of zone `{}`", zone.id))
+                .await;
+        }
+        Some(self.condition(VANITY_NAME_SERVERS_CONDITION, "True", "Applied", None))
+    }
+
+    /// Apply `spec.settings`, only touching settings whose value differs
+    ///
+    /// Settings are changed one by one so a single rejected value doesn't hold back the others,
+    /// every failure ends up in the message of the `SettingsApplied` condition.
+    async fn sync_settings(
+        &self,
+        ctx: &Context,
+        cf_client: &CloudflareClient,
+        zone_id: &str,
+        drift: &mut Drift,
+    ) -> Option<Condition> {
+        let desired = self.spec.settings.as_ref()?.desired();
+        let current = match cf_client.get_all_zone_settings(zone_id).await {
+            Ok(settings) => settings,
+            Err(e) => {
+                return Some(self.condition(
+                    SETTINGS_APPLIED_CONDITION,
+                    "Unknown",
+                    "ListFailed",
+                    Some(format!("Failed to read zone settings: {e}")),
+                ));
+            }
+        };
+
+        let mut failures = vec![];
+        let mut applied = vec![];
+        let mut differing = vec![];
+        for setting in desired {
+            let id = setting.id();
+            if current
+                .iter()
+                .any(|current| current.typed().as_ref() == Some(&setting))
+            {
+                continue;
+            }
+            if self.report_only() {
+                differing.push(id);
+                continue;
+            }
+            info!("Setting {} of zone {} to {}", id, zone_id, setting.value());
+            match cf_client.patch_zone_setting(zone_id, &setting).await {
+                Ok(_) => applied.push(id),
                 Err(e) => {
-                    return Err(Error::KubeError(e));
+                    warn!("Failed to apply setting {} of zone {}: {}", id, zone_id, e);
+                    failures.push(format!("{id}: {e}"));
                 }
             }
         }
+        if !applied.is_empty() {
+            let note = format!("Set {} of zone `{zone_id}`", applied.join(", "));
+            self.changed(ctx, note).await;
+        }
+        if !differing.is_empty() {
+            let message = format!(
+                "{} of zone `{zone_id}` differ from the spec",
+                differing.join(", ")
+            );
+            drift.report(self, ctx, message.clone()).await;
+            return Some(self.condition(SETTINGS_APPLIED_CONDITION, "False", "Drifted", Some(message)));
+        }
 
+        Some(if failures.is_empty() {
+            self.condition(SETTINGS_APPLIED_CONDITION, "True", "Applied", None)
+        } else {
+            self.condition(
+                SETTINGS_APPLIED_CONDITION,
+                "False",
+                "ApplyFailed",
+                Some(failures.join("; ")),
+            )
+        })
+    }
 
-        if name == "illegal" {
-            return Err(Error::IllegalDocument); // error names show up in metrics
+    /// Announce a change made on the Cloudflare side, `DriftCorrected` when the spec didn't ask for it
+    async fn changed(&self, ctx: &Context, note: String) {
+        if status::spec_changed(self) {
+            self.publish(ctx, "Updated", note).await;
+        } else {
+            warn!("Zone \"{}\" drifted: {}", self.name_any(), note);
+            ctx.metrics.reconcile.set_drift(self);
+            self.publish(ctx, "DriftCorrected", note).await;
         }
+    }
 
-        // If no events were received, check back every 5 minutes
-        Ok(Action::requeue(Duration::from_secs(5 * 60)))
+    async fn publish(&self, ctx: &Context, reason: &str, note: String) {
+        let event = Event {
+            type_: EventType::Normal,
+            reason: reason.into(),
+            note: Some(note),
+            action: "Reconciling".into(),
+            secondary: None,
+        };
+        if let Err(e) = ctx.recorder.publish(&event, &self.object_ref(&())).await {
+            warn!("failed to publish {} event: {}", reason, e);
+        }
+    }
+
+    /// Build a condition, keeping the transition time when the status didn't change
+    fn condition(&self, type_: &str, status: &str, reason: &str, message: Option<String>) -> Condition {
+        Condition::new(type_, status, reason, message, self.conditions()).observed(self.meta().generation)
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|s| s.conditions.as_slice())
+            .unwrap_or_default()
     }
 
     // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
     async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
         let oref = self.object_ref(&());
-        // Document doesn't have any real cleanup, so we just publish an event
-        ctx.recorder
-            .publish(
-                &Event {
+        // a zone bound by id was there before us, so it's left alone unless asked otherwise
+        let default = if self.spec.zone_id.is_some() {
+            DeletionPolicy::Abandon
+        } else {
+            DeletionPolicy::Delete
+        };
+        let abandon = DeletionPolicy::of(self, default) == DeletionPolicy::Abandon || self.report_only();
+        let zone_id = self.status.as_ref().and_then(|s| s.id.clone());
+
+        let event = match zone_id {
+            Some(zone_id) if abandon => Event {
+                type_: EventType::Normal,
+                reason: "Abandoned".into(),
+                note: Some(format!(
+                    "Left zone `{zone_id}` in Cloudflare as requested by the deletion or reconcile policy"
+                )),
+                action: "Deleting".into(),
+                secondary: None,
+            },
+            Some(zone_id) => {
+                let ns = self.namespace().unwrap(); // zone is namespace scoped
+                let cf_client = ctx.provider.get_client(self, &ns).await?;
+                // an error keeps the finalizer in place, so the object stays around until the delete goes through
+                cf_client.delete_zone(&zone_id).await?;
+                Event {
                     type_: EventType::Normal,
-                    reason: "DeleteRequested".into(),
-                    note: Some(format!("Delete `{}`", self.name_any())),
+                    reason: "Deleted".into(),
+                    note: Some(format!("Deleted zone `{zone_id}` from Cloudflare")),
                     action: "Deleting".into(),
                     secondary: None,
-                },
-                &oref,
-            )
+                }
+            }
+            // nothing was ever created on the Cloudflare side
+            None => Event {
+                type_: EventType::Normal,
+                reason: "DeleteRequested".into(),
+                note: Some(format!("Delete `{}`", self.name_any())),
+                action: "Deleting".into(),
+                secondary: None,
+            },
+        };
+        ctx.recorder
+            .publish(&event, &oref)
             .await
             .map_err(Error::KubeError)?;
         Ok(Action::await_change())
     }
 }
 
+/// Whether the name is a usable DNS hostname (letters, digits and dashes, at least two labels)
+fn is_hostname(name: &str) -> bool {
+    let name = name.trim_end_matches('.');
+    let labels: Vec<_> = name.split('.').collect();
+    name.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// Status of a zone that exists in Cloudflare
+fn zone_status(zone: &CfZone) -> ZoneStatus {
+    ZoneStatus {
+        observed_generation: None, // set by status::patch
+        consecutive_failures: None,
+        ready: true,
+        id: Some(zone.id.clone()),
+        error: None,
+        assigned_nameservers: zone.name_servers.clone(),
+        original_nameservers: zone.original_name_servers.clone().unwrap_or_default(),
+        activation_status: serde_json::to_value(&zone.status)
+            .ok()
+            .and_then(|status| status.as_str().map(String::from)),
+        last_activation_check: None,
+        paused: zone.paused,
+        plan: zone.plan.as_ref().map(|plan| plan.name.clone()),
+        conditions: vec![],
+        summary: None,
+    }
+}
+
 /// Initialize the controller and shared state (given the crd is installed)
 pub async fn run(state: State) {
     let client = Client::try_default().await.expect("failed to create kube Client");
-    let docs = Api::<Zone>::all(client.clone());
+    let scopes = state.namespaces().scopes();
+    let docs: Api<Zone> = scoped(client.clone(), scopes[0].as_deref());
     if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
         error!("CRD is not queryable; {e:?}. Is the CRD installed?");
         info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
         std::process::exit(1);
     }
 
-    let api_key =
-        std::env::var("CLOUDFLARE_API_TOKEN").expect("CLOUDFLARE_API_TOKEN environment variable must be set");
-    Controller::new(docs, Config::default().any_semantic())
-        .shutdown_on_signal()
-        .run(reconcile, error_policy, state.to_context(client, api_key).await)
-        .filter_map(|x| async move { std::result::Result::ok(x) })
-        .for_each(|_| futures::future::ready(()))
+    let settings = ControllerSettings::from_env("Zone");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
         .await;
+    // one controller per watched namespace, they share the context and with it the Cloudflare clients
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        let controller = state
+            .controller("Zone", scoped::<Zone>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config());
+        let zones = controller.store();
+        state.health().watch("Zone", zones.clone());
+        controller
+            // Zones waiting for their Account go as soon as it becomes ready instead of on the next requeue
+            .watches(
+                scoped::<Account>(client.clone(), ns.as_deref()),
+                Config::default(),
+                wake_dependents(zones, |zone: &Zone| {
+                    zone.spec.account_ref.as_ref().map(|a_ref| a_ref.name.as_str())
+                }),
+            )
+            .reconcile_on(state.triggers().zone.stream())
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("Zone", result))
+    }))
+    .await;
 }
@@ -0,0 +1,110 @@
+//! Zone v1beta1, a typed plan and `vanityNameservers` spelled like the status fields
+//!
+//! v1alpha1 stays the stored version the controller works with, the conversion webhook translates
+//! between the two.
+use k8s_openapi::api::core::v1::{LocalObjectReference, SecretKeySelector};
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::crd::{self, ZoneSettings, ZoneStatus, ZoneType};
+use crate::reconcile_policy::ReconcilePolicy;
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(kind = "Zone", group = "cloudflare.com", version = "v1beta1", namespaced)]
+#[kube(
+    status = "ZoneStatus",
+    shortname = "zone",
+    shortname = "cfzone",
+    category = "cloudflare"
+)]
+#[kube(printcolumn = r#"{"name":"Ready", "type":"boolean", "jsonPath":".status.ready"}"#)]
+#[kube(printcolumn = r#"{"name":"Activation", "type":"string", "jsonPath":".status.activationStatus"}"#)]
+#[kube(printcolumn = r#"{"name":"Summary", "type":"string", "jsonPath":".status.summary"}"#)]
+#[kube(
+    printcolumn = r#"{"name":"Nameservers", "type":"string", "jsonPath":".status.assignedNameservers", "priority":1}"#
+)]
+#[kube(printcolumn = r#"{"name":"Plan", "type":"string", "jsonPath":".status.plan", "priority":1}"#)]
+#[kube(printcolumn = r#"{"name":"Paused", "type":"boolean", "jsonPath":".status.paused", "priority":1}"#)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneSpec {
+    pub account_ref: Option<LocalObjectReference>,
+    pub secret_ref: Option<SecretKeySelector>,
+    /// Bind to this existing Cloudflare zone instead of looking it up by name or creating it
+    pub zone_id: Option<String>,
+    /// How DNS for the zone is set up, only used when the zone is created
+    #[serde(rename = "type")]
+    pub zone_type: Option<ZoneType>,
+    /// Rate plan of the zone subscription
+    pub plan: Option<Plan>,
+    /// Scan for existing DNS records when the zone is created
+    pub jump_start: Option<bool>,
+    /// Pause Cloudflare for the domain, traffic goes straight to the origin while paused
+    pub paused: Option<bool>,
+    /// Custom nameservers to hand out instead of the assigned ones, business and enterprise plans only
+    pub vanity_nameservers: Option<Vec<String>>,
+    /// Zone settings to enforce, settings left out are not touched
+    pub settings: Option<ZoneSettings>,
+    /// `DriftReportOnly` to only compare the zone with Cloudflare, `Enforce` by default
+    pub reconcile_policy: Option<ReconcilePolicy>,
+    /// Cloudflare API to manage the zone through instead of the operator's (the China network, a mock
+    /// server), records of the zone follow it; without a `secretRef` only `CLOUDFLARE_ALLOWED_API_URLS`
+    pub api_url: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Plan {
+    Free,
+    Pro,
+    Business,
+    Enterprise,
+}
+
+impl TryFrom<crd::ZoneSpec> for ZoneSpec {
+    type Error = String;
+
+    fn try_from(spec: crd::ZoneSpec) -> Result<Self, Self::Error> {
+        let plan = spec
+            .plan
+            .map(|plan| {
+                serde_json::from_value(plan.clone().into())
+                    .map_err(|_| format!("plan {plan:?} has no v1beta1 equivalent"))
+            })
+            .transpose()?;
+        Ok(ZoneSpec {
+            account_ref: spec.account_ref,
+            secret_ref: spec.secret_ref,
+            zone_id: spec.zone_id,
+            zone_type: spec.zone_type,
+            plan,
+            jump_start: spec.jump_start,
+            paused: spec.paused,
+            vanity_nameservers: spec.vanity_name_servers,
+            settings: spec.settings,
+            reconcile_policy: spec.reconcile_policy,
+            api_url: spec.api_url,
+        })
+    }
+}
+
+impl From<ZoneSpec> for crd::ZoneSpec {
+    fn from(spec: ZoneSpec) -> Self {
+        crd::ZoneSpec {
+            account_ref: spec.account_ref,
+            secret_ref: spec.secret_ref,
+            zone_id: spec.zone_id,
+            zone_type: spec.zone_type,
+            plan: spec
+                .plan
+                .and_then(|plan| serde_json::to_value(plan).ok())
+                .and_then(|plan| plan.as_str().map(String::from)),
+            jump_start: spec.jump_start,
+            paused: spec.paused,
+            vanity_name_servers: spec.vanity_nameservers,
+            settings: spec.settings,
+            reconcile_policy: spec.reconcile_policy,
+            api_url: spec.api_url,
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Grants namespaces the right to manage records and page rules under some subdomains of a zone
+///
+/// Cluster scoped, so a namespace can't grant itself anything. Once a namespace is named by a binding,
+/// its objects may only target zones bound to it, and only hostnames matching the binding. With
+/// `REQUIRE_ZONE_BINDINGS=true` namespaces no binding names may not target any zone.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(Default))]
+#[kube(kind = "ZoneBinding", group = "cloudflare.com", version = "v1alpha1")]
+#[kube(shortname = "zb", shortname = "cfzb", category = "cloudflare")]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneBindingSpec {
+    /// Namespaces granted the hostnames, `*` for every namespace
+    pub namespaces: Vec<String>,
+    /// Name of the Zone objects the namespaces reference, the zone's domain
+    pub zone: String,
+    /// Hostnames that may be managed, `*.team-a.example.com` matches any subdomain of team-a.example.com
+    pub hostnames: Vec<String>,
+}
@@ -0,0 +1,90 @@
+use crate::{Error, Result, namespaces, policy::hostname_matches, zone_binding::ZoneBinding};
+use kube::{
+    Client,
+    api::{Api, ListParams},
+};
+
+/// Whether namespaces no binding names are denied every zone, from `REQUIRE_ZONE_BINDINGS`
+pub fn required() -> bool {
+    std::env::var("REQUIRE_ZONE_BINDINGS").is_ok_and(|v| v == "true")
+}
+
+/// Check a hostname in `zone` against the bindings naming `namespace`
+///
+/// `zone` is `None` for objects naming a zone id instead of a Zone, those can't be bound. Returns the
+/// reason of the denial, `None` when the hostname is allowed.
+pub async fn check(
+    client: Client,
+    namespace: &str,
+    zone: Option<&str>,
+    hostname: &str,
+) -> Result<Option<String>> {
+    let bindings: Api<ZoneBinding> = Api::all(client);
+    let bindings = bindings
+        .list(&ListParams::default())
+        .await
+        .map_err(Error::KubeError)?;
+    Ok(denial(&bindings.items, required(), namespace, zone, hostname))
+}
+
+fn denial(
+    bindings: &[ZoneBinding],
+    required: bool,
+    namespace: &str,
+    zone: Option<&str>,
+    hostname: &str,
+) -> Option<String> {
+    let mut named = bindings
+        .iter()
+        .filter(|b| namespaces::selects(&b.spec.namespaces, namespace))
+        .peekable();
+    if named.peek().is_none() && !required {
+        return None;
+    }
+    let Some(zone) = zone else {
+        return Some(format!(
+            "Namespace {namespace} is limited to its ZoneBindings, reference the zone with a zoneRef"
+        ));
+    };
+
+    let mut bound = named.filter(|b| b.spec.zone == zone).peekable();
+    if bound.peek().is_none() {
+        return Some(format!("Zone {zone} is not bound to namespace {namespace}"));
+    }
+    if bound.any(|b| b.spec.hostnames.iter().any(|p| hostname_matches(p, hostname))) {
+        return None;
+    }
+    Some(format!(
+        "Hostname {hostname} is outside of the subdomains bound to namespace {namespace}"
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zone_binding::ZoneBindingSpec;
+
+    #[test]
+    fn bindings_limit_the_namespaces_they_name() {
+        let bindings = vec![ZoneBinding::new("team-a", ZoneBindingSpec {
+            namespaces: vec!["team-a".into()],
+            zone: "example.com".into(),
+            hostnames: vec!["*.team-a.example.com".into()],
+        })];
+        let check =
+            |required, namespace, zone, hostname| denial(&bindings, required, namespace, zone, hostname);
+        assert_eq!(
+            check(false, "team-a", Some("example.com"), "app.team-a.example.com"),
+            None
+        );
+        assert!(check(false, "team-a", Some("example.com"), "app.team-b.example.com").is_some());
+        assert!(check(false, "team-a", Some("example.org"), "app.team-a.example.com").is_some());
+        assert!(check(false, "team-a", None, "app.team-a.example.com").is_some());
+        // unnamed namespaces are only denied once bindings are required
+        assert_eq!(
+            check(false, "team-b", Some("example.com"), "app.team-b.example.com"),
+            None
+        );
+        assert!(check(true, "team-b", Some("example.com"), "app.team-b.example.com").is_some());
+    }
+}
@@ -0,0 +1,5 @@
+mod crd;
+mod evaluate;
+
+pub use crd::{ZoneBinding, ZoneBindingSpec};
+pub use evaluate::check;
@@ -0,0 +1,85 @@
+use k8s_openapi::api::core::v1::{ConfigMapKeySelector, LocalObjectReference, SecretKeySelector};
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conditions::{Condition, Conditions},
+    reconcile_policy::ReconcilePolicy,
+    zone::{ZoneSettings, ZoneType},
+};
+
+/// Onboards many nearly identical domains, one managed Zone per domain
+///
+/// The Zones are owned by the set and named after their domain. Domains dropped from the set have their
+/// Zone deleted, the `cloudflare.com/deletion-policy` annotation of the set is passed on to its Zones.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(Default))]
+#[kube(kind = "ZoneSet", group = "cloudflare.com", version = "v1alpha1", namespaced)]
+#[kube(
+    status = "ZoneSetStatus",
+    shortname = "zs",
+    shortname = "cfzs",
+    category = "cloudflare"
+)]
+#[kube(printcolumn = r#"{"name":"Zones", "type":"integer", "jsonPath":".status.zones"}"#)]
+#[kube(printcolumn = r#"{"name":"Ready", "type":"integer", "jsonPath":".status.readyZones"}"#)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneSetSpec {
+    #[serde(default)]
+    pub domains: Vec<String>,
+    /// ConfigMap key listing more domains, one per line, `#` starts a comment
+    pub domains_from: Option<ConfigMapKeySelector>,
+    /// Shared by every Zone of the set
+    pub template: ZoneTemplate,
+}
+
+/// The part of a Zone spec that makes sense for many domains at once
+#[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneTemplate {
+    pub account_ref: Option<LocalObjectReference>,
+    pub secret_ref: Option<SecretKeySelector>,
+    #[serde(rename = "type")]
+    pub zone_type: Option<ZoneType>,
+    pub plan: Option<String>,
+    pub jump_start: Option<bool>,
+    pub paused: Option<bool>,
+    pub settings: Option<ZoneSettings>,
+    pub reconcile_policy: Option<ReconcilePolicy>,
+    pub api_url: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneSetStatus {
+    /// Domains in the set
+    pub zones: u32,
+    /// Zones of the set that are ready
+    pub ready_zones: u32,
+    /// Domains skipped because a Zone of that name already exists outside the set
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    pub error: Option<String>,
+    /// Only `Stalled` after failing over and over, the next sync clears it
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Generation of the spec the status was written for
+    pub observed_generation: Option<i64>,
+    /// Reconciles failed in a row, gone once one succeeds
+    pub consecutive_failures: Option<u32>,
+}
+
+impl Conditions for ZoneSetStatus {
+    fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition> {
+        &mut self.conditions
+    }
+
+    fn set_failed(&mut self, message: &str) {
+        self.error = Some(message.into());
+    }
+}
@@ -0,0 +1,5 @@
+mod crd;
+mod reconcile;
+
+pub use crd::{ZoneSet, ZoneSetSpec, ZoneSetStatus, ZoneTemplate};
+pub use reconcile::{ZONE_SET_LABEL, run};
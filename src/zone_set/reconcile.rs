@@ -0,0 +1,227 @@
+use crate::{
+    Context, Error, Result, State,
+    deletion_policy::DELETION_POLICY_ANNOTATION,
+    failures,
+    namespaces::scoped,
+    settings::ControllerSettings,
+    status, telemetry,
+    zone::{Zone, ZoneSpec},
+    zone_set::{ZoneSet, ZoneSetStatus},
+};
+use anyhow::bail;
+use chrono::Utc;
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::{
+    Resource,
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams, ResourceExt},
+    client::Client,
+    runtime::{controller::Action, watcher::Config},
+};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+use tracing::*;
+
+/// Label on the Zones of a set, holding the name of the set
+pub static ZONE_SET_LABEL: &str = "cloudflare.com/zone-set";
+
+#[instrument(skip(ctx, doc), fields(trace_id))]
+async fn reconcile(doc: Arc<ZoneSet>, ctx: Arc<Context>) -> Result<Action> {
+    let trace_id = telemetry::get_trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        Span::current().record("trace_id", field::display(&trace_id));
+    }
+    let _timer = ctx.metrics.reconcile.count_and_measure(doc.as_ref(), &trace_id);
+    ctx.diagnostics.write().await.last_event = Utc::now();
+
+    info!(
+        "Reconciling ZoneSet \"{}\" in {}",
+        doc.name_any(),
+        doc.namespace().unwrap()
+    );
+    // the Zones are owned by the set, so the garbage collector cleans up after it without a finalizer
+    doc.reconcile(ctx).await
+}
+
+fn error_policy(doc: Arc<ZoneSet>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}", error);
+    ctx.metrics.reconcile.set_failure(doc.as_ref(), error);
+    failures::requeue(doc, error, &ctx)
+}
+
+impl ZoneSet {
+    async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action> {
+        let client = ctx.client.clone();
+        let ns = self.namespace().unwrap(); // doc is namespace scoped
+        let name = self.name_any();
+        let zones: Api<Zone> = Api::namespaced(client.clone(), &ns);
+
+        let status = match self.domains(client, &ns).await {
+            Ok(domains) => self.sync_zones(&zones, &domains).await?,
+            Err(e) => {
+                warn!("ZoneSet \"{}\": {}", name, e);
+                ZoneSetStatus {
+                    error: Some(e.to_string()),
+                    conditions: vec![],
+                    ..self.status.clone().unwrap_or_default()
+                }
+            }
+        };
+        status::patch(self, ctx.client.clone(), &status).await?;
+
+        // picks up changes to the domains ConfigMap, which isn't watched
+        Ok(Action::requeue(ctx.settings.requeue))
+    }
+
+    /// Every domain of the set, normalized and without duplicates
+    async fn domains(&self, client: Client, ns: &str) -> anyhow::Result<BTreeSet<String>> {
+        let mut domains = self.spec.domains.clone();
+        if let Some(selector) = &self.spec.domains_from {
+            let config_maps: Api<ConfigMap> = Api::namespaced(client, ns);
+            let listed = config_maps
+                .get_opt(&selector.name)
+                .await?
+                .and_then(|cm| cm.data)
+                .and_then(|mut data| data.remove(&selector.key));
+            match listed {
+                Some(listed) => domains.extend(
+                    listed
+                        .lines()
+                        .map(|line| line.split('#').next().unwrap_or_default().to_string()),
+                ),
+                None if selector.optional == Some(true) => {}
+                None => bail!("ConfigMap key {}/{} not found", selector.name, selector.key),
+            }
+        }
+        Ok(domains
+            .iter()
+            .map(|domain| domain.trim().trim_end_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect())
+    }
+
+    /// Apply a Zone for every domain and delete the Zones of domains that left the set
+    async fn sync_zones(&self, zones: &Api<Zone>, domains: &BTreeSet<String>) -> Result<ZoneSetStatus> {
+        let name = self.name_any();
+        let owned = zones
+            .list(&ListParams::default().labels(&format!("{ZONE_SET_LABEL}={name}")))
+            .await
+            .map_err(Error::KubeError)?;
+        let owned_names: BTreeSet<_> = owned.iter().map(ResourceExt::name_any).collect();
+
+        let mut conflicts = vec![];
+        let mut failures = vec![];
+        for domain in domains {
+            if !owned_names.contains(domain)
+                && zones.get_opt(domain).await.map_err(Error::KubeError)?.is_some()
+            {
+                warn!("ZoneSet \"{}\": Zone {} exists outside the set", name, domain);
+                conflicts.push(domain.clone());
+                continue;
+            }
+            // a bad line shouldn't hold up the rest of the set
+            if let Err(e) = zones
+                .patch(
+                    domain,
+                    &PatchParams::apply("zoneset").force(),
+                    &Patch::Apply(self.zone(domain)),
+                )
+                .await
+            {
+                warn!("ZoneSet \"{}\": failed to apply Zone {}: {}", name, domain, e);
+                failures.push(format!("{domain}: {e}"));
+            }
+        }
+        for zone in owned.iter().filter(|zone| !domains.contains(&zone.name_any())) {
+            info!("Removing Zone {} from set {}", zone.name_any(), name);
+            zones
+                .delete(&zone.name_any(), &DeleteParams::default())
+                .await
+                .map_err(Error::KubeError)?;
+        }
+
+        let ready = owned
+            .iter()
+            .filter(|zone| domains.contains(&zone.name_any()))
+            .filter(|zone| zone.status.as_ref().is_some_and(|status| status.ready))
+            .count();
+        Ok(ZoneSetStatus {
+            observed_generation: None, // set by status::patch
+            zones: (domains.len() - conflicts.len()) as u32,
+            ready_zones: ready as u32,
+            conflicts,
+            error: (!failures.is_empty()).then(|| failures.join("; ")),
+            conditions: vec![],
+            consecutive_failures: None,
+        })
+    }
+
+    /// The Zone the set keeps for a domain
+    fn zone(&self, domain: &str) -> serde_json::Value {
+        let template = &self.spec.template;
+        let annotations: BTreeMap<_, _> = self
+            .annotations()
+            .get(DELETION_POLICY_ANNOTATION)
+            .map(|policy| (DELETION_POLICY_ANNOTATION, policy.clone()))
+            .into_iter()
+            .collect();
+        json!({
+            "apiVersion": "cloudflare.com/v1alpha1",
+            "kind": "Zone",
+            "metadata": {
+                "name": domain,
+                "labels": { ZONE_SET_LABEL: self.name_any() },
+                "annotations": annotations,
+                "ownerReferences": [self.controller_owner_ref(&()).unwrap()],
+            },
+            "spec": ZoneSpec {
+                account_ref: template.account_ref.clone(),
+                secret_ref: template.secret_ref.clone(),
+                zone_id: None,
+                zone_type: template.zone_type,
+                plan: template.plan.clone(),
+                jump_start: template.jump_start,
+                paused: template.paused,
+                vanity_name_servers: None,
+                settings: template.settings.clone(),
+                reconcile_policy: template.reconcile_policy,
+                api_url: template.api_url.clone(),
+            },
+        })
+    }
+}
+
+/// Initialize the controller and shared state (given the crd is installed)
+pub async fn run(state: State) {
+    let client = Client::try_default().await.expect("failed to create kube Client");
+    let scopes = state.namespaces().scopes();
+    let docs: Api<ZoneSet> = scoped(client.clone(), scopes[0].as_deref());
+    if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
+        error!("CRD is not queryable; {e:?}. Is the CRD installed?");
+        info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
+        std::process::exit(1);
+    }
+
+    let settings = ControllerSettings::from_env("ZoneSet");
+    let ctx = state
+        .to_controller_context(client.clone(), settings.clone())
+        .await;
+    // one controller per watched namespace, they share the context and with it the Cloudflare clients
+    futures::future::join_all(scopes.into_iter().map(|ns| {
+        let controller = state
+            .controller("ZoneSet", scoped::<ZoneSet>(client.clone(), ns.as_deref()))
+            .with_config(settings.controller_config());
+        state.health().watch("ZoneSet", controller.store());
+        controller
+            // keeps the ready count current as the Zones come up
+            .owns(scoped::<Zone>(client.clone(), ns.as_deref()), Config::default())
+            .reconcile_on(state.triggers().zone_set.stream())
+            .shutdown_on_signal()
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|result| state.reconciled("ZoneSet", result))
+    }))
+    .await;
+}
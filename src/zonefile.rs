@@ -0,0 +1,454 @@
+use crate::{
+    Error, Result,
+    dns_record::{DNSRecord, DNSRecordSpec},
+};
+use k8s_openapi::api::core::v1::LocalObjectReference;
+use kube::{
+    Client, ResourceExt,
+    api::{Api, ListParams},
+};
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+/// TTL written for records that leave it to Cloudflare (ttl unset or 1, which means "auto")
+const DEFAULT_TTL: u32 = 300;
+
+/// Render all DNSRecords of `zone` in `namespace` as a BIND zonefile
+pub async fn export(client: Client, namespace: &str, zone: &str) -> Result<String> {
+    let records: Api<DNSRecord> = Api::namespaced(client, namespace);
+    let mut managed: Vec<DNSRecord> = records
+        .list(&ListParams::default())
+        .await
+        .map_err(Error::KubeError)?
+        .into_iter()
+        .filter(|r| r.spec.zone_ref.name == zone)
+        .collect();
+    // keep the output stable between exports so it diffs nicely
+    managed.sort_by(|a, b| {
+        (&a.spec.name, &a.spec.record_type, a.name_any()).cmp(&(
+            &b.spec.name,
+            &b.spec.record_type,
+            b.name_any(),
+        ))
+    });
+    Ok(render(zone, &managed))
+}
+
+/// Render records as a BIND zonefile with `origin` as the `$ORIGIN`
+pub fn render(origin: &str, records: &[DNSRecord]) -> String {
+    let origin = fqdn(origin);
+    let mut out = String::new();
+    let _ = writeln!(out, ";; Exported by the cloudflare operator");
+    let _ = writeln!(out, "$ORIGIN {origin}");
+    let _ = writeln!(out, "$TTL {DEFAULT_TTL}");
+    for record in records {
+        let spec = &record.spec;
+        let ttl = spec.ttl.filter(|ttl| *ttl > 1).unwrap_or(DEFAULT_TTL);
+        let rdata = match spec.record_type.as_str() {
+            "CNAME" | "NS" => fqdn(&spec.content),
+            "MX" => format!("{} {}", spec.priority.unwrap_or(10), fqdn(&spec.content)),
+            "TXT" => quote(&spec.content),
+            _ => spec.content.clone(),
+        };
+        let proxied = if spec.proxied.unwrap_or(false) {
+            " ; cf_tags=cf-proxied:true"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            out,
+            "{}\t{}\tIN\t{}\t{}{}",
+            fqdn(&spec.name),
+            ttl,
+            spec.record_type,
+            rdata,
+            proxied
+        );
+    }
+    out
+}
+
+fn fqdn(name: &str) -> String {
+    if name.ends_with('.') {
+        name.to_string()
+    } else {
+        format!("{name}.")
+    }
+}
+
+fn quote(text: &str) -> String {
+    if text.starts_with('"') && text.ends_with('"') && text.len() > 1 {
+        return text.to_string();
+    }
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Result of reading a zonefile, records we can manage plus everything we had to leave out
+#[derive(Debug, Default)]
+pub struct Import {
+    pub records: Vec<DNSRecordSpec>,
+    pub skipped: Vec<Skipped>,
+}
+
+/// A zonefile entry that doesn't map to a DNSRecord
+#[derive(Debug)]
+pub struct Skipped {
+    pub line: usize,
+    pub record_type: String,
+    pub reason: String,
+}
+
+impl Import {
+    /// Render the imported records as a multi-document stream of DNSRecord manifests
+    pub fn to_manifests(&self, namespace: &str) -> Result<String> {
+        let mut used_names = HashSet::new();
+        let mut out = String::new();
+        for spec in &self.records {
+            let base = resource_name(&spec.record_type, &spec.name);
+            let mut name = base.clone();
+            let mut n = 2;
+            while !used_names.insert(name.clone()) {
+                name = format!("{base}-{n}");
+                n += 1;
+            }
+            let mut record = DNSRecord::new(&name, spec.clone());
+            record.metadata.namespace = Some(namespace.to_string());
+            let _ = writeln!(out, "---");
+            out.push_str(&serde_yaml::to_string(&record).map_err(|_| Error::IllegalDocument)?);
+        }
+        Ok(out)
+    }
+}
+
+/// Parse a BIND zonefile for `zone` into DNSRecord specs referencing the Zone CR of the same name
+pub fn parse(zone: &str, text: &str) -> Import {
+    let mut import = Import::default();
+    let mut origin = fqdn(zone);
+    let mut default_ttl = None;
+    let mut last_owner: Option<String> = None;
+
+    for (line, entry, comment) in logical_entries(text) {
+        let tokens = tokenize(&entry);
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+
+        match first.text.to_ascii_uppercase().as_str() {
+            "$ORIGIN" => {
+                if let Some(o) = tokens.get(1) {
+                    origin = qualify(&o.text, &origin) + ".";
+                }
+                continue;
+            }
+            "$TTL" => {
+                default_ttl = tokens.get(1).and_then(|t| parse_ttl(&t.text));
+                continue;
+            }
+            directive if directive.starts_with('$') => {
+                import.skipped.push(Skipped {
+                    line,
+                    record_type: directive.to_string(),
+                    reason: "directive is not supported".into(),
+                });
+                continue;
+            }
+            _ => {}
+        }
+
+        // an entry starting with whitespace reuses the owner of the previous one
+        let mut rest = tokens.iter();
+        let owner = if entry.starts_with([' ', '\t']) {
+            last_owner.clone()
+        } else {
+            rest.next().map(|t| qualify(&t.text, &origin))
+        };
+        let Some(owner) = owner else {
+            continue;
+        };
+        last_owner = Some(owner.clone());
+
+        let mut ttl = None;
+        let mut record_type = None;
+        for token in rest.by_ref() {
+            if let Some(t) = parse_ttl(&token.text) {
+                ttl = Some(t);
+            } else if ["IN", "CH", "HS"].contains(&token.text.to_ascii_uppercase().as_str()) {
+                continue;
+            } else {
+                record_type = Some(token.text.to_ascii_uppercase());
+                break;
+            }
+        }
+        let Some(record_type) = record_type else {
+            continue;
+        };
+        let rdata: Vec<&Token> = rest.collect();
+
+        let skip = |reason: &str| Skipped {
+            line,
+            record_type: record_type.clone(),
+            reason: reason.to_string(),
+        };
+        let (content, priority) = match record_type.as_str() {
+            "A" | "AAAA" | "CNAME" if rdata.len() != 1 => {
+                import.skipped.push(skip("expected exactly one value"));
+                continue;
+            }
+            "A" if rdata[0].text.parse::<Ipv4Addr>().is_err() => {
+                import.skipped.push(skip("value is not an IPv4 address"));
+                continue;
+            }
+            "AAAA" if rdata[0].text.parse::<Ipv6Addr>().is_err() => {
+                import.skipped.push(skip("value is not an IPv6 address"));
+                continue;
+            }
+            "A" | "AAAA" => (rdata[0].text.clone(), None),
+            "CNAME" => (qualify(&rdata[0].text, &origin), None),
+            "MX" => match (rdata.first().map(|t| t.text.parse::<u16>()), rdata.get(1)) {
+                (Some(Ok(priority)), Some(target)) if rdata.len() == 2 => {
+                    (qualify(&target.text, &origin), Some(priority))
+                }
+                _ => {
+                    import.skipped.push(skip("expected `<priority> <target>`"));
+                    continue;
+                }
+            },
+            "TXT" if !rdata.is_empty() => (rdata.iter().map(|t| t.text.as_str()).collect::<String>(), None),
+            "TXT" => {
+                import.skipped.push(skip("record has no value"));
+                continue;
+            }
+            "SOA" | "NS" if owner == origin.trim_end_matches('.') => {
+                import
+                    .skipped
+                    .push(skip("apex SOA/NS records are managed by Cloudflare"));
+                continue;
+            }
+            _ => {
+                import
+                    .skipped
+                    .push(skip("record type is not supported by DNSRecord"));
+                continue;
+            }
+        };
+
+        let proxied = comment
+            .as_deref()
+            .is_some_and(|c| c.contains("cf-proxied:true"))
+            .then_some(true);
+        import.records.push(DNSRecordSpec {
+            zone_ref: LocalObjectReference {
+                name: zone.trim_end_matches('.').to_string(),
+            },
+            name: owner,
+            record_type,
+            content,
+            ttl: ttl.or(default_ttl),
+            priority,
+            proxied,
+            reconcile_policy: None,
+            custom_hostname: None,
+        });
+    }
+    import
+}
+
+#[derive(Debug)]
+struct Token {
+    text: String,
+}
+
+/// Split the zonefile into logical entries, joining parenthesized continuations
+///
+/// Yields the starting line number, the entry with comments and parens stripped, and the comment text.
+fn logical_entries(text: &str) -> Vec<(usize, String, Option<String>)> {
+    let mut entries = vec![];
+    let mut current: Option<(usize, String, Option<String>)> = None;
+    let mut depth = 0usize;
+
+    for (idx, raw) in text.lines().enumerate() {
+        let (data, comment) = strip_comment(raw);
+        let mut cleaned = String::with_capacity(data.len());
+        let mut in_quotes = false;
+        for c in data.chars() {
+            match c {
+                '"' => {
+                    in_quotes = !in_quotes;
+                    cleaned.push(c);
+                }
+                '(' if !in_quotes => {
+                    depth += 1;
+                    cleaned.push(' ');
+                }
+                ')' if !in_quotes => {
+                    depth = depth.saturating_sub(1);
+                    cleaned.push(' ');
+                }
+                _ => cleaned.push(c),
+            }
+        }
+
+        let entry = current.get_or_insert_with(|| (idx + 1, String::new(), None));
+        if entry.1.is_empty() {
+            entry.1 = cleaned;
+        } else {
+            entry.1.push(' ');
+            entry.1.push_str(cleaned.trim());
+        }
+        if let Some(comment) = comment {
+            entry.2.get_or_insert_with(String::new).push_str(&comment);
+        }
+
+        if depth == 0 {
+            let entry = current.take().unwrap();
+            if !entry.1.trim().is_empty() {
+                entries.push(entry);
+            }
+        }
+    }
+    if let Some(entry) = current.filter(|e| !e.1.trim().is_empty()) {
+        entries.push(entry);
+    }
+    entries
+}
+
+fn strip_comment(line: &str) -> (&str, Option<String>) {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => return (&line[..i], Some(line[i + 1..].to_string())),
+            _ => {}
+        }
+    }
+    (line, None)
+}
+
+fn tokenize(entry: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = entry.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => text.extend(chars.next()),
+                    '"' => break,
+                    _ => text.push(c),
+                }
+            }
+            tokens.push(Token { text });
+        } else {
+            let mut text = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+            tokens.push(Token { text });
+        }
+    }
+    tokens
+}
+
+/// TTLs are either plain seconds or BIND style durations like `1h30m`
+fn parse_ttl(value: &str) -> Option<u32> {
+    if !value.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let mut total = 0u32;
+    let mut number = 0u32;
+    for c in value.chars() {
+        if let Some(d) = c.to_digit(10) {
+            number = number.checked_mul(10)?.checked_add(d)?;
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        total = total.checked_add(number.checked_mul(unit)?)?;
+        number = 0;
+    }
+    total.checked_add(number)
+}
+
+/// Turn a zonefile name into a fully qualified name without the trailing dot
+fn qualify(name: &str, origin: &str) -> String {
+    let origin = origin.trim_end_matches('.');
+    if name == "@" {
+        origin.to_string()
+    } else if let Some(absolute) = name.strip_suffix('.') {
+        absolute.to_string()
+    } else {
+        format!("{name}.{origin}")
+    }
+}
+
+fn resource_name(record_type: &str, name: &str) -> String {
+    let raw = format!("{record_type}-{name}").to_ascii_lowercase();
+    let mut out = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    out.trim_matches('-').chars().take(253).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_common_records_and_reports_unsupported() {
+        let zonefile = r#"
+$ORIGIN example.com.
+$TTL 3600
+@       IN  SOA ns1.example.com. admin.example.com. (
+            2024010101 ; serial
+            7200 3600 1209600 3600 )
+@       IN  NS  ns1.example.com.
+@       IN  A   192.0.2.1 ; cf_tags=cf-proxied:true
+www     300 IN  CNAME example.com.
+        IN  TXT "v=spf1 " "-all"
+mail    IN  MX  10 mx.example.com.
+_sip._tcp IN SRV 10 5 5060 sip.example.com.
+"#;
+        let import = parse("example.com", zonefile);
+
+        let summary: Vec<_> = import
+            .records
+            .iter()
+            .map(|r| (r.name.as_str(), r.record_type.as_str(), r.content.as_str(), r.ttl))
+            .collect();
+        assert_eq!(summary, vec![
+            ("example.com", "A", "192.0.2.1", Some(3600)),
+            ("www.example.com", "CNAME", "example.com", Some(300)),
+            ("www.example.com", "TXT", "v=spf1 -all", Some(3600)),
+            ("mail.example.com", "MX", "mx.example.com", Some(3600)),
+        ]);
+        assert_eq!(import.records[0].proxied, Some(true));
+        assert_eq!(import.records[3].priority, Some(10));
+
+        let skipped: Vec<_> = import.skipped.iter().map(|s| s.record_type.as_str()).collect();
+        assert_eq!(skipped, vec!["SOA", "NS", "SRV"]);
+    }
+}